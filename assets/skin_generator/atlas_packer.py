@@ -0,0 +1,70 @@
+# Packs skin images into a single sprite atlas, and writes out where each one went.
+#
+# Usage: python atlas_packer.py <image dir> <atlas.png> <rects.ron>
+#
+# The rects are the pixel source rectangles of the sprites, keyed by image file name without
+# its extension. Point the styling's `stylesheet_image` at the atlas and its `sprite_rects` at
+# the rects; sprites (e.g. a background's `src_rect`) can then be given by name, as in
+# `src_rect: "background"`, and follow the images wherever they're packed.
+
+import sys
+from pathlib import Path
+from typing import Dict, Tuple
+
+from PIL import Image
+
+from skin_generator import Rect
+
+# Transparent pixels around each image, so its neighbors don't bleed in when it's scaled.
+PADDING = 1
+# How wide the atlas may get; an image wider than this gets a row to itself.
+MAX_WIDTH = 4096
+
+Size = Tuple[int, int]
+
+
+def pack(sizes: Dict[str, Size], max_width: int = MAX_WIDTH) -> Tuple[Size, Dict[str, Rect]]:
+    """Lay out images of the given (width, height) in rows, tallest first. Returns the size of
+    the atlas, and each image's rectangle in it."""
+    rects = {}
+    x, y, row_height, width = 0, 0, 0, 0
+    for name in sorted(sizes, key=lambda name: (-sizes[name][1], name)):
+        image_width, image_height = sizes[name]
+        if x > 0 and x + PADDING + image_width + PADDING > max_width:
+            x, y, row_height = 0, y + row_height, 0
+        left, top = x + PADDING, y + PADDING
+        rects[name] = Rect(left, top, left + image_width, top + image_height)
+        x = left + image_width
+        row_height = max(row_height, PADDING + image_height)
+        width = max(width, x)
+    return (width + PADDING, y + row_height + PADDING), rects
+
+
+def write_rects(rects: Dict[str, Rect], path: Path) -> None:
+    with open(path, "w") as out:
+        out.write("{\n")
+        for name in sorted(rects):
+            out.write(f'    "{name}": {rects[name].to_str()},\n')
+        out.write("}\n")
+
+
+def main() -> None:
+    if len(sys.argv) != 4:
+        print(f"Usage: {sys.argv[0]} <image dir> <atlas.png> <rects.ron>")
+        sys.exit(1)
+    image_dir, atlas_path, rects_path = (Path(arg) for arg in sys.argv[1:])
+
+    images = {
+        path.stem: Image.open(path).convert("RGBA")
+        for path in sorted(image_dir.glob("*.png"))
+    }
+    size, rects = pack({name: image.size for name, image in images.items()})
+    atlas = Image.new("RGBA", size, (0, 0, 0, 0))
+    for name, image in images.items():
+        atlas.paste(image, (rects[name].x1, rects[name].y1))
+    atlas.save(atlas_path)
+    write_rects(rects, rects_path)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,281 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, Ident, Lit, Meta, NestedMeta,
+    Result,
+};
+
+/// Generates `update_param`, `read_parameter`, `format_value`, `format_normalized` and
+/// `parse_value` for a parameter struct.
+///
+/// The struct names its parameter enum with `#[params(EFooParams)]`, and each parameter field
+/// names its variant and the `ParamsMeta` field describing it:
+///
+/// ```ignore
+/// #[derive(ParamFields)]
+/// #[params(EDriftParams)]
+/// pub struct DriftParams {
+///     #[param(Rate, meta = "drift_rate_meta")]
+///     pub rate: f64,
+/// }
+/// ```
+#[proc_macro_derive(ParamFields, attributes(params, param))]
+pub fn param_fields_macro(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    proc_macro::TokenStream::from(param_fields(&input).unwrap_or_else(|e| e.to_compile_error()))
+}
+
+/// Generates `as_string` and `host_name` for a parameter enum, along with a fixed ordering of its
+/// variants: `COUNT`, `ALL`, and each variant's `index` into `ALL`.
+///
+/// Variants are named after themselves unless given `#[param(name = "...")]`; `host_name`
+/// overrides the name reported to the host, if it differs from the display name.
+#[proc_macro_derive(ParamNames, attributes(param))]
+pub fn param_names_macro(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    proc_macro::TokenStream::from(param_names(&input).unwrap_or_else(|e| e.to_compile_error()))
+}
+
+// The contents of every `#[<name>(...)]` attribute.
+fn attr_args(attrs: &[Attribute], name: &str) -> Result<Vec<NestedMeta>> {
+    let mut args = vec![];
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident(name)) {
+        match attr.parse_meta()? {
+            Meta::List(list) => args.extend(list.nested),
+            meta => {
+                return Err(Error::new(
+                    meta.span(),
+                    format!("expected #[{}(...)]", name),
+                ))
+            }
+        }
+    }
+    Ok(args)
+}
+
+fn string_value(lit: &Lit) -> Result<String> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        _ => Err(Error::new(lit.span(), "expected a string")),
+    }
+}
+
+fn param_fields(input: &DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let enum_name = match attr_args(&input.attrs, "params")?.as_slice() {
+        [NestedMeta::Meta(Meta::Path(path))] => path.clone(),
+        _ => {
+            return Err(Error::new(
+                name.span(),
+                "expected the parameter enum, as #[params(EFooParams)]",
+            ))
+        }
+    };
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new(name.span(), "expected named fields")),
+        },
+        _ => return Err(Error::new(name.span(), "ParamFields only supports structs")),
+    };
+
+    let mut updates = vec![];
+    let mut reads = vec![];
+    let mut formats = vec![];
+    let mut normalized_formats = vec![];
+    let mut parses = vec![];
+    for field in fields {
+        let args = attr_args(&field.attrs, "param")?;
+        if args.is_empty() {
+            continue;
+        }
+        let mut variant = None;
+        let mut meta_field = None;
+        for arg in &args {
+            match arg {
+                NestedMeta::Meta(Meta::Path(path)) => variant = Some(path.clone()),
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("meta") => {
+                    meta_field = Some(Ident::new(&string_value(&nv.lit)?, nv.lit.span()));
+                }
+                _ => return Err(Error::new(arg.span(), "unexpected parameter attribute")),
+            }
+        }
+        let (variant, meta_field) = match (variant, meta_field) {
+            (Some(variant), Some(meta_field)) => (variant, meta_field),
+            _ => {
+                return Err(Error::new(
+                    field.span(),
+                    "expected #[param(Variant, meta = \"meta_field\")]",
+                ))
+            }
+        };
+        let field_name = &field.ident;
+        let field_ty = &field.ty;
+        updates.push(quote_spanned! {field.span()=>
+            #enum_name::#variant => {
+                self.#field_name = crate::params::types::ParamType::vst_float_to_value(
+                    &meta.#meta_field.0,
+                    new_value,
+                );
+            }
+        });
+        reads.push(quote_spanned! {field.span()=>
+            #enum_name::#variant => crate::params::types::ParamType::value_to_vst_float(
+                &meta.#meta_field.0,
+                self.#field_name,
+            ),
+        });
+        formats.push(quote_spanned! {field.span()=>
+            #enum_name::#variant => crate::params::fmt::Formatter::format_value(
+                &meta.#meta_field.1,
+                self.#field_name,
+            ),
+        });
+        normalized_formats.push(quote_spanned! {field.span()=>
+            #enum_name::#variant => crate::params::fmt::Formatter::<#field_ty>::format_value(
+                &meta.#meta_field.1,
+                crate::params::types::ParamType::vst_float_to_value(&meta.#meta_field.0, value),
+            ),
+        });
+        parses.push(quote_spanned! {field.span()=>
+            #enum_name::#variant => crate::params::fmt::Formatter::<#field_ty>::parse_value(
+                &meta.#meta_field.1,
+                text,
+            )
+            .map(|value| crate::params::types::ParamType::value_to_vst_float(
+                &meta.#meta_field.0,
+                value,
+            )),
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            pub(crate) fn update_param(
+                &mut self,
+                meta: &crate::params::ParamsMeta,
+                eparam: #enum_name,
+                new_value: f64,
+            ) {
+                match eparam {
+                    #(#updates)*
+                }
+            }
+
+            pub(crate) fn read_parameter(
+                &self,
+                meta: &crate::params::ParamsMeta,
+                eparam: #enum_name,
+            ) -> f64 {
+                match eparam {
+                    #(#reads)*
+                }
+            }
+
+            pub(crate) fn format_value(
+                &self,
+                meta: &crate::params::ParamsMeta,
+                eparam: #enum_name,
+            ) -> String {
+                match eparam {
+                    #(#formats)*
+                }
+            }
+
+            /// The text `format_value` would give if `eparam` were set to the normalized
+            /// `value`.
+            pub(crate) fn format_normalized(
+                &self,
+                meta: &crate::params::ParamsMeta,
+                eparam: #enum_name,
+                value: f64,
+            ) -> String {
+                match eparam {
+                    #(#normalized_formats)*
+                }
+            }
+
+            pub(crate) fn parse_value(
+                &self,
+                meta: &crate::params::ParamsMeta,
+                eparam: #enum_name,
+                text: &str,
+            ) -> Option<f64> {
+                match eparam {
+                    #(#parses)*
+                }
+            }
+        }
+    })
+}
+
+fn param_names(input: &DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => return Err(Error::new(name.span(), "ParamNames only supports enums")),
+    };
+
+    let mut display = vec![];
+    let mut names = vec![];
+    let mut all = vec![];
+    let mut indices = vec![];
+    for (index, variant) in variants.iter().enumerate() {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new(variant.span(), "expected a unit variant"));
+        }
+        let ident = &variant.ident;
+        let mut display_name = ident.to_string();
+        let mut host_name = None;
+        for arg in attr_args(&variant.attrs, "param")? {
+            match arg {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => {
+                    display_name = string_value(&nv.lit)?;
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("host_name") => {
+                    host_name = Some(string_value(&nv.lit)?);
+                }
+                _ => return Err(Error::new(arg.span(), "unexpected parameter attribute")),
+            }
+        }
+        let host_name = host_name.unwrap_or_else(|| display_name.clone());
+        display.push(quote! { Self::#ident => #display_name, });
+        names.push(quote! { Self::#ident => #host_name, });
+        all.push(quote! { Self::#ident, });
+        indices.push(quote! { Self::#ident => #index, });
+    }
+    let count = variants.len();
+
+    Ok(quote! {
+        impl #name {
+            fn as_string(&self, _short: bool) -> String {
+                let s = match self {
+                    #(#display)*
+                };
+                s.to_string()
+            }
+
+            /// Name shown to the host.
+            fn host_name(&self) -> &'static str {
+                match self {
+                    #(#names)*
+                }
+            }
+
+            const COUNT: usize = #count;
+
+            const ALL: [#name; #count] = [
+                #(#all)*
+            ];
+
+            fn index(self) -> usize {
+                match self {
+                    #(#indices)*
+                }
+            }
+        }
+    })
+}
@@ -1,29 +1,65 @@
 use std::collections::VecDeque;
 
+use copy_from::CopyFrom;
 use num_traits::Float;
 
 use crate::dsp::env;
+use crate::dsp::env::EnvLevels;
 use crate::dsp::filter::Filter;
 use crate::dsp::interpolator::{CachedWaveform, Interpolator};
-use crate::dsp::osc::{Unison, WaveShape};
+use crate::dsp::osc::SoundSource;
+use crate::dsp::simd::F64x2;
 use crate::modulation;
 use crate::modulation::target::ModulationTarget;
+use crate::modulation::voice::VoiceMod;
 use crate::modulation::{ModState, Modulation};
+use crate::params::smoothing::{ParamSmoother, DEFAULT_SMOOTHING_TIME_SEC};
 use crate::params::sync::{MailboxReceiver, Synchronizer};
 use crate::params::NormalizedParams;
-use crate::params::Params;
 use crate::params::ParamsMeta;
-use crate::params::{EFiltParams, EOscParams, EParam};
+use crate::params::{
+    EFiltParams, EOscParams, EParam, EPARAM_COUNT, KEY_TRACK_CENTER_NOTE, MAX_DETUNE_SEMI,
+};
+use crate::params::{OscParams, Params};
 use crate::util::note_freq::NOTE_TO_FREQ;
 
 pub const CHANNEL_COUNT: usize = 2;
+/// Channels when each oscillator's chain has its own stereo pair; see `Sunfish::render`.
+pub const MAX_CHANNEL_COUNT: usize = 2 * CHANNEL_COUNT;
 pub const VOICES_MAX: usize = 128;
+/// Cutoff swing, in semitones, for a full-scale oscillator at 100% filter FM.
+const FILTER_FM_RANGE_SEMI: f64 = 48.0;
+const MAX_VELOCITY: f64 = 127.0;
+/// Modulated pitch steps this often (in samples) while gliding across a buffer.
+const PITCH_RAMP_BLOCK: usize = 32;
+/// Note events per buffer that can be queued without allocating.
+const PENDING_NOTES_CAPACITY: usize = 256;
+
+/// A note event, to be applied partway through the next rendered buffer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteEvent {
+    On {
+        note: u8,
+        velocity: i8,
+    },
+    Off {
+        note: u8,
+    },
+    /// Release every held note.
+    AllNotesOff,
+    /// Silence every voice immediately.
+    AllSoundOff,
+}
 
 #[derive(Debug)]
 pub struct Voice {
     base_note: u8,
     freq_osc1: f64,
     freq_osc2: f64,
+    // Frequencies as of the end of the last rendered buffer; modulation glides
+    // from these to the current ones.
+    last_freq_osc1: f64,
+    last_freq_osc2: f64,
 
     #[allow(dead_code)]
     pitch_bend: f64, // -1.0 - 1.0
@@ -31,23 +67,29 @@ pub struct Voice {
     pitch_bend_range: f64, // +/- this value.
 
     osc1_fine_offset: f64,
+    osc1_detune: f64,
     osc1_semitones_offset: i32,
     osc1_octave_offset: i32,
 
     osc2_fine_offset: f64,
+    osc2_detune: f64,
     osc2_semitones_offset: i32,
     osc2_octave_offset: i32,
 
     #[allow(dead_code)]
     velocity: i8,
-    // Each filter state is per channel (left, right)
-    filter1: Vec<Filter>,
-    filter2: Vec<Filter>,
+    // Each filter processes both channels (left, right) at once.
+    filter1: Filter,
+    filter2: Filter,
     // Amplitude envelope:
     amp_envelope: env::Env,
     // Filter envelope:
     mod_envelope: env::Env,
+    // Second, freely assignable modulation envelope:
+    mod_envelope2: env::Env,
     mod_state: ModState,
+    // This voice's own modulation, on top of the shared modulated parameters.
+    voice_mod: VoiceMod,
 
     cached_waveforms_osc1: Vec<CachedWaveform>,
     cached_waveforms_osc2: Vec<CachedWaveform>,
@@ -60,15 +102,20 @@ struct VoiceInfo<'a> {
     note: u8,
     velocity: i8,
     osc1_fine_offset: f64,
+    osc1_detune: f64,
     osc1_semitones_offset: i32,
     osc1_octave_offset: i32,
     osc2_fine_offset: f64,
+    osc2_detune: f64,
     osc2_semitones_offset: i32,
     osc2_octave_offset: i32,
     amp_adsr: env::ADSR,
     mod_adsr: env::ADSR,
+    mod2_adsr: env::ADSR,
     params: &'a Params,
     meta: &'a ParamsMeta,
+    // Most recent voice still held when this note started, if any.
+    legato_from: Option<&'a Voice>,
 }
 
 impl Voice {
@@ -88,26 +135,45 @@ impl Voice {
                           // params: &Params,
                           // meta: &ParamsMeta
     ) -> Voice {
-        let mut filter1: Vec<Filter> = Vec::with_capacity(CHANNEL_COUNT);
-        let mut filter2: Vec<Filter> = Vec::with_capacity(CHANNEL_COUNT);
-        for _channel_idx in 0..CHANNEL_COUNT {
-            filter1.push(Filter::new(
-                info.sample_rate,
-                &info.params.filt1.mode,
-                &info.params.filt1.cutoff_semi,
-                &info.params.filt1.resonance,
-            ));
-            filter2.push(Filter::new(
-                info.sample_rate,
-                &info.params.filt2.mode,
-                &info.params.filt2.cutoff_semi,
-                &info.params.filt2.resonance,
-            ));
-        }
+        let mut filter1 = Filter::new(
+            info.sample_rate,
+            &info.params.filt1.mode,
+            &info.params.filt1.cutoff_semi,
+            &info.params.filt1.resonance,
+            &info.params.filt1.vowel,
+        );
+        filter1.set_key_track(key_track_semi(info.note, info.params.filt1.key_track));
+        filter1.set_res_comp(info.params.filt1.res_comp);
+        let mut filter2 = Filter::new(
+            info.sample_rate,
+            &info.params.filt2.mode,
+            &info.params.filt2.cutoff_semi,
+            &info.params.filt2.resonance,
+            &info.params.filt2.vowel,
+        );
+        filter2.set_key_track(key_track_semi(info.note, info.params.filt2.key_track));
+        filter2.set_res_comp(info.params.filt2.res_comp);
+        let velocity = (info.velocity as f64 / MAX_VELOCITY).clamp(0.0, 1.0);
         let mut amp_envelope = env::Env::new(info.amp_adsr, info.sample_rate);
-        amp_envelope.start();
+        amp_envelope.set_velocity(velocity);
         let mut mod_envelope = env::Env::new(info.mod_adsr, info.sample_rate);
-        mod_envelope.start();
+        mod_envelope.set_velocity(velocity);
+        match info.legato_from {
+            Some(held) if !info.amp_adsr.retrigger => amp_envelope.start_legato(&held.amp_envelope),
+            _ => amp_envelope.start(),
+        }
+        match info.legato_from {
+            Some(held) if !info.mod_adsr.retrigger => mod_envelope.start_legato(&held.mod_envelope),
+            _ => mod_envelope.start(),
+        }
+        let mut mod_envelope2 = env::Env::new(info.mod2_adsr, info.sample_rate);
+        mod_envelope2.set_velocity(velocity);
+        match info.legato_from {
+            Some(held) if !info.mod2_adsr.retrigger => {
+                mod_envelope2.start_legato(&held.mod_envelope2)
+            }
+            _ => mod_envelope2.start(),
+        }
 
         // TODO: If note isn't valid, set velocity to 0.
         let cached_waveforms_osc1 = vec![CachedWaveform::zero(); CHANNEL_COUNT];
@@ -125,16 +191,20 @@ impl Voice {
             base_note: info.note,
             freq_osc1: 0.0,
             freq_osc2: 0.0,
+            last_freq_osc1: 0.0,
+            last_freq_osc2: 0.0,
 
             // TODO: Support pitch bending.
             pitch_bend: 0.0,
             pitch_bend_range: 1.0,
 
             osc1_fine_offset: info.osc1_fine_offset,
+            osc1_detune: info.osc1_detune,
             osc1_semitones_offset: info.osc1_semitones_offset,
             osc1_octave_offset: info.osc1_octave_offset,
 
             osc2_fine_offset: info.osc2_fine_offset,
+            osc2_detune: info.osc2_detune,
             osc2_semitones_offset: info.osc2_semitones_offset,
             osc2_octave_offset: info.osc2_octave_offset,
 
@@ -143,7 +213,9 @@ impl Voice {
             filter2,
             amp_envelope,
             mod_envelope,
+            mod_envelope2,
             mod_state,
+            voice_mod: VoiceMod::default(),
 
             cached_waveforms_osc1,
             cached_waveforms_osc2,
@@ -152,6 +224,8 @@ impl Voice {
         };
         inst.update_osc1_freq();
         inst.update_osc2_freq();
+        inst.last_freq_osc1 = inst.freq_osc1;
+        inst.last_freq_osc2 = inst.freq_osc2;
         inst
     }
 
@@ -159,8 +233,11 @@ impl Voice {
         for cw in self.cached_waveforms_osc1.iter_mut() {
             cw.reset();
         }
+        let detune = (self.osc1_detune + self.voice_mod.osc1_detune)
+            .clamp(-MAX_DETUNE_SEMI, MAX_DETUNE_SEMI);
         self.freq_osc1 = self.calculate_freq(
             self.osc1_fine_offset,
+            detune,
             self.osc1_octave_offset,
             self.osc1_semitones_offset,
         );
@@ -170,8 +247,11 @@ impl Voice {
         for cw in self.cached_waveforms_osc2.iter_mut() {
             cw.reset();
         }
+        let detune = (self.osc2_detune + self.voice_mod.osc2_detune)
+            .clamp(-MAX_DETUNE_SEMI, MAX_DETUNE_SEMI);
         self.freq_osc2 = self.calculate_freq(
             self.osc2_fine_offset,
+            detune,
             self.osc2_octave_offset,
             self.osc2_semitones_offset,
         );
@@ -180,6 +260,7 @@ impl Voice {
     fn calculate_freq(
         &mut self,
         fine_offset: f64,
+        detune: f64,
         octave_offset: i32,
         semitones_offset: i32,
     ) -> f64 {
@@ -190,6 +271,8 @@ impl Voice {
         let note = note + semitones_offset;
 
         let freq = *NOTE_TO_FREQ.get(&note).unwrap_or(&0.0);
+        // Detune (and pitch modulation) is in semitones, so it sounds the same at any note.
+        let freq = freq * 2.0f64.powf(detune / 12.0);
         // TODO: Pitch bending.
         freq + fine_offset
     }
@@ -208,6 +291,11 @@ impl Voice {
     }
 }
 
+/// Cutoff offset, in semitones, for the given note and key tracking amount.
+fn key_track_semi(note: u8, key_track: f64) -> f64 {
+    (note as f64 - KEY_TRACK_CENTER_NOTE as f64) * key_track
+}
+
 pub struct Tempo {
     // The following is a silly hack to minimize the number of
     // time we have to downcast or upcast floats.
@@ -258,12 +346,26 @@ pub struct Sunfish {
     pub modulation: Modulation,
     pub params_sync: Synchronizer,
     param_reader: MailboxReceiver,
-    last_epoch_recorded: u32,
-
-    // Common buffer when processing audio.
-    buf: Vec<f64>,
-    // Preallocated amp & filter envelope.
-    amp_filt_env_buf: Vec<(f64, f64)>,
+    // Parameters whose modulated values have been published to the GUI.
+    published_modulation: Vec<EParam>,
+    // Note events for the next rendered buffer, with their sample offsets, in order.
+    pending_notes: Vec<(usize, NoteEvent)>,
+    // Note events dropped because the queue was full.
+    dropped_notes: usize,
+
+    // Common buffers when processing audio, one per oscillator and channel.
+    osc1_bufs: [Vec<f64>; CHANNEL_COUNT],
+    osc2_bufs: [Vec<f64>; CHANNEL_COUNT],
+    // The host's audio input for the next rendered buffer, per channel.
+    input_bufs: [Vec<f64>; CHANNEL_COUNT],
+    // Preallocated envelope levels.
+    env_levels_buf: Vec<EnvLevels>,
+    // Gains as of the end of the last rendered buffer, to ramp from.
+    last_osc_gains: (f64, f64),
+    last_output_gain: f64,
+    // Ramps host and GUI changes to continuous parameters.
+    smoother: ParamSmoother,
+    smoothing_time_sec: f64,
 }
 
 impl Sunfish {
@@ -283,6 +385,9 @@ impl Sunfish {
             .clone_inner()
             .unwrap_or_else(|| Params::new(sample_rate));
         let params_modulated = params.clone();
+        let last_osc_gains = (params.osc1.gain, params.osc2.gain);
+        let last_output_gain = params.output_gain;
+        let smoother = ParamSmoother::new(&meta, &params, sample_rate, DEFAULT_SMOOTHING_TIME_SEC);
 
         Sunfish {
             voices: VecDeque::with_capacity(VOICES_MAX),
@@ -299,12 +404,20 @@ impl Sunfish {
             params_modulated,
             params_sync,
             param_reader,
-            last_epoch_recorded: 0,
+            published_modulation: Vec::with_capacity(EPARAM_COUNT),
+            pending_notes: Vec::with_capacity(PENDING_NOTES_CAPACITY),
+            dropped_notes: 0,
 
             // Modulation
             modulation,
-            buf: Vec::with_capacity(1024),
-            amp_filt_env_buf: Vec::with_capacity(1024),
+            osc1_bufs: [Vec::with_capacity(1024), Vec::with_capacity(1024)],
+            osc2_bufs: [Vec::with_capacity(1024), Vec::with_capacity(1024)],
+            input_bufs: [Vec::with_capacity(1024), Vec::with_capacity(1024)],
+            env_levels_buf: Vec::with_capacity(1024),
+            last_osc_gains,
+            last_output_gain,
+            smoother,
+            smoothing_time_sec: DEFAULT_SMOOTHING_TIME_SEC,
         }
     }
 
@@ -313,9 +426,42 @@ impl Sunfish {
 
         // TODO update GUI sample rate
 
+        self.dt = 1.0 / sample_rate;
+        self.smoother = ParamSmoother::new(
+            &self.meta,
+            &self.params,
+            sample_rate,
+            self.smoothing_time_sec,
+        );
+
         // Regenerate all waves.
         self.interpolator = Interpolator::new(sample_rate);
-        self.buf.clear();
+        for buf in self.osc1_bufs.iter_mut().chain(self.osc2_bufs.iter_mut()) {
+            buf.clear();
+        }
+    }
+
+    /// How long continuous parameters (gains, cutoff, tuning) take to follow changes from the
+    /// host or GUI; zero makes them immediate.
+    pub fn set_param_smoothing(&mut self, time_sec: f64) {
+        self.smoothing_time_sec = time_sec.max(0.0);
+        self.smoother = ParamSmoother::new(
+            &self.meta,
+            &self.params,
+            1.0 / self.dt,
+            self.smoothing_time_sec,
+        );
+    }
+
+    /// Pass along the host's song position (in quarter notes), if known, and whether the
+    /// transport is playing.
+    pub fn update_transport(&mut self, ppq_pos: Option<f64>, playing: bool) {
+        self.modulation.update_transport(
+            &self.params_modulated,
+            self.tempo.tempo_bps,
+            ppq_pos,
+            playing,
+        );
     }
 
     pub fn note_on(&mut self, note: u8, velocity: i8) {
@@ -323,31 +469,43 @@ impl Sunfish {
             return;
         }
 
-        // If there's an active, unreleased note, release it now.
-        for voice in self.voices.iter_mut().filter(|v| !v.note_released) {
-            if voice.base_note == note {
-                voice.release();
-            }
-        }
-
         let voice = Voice::new(&VoiceInfo {
             sample_rate: self.params.sample_rate,
             note,
             velocity,
             osc1_fine_offset: self.params_modulated.osc1.fine_offset,
+            osc1_detune: self.params_modulated.osc1.detune,
             osc1_semitones_offset: self.params_modulated.osc1.semitones_offset,
             osc1_octave_offset: self.params_modulated.osc1.octave_offset,
             osc2_fine_offset: self.params_modulated.osc2.fine_offset,
+            osc2_detune: self.params_modulated.osc2.detune,
             osc2_semitones_offset: self.params_modulated.osc2.semitones_offset,
             osc2_octave_offset: self.params_modulated.osc2.octave_offset,
             amp_adsr: self.params_modulated.amp_env,
             mod_adsr: self.params_modulated.mod_env,
+            mod2_adsr: self.params_modulated.mod_env2,
             params: &self.params_modulated,
             meta: &self.meta,
+            legato_from: self.voices.iter().rev().find(|v| !v.note_released),
         });
 
+        let first_note = self.voices.iter().all(|v| v.note_released);
+
+        // If there's an active, unreleased note, release it now.
+        for voice in self.voices.iter_mut().filter(|v| !v.note_released) {
+            if voice.base_note == note {
+                voice.release();
+            }
+        }
+
         self.voices.push_back(voice);
         self.active_voices += 1;
+        self.modulation.note_on(
+            &self.params_modulated,
+            note,
+            (velocity as f64 / MAX_VELOCITY).clamp(0.0, 1.0),
+            first_note,
+        );
     }
 
     pub fn note_off(&mut self, note: u8) {
@@ -358,6 +516,120 @@ impl Sunfish {
         }
     }
 
+    /// Release every held note, letting their envelopes finish.
+    pub fn all_notes_off(&mut self) {
+        for voice in self.voices.iter_mut() {
+            voice.release();
+        }
+    }
+
+    /// Stop every voice immediately, discarding their filter and envelope state.
+    pub fn all_sound_off(&mut self) {
+        self.voices.clear();
+        self.active_voices = 0;
+        self.modulation.set_mod_envs(0.0, 0.0);
+    }
+
+    /// Drop everything left over from earlier processing: voices, with their filter and envelope
+    /// state, and notes queued for the next buffer.
+    pub fn reset(&mut self) {
+        self.all_sound_off();
+        self.pending_notes.clear();
+    }
+
+    /// How many note events have been dropped because too many were queued for one buffer.
+    pub fn dropped_notes(&self) -> usize {
+        self.dropped_notes
+    }
+
+    /// The parameters as last received, before smoothing.
+    pub fn received_params(&self) -> &Params {
+        self.param_reader.latest()
+    }
+
+    /// How long a voice keeps sounding after its note is released, in samples.
+    pub fn tail_samples(&self) -> usize {
+        (self.params.amp_env.release * self.params.sample_rate).ceil() as usize
+    }
+
+    /// Schedule a note event `sample_offset` samples into the next call to
+    /// `render`. Events at the same offset apply in the order they're queued;
+    /// offsets past the end of the buffer apply once it has been rendered.
+    ///
+    /// Once `PENDING_NOTES_CAPACITY` events are queued, further ones are dropped (and counted)
+    /// rather than grow the queue on the audio thread.
+    pub fn queue_note(&mut self, sample_offset: usize, event: NoteEvent) {
+        if self.pending_notes.len() >= self.pending_notes.capacity() {
+            self.dropped_notes += 1;
+            return;
+        }
+        let index = self
+            .pending_notes
+            .iter()
+            .rposition(|(offset, _)| *offset <= sample_offset)
+            .map_or(0, |index| index + 1);
+        self.pending_notes.insert(index, (sample_offset, event));
+    }
+
+    /// Act on a MIDI message `sample_offset` samples into the next call to `render`, on any
+    /// channel. Only notes are placed within the buffer; the rest apply right away. Program
+    /// changes are left to the plugin, which has the presets.
+    pub fn handle_midi(&mut self, data: [u8; 3], sample_offset: usize) {
+        // The first byte's upper half says what kind of message this is, and its lower half the
+        // channel; see https://www.midi.org/specifications/item/table-1-summary-of-midi-message
+        match data[0] & 0xF0 {
+            // Note on; at velocity 0, it's taken as a note off.
+            0x90 if data[2] == 0 => {
+                self.queue_note(sample_offset, NoteEvent::Off { note: data[1] })
+            }
+            0x90 => {
+                let (note, velocity) = (data[1], data[2] as i8);
+                self.queue_note(sample_offset, NoteEvent::On { note, velocity });
+            }
+
+            // Note off.
+            0x80 => self.queue_note(sample_offset, NoteEvent::Off { note: data[1] }),
+
+            // Control change.
+            0xB0 => {
+                let (cc, value) = (data[1], data[2]);
+                match cc {
+                    // Mod wheel.
+                    1 => self.modulation.set_mod_wheel(value as f64 / 127.0),
+                    // All sound off.
+                    120 => self.queue_note(sample_offset, NoteEvent::AllSoundOff),
+                    // Reset all controllers.
+                    121 => self.modulation.reset_controllers(),
+                    // All notes off.
+                    123 => self.queue_note(sample_offset, NoteEvent::AllNotesOff),
+                    _ => (),
+                }
+                // CC120 and up are channel mode messages, not controllers.
+                if cc < 120 {
+                    self.params_sync.handle_cc(cc, value);
+                }
+            }
+
+            // Polyphonic key pressure. Modulation isn't per-voice, so the latest
+            // pressure from any key drives the aftertouch source.
+            0xA0 => self.modulation.set_aftertouch(data[2] as f64 / 127.0),
+
+            // Channel pressure (aftertouch).
+            0xD0 => self.modulation.set_aftertouch(data[1] as f64 / 127.0),
+
+            _ => (),
+        }
+    }
+
+    fn apply_note(&mut self, event: NoteEvent) {
+        match event {
+            NoteEvent::On { note, velocity } => self.note_on(note, velocity),
+            NoteEvent::Off { note } => self.note_off(note),
+            NoteEvent::AllNotesOff => self.all_notes_off(),
+            NoteEvent::AllSoundOff => self.all_sound_off(),
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn on_param_update(
         meta: &ParamsMeta,
@@ -387,7 +659,7 @@ impl Sunfish {
         // If this parameter isn't being modulated, reflect the change to
         // mod parameters. If it is being modulated, the modulation tick
         // will handle it.
-        if !modulation.mod_state.modulated_params.contains(&param) {
+        if !modulation.mod_state.modulated_params.contains(param) {
             params_modulated.write_parameter(meta, param, param_value);
         }
     }
@@ -399,64 +671,94 @@ impl Sunfish {
             // TODO: May need shape here.
             EParam::Osc1(EOscParams::SemitonesOffset)
             | EParam::Osc1(EOscParams::OctaveOffset)
-            | EParam::Osc1(EOscParams::FineOffset) => {
+            | EParam::Osc1(EOscParams::FineOffset)
+            | EParam::Osc1(EOscParams::Detune) => {
                 for voice in voices.iter_mut() {
                     voice.osc1_semitones_offset = params_modulated.osc1.semitones_offset;
                     voice.osc1_octave_offset = params_modulated.osc1.octave_offset;
                     voice.osc1_fine_offset = params_modulated.osc1.fine_offset;
+                    voice.osc1_detune = params_modulated.osc1.detune;
                     voice.update_osc1_freq();
                 }
             }
             EParam::Osc2(EOscParams::SemitonesOffset)
             | EParam::Osc2(EOscParams::OctaveOffset)
-            | EParam::Osc2(EOscParams::FineOffset) => {
+            | EParam::Osc2(EOscParams::FineOffset)
+            | EParam::Osc2(EOscParams::Detune) => {
                 for voice in voices.iter_mut() {
                     voice.osc2_semitones_offset = params_modulated.osc2.semitones_offset;
                     voice.osc2_octave_offset = params_modulated.osc2.octave_offset;
                     voice.osc2_fine_offset = params_modulated.osc2.fine_offset;
+                    voice.osc2_detune = params_modulated.osc2.detune;
                     voice.update_osc2_freq();
                 }
             }
             EParam::Filt1(EFiltParams::Mode) => {
                 for voice in voices.iter_mut() {
-                    for filter in voice.filter1.iter_mut() {
-                        filter.set_mode(&params_modulated.filt1.mode);
-                    }
+                    voice.filter1.set_mode(&params_modulated.filt1.mode);
                 }
             }
             EParam::Filt1(EFiltParams::Cutoff) => {
                 for voice in voices.iter_mut() {
-                    for filter in voice.filter1.iter_mut() {
-                        filter.set_cutoff(params_modulated.filt1.cutoff_semi);
-                    }
+                    voice.filter1.set_cutoff(params_modulated.filt1.cutoff_semi);
                 }
             }
             EParam::Filt1(EFiltParams::Resonance) => {
                 for voice in voices.iter_mut() {
-                    for filter in voice.filter1.iter_mut() {
-                        filter.set_resonance(params_modulated.filt1.resonance);
-                    }
+                    voice
+                        .filter1
+                        .set_resonance(params_modulated.filt1.resonance);
+                }
+            }
+            EParam::Filt1(EFiltParams::Vowel) => {
+                for voice in voices.iter_mut() {
+                    voice.filter1.set_vowel(params_modulated.filt1.vowel);
+                }
+            }
+            EParam::Filt1(EFiltParams::KeyTrack) => {
+                for voice in voices.iter_mut() {
+                    let key_track =
+                        key_track_semi(voice.base_note, params_modulated.filt1.key_track);
+                    voice.filter1.set_key_track(key_track);
+                }
+            }
+            EParam::Filt1(EFiltParams::ResComp) => {
+                for voice in voices.iter_mut() {
+                    voice.filter1.set_res_comp(params_modulated.filt1.res_comp);
                 }
             }
             EParam::Filt2(EFiltParams::Mode) => {
                 for voice in voices.iter_mut() {
-                    for filter in voice.filter2.iter_mut() {
-                        filter.set_mode(&params_modulated.filt2.mode);
-                    }
+                    voice.filter2.set_mode(&params_modulated.filt2.mode);
                 }
             }
             EParam::Filt2(EFiltParams::Cutoff) => {
                 for voice in voices.iter_mut() {
-                    for filter in voice.filter2.iter_mut() {
-                        filter.set_cutoff(params_modulated.filt2.cutoff_semi);
-                    }
+                    voice.filter2.set_cutoff(params_modulated.filt2.cutoff_semi);
                 }
             }
             EParam::Filt2(EFiltParams::Resonance) => {
                 for voice in voices.iter_mut() {
-                    for filter in voice.filter2.iter_mut() {
-                        filter.set_resonance(params_modulated.filt2.resonance);
-                    }
+                    voice
+                        .filter2
+                        .set_resonance(params_modulated.filt2.resonance);
+                }
+            }
+            EParam::Filt2(EFiltParams::Vowel) => {
+                for voice in voices.iter_mut() {
+                    voice.filter2.set_vowel(params_modulated.filt2.vowel);
+                }
+            }
+            EParam::Filt2(EFiltParams::KeyTrack) => {
+                for voice in voices.iter_mut() {
+                    let key_track =
+                        key_track_semi(voice.base_note, params_modulated.filt2.key_track);
+                    voice.filter2.set_key_track(key_track);
+                }
+            }
+            EParam::Filt2(EFiltParams::ResComp) => {
+                for voice in voices.iter_mut() {
+                    voice.filter2.set_res_comp(params_modulated.filt2.res_comp);
                 }
             }
             EParam::AmpEnv(_amp_env_param) => {
@@ -469,72 +771,185 @@ impl Sunfish {
                     voice.mod_envelope.update_adsr(&params_modulated.mod_env);
                 }
             }
+            EParam::ModEnv2(_mod_env_param) => {
+                for voice in voices.iter_mut() {
+                    voice.mod_envelope2.update_adsr(&params_modulated.mod_env2);
+                }
+            }
             _ => {}
         }
     }
 
+    /// Take the host's audio input for the next rendered buffer; the filter
+    /// section plays it instead of the oscillators when the sound source is
+    /// `SoundSource::ExternalInput`. Missing channels are silent.
+    pub fn write_input<F: Float>(&mut self, inputs: &[&[F]]) {
+        for (channel_idx, buf) in self.input_bufs.iter_mut().enumerate() {
+            buf.clear();
+            if let Some(input) = inputs.get(channel_idx) {
+                buf.extend(input.iter().map(|sample| sample.to_f64().unwrap_or(0.0)));
+            }
+        }
+    }
+
+    /// Render the next buffer, splitting it wherever a queued note event
+    /// falls so that notes start and stop on the exact sample.
+    ///
+    /// Given `MAX_CHANNEL_COUNT` outputs, the oscillators' chains go to
+    /// separate stereo pairs: filter 1 and oscillator 1's dry signal to the
+    /// first, filter 2 and oscillator 2's dry signal to the second. With the
+    /// default routing, that's one oscillator per pair.
     pub fn render<F: Float>(&mut self, outputs: &mut [&mut [F]]) {
+        self.read_param_updates();
+
+        let buf_len = outputs[0].len();
+        let mut start = 0;
+        for index in 0..self.pending_notes.len() {
+            let (offset, event) = self.pending_notes[index];
+            let offset = offset.min(buf_len);
+            if offset > start {
+                self.render_range(outputs, start, offset);
+                start = offset;
+            }
+            self.apply_note(event);
+        }
+        self.pending_notes.clear();
+        if start < buf_len {
+            self.render_range(outputs, start, buf_len);
+        }
+        self.params_sync.set_active_voices(self.active_voices);
+    }
+
+    fn render_range<F: Float>(&mut self, outputs: &mut [&mut [F]], start: usize, end: usize) {
+        // A fixed array of sub-slices, to avoid any heap allocations.
+        let mut block: [&mut [F]; MAX_CHANNEL_COUNT] = Default::default();
+        let channel_count = outputs.len().min(MAX_CHANNEL_COUNT);
+        for (block_channel, output_channel) in block.iter_mut().zip(outputs.iter_mut()) {
+            *block_channel = &mut output_channel[start..end];
+        }
+        self.render_block(&mut block[..channel_count], start);
+    }
+
+    fn read_param_updates(&mut self) {
         // TODO: Throttle this update to something more reasonable (~10khz?)
-        self.param_reader
-            .check_and_update(&mut self.last_epoch_recorded, |params, changes| {
-                self.params = params;
-
-                // if the existing epoch is newer than the last one we saw, apply changes to the
-                // mirror to ensure nothing is lost.
-                for (eparam, value) in changes {
-                    Self::on_param_update(
-                        &self.meta,
-                        &mut self.params,
-                        &mut self.params_modulated,
-                        &self.tempo,
-                        &mut self.voices,
-                        &mut self.modulation,
-                        *eparam,
-                        *value,
-                    );
+        self.param_reader.check_and_update(|params, changes| {
+            self.params.copy_from(params);
+            self.smoother.retarget(&self.meta, &mut self.params);
+
+            // Apply the changes to the modulated mirror too, to ensure nothing is lost.
+            for (eparam, value) in changes {
+                // Smoothed parameters are applied as they ramp, below.
+                if self.smoother.is_smoothed(*eparam) {
+                    continue;
                 }
-            });
+                Self::on_param_update(
+                    &self.meta,
+                    &mut self.params,
+                    &mut self.params_modulated,
+                    &self.tempo,
+                    &mut self.voices,
+                    &mut self.modulation,
+                    *eparam,
+                    *value,
+                );
+            }
+        });
+    }
 
+    /// Pass the modulated values of parameters on to the GUI, and clear any that are no longer
+    /// modulated.
+    fn publish_modulation(&mut self) {
+        let modulated_params = &self.modulation.mod_state.modulated_params;
+        let params_sync = &self.params_sync;
+        self.published_modulation.retain(|eparam| {
+            let modulated = modulated_params.contains(*eparam);
+            if !modulated {
+                params_sync.set_modulated_value(*eparam, None);
+            }
+            modulated
+        });
+        for eparam in modulated_params.iter() {
+            let value = self.params_modulated.read_parameter(&self.meta, eparam);
+            params_sync.set_modulated_value(eparam, Some(value));
+            if !self.published_modulation.contains(&eparam) {
+                self.published_modulation.push(eparam);
+            }
+        }
+    }
+
+    /// Render `outputs`, which start `start` samples into the host's buffer.
+    fn render_block<F: Float>(&mut self, outputs: &mut [&mut [F]], start: usize) {
         let buf_len = outputs[0].len();
         let buf_len_float = buf_len as f64;
-        self.buf.resize(buf_len, 0.0);
-
-        let delta_time = buf_len_float * self.dt;
-        let (update_eparam_lfo1, update_eparam_lfo2) =
-            self.modulation
-                .tick(delta_time, &self.params, &mut self.params_modulated);
-        if let Some(eparam_lfo1) = update_eparam_lfo1 {
-            Self::update_voices(&mut self.voices, &mut self.params_modulated, eparam_lfo1);
+        for buf in self.osc1_bufs.iter_mut().chain(self.osc2_bufs.iter_mut()) {
+            buf.resize(buf_len, 0.0);
         }
-        if let Some(eparam_lfo2) = update_eparam_lfo2 {
-            Self::update_voices(&mut self.voices, &mut self.params_modulated, eparam_lfo2);
+
+        for (eparam, value) in self.smoother.tick(buf_len, &self.meta, &mut self.params) {
+            Self::on_param_update(
+                &self.meta,
+                &mut self.params,
+                &mut self.params_modulated,
+                &self.tempo,
+                &mut self.voices,
+                &mut self.modulation,
+                *eparam,
+                *value,
+            );
         }
 
-        let osc1_enabled = self.params_modulated.osc1.enabled;
-        let osc2_enabled = self.params_modulated.osc2.enabled;
+        let delta_time = buf_len_float * self.dt;
+        let updated_params = self.modulation.tick(
+            delta_time,
+            &self.meta,
+            &self.params,
+            &mut self.params_modulated,
+        );
+        for eparam in updated_params {
+            Self::update_voices(&mut self.voices, &mut self.params_modulated, *eparam);
+        }
+        self.publish_modulation();
 
         let filter1_enabled = self.params_modulated.filt1.enable;
         let filter2_enabled = self.params_modulated.filt2.enable;
+        // Modulation updates once per buffer; ramp across the buffer rather than stepping.
+        let osc1_gain = (self.last_osc_gains.0, self.params_modulated.osc1.gain);
+        let osc2_gain = (self.last_osc_gains.1, self.params_modulated.osc2.gain);
+        let external_input = self.params_modulated.input.source == SoundSource::ExternalInput;
 
         for voice in self.voices.iter_mut() {
-            let freq_osc1 = voice.freq_osc1;
-            let freq_osc2 = voice.freq_osc2;
-            if freq_osc1 == 0.0 || freq_osc2 == 0.0 {
+            let voice_mod = self.modulation.voice_mod(
+                &self.params,
+                voice.mod_envelope.get_level(),
+                voice.mod_envelope2.get_level(),
+            );
+            if voice_mod != voice.voice_mod {
+                voice.voice_mod = voice_mod;
+                voice.update_osc1_freq();
+                voice.update_osc2_freq();
+            }
+            let freq_osc1 = (voice.last_freq_osc1, voice.freq_osc1);
+            let freq_osc2 = (voice.last_freq_osc2, voice.freq_osc2);
+            if voice.freq_osc1 == 0.0 || voice.freq_osc2 == 0.0 {
                 continue;
             }
+            voice.last_freq_osc1 = voice.freq_osc1;
+            voice.last_freq_osc2 = voice.freq_osc2;
             // First get the envelope, independent of channel.
-            self.amp_filt_env_buf.clear();
+            self.env_levels_buf.clear();
             let output_len = outputs[0].len();
-            if output_len > self.amp_filt_env_buf.len() {
-                self.amp_filt_env_buf.resize(output_len, (0.0, 0.0));
+            if output_len > self.env_levels_buf.len() {
+                self.env_levels_buf.resize(output_len, EnvLevels::default());
             }
             for env_i in 0..output_len {
                 voice.amp_envelope.next();
                 voice.mod_envelope.next();
-                self.amp_filt_env_buf[env_i] = (
-                    voice.amp_envelope.get_level(),
-                    voice.mod_envelope.get_level(),
-                );
+                voice.mod_envelope2.next();
+                self.env_levels_buf[env_i] = EnvLevels {
+                    amp: voice.amp_envelope.get_level(),
+                    mod1: voice.mod_envelope.get_level(),
+                    mod2: voice.mod_envelope2.get_level(),
+                };
             }
 
             // Check if we should drop the note.
@@ -542,64 +957,76 @@ impl Sunfish {
                 continue;
             }
 
+            // Oscillators always render in stereo, regardless of the output
+            // channel count, since the filters process both channels at once.
             let mut channel_idx_float = 0.0;
-            for (channel_idx, output_channel) in outputs.iter_mut().enumerate() {
-                let stereo_width = channel_idx_float * self.params_modulated.osc1.stereo_width;
-                if osc1_enabled {
-                    // Oscillator 1
-                    let filt = if filter1_enabled {
-                        Some(&mut voice.filter1[channel_idx])
-                    } else {
-                        None
-                    };
-                    Self::render_chain(
-                        &mut self.buf,
-                        self.dt,
-                        &mut self.interpolator,
-                        &mut voice.cached_waveforms_osc1[channel_idx],
-                        filt,
-                        freq_osc1,
-                        &self.amp_filt_env_buf,
-                        &mut voice.mod_state,
-                        self.params_modulated.filt1.cutoff_semi,
-                        self.params_modulated.filt1.env_amt,
-                        output_channel,
-                        stereo_width,
-                        &self.params_modulated.osc1.shape,
-                        &self.params_modulated.osc1.unison,
-                        self.params_modulated.osc1.unison_amt,
-                        self.params_modulated.osc1.gain,
-                    );
-                }
-
-                if osc2_enabled {
-                    // Oscillator 2
-                    let filt = if filter2_enabled {
-                        Some(&mut voice.filter2[channel_idx])
-                    } else {
-                        None
-                    };
-                    Self::render_chain(
-                        &mut self.buf,
-                        self.dt,
-                        &mut self.interpolator,
-                        &mut voice.cached_waveforms_osc2[channel_idx],
-                        filt,
-                        freq_osc2,
-                        &self.amp_filt_env_buf,
-                        &mut voice.mod_state,
-                        self.params_modulated.filt2.cutoff_semi,
-                        self.params_modulated.filt2.env_amt,
-                        output_channel,
-                        stereo_width,
-                        &self.params_modulated.osc2.shape,
-                        &self.params_modulated.osc2.unison,
-                        self.params_modulated.osc2.unison_amt,
-                        self.params_modulated.osc2.gain,
+            for channel_idx in 0..CHANNEL_COUNT {
+                if external_input {
+                    Self::render_input(
+                        &mut self.osc1_bufs[channel_idx],
+                        &self.input_bufs[channel_idx],
+                        start,
+                        osc1_gain,
+                        channel_idx_float,
+                        &self.params_modulated.osc1,
                     );
+                    for value in self.osc2_bufs[channel_idx].iter_mut() {
+                        *value = 0.0;
+                    }
+                    channel_idx_float += 1.0;
+                    continue;
                 }
+                Self::render_osc(
+                    &mut self.osc1_bufs[channel_idx],
+                    &mut self.interpolator,
+                    &mut voice.cached_waveforms_osc1[channel_idx],
+                    freq_osc1,
+                    osc1_gain,
+                    channel_idx_float,
+                    &self.params_modulated.osc1,
+                );
+                Self::render_osc(
+                    &mut self.osc2_bufs[channel_idx],
+                    &mut self.interpolator,
+                    &mut voice.cached_waveforms_osc2[channel_idx],
+                    freq_osc2,
+                    osc2_gain,
+                    channel_idx_float,
+                    &self.params_modulated.osc2,
+                );
                 channel_idx_float += 1.0;
             }
+
+            let filt1 = if filter1_enabled {
+                Some(&mut voice.filter1)
+            } else {
+                None
+            };
+            let filt2 = if filter2_enabled {
+                Some(&mut voice.filter2)
+            } else {
+                None
+            };
+            Self::render_chain(
+                &self.osc1_bufs,
+                &self.osc2_bufs,
+                self.dt,
+                filt1,
+                filt2,
+                &self.env_levels_buf,
+                &mut voice.mod_state,
+                &voice.voice_mod,
+                &self.params_modulated,
+                outputs,
+            );
+        }
+
+        // Per-note modulation sources follow the most recent note.
+        if let Some(voice) = self.voices.back() {
+            self.modulation.set_mod_envs(
+                voice.mod_envelope.get_level(),
+                voice.mod_envelope2.get_level(),
+            );
         }
 
         // // Drop all voices that have done playing.
@@ -612,77 +1039,425 @@ impl Sunfish {
             }
         }
 
-        for (_channel_idx, output_channel) in outputs.iter_mut().enumerate() {
+        self.last_osc_gains = (osc1_gain.1, osc2_gain.1);
+
+        // Apply global gain, ramping from the last buffer's.
+        let output_gain = self.params_modulated.output_gain;
+        let output_gain_step = (output_gain - self.last_output_gain) / buf_len_float;
+        for output_channel in outputs.iter_mut() {
+            let mut gain = self.last_output_gain;
             for output_sample in output_channel.iter_mut() {
-                // Apply global gain.
-                *output_sample =
-                    *output_sample * num::cast(self.params_modulated.output_gain).unwrap();
+                gain += output_gain_step;
+                *output_sample = *output_sample * num::cast(gain).unwrap();
             }
         }
+        self.last_output_gain = output_gain;
     }
 
+    /// Render a single oscillator (including its gain) into `buf`. Disabled
+    /// oscillators render silence.
+    ///
+    /// Frequency and gain are given as (start, end) pairs and ramp linearly
+    /// across the buffer; the frequency moves in steps of `PITCH_RAMP_BLOCK`
+    /// samples.
     #[allow(clippy::too_many_arguments)]
     #[inline(always)]
-    fn render_chain<F: Float>(
+    fn render_osc(
         buf: &mut [f64],
-        dt: f64, // Delta time per element of buf
         interpolator: &mut Interpolator,
         cached_waveform: &mut CachedWaveform,
-        mut voice_filter: Option<&mut Filter>,
-        f: f64,
-        amp_and_mod_env_levels: &[(f64, f64)],
-        voice_mod: &mut ModState,
-        cutoff_semi: f64,
-        filt_env_amount: f64,
-        output_channel: &mut [F],
-        stereo_width: f64,
-        shape: &WaveShape,
-        unison: &Unison,
-        unison_amt: f64,
-        osc_gain: f64,
+        f: (f64, f64),
+        gain: (f64, f64),
+        channel_idx_float: f64,
+        osc: &OscParams,
     ) {
-        // output_channel has type &mut [f64]
-        interpolator.populate(
-            *shape,               // shape
-            f + stereo_width,     // freq
-            buf,                  // output_buf
-            output_channel.len(), // output_count
-            cached_waveform,      // cached_waveform
-            *unison,              // unison
-            unison_amt,           // unison_amt
-        );
+        if !osc.enabled {
+            for value in buf.iter_mut() {
+                *value = 0.0;
+            }
+            return;
+        }
+        let stereo_width = channel_idx_float * osc.stereo_width;
+        let buf_len_float = buf.len() as f64;
+        let (f_start, f_end) = f;
+        let mut rendered = 0.0;
+        for chunk in buf.chunks_mut(PITCH_RAMP_BLOCK) {
+            let output_count = chunk.len();
+            rendered += output_count as f64;
+            // Aim for where the ramp ends up at the end of this chunk, so the
+            // last chunk lands exactly on the new frequency.
+            let freq = f_start + (f_end - f_start) * (rendered / buf_len_float);
+            interpolator.populate(
+                osc.shape,           // shape
+                freq + stereo_width, // freq
+                chunk,               // output_buf
+                output_count,        // output_count
+                cached_waveform,     // cached_waveform
+                osc.unison,          // unison
+                osc.unison_amt,      // unison_amt
+            );
+        }
+        Self::apply_gain(buf, gain, channel_idx_float, osc.pan);
+    }
+
+    /// Copy the host's input into `buf`, starting `start` samples in, and
+    /// apply oscillator 1's gain and pan to it. The input plays even with
+    /// oscillator 1 disabled.
+    #[inline(always)]
+    fn render_input(
+        buf: &mut [f64],
+        input: &[f64],
+        start: usize,
+        gain: (f64, f64),
+        channel_idx_float: f64,
+        osc: &OscParams,
+    ) {
+        let input = input.get(start..).unwrap_or(&[]);
+        for (index, value) in buf.iter_mut().enumerate() {
+            *value = input.get(index).copied().unwrap_or(0.0);
+        }
+        Self::apply_gain(buf, gain, channel_idx_float, osc.pan);
+    }
+
+    /// Scale `buf` by a gain ramping linearly from `gain.0` to `gain.1`,
+    /// balanced for the channel by `pan`.
+    #[inline(always)]
+    fn apply_gain(buf: &mut [f64], gain: (f64, f64), channel_idx_float: f64, pan: f64) {
+        // Balance: the far channel fades out as the signal pans away from it.
+        let side = 2.0 * channel_idx_float - 1.0;
+        let pan_gain = (1.0 + side * pan).min(1.0);
+        let (gain_start, gain_end) = (gain.0 * pan_gain, gain.1 * pan_gain);
+        let gain_step = (gain_end - gain_start) / buf.len() as f64;
+        let mut gain = gain_start;
+        for value in buf.iter_mut() {
+            gain += gain_step;
+            *value *= gain;
+        }
+    }
 
-        // Iterate over each sample in this channel, zipping with both
-        // the amplitude and mod envelopes.
+    /// Route both oscillators through the filters according to their filter
+    /// route, apply the amplitude envelope, and accumulate into the outputs.
+    /// Left and right are processed together as a stereo pair.
+    ///
+    /// Each filter's cutoff is also frequency-modulated at audio rate by the
+    /// other oscillator (filter 1 by oscillator 2, and vice versa), using the
+    /// mid (L+R) signal since both channels share coefficients.
+    #[allow(clippy::too_many_arguments)]
+    #[inline(always)]
+    fn render_chain<F: Float>(
+        osc1_bufs: &[Vec<f64>; CHANNEL_COUNT],
+        osc2_bufs: &[Vec<f64>; CHANNEL_COUNT],
+        dt: f64, // Delta time per element of buf
+        mut filter1: Option<&mut Filter>,
+        mut filter2: Option<&mut Filter>,
+        env_levels: &[EnvLevels],
+        voice_mod_state: &mut ModState,
+        voice_mod: &VoiceMod,
+        params: &Params,
+        outputs: &mut [&mut [F]],
+    ) {
+        let (osc1_to_filt1, osc1_to_filt2, osc1_dry) = params.osc1.filter_route.sends();
+        let (osc2_to_filt1, osc2_to_filt2, osc2_dry) = params.osc2.filter_route.sends();
+        let filt1_fm = params.filt1.fm_amt * FILTER_FM_RANGE_SEMI;
+        let filt2_fm = params.filt2.fm_amt * FILTER_FM_RANGE_SEMI;
+        let filt1_env_amt = params.filt1.env_amt * params.filt1.env_polarity.sign();
+        let filt2_env_amt = params.filt2.env_amt * params.filt2.env_polarity.sign();
+        let [osc1_left, osc1_right] = osc1_bufs;
+        let [osc2_left, osc2_right] = osc2_bufs;
+        let split_outputs = outputs.len() >= MAX_CHANNEL_COUNT;
+
+        // Iterate over each sample, along with the envelope levels.
         let mut i = 0.0;
-        for (value, amp_and_filt_env) in buf.iter_mut().zip(amp_and_mod_env_levels) {
-            let (amp_env, mod_env) = amp_and_filt_env;
-
-            let filtered = if let Some(ref mut filter) = voice_filter {
-                // Avoid cast in tight loop: let delta_time = (index as f64) * dt;
-                let delta_time = i * dt;
-                // Step the voice mod.
-                let did_modulate = voice_mod.tick(delta_time).is_some();
+        for (sample_idx, env_level) in env_levels.iter().take(osc1_left.len()).enumerate() {
+            // Avoid cast in tight loop: let delta_time = (index as f64) * dt;
+            let delta_time = i * dt;
+            // Step the voice mod.
+            let did_modulate = voice_mod_state.tick(delta_time).is_some();
+
+            let osc1 = F64x2::new(osc1_left[sample_idx], osc1_right[sample_idx]);
+            let osc2 = F64x2::new(osc2_left[sample_idx], osc2_right[sample_idx]);
+            let filt1_in = osc1 * osc1_to_filt1 + osc2 * osc2_to_filt1;
+            let filt2_in = osc1 * osc1_to_filt2 + osc2 * osc2_to_filt2;
+            let osc1_dry = osc1 * osc1_dry;
+            let osc2_dry = osc2 * osc2_dry;
+
+            let filt1_out = if let Some(ref mut filter) = filter1 {
                 if did_modulate {
                     // Apply the modulation. Filters only for now. Eventually,
                     // we can make these per-voice envelopes customizable.
 
                     // Since we've ticked, we need to compute the effective
                     // cutoff.
-                    let mod_env = mod_env * filt_env_amount;
-                    let modulated_cutoff = modulation::modulate(voice_mod, 0, cutoff_semi, mod_env);
+                    let env = env_level.get(params.filt1.env_source) * filt1_env_amt;
+                    let cutoff = params.filt1.cutoff_semi + voice_mod.filt1_cutoff;
+                    let modulated_cutoff = modulation::modulate(voice_mod_state, 0, cutoff, env);
                     filter.set_cutoff(modulated_cutoff);
                 }
-                filter.apply(*value)
+                filter.set_fm(osc2.mid() * filt1_fm);
+                filter.apply(filt1_in)
             } else {
-                *value
+                filt1_in
+            };
+            let filt2_out = if let Some(ref mut filter) = filter2 {
+                if did_modulate {
+                    let env = env_level.get(params.filt2.env_source) * filt2_env_amt;
+                    let cutoff = params.filt2.cutoff_semi + voice_mod.filt2_cutoff;
+                    let modulated_cutoff = modulation::modulate(voice_mod_state, 0, cutoff, env);
+                    filter.set_cutoff(modulated_cutoff);
+                }
+                filter.set_fm(osc1.mid() * filt2_fm);
+                filter.apply(filt2_in)
+            } else {
+                filt2_in
             };
 
-            *value = filtered * amp_env * osc_gain;
+            let values = if split_outputs {
+                let osc1_chain = ((filt1_out + osc1_dry) * env_level.amp).to_array();
+                let osc2_chain = ((filt2_out + osc2_dry) * env_level.amp).to_array();
+                [osc1_chain[0], osc1_chain[1], osc2_chain[0], osc2_chain[1]]
+            } else {
+                let mix =
+                    ((filt1_out + filt2_out + osc1_dry + osc2_dry) * env_level.amp).to_array();
+                [mix[0], mix[1], 0.0, 0.0]
+            };
+            for (output_channel, value) in outputs.iter_mut().zip(values.iter()) {
+                let output_sample = &mut output_channel[sample_idx];
+                *output_sample = *output_sample + num::cast(*value).unwrap();
+            }
             i += 1.0;
         }
-        for (output_sample, value) in output_channel.iter_mut().zip(buf) {
-            *output_sample = *output_sample + num::cast(*value).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::modulation::matrix::{MatrixTarget, ModSlot, ModSource};
+    use crate::params::EInputParams;
+
+    fn sunfish() -> Sunfish {
+        let sample_rate = 44100.0;
+        let meta = ParamsMeta::new();
+        let mut sync = Synchronizer::new(meta.clone(), Params::new(sample_rate));
+        let mailbox = sync.mailbox();
+        Sunfish::new(
+            meta,
+            sample_rate,
+            mailbox,
+            sync,
+            Modulation::new(sample_rate),
+            Tempo::new(1.0),
+        )
+    }
+
+    #[test]
+    fn notes_start_at_their_offset() {
+        let mut sunfish = sunfish();
+        sunfish.queue_note(
+            100,
+            NoteEvent::On {
+                note: 60,
+                velocity: 100,
+            },
+        );
+        let (mut left, mut right) = (vec![0.0f32; 256], vec![0.0f32; 256]);
+        sunfish.render(&mut [&mut left, &mut right]);
+
+        assert!(left[..100].iter().all(|sample| *sample == 0.0));
+        assert!(left[100..].iter().any(|sample| *sample != 0.0));
+        assert!(sunfish.pending_notes.is_empty());
+    }
+
+    #[test]
+    fn queued_notes_keep_their_order() {
+        let mut sunfish = sunfish();
+        sunfish.queue_note(20, NoteEvent::Off { note: 60 });
+        sunfish.queue_note(
+            10,
+            NoteEvent::On {
+                note: 62,
+                velocity: 1,
+            },
+        );
+        sunfish.queue_note(
+            20,
+            NoteEvent::On {
+                note: 64,
+                velocity: 1,
+            },
+        );
+        sunfish.queue_note(
+            0,
+            NoteEvent::On {
+                note: 60,
+                velocity: 1,
+            },
+        );
+
+        let offsets: Vec<_> = sunfish
+            .pending_notes
+            .iter()
+            .map(|(offset, _)| *offset)
+            .collect();
+        assert_eq!(offsets, vec![0, 10, 20, 20]);
+        assert_eq!(sunfish.pending_notes[2].1, NoteEvent::Off { note: 60 });
+    }
+
+    #[test]
+    fn queued_notes_past_capacity_are_dropped() {
+        let mut sunfish = sunfish();
+        let capacity = sunfish.pending_notes.capacity();
+        for offset in 0..capacity + 3 {
+            sunfish.queue_note(offset, NoteEvent::Off { note: 60 });
         }
+        assert_eq!(sunfish.pending_notes.len(), capacity);
+        assert_eq!(sunfish.pending_notes.capacity(), capacity);
+        assert_eq!(sunfish.dropped_notes(), 3);
+    }
+
+    #[test]
+    fn midi_notes_on_any_channel() {
+        let mut sunfish = sunfish();
+        sunfish.handle_midi([0x93, 60, 100], 0);
+        sunfish.handle_midi([0x93, 60, 0], 10);
+        sunfish.handle_midi([0x9F, 64, 100], 20);
+        sunfish.handle_midi([0x8F, 64, 0], 30);
+        let events: Vec<_> = sunfish
+            .pending_notes
+            .iter()
+            .map(|(_, event)| *event)
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                NoteEvent::On {
+                    note: 60,
+                    velocity: 100
+                },
+                NoteEvent::Off { note: 60 },
+                NoteEvent::On {
+                    note: 64,
+                    velocity: 100
+                },
+                NoteEvent::Off { note: 64 },
+            ]
+        );
+    }
+
+    #[test]
+    fn all_notes_and_sound_off() {
+        let mut sunfish = sunfish();
+        let (mut left, mut right) = (vec![0.0f32; 64], vec![0.0f32; 64]);
+        sunfish.note_on(60, 100);
+        sunfish.note_on(64, 100);
+
+        sunfish.queue_note(32, NoteEvent::AllNotesOff);
+        sunfish.render(&mut [&mut left, &mut right]);
+        assert_eq!(sunfish.voices.len(), 2);
+        assert_eq!(sunfish.params_sync.active_voices(), 2);
+        assert!(sunfish.voices.iter().all(|voice| voice.note_released));
+
+        sunfish.all_sound_off();
+        assert!(sunfish.voices.is_empty());
+        assert_eq!(sunfish.active_voices, 0);
+        let (mut left, mut right) = (vec![0.0f32; 64], vec![0.0f32; 64]);
+        sunfish.render(&mut [&mut left, &mut right]);
+        assert_eq!(sunfish.params_sync.active_voices(), 0);
+        assert!(left.iter().chain(right.iter()).all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn reset_drops_voices_and_queued_notes() {
+        let mut sunfish = sunfish();
+        sunfish.note_on(60, 100);
+        sunfish.queue_note(
+            16,
+            NoteEvent::On {
+                note: 64,
+                velocity: 100,
+            },
+        );
+
+        sunfish.reset();
+        assert!(sunfish.voices.is_empty());
+        assert!(sunfish.pending_notes.is_empty());
+        let (mut left, mut right) = (vec![0.0f32; 64], vec![0.0f32; 64]);
+        sunfish.render(&mut [&mut left, &mut right]);
+        assert!(left.iter().chain(right.iter()).all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn modulated_values_reach_the_gui() {
+        let mut sunfish = sunfish();
+        let gui_sync = sunfish.params_sync.clone();
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+        sunfish.params.mod_matrix[0] = ModSlot {
+            source: ModSource::Velocity,
+            target: MatrixTarget::Param(cutoff),
+            depth: 0.5,
+            ..ModSlot::default()
+        };
+        sunfish.note_on(60, 100);
+        let (mut left, mut right) = (vec![0.0f32; 256], vec![0.0f32; 256]);
+        sunfish.render(&mut [&mut left, &mut right]);
+        let modulated = sunfish
+            .params_modulated
+            .read_parameter(&sunfish.meta, cutoff);
+        assert_eq!(gui_sync.modulated_value(cutoff), Some(modulated));
+        assert_eq!(gui_sync.modulated_value(EParam::OutputGain), None);
+
+        sunfish.params.mod_matrix[0] = ModSlot::default();
+        sunfish.render(&mut [&mut left, &mut right]);
+        assert_eq!(gui_sync.modulated_value(cutoff), None);
+    }
+
+    #[test]
+    fn tail_follows_the_amp_release() {
+        let mut sunfish = sunfish();
+        sunfish.params.amp_env.release = 0.5;
+        assert_eq!(sunfish.tail_samples(), 22050);
+        sunfish.params.amp_env.release = 0.0;
+        assert_eq!(sunfish.tail_samples(), 0);
+    }
+
+    #[test]
+    fn split_outputs_carry_one_oscillator_each() {
+        let mut sunfish = sunfish();
+        sunfish
+            .params_sync
+            .write_parameter(EParam::Osc2(EOscParams::Enable), 0.0);
+        sunfish.note_on(60, 100);
+        let mut outputs = vec![vec![0.0f32; 256]; MAX_CHANNEL_COUNT];
+        let mut channels: Vec<&mut [f32]> = outputs.iter_mut().map(|c| &mut c[..]).collect();
+        sunfish.render(&mut channels);
+
+        let is_silent = |channel: &Vec<f32>| channel.iter().all(|sample| *sample == 0.0);
+        assert!(!is_silent(&outputs[0]) && !is_silent(&outputs[1]));
+        assert!(is_silent(&outputs[2]) && is_silent(&outputs[3]));
+    }
+
+    #[test]
+    fn external_input_replaces_the_oscillators() {
+        let mut sunfish = sunfish();
+        sunfish
+            .params_sync
+            .write_parameter(EParam::Input(EInputParams::Source), 1.0);
+        sunfish.note_on(60, 100);
+
+        let render = |sunfish: &mut Sunfish, input: f32| {
+            let inputs = vec![vec![input; 256]; CHANNEL_COUNT];
+            let inputs: Vec<&[f32]> = inputs.iter().map(|c| &c[..]).collect();
+            sunfish.write_input(&inputs);
+            let (mut left, mut right) = (vec![0.0f32; 256], vec![0.0f32; 256]);
+            sunfish.render(&mut [&mut left, &mut right]);
+            left.iter().chain(right.iter()).all(|sample| *sample == 0.0)
+        };
+        // Silent input stays silent despite the held note.
+        assert!(render(&mut sunfish, 0.0));
+        assert_eq!(
+            sunfish.params_modulated.input.source,
+            SoundSource::ExternalInput
+        );
+        assert!(!render(&mut sunfish, 0.5));
     }
 }
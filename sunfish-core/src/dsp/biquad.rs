@@ -1,3 +1,4 @@
+use crate::dsp::simd::F64x2;
 use crate::dsp::TAU;
 
 #[derive(Debug)]
@@ -69,6 +70,23 @@ impl BiquadCoefs {
         BiquadCoefs::from_h_terms(b0, b1, b2, a0, a1, a2)
     }
 
+    /// Band-pass with a constant 0 dB peak gain.
+    pub fn bpf_peak(sample_rate: f64, f0: f64, q: f64) -> Self {
+        let w0 = TAU * (f0 / sample_rate);
+        let cos_w0 = w0.cos();
+        let sin_w0 = w0.sin();
+        let alpha = sin_w0 / (2.0 * q);
+
+        let b0 = alpha;
+        let b1 = 0.0;
+        let b2 = -alpha;
+        let a0 = 1.0 + alpha;
+        let a1 = -2.0 * cos_w0;
+        let a2 = 1.0 - alpha;
+
+        BiquadCoefs::from_h_terms(b0, b1, b2, a0, a1, a2)
+    }
+
     fn from_h_terms(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
         let c0 = b0 / a0;
         let c1 = b1 / a0;
@@ -161,6 +179,54 @@ pub fn biquad_direct_form_apply(
     (c0 * xn) + (c1 * xn1) + (c2 * xn2) + (c3 * yn1) + (c4 * yn2)
 }
 
+/// Biquad history for a stereo pair; both channels share the coefficients and
+/// are computed together.
+#[derive(Debug)]
+pub struct StereoBiquadState {
+    xn1: F64x2,
+    xn2: F64x2,
+    yn1: F64x2,
+    yn2: F64x2,
+}
+
+impl StereoBiquadState {
+    pub fn new() -> Self {
+        StereoBiquadState {
+            xn1: F64x2::zero(),
+            xn2: F64x2::zero(),
+            yn1: F64x2::zero(),
+            yn2: F64x2::zero(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn apply(&mut self, input: F64x2, coefficients: &BiquadCoefs) -> F64x2 {
+        let BiquadCoefs { c0, c1, c2, c3, c4 } = *coefficients;
+        let output =
+            (input * c0) + (self.xn1 * c1) + (self.xn2 * c2) + (self.yn1 * c3) + (self.yn2 * c4);
+        self.xn2 = self.xn1;
+        self.xn1 = input;
+        self.yn2 = self.yn1;
+        self.yn1 = output;
+        output
+    }
+
+    /// Like `apply`, but soft clips the output (and so the feedback path),
+    /// keeping a highly resonant filter bounded.
+    #[inline(always)]
+    pub fn apply_saturated(&mut self, input: F64x2, coefficients: &BiquadCoefs) -> F64x2 {
+        let output = self.apply(input, coefficients).soft_clip();
+        self.yn1 = output;
+        output
+    }
+}
+
+impl Default for StereoBiquadState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -211,6 +277,27 @@ mod test {
         assert_similar_f64(&result, &expected, 1e8);
     }
 
+    #[test]
+    fn stereo_state_matches_mono() {
+        let coeffs = BiquadCoefs::lpf(SAMPLING_RATE, 123.45, 1.01);
+        let left = vec![1.0, 2.0, 0.5, 1.5, 2.5, -0.5, -1.0, 1.25];
+        let right: Vec<f64> = left.iter().map(|x| -2.0 * x).collect();
+        let expected_left = biquad_direct_form(&left, &coeffs, 0.0, 0.0, 0.0, 0.0);
+        let expected_right = biquad_direct_form(&right, &coeffs, 0.0, 0.0, 0.0, 0.0);
+
+        let mut state = StereoBiquadState::new();
+        let (result_left, result_right): (Vec<f64>, Vec<f64>) = left
+            .iter()
+            .zip(right.iter())
+            .map(|(l, r)| {
+                let [l, r] = state.apply(F64x2::new(*l, *r), &coeffs).to_array();
+                (l, r)
+            })
+            .unzip();
+        assert_similar_f64(&result_left, &expected_left, 1e8);
+        assert_similar_f64(&result_right, &expected_right, 1e8);
+    }
+
     #[test]
     fn biquad_direct_form_in_place_values() {
         let f0 = 123.45;
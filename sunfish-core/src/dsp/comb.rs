@@ -0,0 +1,97 @@
+// Feedback comb filter with a fractional (linearly interpolated) delay.
+use crate::dsp::simd::F64x2;
+
+/// Lowest frequency (Hz) the comb can be tuned to; this bounds the delay line.
+pub const MIN_COMB_FREQ: f64 = 20.0;
+/// Feedback is capped below unity to keep the filter stable.
+pub const MAX_COMB_FEEDBACK: f64 = 0.98;
+
+#[derive(Debug)]
+pub struct CombFilter {
+    // Stereo pairs; both channels share the delay and feedback.
+    buffer: Vec<F64x2>,
+    write_idx: usize,
+    delay_samples: f64,
+    feedback: f64,
+}
+
+impl CombFilter {
+    pub fn new(sample_rate: f64) -> Self {
+        // Leave room for the interpolated read.
+        let len = (sample_rate / MIN_COMB_FREQ).ceil() as usize + 2;
+        CombFilter {
+            buffer: vec![F64x2::zero(); len],
+            write_idx: 0,
+            delay_samples: 1.0,
+            feedback: 0.0,
+        }
+    }
+
+    /// Tune the comb; the delay is one period of `freq_hz`.
+    pub fn set_frequency(&mut self, sample_rate: f64, freq_hz: f64) {
+        let max_delay = (self.buffer.len() - 2) as f64;
+        self.delay_samples = (sample_rate / freq_hz).clamp(1.0, max_delay);
+    }
+
+    /// Set the feedback gain; negative values give the "negative" comb
+    /// (odd harmonics only, an octave down).
+    pub fn set_feedback(&mut self, feedback: f64) {
+        self.feedback = feedback.clamp(-MAX_COMB_FEEDBACK, MAX_COMB_FEEDBACK);
+    }
+
+    pub fn apply(&mut self, input: F64x2) -> F64x2 {
+        let len = self.buffer.len();
+        let read_pos = self.write_idx as f64 + len as f64 - self.delay_samples;
+        let read_floor = read_pos.floor();
+        let eta = read_pos - read_floor;
+        let idx0 = read_floor as usize % len;
+        let idx1 = (idx0 + 1) % len;
+        let delayed = (self.buffer[idx0] * (1.0 - eta)) + (self.buffer[idx1] * eta);
+
+        let output = input + delayed * self.feedback;
+        self.buffer[self.write_idx] = output;
+        self.write_idx = (self.write_idx + 1) % len;
+
+        // Normalize so that the resonant peaks sit at unity gain.
+        output * (1.0 - self.feedback.abs())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 44100.0;
+
+    #[test]
+    fn impulse_repeats_at_delay() {
+        let mut comb = CombFilter::new(SAMPLE_RATE);
+        // 100 samples of delay.
+        comb.set_frequency(SAMPLE_RATE, SAMPLE_RATE / 100.0);
+        comb.set_feedback(0.5);
+        let output: Vec<f64> = (0..201)
+            .map(|i| {
+                comb.apply(F64x2::splat(if i == 0 { 1.0 } else { 0.0 }))
+                    .to_array()[0]
+            })
+            .collect();
+        assert!((output[0] - 0.5).abs() < 1e-9);
+        assert!((output[100] - 0.25).abs() < 1e-9);
+        assert!((output[200] - 0.125).abs() < 1e-9);
+        assert!(output[50].abs() < 1e-9);
+    }
+
+    #[test]
+    fn negative_feedback_inverts_echoes() {
+        let mut comb = CombFilter::new(SAMPLE_RATE);
+        comb.set_frequency(SAMPLE_RATE, SAMPLE_RATE / 10.0);
+        comb.set_feedback(-0.5);
+        let output: Vec<f64> = (0..11)
+            .map(|i| {
+                comb.apply(F64x2::splat(if i == 0 { 1.0 } else { 0.0 }))
+                    .to_array()[0]
+            })
+            .collect();
+        assert!(output[10] < 0.0);
+    }
+}
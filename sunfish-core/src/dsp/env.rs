@@ -1,14 +1,52 @@
 /// Envelope generator.
+use params_derive::ParamFields;
 use serde::{Deserialize, Serialize};
 
+use crate::params::EAdsrParams;
 use crate::util;
+use crate::util::enumerable::Enumerable;
 
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+/// Steepness of the most extreme stage curve (at +/-1.0).
+const CURVE_STEEPNESS: f64 = 6.0;
+pub const MIN_CURVE: f64 = -1.0;
+pub const MAX_CURVE: f64 = 1.0;
+
+#[derive(Clone, Copy, Debug, Deserialize, ParamFields, Serialize)]
+#[params(EAdsrParams)]
 pub struct ADSR {
+    /// Time before the attack starts.
+    #[param(Delay, meta = "delay_meta")]
+    pub delay: f64,
+    #[param(Attack, meta = "attack_meta")]
     pub attack: f64,
+    /// Time spent at the peak before the decay starts.
+    #[param(Hold, meta = "hold_meta")]
+    pub hold: f64,
+    #[param(Decay, meta = "decay_meta")]
     pub decay: f64,
+    #[param(Sustain, meta = "sustain_meta")]
     pub sustain: f64,
+    #[param(Release, meta = "release_meta")]
     pub release: f64,
+    // Per-stage curvature: 0.0 is linear, positive values are exponential
+    // (fast start, slow finish, like an analog RC envelope), negative values
+    // are logarithmic (slow start, fast finish).
+    #[param(AttackCurve, meta = "curve_meta")]
+    pub attack_curve: f64,
+    #[param(DecayCurve, meta = "curve_meta")]
+    pub decay_curve: f64,
+    #[param(ReleaseCurve, meta = "curve_meta")]
+    pub release_curve: f64,
+    /// Restart on every note-on; otherwise, notes played while another is
+    /// held (legato) continue from that note's envelope.
+    #[param(Retrigger, meta = "retrigger_meta")]
+    pub retrigger: bool,
+    // Velocity sensitivity (0.0 to 1.0): how much softer notes lower the peak
+    // level, and how much harder notes shorten the attack.
+    #[param(VelToLevel, meta = "vel_amt_meta")]
+    pub vel_to_level: f64,
+    #[param(VelToAttack, meta = "vel_amt_meta")]
+    pub vel_to_attack: f64,
 }
 
 impl ADSR {
@@ -19,6 +57,7 @@ impl ADSR {
             decay,
             sustain,
             release,
+            ..ADSR::default()
         }
     }
 }
@@ -26,10 +65,88 @@ impl ADSR {
 impl Default for ADSR {
     fn default() -> Self {
         ADSR {
+            delay: 0.0,
             attack: 0.01,
+            hold: 0.0,
             decay: 0.02,
             sustain: 0.80,
             release: 0.01,
+            attack_curve: 0.0,
+            decay_curve: 0.5,
+            release_curve: 0.5,
+            retrigger: true,
+            vel_to_level: 0.0,
+            vel_to_attack: 0.0,
+        }
+    }
+}
+
+/// Map linear progress through a stage (0.0 to 1.0) onto the stage's curve.
+#[inline(always)]
+fn curve_shape(t: f64, curve: f64) -> f64 {
+    if curve.abs() < 1e-3 {
+        return t;
+    }
+    let k = curve * CURVE_STEEPNESS;
+    (1.0 - (-k * t).exp()) / (1.0 - (-k).exp())
+}
+
+/// Which envelope drives a destination (e.g. a filter's cutoff).
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum EnvSource {
+    Amp,
+    Mod,
+    Mod2,
+}
+
+impl EnvSource {
+    pub fn as_string(self) -> String {
+        match self {
+            EnvSource::Amp => "Amp Env".to_string(),
+            EnvSource::Mod => "Mod Env".to_string(),
+            EnvSource::Mod2 => "Mod Env 2".to_string(),
+        }
+    }
+}
+
+impl Enumerable<EnvSource> for EnvSource {
+    fn enumerate() -> Vec<EnvSource> {
+        vec![EnvSource::Amp, EnvSource::Mod, EnvSource::Mod2]
+    }
+}
+
+impl From<EnvSource> for String {
+    fn from(f: EnvSource) -> String {
+        f.as_string()
+    }
+}
+
+impl From<String> for EnvSource {
+    fn from(s: String) -> EnvSource {
+        match s.as_ref() {
+            "Amp Env" => EnvSource::Amp,
+            "Mod Env" => EnvSource::Mod,
+            "Mod Env 2" => EnvSource::Mod2,
+            _ => EnvSource::Mod,
+        }
+    }
+}
+
+/// A voice's envelope levels at one point in time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EnvLevels {
+    pub amp: f64,
+    pub mod1: f64,
+    pub mod2: f64,
+}
+
+impl EnvLevels {
+    #[inline(always)]
+    pub fn get(&self, source: EnvSource) -> f64 {
+        match source {
+            EnvSource::Amp => self.amp,
+            EnvSource::Mod => self.mod1,
+            EnvSource::Mod2 => self.mod2,
         }
     }
 }
@@ -37,65 +154,90 @@ impl Default for ADSR {
 #[derive(Clone, Debug, Eq, PartialEq)]
 enum ADSRStage {
     Idle,
+    Delay,
     Attack,
+    Hold,
     Sustain,
     Decay,
     Release,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Env {
     level: f64,
     stage: ADSRStage,
-    target_level_opt: Option<f64>,
-    coeff: f64,
+    // Progress through the current stage, from 0.0 to 1.0, and its per-sample
+    // increment.
+    phase: f64,
+    phase_inc: f64,
+    start_level: f64,
+    target_level: f64,
+    curve: f64,
+    // Note velocity, from 0.0 to 1.0.
+    velocity: f64,
     sample_rate: f64,
     adsr: ADSR,
 }
 
 /*
- * Envelopes are generated using a coefficient that approximates exponential decay. Since the
- * heuristics don't work well with nonzero start/ends, we compute a base level, then apply a scale
- * and offset.
+ * Each stage moves from the level it was entered at to its target level over the stage's time.
+ * Progress through the stage is linear; the stage's curve then bends it.
  */
 impl Env {
     pub fn new(adsr: ADSR, sample_rate: f64) -> Env {
         Env {
             level: 0.0,
             stage: ADSRStage::Idle,
-            target_level_opt: None,
-            coeff: 0.0,
+            phase: 0.0,
+            phase_inc: 0.0,
+            start_level: 0.0,
+            target_level: 0.0,
+            curve: 0.0,
+            velocity: 1.0,
             sample_rate,
             adsr,
         }
     }
 
+    /// Set the note velocity (0.0 to 1.0), which scales the attack time and
+    /// peak level according to the ADSR's velocity sensitivity.
+    pub fn set_velocity(&mut self, velocity: f64) {
+        self.velocity = velocity.clamp(0.0, 1.0);
+    }
+
+    #[inline(always)]
+    fn peak_level(&self) -> f64 {
+        1.0 - self.adsr.vel_to_level * (1.0 - self.velocity)
+    }
+
+    #[inline(always)]
+    fn attack_time(&self) -> f64 {
+        self.adsr.attack * (1.0 - self.adsr.vel_to_attack * self.velocity)
+    }
+
     pub fn next(&mut self) {
         util::undenormalize(&mut self.level);
-        if let Some(target_level) = self.target_level_opt {
-            match self.stage {
-                ADSRStage::Attack => {
-                    if self.level >= target_level {
-                        self.enter_stage(ADSRStage::Decay);
-                        return;
-                    }
-                }
-                ADSRStage::Decay => {
-                    if self.level <= target_level {
-                        self.enter_stage(ADSRStage::Sustain);
-                        return;
-                    }
-                }
-                ADSRStage::Release => {
-                    if self.level <= target_level {
-                        self.enter_stage(ADSRStage::Idle);
-                        return;
+        match self.stage {
+            ADSRStage::Idle | ADSRStage::Sustain => {}
+            _ => {
+                self.phase += self.phase_inc;
+                if self.phase >= 1.0 {
+                    self.level = self.target_level;
+                    match self.stage {
+                        ADSRStage::Delay => self.enter_stage(ADSRStage::Attack),
+                        ADSRStage::Attack if self.adsr.hold > 0.0 => {
+                            self.enter_stage(ADSRStage::Hold)
+                        }
+                        ADSRStage::Attack | ADSRStage::Hold => self.enter_stage(ADSRStage::Decay),
+                        ADSRStage::Decay => self.enter_stage(ADSRStage::Sustain),
+                        _ => self.enter_stage(ADSRStage::Idle),
                     }
+                    return;
                 }
-                _ => {}
+                self.level = self.start_level
+                    + (self.target_level - self.start_level) * curve_shape(self.phase, self.curve);
             }
         }
-        self.level *= self.coeff;
     }
 
     pub fn get_level(&self) -> f64 {
@@ -103,9 +245,30 @@ impl Env {
     }
 
     pub fn start(&mut self) {
-        // enter the attack stage
+        // enter the delay stage, or go straight to the attack if there's none
         self.level = 0.0;
-        self.enter_stage(ADSRStage::Attack);
+        if self.adsr.delay > 0.0 {
+            self.enter_stage(ADSRStage::Delay);
+        } else {
+            self.enter_stage(ADSRStage::Attack);
+        }
+    }
+
+    /// Start legato: pick up from another (held) envelope's current stage and
+    /// level, rather than restarting from silence.
+    pub fn start_legato(&mut self, other: &Env) {
+        match other.stage {
+            ADSRStage::Idle | ADSRStage::Release => self.start(),
+            _ => {
+                self.level = other.level;
+                self.stage = other.stage.clone();
+                self.phase = other.phase;
+                self.phase_inc = other.phase_inc;
+                self.start_level = other.start_level;
+                self.target_level = other.target_level;
+                self.curve = other.curve;
+            }
+        }
     }
 
     pub fn release(&mut self) {
@@ -118,23 +281,38 @@ impl Env {
     fn enter_stage(&mut self, stage: ADSRStage) {
         match stage {
             ADSRStage::Idle => {
-                self.coeff = 1.0;
-                self.target_level_opt = None;
+                self.level = 0.0;
+                self.hold();
+            }
+            ADSRStage::Delay => {
+                // Stay silent.
+                self.begin_segment(self.adsr.delay, 0.0, 0.0);
             }
             ADSRStage::Attack => {
-                // Ramp up to 1.0
-                self.calc_coeff(self.adsr.attack, 1.0);
+                // Ramp up to the peak
+                self.begin_segment(
+                    self.attack_time(),
+                    self.peak_level(),
+                    self.adsr.attack_curve,
+                );
+            }
+            ADSRStage::Hold => {
+                // Stay at the peak.
+                self.begin_segment(self.adsr.hold, self.peak_level(), 0.0);
             }
             ADSRStage::Sustain => {
                 // Keep the current level;
-                self.coeff = 1.0;
-                self.target_level_opt = None;
+                self.hold();
             }
             ADSRStage::Decay => {
-                self.calc_coeff(self.adsr.decay, self.adsr.sustain);
+                self.begin_segment(
+                    self.adsr.decay,
+                    self.adsr.sustain * self.peak_level(),
+                    self.adsr.decay_curve,
+                );
             }
             ADSRStage::Release => {
-                self.calc_coeff(self.adsr.release, 0.0);
+                self.begin_segment(self.adsr.release, 0.0, self.adsr.release_curve);
             }
         }
         self.stage = stage;
@@ -144,26 +322,33 @@ impl Env {
         self.stage == ADSRStage::Idle
     }
 
-    fn calc_coeff(&mut self, mut time: f64, mut target_level: f64) {
-        // make sure coeff is never inf or nan
-        const ALMOST_ZERO: f64 = 1e-3;
-        if target_level == 0.0 {
-            target_level = ALMOST_ZERO;
-        }
-        if self.level == 0.0 {
-            self.level = ALMOST_ZERO;
-        }
-        if time == 0.0 {
-            time = ALMOST_ZERO;
-        }
-        self.coeff = 1.0 + (target_level.ln() - self.level.ln()) / (time * self.sample_rate);
-        self.target_level_opt = Some(target_level);
+    fn hold(&mut self) {
+        self.phase = 0.0;
+        self.phase_inc = 0.0;
+        self.start_level = self.level;
+        self.target_level = self.level;
+    }
+
+    fn begin_segment(&mut self, time: f64, target_level: f64, curve: f64) {
+        let samples = time * self.sample_rate;
+        self.phase = 0.0;
+        // Segments shorter than a sample complete on the next step.
+        self.phase_inc = if samples > 1.0 { 1.0 / samples } else { 1.0 };
+        self.start_level = self.level;
+        self.target_level = target_level;
+        self.curve = curve;
     }
 
     pub fn update_adsr(&mut self, adsr: &ADSR) {
         self.adsr = *adsr;
-        // Re-enter the stage; the level stays as is, so we should be okay.
+        // Re-enter the stage to pick up the new time and target, keeping its progress so frequent
+        // updates (e.g. from modulation) don't keep restarting it.
+        let (phase, start_level) = (self.phase, self.start_level);
         self.enter_stage(self.stage.clone());
+        if self.phase_inc > 0.0 {
+            self.phase = phase;
+            self.start_level = start_level;
+        }
     }
 }
 
@@ -202,6 +387,100 @@ mod test {
         assert_eq!(eg.stage, ADSRStage::Decay);
     }
 
+    #[test]
+    fn delay_and_hold() {
+        let mut adsr = ADSR::new(0.001, 0.001, 0.5, 0.001);
+        adsr.delay = 0.001;
+        adsr.hold = 0.001;
+        let mut eg = Env::new(adsr, SAMPLE_RATE);
+        let samples = (1e-3 / DT) as usize;
+        eg.start();
+        assert_eq!(eg.stage, ADSRStage::Delay);
+        for _ in 0..samples - 1 {
+            eg.next();
+            assert_eq!(eg.get_level(), 0.0);
+        }
+        for _ in 0..samples + 3 {
+            eg.next();
+        }
+        assert_eq!(eg.stage, ADSRStage::Hold);
+        for _ in 0..samples - 2 {
+            eg.next();
+            assert_eq!(eg.get_level(), 1.0);
+        }
+        for _ in 0..3 {
+            eg.next();
+        }
+        assert_eq!(eg.stage, ADSRStage::Decay);
+    }
+
+    #[test]
+    fn legato_continues_held_envelope() {
+        let mut held = Env::new(default_adsr(), SAMPLE_RATE);
+        held.start();
+        for _ in 0..100 {
+            held.next();
+        }
+        let mut eg = Env::new(default_adsr(), SAMPLE_RATE);
+        eg.start_legato(&held);
+        assert_eq!(eg.stage, held.stage);
+        assert_eq!(eg.get_level(), held.get_level());
+
+        // A released envelope isn't held, so start over.
+        held.release();
+        eg.start_legato(&held);
+        assert_eq!(eg.stage, ADSRStage::Attack);
+        assert_eq!(eg.get_level(), 0.0);
+    }
+
+    #[test]
+    fn velocity_scales_peak_and_attack() {
+        let mut adsr = ADSR::new(0.01, 0.01, 0.5, 0.01);
+        adsr.hold = 1.0;
+        adsr.vel_to_level = 0.5;
+        adsr.vel_to_attack = 0.5;
+        let attack_samples = |velocity: f64| {
+            let mut eg = Env::new(adsr, SAMPLE_RATE);
+            eg.set_velocity(velocity);
+            eg.start();
+            let mut samples = 0;
+            while eg.stage == ADSRStage::Attack {
+                eg.next();
+                samples += 1;
+            }
+            (samples, eg.get_level())
+        };
+        let (soft_samples, soft_peak) = attack_samples(0.0);
+        let (hard_samples, hard_peak) = attack_samples(1.0);
+        assert!((soft_peak - 0.5).abs() < 1e-9);
+        assert!((hard_peak - 1.0).abs() < 1e-9);
+        assert!(hard_samples < soft_samples);
+    }
+
+    #[test]
+    fn curves_bend_segments() {
+        // Halfway through a release, an exponential curve has fallen further
+        // than a linear one, and a logarithmic curve less.
+        let release_level = |curve: f64| {
+            let mut adsr = ADSR::new(0.0, 0.0, 1.0, 0.01);
+            adsr.release_curve = curve;
+            let mut eg = Env::new(adsr, SAMPLE_RATE);
+            eg.start();
+            for _ in 0..4 {
+                eg.next();
+            }
+            eg.release();
+            for _ in 0..(0.005 * SAMPLE_RATE) as usize {
+                eg.next();
+            }
+            eg.get_level()
+        };
+        let linear = release_level(0.0);
+        assert!((linear - 0.5).abs() < 0.01);
+        assert!(release_level(1.0) < linear);
+        assert!(release_level(-1.0) > linear);
+    }
+
     fn default_adsr() -> ADSR {
         ADSR::new(0.001, 0.002, 0.8, 0.003)
     }
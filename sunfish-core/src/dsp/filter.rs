@@ -1,8 +1,11 @@
 use serde::{Deserialize, Serialize};
 
-use crate::dsp::biquad::{biquad_direct_form_apply, BiquadCoefs};
+use crate::dsp::biquad::{BiquadCoefs, StereoBiquadState};
+use crate::dsp::comb::{CombFilter, MAX_COMB_FEEDBACK};
+use crate::dsp::formant::FormantFilter;
+use crate::dsp::simd::F64x2;
 use crate::dsp::smoothing::SlewRateLimiter;
-use crate::params::MIN_CUTOFF_FREQ;
+use crate::params::{MAX_RESONANCE, MIN_CUTOFF_FREQ, MIN_RESONANCE};
 use crate::util;
 use crate::util::enumerable::Enumerable;
 
@@ -14,6 +17,13 @@ const SLEW_RATE_HZ: f64 = 1000.0;
 const SLEW_RATE_S: f64 = 1.0 / SLEW_RATE_HZ;
 const SLEW_THRESHOLD_SEMIS: f64 = 0.001;
 const SLEW_THRESHOLD_RES: f64 = 0.001;
+const SLEW_THRESHOLD_VOWEL: f64 = 0.001;
+/// How far (in semitones) filter FM has to move before the coefficients follow it.
+const FM_THRESHOLD_SEMIS: f64 = 0.05;
+/// Highest cutoff, as a fraction of the sample rate.
+const MAX_CUTOFF_RATIO: f64 = 0.45;
+/// Q of a maximally flat (Butterworth) response; resonance above this peaks.
+const BUTTERWORTH_Q: f64 = std::f64::consts::FRAC_1_SQRT_2;
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum FilterMode {
@@ -21,6 +31,9 @@ pub enum FilterMode {
     HighPass,
     BandPass,
     PassThru,
+    Formant,
+    CombPositive,
+    CombNegative,
 }
 
 impl Enumerable<FilterMode> for FilterMode {
@@ -30,6 +43,9 @@ impl Enumerable<FilterMode> for FilterMode {
             FilterMode::HighPass,
             FilterMode::BandPass,
             FilterMode::PassThru,
+            FilterMode::Formant,
+            FilterMode::CombPositive,
+            FilterMode::CombNegative,
         ]
     }
 }
@@ -41,6 +57,9 @@ impl From<FilterMode> for String {
             FilterMode::HighPass => "HighPass".to_string(),
             FilterMode::BandPass => "BandPass".to_string(),
             FilterMode::PassThru => "PassThru".to_string(),
+            FilterMode::Formant => "Formant".to_string(),
+            FilterMode::CombPositive => "Comb+".to_string(),
+            FilterMode::CombNegative => "Comb-".to_string(),
         }
     }
 }
@@ -52,6 +71,9 @@ impl From<String> for FilterMode {
             "HighPass" => FilterMode::HighPass,
             "BandPass" => FilterMode::BandPass,
             "PassThru" => FilterMode::PassThru,
+            "Formant" => FilterMode::Formant,
+            "Comb+" => FilterMode::CombPositive,
+            "Comb-" => FilterMode::CombNegative,
             _ => panic!("Invalid filter mode!"),
         }
     }
@@ -64,13 +86,77 @@ impl FilterMode {
             "HighPass" => FilterMode::HighPass,
             "BandPass" => FilterMode::BandPass,
             "PassThru" => FilterMode::PassThru,
+            "Formant" => FilterMode::Formant,
+            "Comb+" => FilterMode::CombPositive,
+            "Comb-" => FilterMode::CombNegative,
             _ => panic!("Invalid filter mode!"),
         }
     }
 }
 
+/// Which filter(s) an oscillator is routed through.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum FilterRoute {
+    Filter1,
+    Filter2,
+    /// Split evenly between both filters (in parallel).
+    Both,
+    Bypass,
+}
+
+impl FilterRoute {
+    pub fn as_string(self) -> String {
+        match self {
+            FilterRoute::Filter1 => "Filter 1".to_string(),
+            FilterRoute::Filter2 => "Filter 2".to_string(),
+            FilterRoute::Both => "Both".to_string(),
+            FilterRoute::Bypass => "Bypass".to_string(),
+        }
+    }
+
+    /// How much of the oscillator is sent to (filter 1, filter 2, dry).
+    #[inline(always)]
+    pub fn sends(self) -> (f64, f64, f64) {
+        match self {
+            FilterRoute::Filter1 => (1.0, 0.0, 0.0),
+            FilterRoute::Filter2 => (0.0, 1.0, 0.0),
+            FilterRoute::Both => (0.5, 0.5, 0.0),
+            FilterRoute::Bypass => (0.0, 0.0, 1.0),
+        }
+    }
+}
+
+impl Enumerable<FilterRoute> for FilterRoute {
+    fn enumerate() -> Vec<FilterRoute> {
+        vec![
+            FilterRoute::Filter1,
+            FilterRoute::Filter2,
+            FilterRoute::Both,
+            FilterRoute::Bypass,
+        ]
+    }
+}
+
+impl From<FilterRoute> for String {
+    fn from(f: FilterRoute) -> String {
+        f.as_string()
+    }
+}
+
+impl From<String> for FilterRoute {
+    fn from(s: String) -> FilterRoute {
+        match s.as_ref() {
+            "Filter 1" => FilterRoute::Filter1,
+            "Filter 2" => FilterRoute::Filter2,
+            "Both" => FilterRoute::Both,
+            "Bypass" => FilterRoute::Bypass,
+            _ => FilterRoute::Filter1,
+        }
+    }
+}
+
 // We will process filters over vectors; we only need to store the last two input and output
-// points.
+// points. Both channels of a stereo pair are filtered together, sharing coefficients.
 #[derive(Debug)]
 pub struct Filter {
     coeffs: BiquadCoefs,
@@ -81,19 +167,37 @@ pub struct Filter {
     cutoff_semi_srl: SlewRateLimiter,
     resonance: f64,
     resonance_srl: SlewRateLimiter,
+    vowel: f64,
+    vowel_srl: SlewRateLimiter,
+    formant: FormantFilter,
+    comb: CombFilter,
+    // Cutoff offset from key tracking, in semitones.
+    key_track_semi: f64,
+    // Audio-rate cutoff offset, in semitones, as last applied to the coefficients; not smoothed.
+    fm_semi: f64,
+    fm_changed: bool,
+    // Resonance compensation: scale down the resonant peak and soft clip the
+    // feedback path.
+    res_comp: bool,
+    res_comp_gain: f64,
 
-    prev_xn1: f64,
-    prev_xn2: f64,
-    prev_yn1: f64,
-    prev_yn2: f64,
+    state: StereoBiquadState,
 }
 
 impl Filter {
-    pub fn new(sample_rate: f64, mode: &FilterMode, cutoff_semi: &f64, resonance: &f64) -> Filter {
+    pub fn new(
+        sample_rate: f64,
+        mode: &FilterMode,
+        cutoff_semi: &f64,
+        resonance: &f64,
+        vowel: &f64,
+    ) -> Filter {
         let cutoff_semi_srl =
             SlewRateLimiter::new(*cutoff_semi, sample_rate, SLEW_RATE_S, SLEW_THRESHOLD_SEMIS);
         let resonance_srl =
             SlewRateLimiter::new(*resonance, sample_rate, SLEW_RATE_S, SLEW_THRESHOLD_RES);
+        let vowel_srl =
+            SlewRateLimiter::new(*vowel, sample_rate, SLEW_RATE_S, SLEW_THRESHOLD_VOWEL);
         let mut inst = Filter {
             coeffs: BiquadCoefs::zeros(),
             sample_rate,
@@ -102,13 +206,20 @@ impl Filter {
             cutoff_semi_srl,
             resonance: 0.0, // likewise
             resonance_srl,
-            prev_xn1: 0.0,
-            prev_xn2: 0.0,
-            prev_yn1: 0.0,
-            prev_yn2: 0.0,
+            vowel: 0.0, // likewise
+            vowel_srl,
+            formant: FormantFilter::new(sample_rate, *vowel, *resonance),
+            comb: CombFilter::new(sample_rate),
+            key_track_semi: 0.0,
+            fm_semi: 0.0,
+            fm_changed: false,
+            res_comp: false,
+            res_comp_gain: 1.0,
+            state: StereoBiquadState::new(),
         };
         inst.set_cutoff(*cutoff_semi);
         inst.set_resonance(*resonance);
+        inst.set_vowel(*vowel);
         inst.update_coeff();
         inst
     }
@@ -131,11 +242,52 @@ impl Filter {
         self.resonance_srl.update(resonance);
     }
 
+    /// Update the vowel (formant mode only), from A (0.0) to U (4.0).
+    pub fn set_vowel(&mut self, vowel: f64) {
+        self.vowel = vowel;
+        self.vowel_srl.update(vowel);
+    }
+
+    /// Update the key tracking offset (in semitones) added to the cutoff.
+    pub fn set_key_track(&mut self, key_track_semi: f64) {
+        self.key_track_semi = key_track_semi;
+        self.update_coeff();
+    }
+
+    /// Enable or disable resonance compensation.
+    pub fn set_res_comp(&mut self, res_comp: bool) {
+        self.res_comp = res_comp;
+        self.update_coeff();
+    }
+
+    /// Offset the cutoff (in semitones) for the next sample. Unlike
+    /// `set_cutoff`, this bypasses smoothing so it can be driven at audio rate.
+    pub fn set_fm(&mut self, fm_semi: f64) {
+        // Redesigning the filter every sample is costly, so small moves are let go, unless FM
+        // has just been turned off. The formant and pass-through modes ignore the cutoff.
+        let uses_cutoff = !matches!(self.mode, FilterMode::Formant | FilterMode::PassThru);
+        let moved = (fm_semi - self.fm_semi).abs() >= FM_THRESHOLD_SEMIS
+            || (fm_semi == 0.0 && self.fm_semi != 0.0);
+        if uses_cutoff && moved {
+            self.fm_semi = fm_semi;
+            self.fm_changed = true;
+        }
+    }
+
     fn update_coeff(&mut self) {
         // TODO: Do we need to invalidate prev_* values?
-        let cutoff_semi = self.cutoff_semi_srl.filtered_value;
-        let cutoff_hz = util::semitones_to_frequency(cutoff_semi, MIN_CUTOFF_FREQ);
+        let cutoff_semi = self.cutoff_semi_srl.filtered_value + self.key_track_semi + self.fm_semi;
+        // Key tracking and FM can push the cutoff past Nyquist; keep it stable.
+        let cutoff_hz = util::semitones_to_frequency(cutoff_semi, MIN_CUTOFF_FREQ)
+            .min(self.sample_rate * MAX_CUTOFF_RATIO);
         let resonance = self.resonance_srl.filtered_value;
+        // The biquad's resonant peak rises with Q; halve it in dB so that
+        // resonance still sounds resonant, without getting much louder.
+        self.res_comp_gain = if self.res_comp {
+            (BUTTERWORTH_Q / resonance.max(BUTTERWORTH_Q)).sqrt()
+        } else {
+            1.0
+        };
         match &self.mode {
             FilterMode::LowPass => {
                 self.coeffs = BiquadCoefs::lpf(self.sample_rate, cutoff_hz, resonance);
@@ -147,30 +299,77 @@ impl Filter {
                 self.coeffs = BiquadCoefs::bpf(self.sample_rate, cutoff_hz, resonance);
             }
             FilterMode::PassThru => {}
+            FilterMode::Formant => {
+                // Cutoff is unused; resonance sharpens the formant peaks.
+                let vowel = self.vowel_srl.filtered_value;
+                self.formant.update(vowel, resonance);
+            }
+            FilterMode::CombPositive | FilterMode::CombNegative => {
+                // The comb is tuned to the cutoff; resonance sets the feedback.
+                let feedback = ((resonance - MIN_RESONANCE) / (MAX_RESONANCE - MIN_RESONANCE))
+                    .clamp(0.0, 1.0)
+                    * MAX_COMB_FEEDBACK;
+                self.comb.set_frequency(self.sample_rate, cutoff_hz);
+                if self.mode == FilterMode::CombPositive {
+                    self.comb.set_feedback(feedback);
+                } else {
+                    self.comb.set_feedback(-feedback);
+                }
+            }
         };
     }
 
-    /// Apply the filter to the given input signal.
-    pub fn apply(&mut self, input: f64) -> f64 {
+    /// Apply the filter to the given (left, right) input pair.
+    pub fn apply(&mut self, input: F64x2) -> F64x2 {
         // Determine if we need to update
         let cutoff_changed = self.cutoff_semi_srl.step();
         let res_changed = self.resonance_srl.step();
-        if cutoff_changed || res_changed {
+        let vowel_changed = self.vowel_srl.step();
+        if cutoff_changed || res_changed || vowel_changed || self.fm_changed {
+            self.fm_changed = false;
             self.update_coeff();
         }
 
-        let output = biquad_direct_form_apply(
-            input,
-            &self.coeffs,
-            self.prev_xn1,
-            self.prev_xn2,
-            self.prev_yn1,
-            self.prev_yn2,
-        );
-        self.prev_xn2 = self.prev_xn1;
-        self.prev_xn1 = input;
-        self.prev_yn2 = self.prev_yn1;
-        self.prev_yn1 = output;
-        output
+        match self.mode {
+            FilterMode::Formant => return self.formant.apply(input),
+            FilterMode::CombPositive | FilterMode::CombNegative => return self.comb.apply(input),
+            _ => {}
+        }
+
+        if self.res_comp {
+            self.state.apply_saturated(input, &self.coeffs) * self.res_comp_gain
+        } else {
+            self.state.apply(input, &self.coeffs)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn filter(mode: FilterMode) -> Filter {
+        Filter::new(44100.0, &mode, &60.0, &1.0, &0.0)
+    }
+
+    #[test]
+    fn follows_fm_past_a_threshold() {
+        let mut filter = filter(FilterMode::LowPass);
+        filter.set_fm(FM_THRESHOLD_SEMIS / 2.0);
+        assert!(!filter.fm_changed);
+        filter.set_fm(FM_THRESHOLD_SEMIS);
+        assert!(filter.fm_changed);
+        filter.apply(F64x2::splat(0.0));
+        assert!(!filter.fm_changed);
+        // Turning FM off always lands on the unmodulated cutoff.
+        filter.set_fm(0.0);
+        assert!(filter.fm_changed);
+    }
+
+    #[test]
+    fn formants_ignore_fm() {
+        let mut filter = filter(FilterMode::Formant);
+        filter.set_fm(12.0);
+        assert!(!filter.fm_changed);
     }
 }
@@ -0,0 +1,179 @@
+//! Formant (vowel) filter: a bank of parallel band-pass filters tuned to the
+//! formants of a sung vowel.
+
+use crate::dsp::biquad::{BiquadCoefs, StereoBiquadState};
+use crate::dsp::simd::F64x2;
+
+pub const FORMANT_COUNT: usize = 3;
+
+/// Highest vowel morph value (A=0, E=1, I=2, O=3, U=4).
+pub const MAX_VOWEL: f64 = (VOWELS.len() - 1) as f64;
+
+/// Formant frequency (Hz), amplitude (linear) and bandwidth (Hz).
+#[derive(Clone, Copy, Debug)]
+struct Formant {
+    freq: f64,
+    amp: f64,
+    bandwidth: f64,
+}
+
+const fn formant(freq: f64, amp: f64, bandwidth: f64) -> Formant {
+    Formant {
+        freq,
+        amp,
+        bandwidth,
+    }
+}
+
+// Bass voice formants (from the Csound manual's formant table); amplitudes
+// converted from dB.
+const VOWELS: [[Formant; FORMANT_COUNT]; 5] = [
+    // A
+    [
+        formant(600.0, 1.0, 60.0),
+        formant(1040.0, 0.446684, 70.0),
+        formant(2250.0, 0.354813, 110.0),
+    ],
+    // E
+    [
+        formant(400.0, 1.0, 40.0),
+        formant(1620.0, 0.251189, 80.0),
+        formant(2400.0, 0.354813, 100.0),
+    ],
+    // I
+    [
+        formant(250.0, 1.0, 60.0),
+        formant(1750.0, 0.031623, 90.0),
+        formant(2600.0, 0.158489, 100.0),
+    ],
+    // O
+    [
+        formant(400.0, 1.0, 40.0),
+        formant(750.0, 0.281838, 80.0),
+        formant(2400.0, 0.089125, 100.0),
+    ],
+    // U
+    [
+        formant(350.0, 1.0, 40.0),
+        formant(600.0, 0.100000, 80.0),
+        formant(2400.0, 0.025119, 100.0),
+    ],
+];
+
+/// Name of the vowel closest to the given morph value.
+pub fn vowel_name(vowel: f64) -> &'static str {
+    match vowel.round().clamp(0.0, MAX_VOWEL) as usize {
+        0 => "A",
+        1 => "E",
+        2 => "I",
+        3 => "O",
+        _ => "U",
+    }
+}
+
+#[derive(Debug)]
+struct FormantBand {
+    coeffs: BiquadCoefs,
+    amp: f64,
+    state: StereoBiquadState,
+}
+
+impl FormantBand {
+    fn new() -> Self {
+        FormantBand {
+            coeffs: BiquadCoefs::zeros(),
+            amp: 0.0,
+            state: StereoBiquadState::new(),
+        }
+    }
+
+    #[inline(always)]
+    fn apply(&mut self, input: F64x2) -> F64x2 {
+        self.state.apply(input, &self.coeffs) * self.amp
+    }
+}
+
+#[derive(Debug)]
+pub struct FormantFilter {
+    sample_rate: f64,
+    bands: [FormantBand; FORMANT_COUNT],
+}
+
+impl FormantFilter {
+    pub fn new(sample_rate: f64, vowel: f64, resonance: f64) -> Self {
+        let mut inst = FormantFilter {
+            sample_rate,
+            bands: [FormantBand::new(), FormantBand::new(), FormantBand::new()],
+        };
+        inst.update(vowel, resonance);
+        inst
+    }
+
+    /// Retune the formants. `vowel` morphs continuously between A-E-I-O-U
+    /// (0.0 to 4.0); `resonance` narrows the formant bandwidths (1.0 leaves
+    /// them as-is).
+    pub fn update(&mut self, vowel: f64, resonance: f64) {
+        let vowel = vowel.clamp(0.0, MAX_VOWEL);
+        let from_idx = vowel.floor() as usize;
+        let to_idx = (from_idx + 1).min(VOWELS.len() - 1);
+        let t = vowel - from_idx as f64;
+        let nyquist = self.sample_rate / 2.0;
+
+        for (band_idx, band) in self.bands.iter_mut().enumerate() {
+            let from = &VOWELS[from_idx][band_idx];
+            let to = &VOWELS[to_idx][band_idx];
+            let freq = (from.freq + t * (to.freq - from.freq)).min(nyquist * 0.95);
+            let bandwidth = from.bandwidth + t * (to.bandwidth - from.bandwidth);
+            let q = (freq / bandwidth) * resonance;
+            band.coeffs = BiquadCoefs::bpf_peak(self.sample_rate, freq, q);
+            band.amp = from.amp + t * (to.amp - from.amp);
+        }
+    }
+
+    pub fn apply(&mut self, input: F64x2) -> F64x2 {
+        self.bands
+            .iter_mut()
+            .fold(F64x2::zero(), |acc, band| acc + band.apply(input))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dsp::TAU;
+
+    const SAMPLE_RATE: f64 = 44100.0;
+
+    fn rms_response(filter: &mut FormantFilter, freq: f64) -> f64 {
+        let n = 8192;
+        let mut sum = 0.0;
+        for i in 0..n {
+            let x = (TAU * freq * (i as f64) / SAMPLE_RATE).sin();
+            let [y, _] = filter.apply(F64x2::splat(x)).to_array();
+            // Skip the transient.
+            if i > n / 2 {
+                sum += y * y;
+            }
+        }
+        (sum / (n / 2) as f64).sqrt()
+    }
+
+    #[test]
+    fn vowel_names() {
+        assert_eq!(vowel_name(0.0), "A");
+        assert_eq!(vowel_name(0.6), "E");
+        assert_eq!(vowel_name(4.0), "U");
+        assert_eq!(vowel_name(10.0), "U");
+    }
+
+    #[test]
+    fn passes_first_formant() {
+        // The first formant of "A" should pass far more than a frequency
+        // between formants.
+        let mut filter = FormantFilter::new(SAMPLE_RATE, 0.0, 1.0);
+        let at_formant = rms_response(&mut filter, 600.0);
+        let mut filter = FormantFilter::new(SAMPLE_RATE, 0.0, 1.0);
+        let between = rms_response(&mut filter, 5000.0);
+        assert!(at_formant > 10.0 * between);
+    }
+}
@@ -235,6 +235,16 @@ impl Interpolator {
         normalize(rendered)
     }
 
+    /// The prerendered waveform `populate` reads from for `shape` at `freq`, with the mipmap
+    /// frequency it was rendered at.
+    pub fn reference(&self, shape: WaveShape, freq: f64) -> Option<(f64, &[f64])> {
+        let ref_freq = closest_number_in(freq, &self.frequencies, true);
+        let key = (shape.value(), HashableF64::from_float(ref_freq));
+        self.references
+            .get(&key)
+            .map(|waveform| (ref_freq, waveform.as_slice()))
+    }
+
     #[inline(always)]
     #[allow(clippy::too_many_arguments)]
     pub fn populate(
@@ -400,4 +410,19 @@ mod test {
         assert_eq!(closest_number_in(1.0, &fs, true), 5.0);
         assert_eq!(closest_number_in(1.0, &fs, false), 5.0);
     }
+
+    #[test]
+    fn reference_waveforms() {
+        let interpolator = Interpolator::new(44100.0);
+        let (ref_freq, sine) = interpolator.reference(WaveShape::Sine, 442.0).unwrap();
+        // A4 is the closest mipmap below.
+        assert!((ref_freq - 440.0).abs() < 0.01);
+        assert_eq!(sine.len(), TABLE_SIZE);
+        assert!((sine[TABLE_SIZE / 4] - 1.0).abs() < 1e-6);
+
+        // Higher up, the saw leaves out the harmonics past Nyquist.
+        let (_, low_saw) = interpolator.reference(WaveShape::HardSaw, 110.0).unwrap();
+        let (_, high_saw) = interpolator.reference(WaveShape::HardSaw, 4000.0).unwrap();
+        assert_ne!(low_saw, high_saw);
+    }
 }
@@ -1,10 +1,13 @@
 pub mod biquad;
+pub mod comb;
 pub mod env;
 pub mod filter;
+pub mod formant;
 pub mod interpolation;
 pub mod interpolator;
 pub mod osc;
 pub mod resonant_filter;
+pub mod simd;
 pub mod smoothing;
 
 pub const TAU: f64 = std::f64::consts::PI * 2.0;
@@ -106,3 +106,43 @@ impl Enumerable<Unison> for Unison {
         ]
     }
 }
+
+/// What feeds the filters and amp envelope.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum SoundSource {
+    Oscillators,
+    /// The host's audio input, in place of both oscillators. It takes oscillator 1's gain, pan
+    /// and filter route, and notes still gate the amp envelope.
+    ExternalInput,
+}
+
+impl SoundSource {
+    pub fn as_string(self) -> String {
+        match self {
+            SoundSource::Oscillators => "Oscillators".to_string(),
+            SoundSource::ExternalInput => "External Input".to_string(),
+        }
+    }
+}
+
+impl From<SoundSource> for String {
+    fn from(f: SoundSource) -> String {
+        f.as_string()
+    }
+}
+
+impl From<String> for SoundSource {
+    fn from(s: String) -> SoundSource {
+        match s.as_ref() {
+            "Oscillators" => SoundSource::Oscillators,
+            "External Input" => SoundSource::ExternalInput,
+            _ => SoundSource::Oscillators,
+        }
+    }
+}
+
+impl Enumerable<SoundSource> for SoundSource {
+    fn enumerate() -> Vec<SoundSource> {
+        vec![SoundSource::Oscillators, SoundSource::ExternalInput]
+    }
+}
@@ -40,7 +40,10 @@ impl ResonantFilter {
             FilterMode::LowPass => self.buf1,
             FilterMode::HighPass => input - self.buf0,
             FilterMode::BandPass => self.buf0 - self.buf1,
-            FilterMode::PassThru => input,
+            FilterMode::PassThru
+            | FilterMode::Formant
+            | FilterMode::CombPositive
+            | FilterMode::CombNegative => input,
         }
     }
 }
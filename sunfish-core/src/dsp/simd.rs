@@ -0,0 +1,279 @@
+// Two-lane f64 vector, used to process the left and right channels together.
+use std::ops::{Add, Div, Mul, Sub};
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+
+#[cfg(target_arch = "x86_64")]
+type Inner = __m128d;
+
+#[cfg(target_arch = "aarch64")]
+type Inner = float64x2_t;
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+type Inner = [f64; 2];
+
+/// (left, right) pair of samples.
+#[derive(Clone, Copy, Debug)]
+pub struct F64x2(Inner);
+
+// SSE2 and NEON are part of the x86_64 and aarch64 baselines respectively, so
+// the intrinsics below are always available on those targets.
+#[cfg(target_arch = "x86_64")]
+impl F64x2 {
+    #[inline(always)]
+    pub fn new(left: f64, right: f64) -> Self {
+        // Note that _mm_set_pd takes the high lane first.
+        F64x2(unsafe { _mm_set_pd(right, left) })
+    }
+
+    #[inline(always)]
+    pub fn splat(value: f64) -> Self {
+        F64x2(unsafe { _mm_set1_pd(value) })
+    }
+
+    #[inline(always)]
+    pub fn to_array(self) -> [f64; 2] {
+        let mut out = [0.0; 2];
+        unsafe { _mm_storeu_pd(out.as_mut_ptr(), self.0) };
+        out
+    }
+
+    #[inline(always)]
+    pub fn min(self, other: F64x2) -> Self {
+        F64x2(unsafe { _mm_min_pd(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    pub fn max(self, other: F64x2) -> Self {
+        F64x2(unsafe { _mm_max_pd(self.0, other.0) })
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Add for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn add(self, other: F64x2) -> F64x2 {
+        F64x2(unsafe { _mm_add_pd(self.0, other.0) })
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Sub for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn sub(self, other: F64x2) -> F64x2 {
+        F64x2(unsafe { _mm_sub_pd(self.0, other.0) })
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Mul for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn mul(self, other: F64x2) -> F64x2 {
+        F64x2(unsafe { _mm_mul_pd(self.0, other.0) })
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Div for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn div(self, other: F64x2) -> F64x2 {
+        F64x2(unsafe { _mm_div_pd(self.0, other.0) })
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl F64x2 {
+    #[inline(always)]
+    pub fn new(left: f64, right: f64) -> Self {
+        let lanes = [left, right];
+        F64x2(unsafe { vld1q_f64(lanes.as_ptr()) })
+    }
+
+    #[inline(always)]
+    pub fn splat(value: f64) -> Self {
+        F64x2(unsafe { vdupq_n_f64(value) })
+    }
+
+    #[inline(always)]
+    pub fn to_array(self) -> [f64; 2] {
+        let mut out = [0.0; 2];
+        unsafe { vst1q_f64(out.as_mut_ptr(), self.0) };
+        out
+    }
+
+    #[inline(always)]
+    pub fn min(self, other: F64x2) -> Self {
+        F64x2(unsafe { vminq_f64(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    pub fn max(self, other: F64x2) -> Self {
+        F64x2(unsafe { vmaxq_f64(self.0, other.0) })
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Add for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn add(self, other: F64x2) -> F64x2 {
+        F64x2(unsafe { vaddq_f64(self.0, other.0) })
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Sub for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn sub(self, other: F64x2) -> F64x2 {
+        F64x2(unsafe { vsubq_f64(self.0, other.0) })
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Mul for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn mul(self, other: F64x2) -> F64x2 {
+        F64x2(unsafe { vmulq_f64(self.0, other.0) })
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Div for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn div(self, other: F64x2) -> F64x2 {
+        F64x2(unsafe { vdivq_f64(self.0, other.0) })
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl F64x2 {
+    #[inline(always)]
+    pub fn new(left: f64, right: f64) -> Self {
+        F64x2([left, right])
+    }
+
+    #[inline(always)]
+    pub fn splat(value: f64) -> Self {
+        F64x2([value, value])
+    }
+
+    #[inline(always)]
+    pub fn to_array(self) -> [f64; 2] {
+        self.0
+    }
+
+    #[inline(always)]
+    pub fn min(self, other: F64x2) -> Self {
+        F64x2([self.0[0].min(other.0[0]), self.0[1].min(other.0[1])])
+    }
+
+    #[inline(always)]
+    pub fn max(self, other: F64x2) -> Self {
+        F64x2([self.0[0].max(other.0[0]), self.0[1].max(other.0[1])])
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl Add for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn add(self, other: F64x2) -> F64x2 {
+        F64x2([self.0[0] + other.0[0], self.0[1] + other.0[1]])
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl Sub for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn sub(self, other: F64x2) -> F64x2 {
+        F64x2([self.0[0] - other.0[0], self.0[1] - other.0[1]])
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl Mul for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn mul(self, other: F64x2) -> F64x2 {
+        F64x2([self.0[0] * other.0[0], self.0[1] * other.0[1]])
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl Div for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn div(self, other: F64x2) -> F64x2 {
+        F64x2([self.0[0] / other.0[0], self.0[1] / other.0[1]])
+    }
+}
+
+impl F64x2 {
+    #[inline(always)]
+    pub fn zero() -> Self {
+        F64x2::splat(0.0)
+    }
+
+    /// Soft clip each lane to [-1, 1] with a rational tanh approximation
+    /// (exact at the +/-3.0 knee, where it reaches unity).
+    #[inline(always)]
+    pub fn soft_clip(self) -> Self {
+        let x = self.max(F64x2::splat(-3.0)).min(F64x2::splat(3.0));
+        let x2 = x * x;
+        x * (F64x2::splat(27.0) + x2) / (F64x2::splat(27.0) + x2 * 9.0)
+    }
+
+    /// Average of both lanes.
+    #[inline(always)]
+    pub fn mid(self) -> f64 {
+        let [left, right] = self.to_array();
+        (left + right) * 0.5
+    }
+}
+
+impl Mul<f64> for F64x2 {
+    type Output = F64x2;
+    #[inline(always)]
+    fn mul(self, other: f64) -> F64x2 {
+        self * F64x2::splat(other)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lanes_are_independent() {
+        let a = F64x2::new(1.0, 2.0);
+        let b = F64x2::new(3.0, 5.0);
+        assert_eq!((a + b).to_array(), [4.0, 7.0]);
+        assert_eq!((a - b).to_array(), [-2.0, -3.0]);
+        assert_eq!((a * b).to_array(), [3.0, 10.0]);
+        assert_eq!((a * 2.0).to_array(), [2.0, 4.0]);
+        assert_eq!((b / a).to_array(), [3.0, 2.5]);
+        assert_eq!(a.max(F64x2::new(0.0, 4.0)).to_array(), [1.0, 4.0]);
+        assert_eq!(b.mid(), 4.0);
+    }
+
+    #[test]
+    fn soft_clip_is_bounded() {
+        let [small, big] = F64x2::new(0.01, 100.0).soft_clip().to_array();
+        assert!((small - 0.01).abs() < 1e-5);
+        assert!((big - 1.0).abs() < 1e-12);
+        let [neg, _] = F64x2::new(-100.0, 0.0).soft_clip().to_array();
+        assert!((neg + 1.0).abs() < 1e-12);
+    }
+}
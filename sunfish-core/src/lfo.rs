@@ -1,13 +1,20 @@
+use std::f64::consts::PI;
+
 use serde::{Deserialize, Serialize};
 
 use crate::dsp::TAU;
 use crate::util::enumerable::Enumerable;
+use crate::util::rng::Rng;
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum LfoShape {
     Sine,
     Saw,
     Triangle,
+    // A new random value each cycle.
+    SampleHold,
+    // Glides between random values, one per cycle.
+    SmoothRandom,
 }
 
 impl LfoShape {
@@ -16,6 +23,8 @@ impl LfoShape {
             LfoShape::Sine => 0,
             LfoShape::Saw => 1,
             LfoShape::Triangle => 2,
+            LfoShape::SampleHold => 3,
+            LfoShape::SmoothRandom => 4,
         }
     }
 
@@ -24,13 +33,57 @@ impl LfoShape {
             LfoShape::Sine => "Sine".to_string(),
             LfoShape::Saw => "Saw".to_string(),
             LfoShape::Triangle => "Triangle".to_string(),
+            LfoShape::SampleHold => "S&H".to_string(),
+            LfoShape::SmoothRandom => "Smooth Random".to_string(),
+        }
+    }
+
+    /// The value of a periodic shape `phase` (0 to 1) of the way through its cycle. Random
+    /// shapes aren't periodic, so give zero.
+    pub fn periodic_value(self, phase: f64) -> f64 {
+        match self {
+            LfoShape::Sine => (TAU * phase).sin(),
+            LfoShape::Saw => -2.0 * phase + 1.0,
+            LfoShape::Triangle => {
+                let p = 4.0 * phase;
+                if phase < 0.25 {
+                    p
+                } else if phase < 0.75 {
+                    2.0 - p
+                } else {
+                    -4.0 + p
+                }
+            }
+            LfoShape::SampleHold | LfoShape::SmoothRandom => 0.0,
+        }
+    }
+
+    /// The shape's value `cycles` cycles in, for drawing it. Random shapes draw each cycle's
+    /// value from the cycle's number, so the same stretch always looks the same.
+    pub fn preview_value(self, cycles: f64) -> f64 {
+        let cycle = cycles.floor();
+        let random = |cycle: f64| Rng::new(cycle as i64 as u64).next_bipolar();
+        match self {
+            LfoShape::SampleHold => random(cycle),
+            LfoShape::SmoothRandom => {
+                let x = 0.5 - 0.5 * (PI * (cycles - cycle)).cos();
+                let prev = random(cycle - 1.0);
+                prev + (random(cycle) - prev) * x
+            }
+            _ => self.periodic_value(cycles - cycle),
         }
     }
 }
 
 impl Enumerable<LfoShape> for LfoShape {
     fn enumerate() -> Vec<LfoShape> {
-        vec![LfoShape::Triangle, LfoShape::Sine, LfoShape::Saw]
+        vec![
+            LfoShape::Triangle,
+            LfoShape::Sine,
+            LfoShape::Saw,
+            LfoShape::SampleHold,
+            LfoShape::SmoothRandom,
+        ]
     }
 }
 
@@ -46,11 +99,60 @@ impl From<String> for LfoShape {
             "Sine" => LfoShape::Sine,
             "Saw" => LfoShape::Saw,
             "Triangle" => LfoShape::Triangle,
+            "S&H" => LfoShape::SampleHold,
+            "Smooth Random" => LfoShape::SmoothRandom,
             _ => LfoShape::Sine,
         }
     }
 }
 
+/// When an LFO restarts its cycle.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum LfoRetrigger {
+    // Never; the LFO runs continuously.
+    Free,
+    // When a note is played with no other notes held.
+    FirstNote,
+    // On every note.
+    EveryNote,
+}
+
+impl LfoRetrigger {
+    pub fn as_string(self) -> String {
+        match self {
+            LfoRetrigger::Free => "Free".to_string(),
+            LfoRetrigger::FirstNote => "First Note".to_string(),
+            LfoRetrigger::EveryNote => "Every Note".to_string(),
+        }
+    }
+}
+
+impl Enumerable<LfoRetrigger> for LfoRetrigger {
+    fn enumerate() -> Vec<LfoRetrigger> {
+        vec![
+            LfoRetrigger::Free,
+            LfoRetrigger::FirstNote,
+            LfoRetrigger::EveryNote,
+        ]
+    }
+}
+
+impl From<LfoRetrigger> for String {
+    fn from(f: LfoRetrigger) -> String {
+        f.as_string()
+    }
+}
+
+impl From<String> for LfoRetrigger {
+    fn from(s: String) -> LfoRetrigger {
+        match s.as_ref() {
+            "First Note" => LfoRetrigger::FirstNote,
+            "Every Note" => LfoRetrigger::EveryNote,
+            _ => LfoRetrigger::Free,
+        }
+    }
+}
+
 // Discrete, synced LFO rate.
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum LfoRateSync {
@@ -68,6 +170,23 @@ pub enum LfoRateSync {
 }
 
 impl LfoRateSync {
+    /// How many LFO cycles fit in one beat (quarter note).
+    pub fn cycles_per_beat(self) -> f64 {
+        match self {
+            LfoRateSync::R1_64 => 16.0,
+            LfoRateSync::R1_32 => 8.0,
+            LfoRateSync::R1_16 => 4.0,
+            LfoRateSync::R1_8 => 2.0,
+            LfoRateSync::R1_4 => 1.0,
+            LfoRateSync::R1_2 => 1.0 / 2.0,
+            LfoRateSync::R1 => 1.0 / 4.0,
+            LfoRateSync::R2_1 => 1.0 / 8.0,
+            LfoRateSync::R4_1 => 1.0 / 16.0,
+            LfoRateSync::R8_1 => 1.0 / 32.0,
+            LfoRateSync::R16_1 => 1.0 / 64.0,
+        }
+    }
+
     pub fn value(self) -> u8 {
         match self {
             LfoRateSync::R1_64 => 0,
@@ -149,13 +268,33 @@ pub enum Rate {
     Synced(LfoRateSync),
 }
 
+/// LFO depth `note_time` seconds after a note starts: silent for `delay`
+/// seconds, then fading in linearly over `fade` seconds.
+pub fn fade_in_gain(delay: f64, fade: f64, note_time: f64) -> f64 {
+    let t = note_time - delay;
+    if t < 0.0 {
+        0.0
+    } else if t >= fade {
+        1.0
+    } else {
+        t / fade
+    }
+}
+
 pub struct Lfo {
     rate: Rate,
     period_sec: f64,
     rate_hz: f64,
     shape: LfoShape,
     time_elapsed: f64,
-    // TODO: Phase shift.
+    // Start phase, as a fraction of a cycle.
+    phase_offset: f64,
+    // Run a single cycle, then hold.
+    one_shot: bool,
+    // Random shapes: the value drawn for the current cycle, and the one before it.
+    rng: Rng,
+    random: f64,
+    prev_random: f64,
 }
 
 impl Lfo {
@@ -168,27 +307,82 @@ impl Lfo {
             rate_hz,
             shape,
             time_elapsed: 0.0,
+            phase_offset: 0.0,
+            one_shot: false,
+            rng: Rng::new(0),
+            random: 0.0,
+            prev_random: 0.0,
         }
     }
 
     pub fn evaluate(&mut self, time_delta: f64) -> f64 {
-        self.time_elapsed = (self.time_elapsed + time_delta) % self.period_sec;
+        if self.one_shot {
+            // Stop at the end of the cycle, holding its final value.
+            self.time_elapsed = (self.time_elapsed + time_delta).min(self.period_sec);
+        } else {
+            let time_elapsed = self.time_elapsed + time_delta;
+            if time_elapsed >= self.period_sec {
+                self.next_random();
+            }
+            self.time_elapsed = time_elapsed % self.period_sec;
+        }
+        let mut t = self.time_elapsed + self.phase_offset * self.period_sec;
+        if t > self.period_sec {
+            t -= self.period_sec;
+        }
         match self.shape {
-            LfoShape::Sine => (TAU * self.rate_hz * self.time_elapsed).sin(),
-            LfoShape::Saw => -2.0 * self.rate_hz * self.time_elapsed + 1.0,
-            LfoShape::Triangle => {
-                let p = 4.0 * self.rate_hz * self.time_elapsed;
-                if self.time_elapsed < self.period_sec / 4.0 {
-                    p
-                } else if self.time_elapsed < 3.0 * self.period_sec / 4.0 {
-                    2.0 - p
-                } else {
-                    -4.0 + p
-                }
+            LfoShape::Sine | LfoShape::Saw | LfoShape::Triangle => {
+                self.shape.periodic_value(self.rate_hz * t)
+            }
+            // Random shapes step once per cycle, so they ignore the phase offset.
+            LfoShape::SampleHold => self.random,
+            LfoShape::SmoothRandom => {
+                let x = 0.5 - 0.5 * (PI * self.time_elapsed / self.period_sec).cos();
+                self.prev_random + (self.random - self.prev_random) * x
             }
         }
     }
 
+    fn next_random(&mut self) {
+        self.prev_random = self.random;
+        self.random = self.rng.next_bipolar();
+    }
+
+    /// Restart the random sequence from `seed`, drawing a new value.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+        self.next_random();
+    }
+
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    /// Restart the cycle from the start phase.
+    pub fn reset(&mut self) {
+        self.time_elapsed = 0.0;
+    }
+
+    pub fn set_one_shot(&mut self, one_shot: bool) {
+        self.one_shot = one_shot;
+    }
+
+    /// Set the start phase, in degrees (0 to 360).
+    pub fn set_phase(&mut self, degrees: f64) {
+        self.phase_offset = (degrees / 360.0).rem_euclid(1.0);
+    }
+
+    /// Change a free-running rate while it's playing, keeping the LFO's place in its cycle.
+    pub fn set_rate_hz(&mut self, rate_hz: f64) {
+        if rate_hz == self.rate_hz || rate_hz <= 0.0 {
+            return;
+        }
+        let period_sec = 1.0 / rate_hz;
+        self.time_elapsed *= period_sec / self.period_sec;
+        self.period_sec = period_sec;
+        self.rate_hz = rate_hz;
+    }
+
     pub fn update_rate(&mut self, rate: Rate, tempo_bps: f64) {
         self.rate = rate;
         let (period_sec, rate_hz) = Self::compute_period_sec(&rate, tempo_bps);
@@ -196,23 +390,20 @@ impl Lfo {
         self.rate_hz = rate_hz;
     }
 
+    /// Lock a synced LFO to the host's song position, in quarter notes (PPQ), so its cycles line
+    /// up with the bars and beats. Free-running rates are left alone.
+    pub fn sync_to_beat(&mut self, ppq_pos: f64) {
+        if let Rate::Synced(rate) = self.rate {
+            let cycle_pos = (ppq_pos * rate.cycles_per_beat()).rem_euclid(1.0);
+            self.time_elapsed = cycle_pos * self.period_sec;
+        }
+    }
+
     pub fn compute_period_sec(rate: &Rate, tempo_bps: f64) -> (f64, f64) {
         match rate {
             Rate::Hz(rate_hz) => (1.0 / rate_hz, *rate_hz),
             Rate::Synced(rate) => {
-                let factor = match rate {
-                    LfoRateSync::R1_64 => 16.0,
-                    LfoRateSync::R1_32 => 8.0,
-                    LfoRateSync::R1_16 => 4.0,
-                    LfoRateSync::R1_8 => 2.0,
-                    LfoRateSync::R1_4 => 1.0,
-                    LfoRateSync::R1_2 => 1.0 / 2.0,
-                    LfoRateSync::R1 => 1.0 / 4.0,
-                    LfoRateSync::R2_1 => 1.0 / 8.0,
-                    LfoRateSync::R4_1 => 1.0 / 16.0,
-                    LfoRateSync::R8_1 => 1.0 / 32.0,
-                    LfoRateSync::R16_1 => 1.0 / 64.0,
-                };
+                let factor = rate.cycles_per_beat();
                 // We get Hz by taking the beats per second, which put another way
                 // is:
                 //    (1 quarter note * factor / 1 sec)
@@ -223,3 +414,96 @@ impl Lfo {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fades_in_after_delay() {
+        assert_eq!(fade_in_gain(0.0, 0.0, 0.0), 1.0);
+        assert_eq!(fade_in_gain(0.5, 1.0, 0.25), 0.0);
+        assert_eq!(fade_in_gain(0.5, 1.0, 1.0), 0.5);
+        assert_eq!(fade_in_gain(0.5, 1.0, 2.0), 1.0);
+    }
+
+    #[test]
+    fn one_shot_holds_end_of_cycle() {
+        let mut lfo = Lfo::new(LfoShape::Saw, Rate::Hz(1.0), 1.0);
+        lfo.set_one_shot(true);
+        assert!((lfo.evaluate(0.5) - 0.0).abs() < 1e-9);
+        assert!((lfo.evaluate(0.75) + 1.0).abs() < 1e-9);
+        assert!((lfo.evaluate(10.0) + 1.0).abs() < 1e-9);
+        lfo.reset();
+        assert!((lfo.evaluate(0.25) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn random_shapes_follow_seed() {
+        let render = |shape| {
+            let mut lfo = Lfo::new(shape, Rate::Hz(1.0), 1.0);
+            lfo.reseed(42);
+            (0..40).map(|_| lfo.evaluate(0.1)).collect::<Vec<f64>>()
+        };
+        let held = render(LfoShape::SampleHold);
+        assert_eq!(held, render(LfoShape::SampleHold));
+        // Steps once per cycle.
+        assert_eq!(held[0], held[8]);
+        assert_ne!(held[8], held[10]);
+
+        let smooth = render(LfoShape::SmoothRandom);
+        assert_eq!(smooth, render(LfoShape::SmoothRandom));
+        let max_step = smooth
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0, f64::max);
+        assert!(max_step < 0.5);
+    }
+
+    #[test]
+    fn phase_offset_shifts_cycle() {
+        let mut lfo = Lfo::new(LfoShape::Sine, Rate::Hz(1.0), 1.0);
+        let mut shifted = Lfo::new(LfoShape::Sine, Rate::Hz(1.0), 1.0);
+        shifted.set_phase(90.0);
+        // A quarter cycle on, the sine peaks; the quadrature LFO is back at zero.
+        assert!((lfo.evaluate(0.25) - 1.0).abs() < 1e-9);
+        assert!(shifted.evaluate(0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sync_to_beat_follows_song_position() {
+        // Quarter-note rate at 120 BPM: one cycle per beat.
+        let mut lfo = Lfo::new(LfoShape::Sine, Rate::Synced(LfoRateSync::R1_4), 2.0);
+        lfo.sync_to_beat(10.25);
+        assert!((lfo.evaluate(0.0) - 1.0).abs() < 1e-9);
+
+        // Free-running LFOs ignore the host position.
+        let mut free = Lfo::new(LfoShape::Sine, Rate::Hz(1.0), 2.0);
+        free.sync_to_beat(10.25);
+        assert!(free.evaluate(0.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn preview_matches_the_lfo() {
+        for shape in [LfoShape::Sine, LfoShape::Saw, LfoShape::Triangle] {
+            let mut lfo = Lfo::new(shape, Rate::Hz(2.0), 1.0);
+            for step in 1..20 {
+                let value = lfo.evaluate(0.03);
+                let cycles = 2.0 * 0.03 * step as f64;
+                assert!((shape.preview_value(cycles) - value).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn random_previews_hold_still() {
+        let hold = LfoShape::SampleHold;
+        assert_eq!(hold.preview_value(3.2), hold.preview_value(3.9));
+        assert_ne!(hold.preview_value(3.9), hold.preview_value(4.1));
+
+        // Smooth random starts each cycle where the last one ended.
+        let smooth = LfoShape::SmoothRandom;
+        assert!((smooth.preview_value(5.0) - hold.preview_value(4.0)).abs() < 1e-9);
+        assert!((smooth.preview_value(5.999999) - hold.preview_value(5.0)).abs() < 1e-6);
+    }
+}
@@ -1,9 +1,12 @@
 pub mod core;
 pub mod dsp;
 pub mod lfo;
+pub mod midi;
 pub mod modulation;
 pub mod params;
 pub mod plugin;
+pub mod presets;
+pub mod shell;
 pub mod ui;
 pub mod util;
 
@@ -16,9 +19,16 @@ use vst::host::Host;
 use vst::plugin::{CanDo, Category, HostCallback, Info, Plugin};
 use vst::plugin_main;
 
-use crate::params::NormalizedParams;
+use crate::shell::{PluginShell, Transport};
 use crate::util::errors;
 
+// Outputs reported to the host, which can't change once the plugin is loaded. With the
+// `multi-output` feature, each oscillator's chain gets a stereo pair of its own.
+#[cfg(feature = "multi-output")]
+const OUTPUT_COUNT: usize = core::MAX_CHANNEL_COUNT;
+#[cfg(not(feature = "multi-output"))]
+const OUTPUT_COUNT: usize = core::CHANNEL_COUNT;
+
 // We're implementing a trait `Plugin` that does all the VST-y stuff for us.
 impl Plugin for plugin::SunfishPlugin {
     fn new(host: HostCallback) -> plugin::SunfishPlugin {
@@ -57,14 +67,15 @@ impl Plugin for plugin::SunfishPlugin {
             // Used by hosts to differentiate between plugins.
             unique_id: 0x78_B5_2B_BC,
 
-            // We don't need inputs
-            inputs: 0,
+            // A stereo input, for the external input sound source.
+            inputs: core::CHANNEL_COUNT as i32,
+
+            // We do need two outputs though (or four, in multi-output builds).
+            outputs: OUTPUT_COUNT as i32,
 
-            // We do need two outputs though.  This is default, but let's be
-            // explicit anyways.
-            outputs: core::CHANNEL_COUNT as i32,
+            parameters: self.param_count() as i32,
 
-            parameters: self.core.meta.count() as i32,
+            presets: self.bank.len() as i32,
 
             // Set our category
             category: Category::Synth,
@@ -72,20 +83,30 @@ impl Plugin for plugin::SunfishPlugin {
             // 64-bit processing.
             f64_precision: true,
 
+            // Save and restore state as a single blob, rather than parameter by parameter.
+            preset_chunks: true,
+
             // We don't care about other stuff, and it can stay default.
             ..Default::default()
         }
     }
 
     fn get_parameter_name(&self, index: i32) -> String {
-        self.core
-            .params
-            .parameter_name(&self.core.meta, index as usize)
+        self.param_name(index as usize).unwrap_or_else(|err| {
+            log::error!("get_parameter_name: {}", err);
+            "(error)".to_string()
+        })
     }
 
     fn get_parameter_text(&self, index: i32) -> String {
-        let eparam = self.core.meta.parameter_index(index as usize);
-        self.core.params.formatted_value(&self.core.meta, eparam)
+        let index = index as usize;
+        let text = self
+            .param_value(index)
+            .and_then(|value| self.param_text(index, value));
+        text.unwrap_or_else(|err| {
+            log::error!("get_parameter_text: {}", err);
+            "".to_string()
+        })
     }
 
     fn get_parameter_label(&self, _index: i32) -> String {
@@ -93,55 +114,89 @@ impl Plugin for plugin::SunfishPlugin {
     }
 
     fn get_parameter(&self, index: i32) -> f32 {
-        let eparam = self.core.meta.parameter_index(index as usize);
-        self.core.params.read_parameter(&self.core.meta, eparam) as f32
+        self.param_value(index as usize).unwrap_or_else(|err| {
+            log::error!("get_parameter: {}", err);
+            0.0
+        }) as f32
     }
 
     fn set_parameter(&mut self, index: i32, value: f32) {
-        let eparam = self.core.meta.parameter_index(index as usize);
-        self.core.params_sync.write_parameter(eparam, value as f64);
+        if let Err(err) = self.set_param_value(index as usize, value as f64) {
+            log::error!("set_parameter: {}", err);
+        }
     }
 
     fn can_be_automated(&self, _index: i32) -> bool {
         true
     }
 
+    fn change_preset(&mut self, preset: i32) {
+        if preset >= 0 {
+            self.select_program(preset as usize);
+        }
+    }
+
+    fn get_preset_num(&self) -> i32 {
+        self.bank.current() as i32
+    }
+
+    fn set_preset_name(&mut self, name: String) {
+        self.bank.rename_current(name);
+    }
+
+    fn get_preset_name(&self, preset: i32) -> String {
+        usize::try_from(preset)
+            .ok()
+            .and_then(|index| self.bank.get(index))
+            .map_or_else(String::new, |preset| preset.meta.name.clone())
+    }
+
+    fn get_preset_data(&mut self) -> Vec<u8> {
+        self.save_state()
+    }
+
+    fn get_bank_data(&mut self) -> Vec<u8> {
+        self.save_bank()
+    }
+
+    fn load_preset_data(&mut self, data: &[u8]) {
+        self.load_state(data);
+    }
+
+    fn load_bank_data(&mut self, data: &[u8]) {
+        self.load_state(data);
+    }
+
     fn set_sample_rate(&mut self, rate: f32) {
-        let rate = rate as f64;
-        self.core.update_sample_rate(rate);
-        self.core.params.update_sample_rate(rate);
-        self.core.dt = 1.0 / rate;
+        PluginShell::set_sample_rate(self, rate as f64);
+    }
+
+    fn suspend(&mut self) {
+        PluginShell::suspend(self);
+    }
+
+    fn resume(&mut self) {
+        PluginShell::resume(self);
+    }
+
+    fn get_tail_size(&self) -> isize {
+        // Zero tells the host nothing; one means there's no tail.
+        self.tail_samples().max(1) as isize
     }
 
-    // Here's the function that allows us to receive events
     fn process_events(&mut self, events: &Events) {
-        // Some events aren't MIDI events - so let's do a match
-        // to make sure we only get MIDI, since that's all we care about.
+        let program = self.bank.current();
+        // Only MIDI events matter to us.
         for event in events.events() {
             if let Event::Midi(ev) = event {
-                // Check if it's a noteon or noteoff event.
-                // This is difficult to explain without knowing how the MIDI standard works.
-                // Basically, the first byte of data tells us if this signal is a note on event
-                // or a note off event.  You can read more about that here:
-                // https://www.midi.org/specifications/item/table-1-summary-of-midi-message
-                match ev.data[0] {
-                    // if note on, increment our counter
-                    144 => {
-                        let note = ev.data[1];
-                        let velocity = unsafe { std::mem::transmute::<u8, i8>(ev.data[2]) };
-                        self.core.note_on(note, velocity);
-                    }
-
-                    // if note off, decrement our counter
-                    128 => {
-                        let note = ev.data[1];
-                        self.core.note_off(note);
-                    }
-
-                    _ => (),
-                }
+                let sample_offset = ev.delta_frames.max(0) as usize;
+                self.midi_event(ev.data, sample_offset);
             }
         }
+        // Let the host show the new program, if a program change picked one.
+        if self.bank.current() != program {
+            self.host.update_display();
+        }
     }
 
     /// Return handle to plugin editor if supported.
@@ -180,32 +235,40 @@ impl plugin::SunfishPlugin {
     fn _process<F: Float>(&mut self, buffer: &mut AudioBuffer<F>) {
         // `buffer.split()` gives us a tuple containing the
         // input and output buffers.
-        let (_, mut output_buffer) = buffer.split();
+        let (input_buffer, mut output_buffer) = buffer.split();
 
+        let mut transport = Transport::default();
         // This is a hack to work around an initialization bug where
         // the host callback isn't set, but process is called (Bitwig does this).
         if self.host.raw_callback().is_some() {
-            //let flags = vst::api::flags::TEMPO_VALID;
-            let flags = vst::api::TimeInfoFlags::TEMPO_VALID;
+            let flags =
+                vst::api::TimeInfoFlags::TEMPO_VALID | vst::api::TimeInfoFlags::PPQ_POS_VALID;
             let time_info_opt = self.host.get_time_info(flags.bits());
 
             if let Some(time_info) = time_info_opt {
-                let tempo_bpm_f64 = time_info.tempo;
-                self.core.tempo.update(tempo_bpm_f64);
+                let time_flags = vst::api::TimeInfoFlags::from_bits_truncate(time_info.flags);
+                transport = Transport {
+                    tempo_bpm: Some(time_info.tempo),
+                    ppq_pos: if time_flags.contains(vst::api::TimeInfoFlags::PPQ_POS_VALID) {
+                        Some(time_info.ppq_pos)
+                    } else {
+                        None
+                    },
+                    playing: time_flags.contains(vst::api::TimeInfoFlags::TRANSPORT_PLAYING),
+                };
             }
         }
 
-        // We need to zero out the buffers, since render assumes they
-        // are zero. There may be a faster way to do this in the future.
-        for output_channel in output_buffer.into_iter() {
-            for output_sample in output_channel {
-                *output_sample = F::from(0.0).unwrap();
-            }
+        // Create fixed slices of slices (to avoid any heap allocations).
+        let mut inputs: [&[F]; core::CHANNEL_COUNT] = Default::default();
+        let input_count = input_buffer.len().min(core::CHANNEL_COUNT);
+        #[allow(clippy::needless_range_loop)]
+        for ch in 0..input_count {
+            inputs[ch] = input_buffer.get(ch);
         }
 
-        // Create a fixed slice of mutable slices (to avoid any heap allocations).
-        let mut v: [&mut [F]; core::CHANNEL_COUNT] = Default::default();
-        let ch_count = output_buffer.len().max(core::CHANNEL_COUNT);
+        let mut v: [&mut [F]; OUTPUT_COUNT] = Default::default();
+        let ch_count = output_buffer.len().min(OUTPUT_COUNT);
 
         #[allow(clippy::needless_range_loop)]
         for ch in 0..ch_count {
@@ -215,7 +278,7 @@ impl plugin::SunfishPlugin {
         // Resolve parameter updates from the GUI.
         self.update_host_parameters();
 
-        self.core.render(&mut v[..ch_count]);
+        PluginShell::process(self, &inputs[..input_count], &mut v[..ch_count], &transport);
     }
 }
 
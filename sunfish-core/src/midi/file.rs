@@ -0,0 +1,282 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+
+/// A quarter note's length until a file sets the tempo (i.e. 120 BPM), in microseconds.
+const DEFAULT_TEMPO: u32 = 500_000;
+
+const META_EVENT: u8 = 0xFF;
+const META_TEMPO: u8 = 0x51;
+const META_END_OF_TRACK: u8 = 0x2F;
+const SYSEX: u8 = 0xF0;
+const SYSEX_ESCAPE: u8 = 0xF7;
+
+/// A channel message (a note, controller and such) from a MIDI file, and when it plays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimedMessage {
+    /// Seconds from the start of the file.
+    pub time: f64,
+    /// The message's bytes; those with a single data byte (e.g. channel pressure) end in a 0.
+    pub data: [u8; 3],
+}
+
+/// Read the channel messages of a Standard MIDI File; see `parse`.
+pub fn load(path: &Path) -> Result<Vec<TimedMessage>> {
+    parse(&fs::read(path)?)
+}
+
+/// The channel messages of all the tracks of a Standard MIDI File, in the order they play.
+/// Notes on at velocity 0 become notes off. Tempo changes are followed; other meta events and
+/// system exclusive messages are left out.
+pub fn parse(bytes: &[u8]) -> Result<Vec<TimedMessage>> {
+    let mut reader = Reader::new(bytes);
+    let (id, header) = reader.chunk()?;
+    if id != b"MThd" || header.len() < 6 {
+        bail!("Not a MIDI file");
+    }
+    let track_count = u16::from_be_bytes([header[2], header[3]]);
+    let division = u16::from_be_bytes([header[4], header[5]]);
+
+    // Tempo changes as (tick, microseconds per quarter note), and messages as (tick, bytes).
+    let mut tempos = vec![];
+    let mut messages = vec![];
+    let mut tracks_read = 0;
+    while tracks_read < track_count && !reader.at_end() {
+        let (id, track) = reader.chunk()?;
+        // Chunks of unknown kinds are to be skipped.
+        if id == b"MTrk" {
+            read_track(track, &mut tempos, &mut messages)?;
+            tracks_read += 1;
+        }
+    }
+    // Stable, so messages at the same tick keep their order within a track.
+    messages.sort_by_key(|(tick, _)| *tick);
+    tempos.sort_by_key(|(tick, _)| *tick);
+
+    let timed = |time: f64, data: [u8; 3]| TimedMessage { time, data };
+    if division & 0x8000 != 0 {
+        // SMPTE timing: the upper byte is minus the frames per second (-29 being 29.97), and
+        // the lower one the ticks per frame. Tempo changes don't apply.
+        let fps = match ((division >> 8) as u8 as i8).checked_neg() {
+            Some(29) => 29.97,
+            Some(fps) if fps > 0 => fps as f64,
+            _ => bail!("Invalid SMPTE frame rate in MIDI file"),
+        };
+        let ticks_per_second = fps * (division & 0xFF) as f64;
+        if ticks_per_second <= 0.0 {
+            bail!("Invalid SMPTE timing in MIDI file");
+        }
+        return Ok(messages
+            .into_iter()
+            .map(|(tick, data)| timed(tick as f64 / ticks_per_second, data))
+            .collect());
+    }
+
+    let ticks_per_quarter = division as f64;
+    if division == 0 {
+        bail!("MIDI file has no ticks per quarter note");
+    }
+    // Walk the tempo changes along with the messages, adding up the time between them.
+    let mut tempos = tempos.into_iter().peekable();
+    let (mut last_tick, mut last_time, mut tempo) = (0, 0.0, DEFAULT_TEMPO);
+    let seconds = |ticks: u64, tempo: u32| ticks as f64 * tempo as f64 / 1e6 / ticks_per_quarter;
+    let mut result = Vec::with_capacity(messages.len());
+    for (tick, data) in messages {
+        while let Some((tempo_tick, new_tempo)) =
+            tempos.next_if(|(tempo_tick, _)| *tempo_tick <= tick)
+        {
+            last_time += seconds(tempo_tick - last_tick, tempo);
+            last_tick = tempo_tick;
+            tempo = new_tempo;
+        }
+        result.push(timed(last_time + seconds(tick - last_tick, tempo), data));
+    }
+    Ok(result)
+}
+
+// Add a track's tempo changes and channel messages to those found so far.
+fn read_track(
+    track: &[u8],
+    tempos: &mut Vec<(u64, u32)>,
+    messages: &mut Vec<(u64, [u8; 3])>,
+) -> Result<()> {
+    let mut reader = Reader::new(track);
+    let mut tick = 0;
+    // A message may leave out its status byte when it's the same as the last one's.
+    let mut running_status = None;
+    while !reader.at_end() {
+        tick += reader.var_len()? as u64;
+        let status = reader.byte()?;
+        match status {
+            META_EVENT => {
+                running_status = None;
+                let kind = reader.byte()?;
+                let len = reader.var_len()?;
+                let data = reader.take(len as usize)?;
+                match kind {
+                    META_TEMPO if len == 3 => {
+                        tempos.push((tick, u32::from_be_bytes([0, data[0], data[1], data[2]])))
+                    }
+                    META_END_OF_TRACK => break,
+                    _ => (),
+                }
+            }
+            SYSEX | SYSEX_ESCAPE => {
+                running_status = None;
+                let len = reader.var_len()?;
+                reader.take(len as usize)?;
+            }
+            0xF1..=0xFE => bail!("Unexpected status {:#x} in MIDI file", status),
+            _ => {
+                let (status, data1) = if status < 0x80 {
+                    // What was read is the first data byte.
+                    let running = running_status
+                        .ok_or_else(|| anyhow!("Data without a status in MIDI file"))?;
+                    (running, status)
+                } else {
+                    running_status = Some(status);
+                    (status, reader.byte()?)
+                };
+                let data2 = match status & 0xF0 {
+                    // Program change and channel pressure have a single data byte.
+                    0xC0 | 0xD0 => 0,
+                    _ => reader.byte()?,
+                };
+                let data = match status & 0xF0 {
+                    0x90 if data2 == 0 => [0x80 | (status & 0x0F), data1, 0],
+                    _ => [status, data1, data2],
+                };
+                messages.push((tick, data));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Reads a MIDI file's bytes in order, failing if they run out.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| anyhow!("MIDI file ends early"))?;
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    // A variable-length quantity: 7 bits per byte, most significant first, in up to 4 bytes.
+    fn var_len(&mut self) -> Result<u32> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("Invalid variable-length number in MIDI file")
+    }
+
+    // A chunk's 4-byte type, and its contents.
+    fn chunk(&mut self) -> Result<(&'a [u8], &'a [u8])> {
+        let id = self.take(4)?;
+        let len = self.take(4)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]);
+        Ok((id, self.take(len as usize)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A chunk as it's stored: type, length and contents.
+    fn chunk(id: &[u8], contents: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(contents.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(contents);
+        bytes
+    }
+
+    fn file(division: u16, tracks: &[&[u8]]) -> Vec<u8> {
+        let mut header = vec![0, 1];
+        header.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+        header.extend_from_slice(&division.to_be_bytes());
+        let mut bytes = chunk(b"MThd", &header);
+        for track in tracks {
+            bytes.extend(chunk(b"MTrk", track));
+        }
+        bytes
+    }
+
+    #[test]
+    fn follows_tempo_changes_across_tracks() {
+        // 96 ticks per quarter note: at 60 BPM for a beat, then at 120 BPM.
+        let tempo_track: &[u8] = &[
+            0x00, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40, // 1,000,000 us per beat
+            0x60, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // 500,000 us per beat
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let note_track: &[u8] = &[
+            0x00, 0x91, 60, 100, // note on, channel 2
+            0x60, 60, 0, // running status, at velocity 0
+            0x00, 0xF0, 0x01, 0xF7, // system exclusive
+            0x60, 0xB1, 1, 64, // mod wheel
+            0x81, 0x40, 0xD1, 90, // channel pressure, 192 ticks later
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let messages = parse(&file(96, &[tempo_track, note_track])).unwrap();
+        let expected = [
+            (0.0, [0x91, 60, 100]),
+            (1.0, [0x81, 60, 0]),
+            (1.5, [0xB1, 1, 64]),
+            (2.5, [0xD1, 90, 0]),
+        ];
+        assert_eq!(messages.len(), expected.len());
+        for (message, (time, data)) in messages.iter().zip(expected.iter()) {
+            assert!((message.time - time).abs() < 1e-9, "{:?}", message);
+            assert_eq!(message.data, *data);
+        }
+    }
+
+    #[test]
+    fn times_smpte_files_by_frame() {
+        // 25 frames per second, 40 ticks per frame: a millisecond per tick.
+        let division = (((-25i8) as u8 as u16) << 8) | 40;
+        let track: &[u8] = &[0x83, 0x60, 0x90, 64, 100];
+        let messages = parse(&file(division, &[track])).unwrap();
+        assert!((messages[0].time - 0.48).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(parse(b"RIFF\0\0\0\0").is_err());
+        let mut truncated = file(96, &[&[0x00, 0x90, 60, 100]]);
+        truncated.truncate(truncated.len() - 2);
+        assert!(parse(&truncated).is_err());
+        // Data bytes before any status.
+        assert!(parse(&file(96, &[&[0x00, 60, 100]])).is_err());
+        // SMPTE timing at -128 frames per second, which can't be negated.
+        assert!(parse(&file(0x8028, &[&[0x00, 0x90, 60, 100]])).is_err());
+    }
+}
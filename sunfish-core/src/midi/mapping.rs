@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::params::EParam;
+
+/// Largest MIDI controller value.
+const MAX_CC_VALUE: f64 = 127.0;
+
+/// How many parameters a single controller change can write at once; see `CcMap::handle`.
+pub const MAX_CC_TARGETS: usize = 32;
+
+/// Which MIDI controllers (CC numbers) drive which parameters. Each parameter has at most one
+/// controller, though a controller may drive several parameters.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CcMap {
+    bindings: BTreeMap<u8, Vec<EParam>>,
+    // The parameter waiting for "MIDI learn", if any.
+    #[serde(skip)]
+    learning: Option<EParam>,
+}
+
+impl CcMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind `cc` to `eparam`, replacing whatever controller `eparam` had.
+    pub fn bind(&mut self, cc: u8, eparam: EParam) {
+        self.unbind(eparam);
+        self.bindings.entry(cc).or_default().push(eparam);
+    }
+
+    pub fn unbind(&mut self, eparam: EParam) {
+        for eparams in self.bindings.values_mut() {
+            eparams.retain(|bound| *bound != eparam);
+        }
+        self.bindings.retain(|_, eparams| !eparams.is_empty());
+    }
+
+    /// The controller bound to `eparam`, if any.
+    pub fn binding(&self, eparam: EParam) -> Option<u8> {
+        self.bindings
+            .iter()
+            .find(|(_, eparams)| eparams.contains(&eparam))
+            .map(|(cc, _)| *cc)
+    }
+
+    /// Bind the next controller received to `eparam`.
+    pub fn learn(&mut self, eparam: EParam) {
+        self.learning = Some(eparam);
+    }
+
+    pub fn cancel_learn(&mut self) {
+        self.learning = None;
+    }
+
+    pub fn learning(&self) -> Option<EParam> {
+        self.learning
+    }
+
+    /// Complete "MIDI learn", binding `cc` to the parameter waiting for it (which is returned).
+    /// Binding allocates, so this is left to the GUI rather than done by `handle`.
+    pub fn finish_learn(&mut self, cc: u8) -> Option<EParam> {
+        let eparam = self.learning.take()?;
+        self.bind(cc, eparam);
+        Some(eparam)
+    }
+
+    /// Handle a controller change, filling `out` with the (normalized) parameter values to
+    /// write and returning those filled. Never allocates, so it's safe on the audio thread;
+    /// parameters beyond `out`'s length are left out.
+    pub fn handle<'a>(
+        &self,
+        cc: u8,
+        value: u8,
+        out: &'a mut [(EParam, f64)],
+    ) -> &'a [(EParam, f64)] {
+        let value = (value as f64 / MAX_CC_VALUE).min(1.0);
+        let eparams = self.bindings.get(&cc).map_or(&[][..], |eparams| eparams);
+        let count = eparams.len().min(out.len());
+        for (slot, eparam) in out.iter_mut().zip(eparams) {
+            *slot = (*eparam, value);
+        }
+        &out[..count]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::{EFiltParams, EOscParams};
+
+    #[test]
+    fn learns_and_maps_controllers() {
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+        let gain = EParam::Osc1(EOscParams::Gain);
+        let mut out = [(EParam::OutputGain, 0.0); MAX_CC_TARGETS];
+        let mut map = CcMap::new();
+        assert!(map.handle(74, 64, &mut out).is_empty());
+
+        map.learn(cutoff);
+        // Handling doesn't bind; the learned controller is bound separately.
+        assert!(map.handle(74, 127, &mut out).is_empty());
+        assert_eq!(map.finish_learn(74), Some(cutoff));
+        assert_eq!(map.finish_learn(75), None);
+        assert_eq!(map.handle(74, 127, &mut out), [(cutoff, 1.0)]);
+        assert_eq!(map.learning(), None);
+        assert_eq!(map.binding(cutoff), Some(74));
+
+        // Rebinding moves the parameter to the new controller.
+        map.bind(71, gain);
+        map.bind(71, cutoff);
+        assert!(map.handle(74, 0, &mut out).is_empty());
+        assert_eq!(map.handle(71, 0, &mut out), [(gain, 0.0), (cutoff, 0.0)]);
+        assert_eq!(map.handle(71, 0, &mut out[..1]), [(gain, 0.0)]);
+
+        let saved = serde_json::to_string(&map).unwrap();
+        assert_eq!(serde_json::from_str::<CcMap>(&saved).unwrap(), map);
+    }
+}
@@ -0,0 +1,24 @@
+pub mod file;
+pub mod mapping;
+
+use crate::util::spsc::{self, Consumer, Producer};
+
+/// How many MIDI messages from the GUI can wait for the audio thread.
+const GUI_QUEUE_CAPACITY: usize = 256;
+
+/// Sends MIDI messages played on the GUI (e.g. on its keyboard) to the audio thread.
+pub type MidiSender = Producer<[u8; 3]>;
+/// Receives the GUI's MIDI messages, to be handled like the host's.
+pub type MidiReceiver = Consumer<[u8; 3]>;
+
+pub fn gui_channel() -> (MidiSender, MidiReceiver) {
+    spsc::channel(GUI_QUEUE_CAPACITY)
+}
+
+pub fn note_on(note: u8, velocity: u8) -> [u8; 3] {
+    [144, note, velocity]
+}
+
+pub fn note_off(note: u8) -> [u8; 3] {
+    [128, note, 0]
+}
@@ -0,0 +1,98 @@
+use crate::util::rng::Rng;
+
+// Fixed, so the same patch always drifts the same way.
+const DRIFT_SEED: u64 = 0x5EED;
+// Largest single step, as a fraction of the full (-1.0 to 1.0) range.
+const DRIFT_MAX_STEP: f64 = 0.5;
+
+/// A random walk: at each step (`rate_hz` times a second) it wanders a random distance from where
+/// it was, staying within -1.0 to 1.0. Unlike the random LFO shapes, consecutive values are
+/// related, so it suits slow, evolving motion.
+#[derive(Clone, Debug)]
+pub struct Drift {
+    rng: Rng,
+    // Where the walk is heading, and where the (smoothed) output is now.
+    target: f64,
+    value: f64,
+    // Time left until the next step, in seconds.
+    time_to_step: f64,
+}
+
+impl Drift {
+    pub fn new() -> Self {
+        Drift {
+            rng: Rng::new(DRIFT_SEED),
+            target: 0.0,
+            value: 0.0,
+            time_to_step: 0.0,
+        }
+    }
+
+    /// Advance by `time_delta` seconds. `smooth` (0.0 to 1.0) sets how long the output takes to
+    /// glide to each new step, as a fraction of the step period; at zero it jumps.
+    pub fn evaluate(&mut self, time_delta: f64, rate_hz: f64, smooth: f64) -> f64 {
+        if rate_hz <= 0.0 {
+            return self.value;
+        }
+        let period_sec = 1.0 / rate_hz;
+        self.time_to_step -= time_delta;
+        while self.time_to_step <= 0.0 {
+            self.time_to_step += period_sec;
+            self.step();
+        }
+
+        let glide_sec = smooth * period_sec;
+        if glide_sec <= 0.0 {
+            self.value = self.target;
+        } else {
+            self.value += (self.target - self.value) * (1.0 - (-time_delta / glide_sec).exp());
+        }
+        self.value
+    }
+
+    fn step(&mut self) {
+        let target = self.target + DRIFT_MAX_STEP * self.rng.next_bipolar();
+        // Bounce off the edges rather than sticking to them.
+        self.target = if target > 1.0 {
+            2.0 - target
+        } else if target < -1.0 {
+            -2.0 - target
+        } else {
+            target
+        };
+    }
+}
+
+impl Default for Drift {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wanders_within_range_repeatably() {
+        let render = |smooth| {
+            let mut drift = Drift::new();
+            (0..1000)
+                .map(|_| drift.evaluate(0.01, 5.0, smooth))
+                .collect::<Vec<f64>>()
+        };
+        let values = render(0.0);
+        assert_eq!(values, render(0.0));
+        assert!(values.iter().all(|v| (-1.0..=1.0).contains(v)));
+        assert!(values.iter().any(|v| *v != values[0]));
+
+        // Smoothing takes the edge off each step.
+        let biggest_jump = |values: &[f64]| {
+            values
+                .windows(2)
+                .map(|w| (w[1] - w[0]).abs())
+                .fold(0.0, f64::max)
+        };
+        assert!(biggest_jump(&render(1.0)) < biggest_jump(&values));
+    }
+}
@@ -0,0 +1,207 @@
+use params_derive::ParamFields;
+use serde::{Deserialize, Serialize};
+
+use crate::modulation::polarity::Polarity;
+use crate::params::{EAdsrParams, EFiltParams, EModSlotParams, EOscParams, EParam};
+use crate::util::enumerable::Enumerable;
+
+/// Number of routes in the modulation matrix.
+pub const MOD_MATRIX_SLOTS: usize = 4;
+
+pub const DEFAULT_MOD_DEPTH: f64 = 0.5;
+
+/// Where a matrix route takes its modulation from.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum ModSource {
+    Off,
+    Lfo1,
+    Lfo2,
+    ModEnv,
+    ModEnv2,
+    Velocity,
+    ModWheel,
+    Aftertouch,
+    KeyTrack,
+    Drift,
+}
+
+impl ModSource {
+    pub fn as_string(self) -> String {
+        let as_str = match self {
+            ModSource::Off => "Off",
+            ModSource::Lfo1 => "LFO1",
+            ModSource::Lfo2 => "LFO2",
+            ModSource::ModEnv => "Mod Env",
+            ModSource::ModEnv2 => "Mod Env 2",
+            ModSource::Velocity => "Velocity",
+            ModSource::ModWheel => "Mod Wheel",
+            ModSource::Aftertouch => "Aftertouch",
+            ModSource::KeyTrack => "Key Track",
+            ModSource::Drift => "Drift",
+        };
+        as_str.to_string()
+    }
+}
+
+impl Enumerable<ModSource> for ModSource {
+    fn enumerate() -> Vec<ModSource> {
+        vec![
+            ModSource::Off,
+            ModSource::Lfo1,
+            ModSource::Lfo2,
+            ModSource::ModEnv,
+            ModSource::ModEnv2,
+            ModSource::Velocity,
+            ModSource::ModWheel,
+            ModSource::Aftertouch,
+            ModSource::KeyTrack,
+            ModSource::Drift,
+        ]
+    }
+}
+
+impl From<ModSource> for String {
+    fn from(f: ModSource) -> String {
+        f.as_string()
+    }
+}
+
+impl From<String> for ModSource {
+    fn from(s: String) -> ModSource {
+        match s.as_ref() {
+            "LFO1" => ModSource::Lfo1,
+            "LFO2" => ModSource::Lfo2,
+            "Mod Env" => ModSource::ModEnv,
+            "Mod Env 2" => ModSource::ModEnv2,
+            "Velocity" => ModSource::Velocity,
+            "Mod Wheel" => ModSource::ModWheel,
+            "Aftertouch" => ModSource::Aftertouch,
+            "Key Track" => ModSource::KeyTrack,
+            "Drift" => ModSource::Drift,
+            _ => ModSource::Off,
+        }
+    }
+}
+
+/// The parameter a matrix route modulates.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum MatrixTarget {
+    Off,
+    Param(EParam),
+}
+
+impl MatrixTarget {
+    pub fn as_string(self) -> String {
+        match self {
+            MatrixTarget::Off => "Off".to_string(),
+            MatrixTarget::Param(eparam) => eparam.as_string(false),
+        }
+    }
+
+    pub fn eparam(self) -> Option<EParam> {
+        match self {
+            MatrixTarget::Off => None,
+            MatrixTarget::Param(eparam) => Some(eparam),
+        }
+    }
+}
+
+impl Enumerable<MatrixTarget> for MatrixTarget {
+    fn enumerate() -> Vec<MatrixTarget> {
+        let mut targets = vec![MatrixTarget::Off];
+        targets.extend(
+            EParam::all()
+                .filter(is_modulatable)
+                .map(MatrixTarget::Param),
+        );
+        targets
+    }
+}
+
+impl From<MatrixTarget> for String {
+    fn from(f: MatrixTarget) -> String {
+        f.as_string()
+    }
+}
+
+impl From<String> for MatrixTarget {
+    fn from(s: String) -> MatrixTarget {
+        MatrixTarget::enumerate()
+            .into_iter()
+            .find(|target| target.as_string() == s)
+            .unwrap_or(MatrixTarget::Off)
+    }
+}
+
+/// Whether a parameter takes a continuous value that the matrix can move.
+/// Switches and choices (shapes, modes, routing) are left alone, as are the
+/// LFO and matrix settings themselves.
+pub fn is_modulatable(eparam: &EParam) -> bool {
+    match eparam {
+        EParam::Osc1(param) | EParam::Osc2(param) => matches!(
+            param,
+            EOscParams::FineOffset
+                | EOscParams::Detune
+                | EOscParams::StereoWidth
+                | EOscParams::UnisonAmt
+                | EOscParams::Gain
+                | EOscParams::Pan
+        ),
+        EParam::Filt1(param) | EParam::Filt2(param) => matches!(
+            param,
+            EFiltParams::Cutoff
+                | EFiltParams::Resonance
+                | EFiltParams::EnvAmt
+                | EFiltParams::Vowel
+                | EFiltParams::KeyTrack
+                | EFiltParams::FmAmt
+        ),
+        EParam::AmpEnv(param) | EParam::ModEnv(param) | EParam::ModEnv2(param) => {
+            !matches!(param, EAdsrParams::Retrigger)
+        }
+        EParam::OutputGain => true,
+        _ => false,
+    }
+}
+
+/// One route of the matrix: `source` moves `target` by up to `depth` of the
+/// target's full range.
+#[derive(Clone, Copy, Debug, Deserialize, ParamFields, PartialEq, Serialize)]
+#[params(EModSlotParams)]
+pub struct ModSlot {
+    #[param(Source, meta = "mod_slot_source_meta")]
+    pub source: ModSource,
+    #[param(Target, meta = "mod_slot_target_meta")]
+    pub target: MatrixTarget,
+    #[param(Depth, meta = "mod_slot_depth_meta")]
+    pub depth: f64,
+    #[param(Polarity, meta = "mod_slot_polarity_meta")]
+    pub polarity: Polarity,
+}
+
+impl Default for ModSlot {
+    fn default() -> Self {
+        Self {
+            source: ModSource::Off,
+            target: MatrixTarget::Off,
+            depth: DEFAULT_MOD_DEPTH,
+            polarity: Polarity::Positive,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn targets_round_trip_through_names() {
+        let targets = MatrixTarget::enumerate();
+        assert_eq!(targets[0], MatrixTarget::Off);
+        assert!(targets.contains(&MatrixTarget::Param(EParam::Filt1(EFiltParams::Cutoff))));
+        assert!(!targets.contains(&MatrixTarget::Param(EParam::Osc1(EOscParams::Shape))));
+        for target in targets {
+            assert_eq!(MatrixTarget::from(target.as_string()), target);
+        }
+    }
+}
@@ -1,11 +1,19 @@
+pub mod drift;
+pub mod matrix;
+pub mod polarity;
 pub mod target;
-
-use std::collections::HashSet;
+pub mod voice;
 
 use crate::lfo;
+use crate::modulation::drift::Drift;
+use crate::modulation::matrix::{ModSource, MOD_MATRIX_SLOTS};
 use crate::modulation::target::ModulationTarget;
-use crate::params::{EFiltParams, ELfoParams, EOscParams, EParam};
-use crate::params::{Params, ParamsMeta};
+use crate::modulation::voice::VoiceMod;
+use crate::params::set::ParamSet;
+use crate::params::LfoParams;
+use crate::params::{EEnvRouteParams, EFiltParams, ELfoParams, EOscParams, EParam, EnvRoute};
+use crate::params::{NormalizedParams, Params, ParamsMeta};
+use crate::params::{KEY_TRACK_CENTER_NOTE, LFO_ROUTES};
 
 const MOD_TICK_HZ: f64 = 200.0; // 5 ms.
 const MOD_TICK_S: f64 = 1.0 / MOD_TICK_HZ;
@@ -14,11 +22,21 @@ const MOD_TICK_S: f64 = 1.0 / MOD_TICK_HZ;
 const MIN_OSC_FREQ: f64 = 20.0;
 const MAX_OSC_FREQ: f64 = 20000.0;
 
+// Mod envelope routes come after the LFO routes in the modulation ranges.
+const ENV_ROUTE_INDEX: usize = 2 * LFO_ROUTES;
+const ENV_ROUTES: usize = 2;
+
+// Notes this far from the key track center reach full scale.
+const KEY_TRACK_SPAN: f64 = 64.0;
+
+// Rate of the mod wheel's vibrato.
+const MOD_WHEEL_VIBRATO_HZ: f64 = 5.5;
+
 // Global modulators.
 #[derive(Clone, Debug)]
 pub struct ModState {
     // Keep track of which parameters are being actively modulated.
-    pub modulated_params: HashSet<EParam>,
+    pub modulated_params: ParamSet,
 
     // Stores how much time has passed between modulation evaluation.
     mod_time_elapsed: f64,
@@ -31,7 +49,7 @@ pub struct ModState {
 impl ModState {
     pub fn new(sample_rate: f64, ranges: usize) -> Self {
         ModState {
-            modulated_params: HashSet::new(),
+            modulated_params: ParamSet::new(),
             mod_time_elapsed: 0.0,
             mod_tick: MOD_TICK_S * (1.0 / sample_rate),
             mod_ranges: vec![ModRange::new(); ranges],
@@ -77,11 +95,62 @@ impl ModRange {
     }
 }
 
+/// Latest value of each modulation source. LFOs, key tracking and drift are
+/// bipolar (-1.0 to 1.0); the rest run from 0.0 to 1.0.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ModSources {
+    pub lfo1: f64,
+    pub lfo2: f64,
+    pub mod_env: f64,
+    pub mod_env2: f64,
+    pub velocity: f64,
+    pub mod_wheel: f64,
+    pub aftertouch: f64,
+    pub key_track: f64,
+    pub drift: f64,
+}
+
+impl ModSources {
+    #[inline(always)]
+    pub fn get(&self, source: ModSource) -> f64 {
+        match source {
+            ModSource::Off => 0.0,
+            ModSource::Lfo1 => self.lfo1,
+            ModSource::Lfo2 => self.lfo2,
+            ModSource::ModEnv => self.mod_env,
+            ModSource::ModEnv2 => self.mod_env2,
+            ModSource::Velocity => self.velocity,
+            ModSource::ModWheel => self.mod_wheel,
+            ModSource::Aftertouch => self.aftertouch,
+            ModSource::KeyTrack => self.key_track,
+            ModSource::Drift => self.drift,
+        }
+    }
+}
+
 pub struct Modulation {
     // LFOs
     lfo1: lfo::Lfo,
     lfo2: lfo::Lfo,
+    // Free-running random walk; unlike the LFOs, notes don't restart it.
+    drift: Drift,
     pub mod_state: ModState,
+    sources: ModSources,
+    // Time since the most recent note-on, in seconds.
+    note_time: f64,
+
+    // Matrix targets and their summed (normalized) offsets, for this tick and
+    // the one before; preallocated to avoid allocating in the audio thread.
+    matrix_offsets: Vec<(EParam, f64)>,
+    previous_matrix_offsets: Vec<(EParam, f64)>,
+    // Parameters changed by the last tick.
+    updated_params: Vec<EParam>,
+    // LFO and mod wheel modulation of voice-scoped targets, shared by every voice.
+    lfo_voice_mod: VoiceMod,
+    // Position in the mod wheel's vibrato cycle, from 0.0 to 1.0.
+    vibrato_phase: f64,
+    // Whether the host transport was playing as of the last process call.
+    transport_playing: bool,
 }
 
 impl Modulation {
@@ -93,22 +162,125 @@ impl Modulation {
         Self {
             lfo1: lfo::Lfo::new(lfo::LfoShape::Triangle, lfo::Rate::Hz(1.0), tempo_bps),
             lfo2: lfo::Lfo::new(lfo::LfoShape::Triangle, lfo::Rate::Hz(1.0), tempo_bps),
-            mod_state: ModState::new(sample_rate, 2),
+            drift: Drift::new(),
+            mod_state: ModState::new(sample_rate, ENV_ROUTE_INDEX + ENV_ROUTES),
+            sources: ModSources::default(),
+            note_time: 0.0,
+            matrix_offsets: Vec::with_capacity(MOD_MATRIX_SLOTS),
+            previous_matrix_offsets: Vec::with_capacity(MOD_MATRIX_SLOTS),
+            updated_params: Vec::with_capacity(MOD_MATRIX_SLOTS + 2 * LFO_ROUTES),
+            transport_playing: false,
+            lfo_voice_mod: VoiceMod::default(),
+            vibrato_phase: 0.0,
+        }
+    }
+
+    /// Follow the host transport: tempo-synced, free-running LFOs are phase-locked to the song
+    /// position (in quarter notes) while the transport plays, and restart when it starts.
+    pub fn update_transport(
+        &mut self,
+        params: &Params,
+        tempo_bps: f64,
+        ppq_pos: Option<f64>,
+        playing: bool,
+    ) {
+        let started = playing && !self.transport_playing;
+        self.transport_playing = playing;
+
+        let lfos = [
+            (&mut self.lfo1, &params.lfo1),
+            (&mut self.lfo2, &params.lfo2),
+        ];
+        for (lfo, lfo_params) in lfos.into_iter() {
+            if !matches!(lfo_params.rate, lfo::Rate::Synced(_)) {
+                continue;
+            }
+            // Pick up any tempo change.
+            lfo.update_rate(lfo_params.rate, tempo_bps);
+            // LFOs that restart with notes keep their own phase.
+            if lfo_params.retrigger != lfo::LfoRetrigger::Free || lfo_params.one_shot {
+                continue;
+            }
+            match ppq_pos {
+                Some(ppq_pos) if playing => lfo.sync_to_beat(ppq_pos),
+                None if started => lfo.reset(),
+                _ => {}
+            }
         }
     }
 
+    /// Handle a new note; velocity is from 0.0 to 1.0. `first_note` is set when no other notes
+    /// are held.
+    pub fn note_on(&mut self, params: &Params, note: u8, velocity: f64, first_note: bool) {
+        let lfos = [
+            (&mut self.lfo1, &params.lfo1),
+            (&mut self.lfo2, &params.lfo2),
+        ];
+        for (lfo_index, (lfo, lfo_params)) in lfos.into_iter().enumerate() {
+            // One-shot LFOs always start their cycle with the note, unless held notes keep them
+            // going. Free-running ones are left alone.
+            let retrigger = match lfo_params.retrigger {
+                lfo::LfoRetrigger::Free => lfo_params.one_shot,
+                lfo::LfoRetrigger::FirstNote => first_note,
+                lfo::LfoRetrigger::EveryNote => true,
+            };
+            if retrigger {
+                // Random shapes restart their sequence too, so renders are repeatable.
+                lfo.reseed(((note as u64) << 8) | lfo_index as u64);
+                lfo.reset();
+            }
+        }
+
+        // Per-note sources follow the most recent note.
+        self.note_time = 0.0;
+        self.sources.velocity = velocity;
+        self.sources.key_track =
+            ((note as f64 - KEY_TRACK_CENTER_NOTE as f64) / KEY_TRACK_SPAN).clamp(-1.0, 1.0);
+    }
+
+    /// Record the mod envelope levels of the most recently played note.
+    pub fn set_mod_envs(&mut self, mod_env: f64, mod_env2: f64) {
+        self.sources.mod_env = mod_env;
+        self.sources.mod_env2 = mod_env2;
+    }
+
+    /// Mod wheel position, from 0.0 to 1.0.
+    pub fn set_mod_wheel(&mut self, value: f64) {
+        self.sources.mod_wheel = value;
+    }
+
+    /// Channel pressure, from 0.0 to 1.0.
+    pub fn set_aftertouch(&mut self, value: f64) {
+        self.sources.aftertouch = value;
+    }
+
+    /// Return the mod wheel and aftertouch to rest.
+    pub fn reset_controllers(&mut self) {
+        self.sources.mod_wheel = 0.0;
+        self.sources.aftertouch = 0.0;
+    }
+
+    /// Advance the modulators by `delta` seconds and update the modulated parameters.
+    ///
+    /// Returns the parameters that changed; these affect all active voices (notes being played).
     pub fn tick(
         &mut self,
         delta: f64,
+        meta: &ParamsMeta,
         params: &Params,
         params_modulated: &mut Params,
-    ) -> (Option<EParam>, Option<EParam>) {
+    ) -> &[EParam] {
+        self.updated_params.clear();
         if let Some(time_elapsed) = self.mod_state.tick(delta) {
-            // Which parameters to update voices on, if any.
-            self.tick_lfos(time_elapsed, params, params_modulated)
-        } else {
-            (None, None)
+            self.tick_lfos(time_elapsed, params, params_modulated);
+            self.tick_mod_wheel(time_elapsed, params);
+            self.sources.drift =
+                self.drift
+                    .evaluate(time_elapsed, params.drift.rate, params.drift.smooth);
+            self.tick_env_routes(params, params_modulated);
+            self.tick_matrix(meta, params, params_modulated);
         }
+        &self.updated_params
     }
 
     /// Deal with modulation target and rate changes. This must happen before the modulated state
@@ -127,64 +299,262 @@ impl Modulation {
         match param {
             // Modulators
             EParam::Lfo1(ELfoParams::Target) => {
-                let previous_target = params_modulated.lfo1.target;
-                let target = params.lfo1.target;
-                update_mod_range(&mut self.mod_state, meta, 0, target);
-                modulation_target_to_eparam(&previous_target)
+                self.update_lfo_target(meta, 0, 0, &params.lfo1, &params_modulated.lfo1)
+            }
+            EParam::Lfo1(ELfoParams::Target2) => {
+                self.update_lfo_target(meta, 0, 1, &params.lfo1, &params_modulated.lfo1)
+            }
+            EParam::Lfo1(ELfoParams::Shape) => {
+                self.lfo1.set_shape(params.lfo1.shape);
+                None
             }
             EParam::Lfo1(ELfoParams::Rate) => {
                 self.lfo1.update_rate(params.lfo1.rate, tempo_bps);
                 None
             }
+            EParam::Lfo1(ELfoParams::Phase) => {
+                self.lfo1.set_phase(params.lfo1.phase);
+                None
+            }
+            EParam::Lfo1(ELfoParams::OneShot) => {
+                self.lfo1.set_one_shot(params.lfo1.one_shot);
+                None
+            }
             EParam::Lfo2(ELfoParams::Target) => {
-                let previous_target = params_modulated.lfo2.target;
-                let target = params.lfo2.target;
-                update_mod_range(&mut self.mod_state, meta, 1, target);
-                modulation_target_to_eparam(&previous_target)
+                self.update_lfo_target(meta, 1, 0, &params.lfo2, &params_modulated.lfo2)
+            }
+            EParam::Lfo2(ELfoParams::Target2) => {
+                self.update_lfo_target(meta, 1, 1, &params.lfo2, &params_modulated.lfo2)
+            }
+            EParam::Lfo2(ELfoParams::Shape) => {
+                self.lfo2.set_shape(params.lfo2.shape);
+                None
             }
             EParam::Lfo2(ELfoParams::Rate) => {
                 self.lfo2.update_rate(params.lfo2.rate, tempo_bps);
                 None
             }
+            EParam::Lfo2(ELfoParams::Phase) => {
+                self.lfo2.set_phase(params.lfo2.phase);
+                None
+            }
+            EParam::Lfo2(ELfoParams::OneShot) => {
+                self.lfo2.set_one_shot(params.lfo2.one_shot);
+                None
+            }
+            EParam::ModEnvRoute(EEnvRouteParams::Target) => self.update_env_target(
+                meta,
+                0,
+                &params.mod_env_route,
+                &params_modulated.mod_env_route,
+            ),
+            EParam::ModEnv2Route(EEnvRouteParams::Target) => self.update_env_target(
+                meta,
+                1,
+                &params.mod_env2_route,
+                &params_modulated.mod_env2_route,
+            ),
             _ => None,
         }
     }
 
-    #[inline]
-    /// "Tick" the modulation by updating all modulated parameters.
-    ///
-    /// Returns:
-    /// -------
-    /// The return type is unusual; for LFO1 and LFO2, it will return an optional EParam.
-    /// If specified, the parameter affects all active voices (notes being played).
-    pub fn tick_lfos(
+    /// Update the range of an LFO route whose target changed; returns the previous target, if
+    /// any, so it can be reset.
+    fn update_lfo_target(
         &mut self,
-        time_delta: f64,
-        params: &Params,
-        params_modulated: &mut Params,
-    ) -> (Option<EParam>, Option<EParam>) {
-        let mod_value = self.lfo1.evaluate(time_delta) * params.lfo1.amt;
-        let target = params.lfo1.target;
-        let update1 = apply_modulation_to(
-            &self.mod_state,
-            params,
-            params_modulated,
-            target,
-            mod_value,
-            0,
-        );
+        meta: &ParamsMeta,
+        lfo_index: usize,
+        route_index: usize,
+        lfo: &LfoParams,
+        lfo_modulated: &LfoParams,
+    ) -> Option<EParam> {
+        let previous_target = lfo_modulated.routes[route_index].target;
+        let target = lfo.routes[route_index].target;
+        let mod_index = lfo_index * LFO_ROUTES + route_index;
+        update_mod_range(&mut self.mod_state, meta, mod_index, target);
+        let previous_param = modulation_target_to_eparam(&previous_target);
+        if let Some(previous_param) = previous_param {
+            self.mod_state.modulated_params.remove(previous_param);
+        }
+        previous_param
+    }
 
-        let mod_value = self.lfo2.evaluate(time_delta) * params.lfo2.amt;
-        let target = params.lfo2.target;
-        let update2 = apply_modulation_to(
-            &self.mod_state,
-            params,
-            params_modulated,
-            target,
-            mod_value,
-            1,
+    /// Same as `update_lfo_target`, for a mod envelope route.
+    fn update_env_target(
+        &mut self,
+        meta: &ParamsMeta,
+        env_index: usize,
+        route: &EnvRoute,
+        route_modulated: &EnvRoute,
+    ) -> Option<EParam> {
+        update_mod_range(
+            &mut self.mod_state,
+            meta,
+            ENV_ROUTE_INDEX + env_index,
+            route.target,
         );
-        (update1, update2)
+        let previous_param = modulation_target_to_eparam(&route_modulated.target);
+        if let Some(previous_param) = previous_param {
+            self.mod_state.modulated_params.remove(previous_param);
+        }
+        previous_param
+    }
+
+    #[inline]
+    fn mark_updated(&mut self, eparam: EParam) {
+        self.mod_state.modulated_params.insert(eparam);
+        if !self.updated_params.contains(&eparam) {
+            self.updated_params.push(eparam);
+        }
+    }
+
+    #[inline]
+    /// Advance the LFOs and apply each to its own targets, if any.
+    fn tick_lfos(&mut self, time_delta: f64, params: &Params, params_modulated: &mut Params) {
+        self.note_time += time_delta;
+        // LFO rates can themselves be modulated (free-running rates only).
+        if let lfo::Rate::Hz(rate_hz) = params_modulated.lfo1.rate {
+            self.lfo1.set_rate_hz(rate_hz);
+        }
+        if let lfo::Rate::Hz(rate_hz) = params_modulated.lfo2.rate {
+            self.lfo2.set_rate_hz(rate_hz);
+        }
+        let fade1 = lfo::fade_in_gain(params.lfo1.delay, params.lfo1.fade, self.note_time);
+        let fade2 = lfo::fade_in_gain(params.lfo2.delay, params.lfo2.fade, self.note_time);
+        self.sources.lfo1 = self.lfo1.evaluate(time_delta) * fade1;
+        self.sources.lfo2 = self.lfo2.evaluate(time_delta) * fade2;
+
+        // Routes come from the modulated parameters, since one LFO can modulate another's amount.
+        let lfos = [
+            (self.sources.lfo1, params_modulated.lfo1.routes),
+            (self.sources.lfo2, params_modulated.lfo2.routes),
+        ];
+        self.lfo_voice_mod = VoiceMod::default();
+        for (lfo_index, (lfo_value, routes)) in lfos.into_iter().enumerate() {
+            for (route_index, route) in routes.iter().enumerate() {
+                let mod_value = route.mode.apply(lfo_value) * route.amt * route.polarity.sign();
+                let mod_index = lfo_index * LFO_ROUTES + route_index;
+                if route.target.is_voice_scoped() {
+                    let offset = mod_value * self.mod_state.mod_ranges[mod_index].range;
+                    self.lfo_voice_mod.add(route.target, offset);
+                    continue;
+                }
+                let updated = apply_modulation_to(
+                    &self.mod_state,
+                    params,
+                    params_modulated,
+                    route.target,
+                    mod_value,
+                    mod_index,
+                );
+                if let Some(eparam) = updated {
+                    self.mark_updated(eparam);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    /// Apply the mod wheel's vibrato and cutoff offsets, which every voice shares.
+    fn tick_mod_wheel(&mut self, time_delta: f64, params: &Params) {
+        self.vibrato_phase = (self.vibrato_phase + time_delta * MOD_WHEEL_VIBRATO_HZ).fract();
+        let wheel = self.sources.mod_wheel;
+        if wheel == 0.0 {
+            return;
+        }
+        let vibrato = (self.vibrato_phase * std::f64::consts::TAU).sin() * params.mod_wheel.vibrato;
+        let offsets = [
+            (ModulationTarget::Osc1Pitch, vibrato),
+            (ModulationTarget::Osc2Pitch, vibrato),
+            (ModulationTarget::Filter1Cutoff, params.mod_wheel.cutoff),
+            (ModulationTarget::Filter2Cutoff, params.mod_wheel.cutoff),
+        ];
+        for (target, offset) in offsets.into_iter() {
+            self.lfo_voice_mod.add(target, offset * wheel);
+        }
+    }
+
+    #[inline]
+    /// Apply the mod envelopes (of the most recent note) to their targets, using the same range
+    /// mapping as the LFOs. Voice-scoped targets are left to `voice_mod`.
+    fn tick_env_routes(&mut self, params: &Params, params_modulated: &mut Params) {
+        let envs = [
+            (self.sources.mod_env, params.mod_env_route),
+            (self.sources.mod_env2, params.mod_env2_route),
+        ];
+        for (env_index, (env_value, route)) in envs.into_iter().enumerate() {
+            if route.target.is_voice_scoped() {
+                continue;
+            }
+            let updated = apply_modulation_to(
+                &self.mod_state,
+                params,
+                params_modulated,
+                route.target,
+                env_value * route.amt,
+                ENV_ROUTE_INDEX + env_index,
+            );
+            if let Some(eparam) = updated {
+                self.mark_updated(eparam);
+            }
+        }
+    }
+
+    /// Modulation of one voice's voice-scoped targets, given the levels of its own mod envelopes.
+    pub fn voice_mod(&self, params: &Params, mod_env: f64, mod_env2: f64) -> VoiceMod {
+        let mut voice_mod = self.lfo_voice_mod;
+        let envs = [
+            (mod_env, params.mod_env_route),
+            (mod_env2, params.mod_env2_route),
+        ];
+        for (env_index, (env_value, route)) in envs.into_iter().enumerate() {
+            if route.target.is_voice_scoped() {
+                let range = self.mod_state.mod_ranges[ENV_ROUTE_INDEX + env_index].range;
+                voice_mod.add(route.target, env_value * route.amt * range);
+            }
+        }
+        voice_mod
+    }
+
+    /// Apply the modulation matrix. Offsets are summed per target, in the target's normalized
+    /// (0.0 to 1.0) range, and added on top of any LFO that targets the same parameter directly.
+    fn tick_matrix(&mut self, meta: &ParamsMeta, params: &Params, params_modulated: &mut Params) {
+        std::mem::swap(&mut self.matrix_offsets, &mut self.previous_matrix_offsets);
+        self.matrix_offsets.clear();
+        for slot in params.mod_matrix.iter() {
+            let eparam = match slot.target.eparam() {
+                Some(eparam) if slot.source != ModSource::Off => eparam,
+                _ => continue,
+            };
+            let offset = self.sources.get(slot.source) * slot.depth * slot.polarity.sign();
+            match self.matrix_offsets.iter_mut().find(|(e, _)| *e == eparam) {
+                Some((_, total)) => *total += offset,
+                None => self.matrix_offsets.push((eparam, offset)),
+            }
+        }
+
+        // Targets dropped since the last tick go back to their user value.
+        for &(eparam, _) in self.previous_matrix_offsets.iter() {
+            let still_modulated = self.matrix_offsets.iter().any(|(e, _)| *e == eparam)
+                || self.updated_params.contains(&eparam);
+            if !still_modulated {
+                let user_value = params.read_parameter(meta, eparam);
+                params_modulated.write_parameter(meta, eparam, user_value);
+                self.mod_state.modulated_params.remove(eparam);
+                self.updated_params.push(eparam);
+            }
+        }
+
+        for &(eparam, offset) in self.matrix_offsets.iter() {
+            let baseline = if self.updated_params.contains(&eparam) {
+                params_modulated.read_parameter(meta, eparam)
+            } else {
+                self.updated_params.push(eparam);
+                params.read_parameter(meta, eparam)
+            };
+            params_modulated.write_parameter(meta, eparam, (baseline + offset).clamp(0.0, 1.0));
+            self.mod_state.modulated_params.insert(eparam);
+        }
     }
 }
 
@@ -226,6 +596,11 @@ pub fn apply_modulation_to(
                 modulate(mod_state, mod_index, params.filt1.resonance, mod_value);
             Some(EParam::Filt1(EFiltParams::Resonance))
         }
+        ModulationTarget::Filter1Vowel => {
+            params_modulated.filt1.vowel =
+                modulate(mod_state, mod_index, params.filt1.vowel, mod_value);
+            Some(EParam::Filt1(EFiltParams::Vowel))
+        }
         ModulationTarget::Osc2Frequency => {
             params_modulated.osc2.fine_offset =
                 modulate(mod_state, mod_index, params.osc2.fine_offset, mod_value);
@@ -251,6 +626,80 @@ pub fn apply_modulation_to(
                 modulate(mod_state, mod_index, params.filt2.resonance, mod_value);
             Some(EParam::Filt2(EFiltParams::Resonance))
         }
+        ModulationTarget::Filter2Vowel => {
+            params_modulated.filt2.vowel =
+                modulate(mod_state, mod_index, params.filt2.vowel, mod_value);
+            Some(EParam::Filt2(EFiltParams::Vowel))
+        }
+        ModulationTarget::Osc1Pitch => {
+            params_modulated.osc1.detune =
+                modulate(mod_state, mod_index, params.osc1.detune, mod_value);
+            Some(EParam::Osc1(EOscParams::Detune))
+        }
+        ModulationTarget::Osc2Pitch => {
+            params_modulated.osc2.detune =
+                modulate(mod_state, mod_index, params.osc2.detune, mod_value);
+            Some(EParam::Osc2(EOscParams::Detune))
+        }
+        ModulationTarget::Osc1Gain => {
+            params_modulated.osc1.gain =
+                modulate(mod_state, mod_index, params.osc1.gain, mod_value);
+            Some(EParam::Osc1(EOscParams::Gain))
+        }
+        ModulationTarget::Osc2Gain => {
+            params_modulated.osc2.gain =
+                modulate(mod_state, mod_index, params.osc2.gain, mod_value);
+            Some(EParam::Osc2(EOscParams::Gain))
+        }
+        ModulationTarget::Osc1Pan => {
+            params_modulated.osc1.pan = modulate(mod_state, mod_index, params.osc1.pan, mod_value);
+            Some(EParam::Osc1(EOscParams::Pan))
+        }
+        ModulationTarget::Osc2Pan => {
+            params_modulated.osc2.pan = modulate(mod_state, mod_index, params.osc2.pan, mod_value);
+            Some(EParam::Osc2(EOscParams::Pan))
+        }
+        ModulationTarget::Filter1EnvAmt => {
+            params_modulated.filt1.env_amt =
+                modulate(mod_state, mod_index, params.filt1.env_amt, mod_value);
+            Some(EParam::Filt1(EFiltParams::EnvAmt))
+        }
+        ModulationTarget::Filter2EnvAmt => {
+            params_modulated.filt2.env_amt =
+                modulate(mod_state, mod_index, params.filt2.env_amt, mod_value);
+            Some(EParam::Filt2(EFiltParams::EnvAmt))
+        }
+        ModulationTarget::OutputGain => {
+            params_modulated.output_gain =
+                modulate(mod_state, mod_index, params.output_gain, mod_value);
+            Some(EParam::OutputGain)
+        }
+        ModulationTarget::Lfo1Rate => match params.lfo1.rate {
+            lfo::Rate::Hz(rate_hz) => {
+                params_modulated.lfo1.rate =
+                    lfo::Rate::Hz(modulate(mod_state, mod_index, rate_hz, mod_value));
+                Some(EParam::Lfo1(ELfoParams::Rate))
+            }
+            lfo::Rate::Synced(_) => None,
+        },
+        ModulationTarget::Lfo1Amount => {
+            params_modulated.lfo1.routes[0].amt =
+                modulate(mod_state, mod_index, params.lfo1.routes[0].amt, mod_value);
+            Some(EParam::Lfo1(ELfoParams::Amt))
+        }
+        ModulationTarget::Lfo2Rate => match params.lfo2.rate {
+            lfo::Rate::Hz(rate_hz) => {
+                params_modulated.lfo2.rate =
+                    lfo::Rate::Hz(modulate(mod_state, mod_index, rate_hz, mod_value));
+                Some(EParam::Lfo2(ELfoParams::Rate))
+            }
+            lfo::Rate::Synced(_) => None,
+        },
+        ModulationTarget::Lfo2Amount => {
+            params_modulated.lfo2.routes[0].amt =
+                modulate(mod_state, mod_index, params.lfo2.routes[0].amt, mod_value);
+            Some(EParam::Lfo2(ELfoParams::Amt))
+        }
         _ => None,
     }
 }
@@ -298,6 +747,38 @@ pub fn update_mod_range(
             mod_range.min = meta.resonance_meta.0.min;
             mod_range.max = meta.resonance_meta.0.max;
         }
+        ModulationTarget::Filter1Vowel | ModulationTarget::Filter2Vowel => {
+            mod_range.min = meta.vowel_meta.0.min;
+            mod_range.max = meta.vowel_meta.0.max;
+        }
+        ModulationTarget::Osc1Pitch | ModulationTarget::Osc2Pitch => {
+            mod_range.min = meta.osc_detune_meta.0.min;
+            mod_range.max = meta.osc_detune_meta.0.max;
+        }
+        ModulationTarget::Lfo1Rate | ModulationTarget::Lfo2Rate => {
+            mod_range.min = meta.mod_rate_hz_meta.0.min;
+            mod_range.max = meta.mod_rate_hz_meta.0.max;
+        }
+        ModulationTarget::Lfo1Amount | ModulationTarget::Lfo2Amount => {
+            mod_range.min = meta.mod_amt_meta.0.min;
+            mod_range.max = meta.mod_amt_meta.0.max;
+        }
+        ModulationTarget::Osc1Gain | ModulationTarget::Osc2Gain => {
+            mod_range.min = meta.osc_gain_meta.0.min;
+            mod_range.max = meta.osc_gain_meta.0.max;
+        }
+        ModulationTarget::Osc1Pan | ModulationTarget::Osc2Pan => {
+            mod_range.min = meta.osc_pan_meta.0.min;
+            mod_range.max = meta.osc_pan_meta.0.max;
+        }
+        ModulationTarget::Filter1EnvAmt | ModulationTarget::Filter2EnvAmt => {
+            mod_range.min = meta.env_amt_meta.0.min;
+            mod_range.max = meta.env_amt_meta.0.max;
+        }
+        ModulationTarget::OutputGain => {
+            mod_range.min = meta.output_gain_meta.0.min;
+            mod_range.max = meta.output_gain_meta.0.max;
+        }
     };
     mod_range.update_range();
 }
@@ -309,11 +790,155 @@ fn modulation_target_to_eparam(target: &ModulationTarget) -> Option<EParam> {
         ModulationTarget::Osc1UnisonAmt => Some(EParam::Osc1(EOscParams::UnisonAmt)),
         ModulationTarget::Filter1Cutoff => Some(EParam::Filt1(EFiltParams::Cutoff)),
         ModulationTarget::Filter1Resonance => Some(EParam::Filt1(EFiltParams::Resonance)),
+        ModulationTarget::Filter1Vowel => Some(EParam::Filt1(EFiltParams::Vowel)),
         ModulationTarget::Osc2Frequency => Some(EParam::Osc2(EOscParams::FineOffset)),
         ModulationTarget::Osc2StereoWidth => Some(EParam::Osc2(EOscParams::StereoWidth)),
         ModulationTarget::Osc2UnisonAmt => Some(EParam::Osc2(EOscParams::UnisonAmt)),
         ModulationTarget::Filter2Cutoff => Some(EParam::Filt2(EFiltParams::Cutoff)),
         ModulationTarget::Filter2Resonance => Some(EParam::Filt2(EFiltParams::Resonance)),
+        ModulationTarget::Filter2Vowel => Some(EParam::Filt2(EFiltParams::Vowel)),
+        ModulationTarget::Osc1Pitch => Some(EParam::Osc1(EOscParams::Detune)),
+        ModulationTarget::Osc2Pitch => Some(EParam::Osc2(EOscParams::Detune)),
+        ModulationTarget::Lfo1Rate => Some(EParam::Lfo1(ELfoParams::Rate)),
+        ModulationTarget::Lfo1Amount => Some(EParam::Lfo1(ELfoParams::Amt)),
+        ModulationTarget::Lfo2Rate => Some(EParam::Lfo2(ELfoParams::Rate)),
+        ModulationTarget::Lfo2Amount => Some(EParam::Lfo2(ELfoParams::Amt)),
+        ModulationTarget::Osc1Gain => Some(EParam::Osc1(EOscParams::Gain)),
+        ModulationTarget::Osc2Gain => Some(EParam::Osc2(EOscParams::Gain)),
+        ModulationTarget::Osc1Pan => Some(EParam::Osc1(EOscParams::Pan)),
+        ModulationTarget::Osc2Pan => Some(EParam::Osc2(EOscParams::Pan)),
+        ModulationTarget::Filter1EnvAmt => Some(EParam::Filt1(EFiltParams::EnvAmt)),
+        ModulationTarget::Filter2EnvAmt => Some(EParam::Filt2(EFiltParams::EnvAmt)),
+        ModulationTarget::OutputGain => Some(EParam::OutputGain),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::modulation::matrix::{MatrixTarget, ModSlot};
+    use crate::modulation::polarity::{Polarity, RouteMode};
+
+    const SAMPLE_RATE: f64 = 44100.0;
+
+    #[test]
+    fn matrix_moves_and_restores_target() {
+        let meta = ParamsMeta::new();
+        let mut params = Params::new(SAMPLE_RATE);
+        params.filt1.cutoff_semi = 0.0;
+        let mut params_modulated = params.clone();
+        let mut modulation = Modulation::new(SAMPLE_RATE);
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+
+        params.mod_matrix[0] = ModSlot {
+            source: ModSource::Velocity,
+            target: MatrixTarget::Param(cutoff),
+            depth: 0.5,
+            ..ModSlot::default()
+        };
+        modulation.note_on(&params, 60, 1.0, true);
+        let updated = modulation.tick(1.0, &meta, &params, &mut params_modulated);
+        assert_eq!(updated, &[cutoff]);
+        assert!((params_modulated.read_parameter(&meta, cutoff) - 0.5).abs() < 1e-9);
+        assert!(modulation.mod_state.modulated_params.contains(cutoff));
+
+        params.mod_matrix[0] = ModSlot::default();
+        let updated = modulation.tick(1.0, &meta, &params, &mut params_modulated);
+        assert_eq!(updated, &[cutoff]);
+        assert_eq!(params_modulated.filt1.cutoff_semi, 0.0);
+        assert!(modulation.mod_state.modulated_params.is_empty());
+    }
+
+    #[test]
+    fn notes_only_restart_retriggered_lfos() {
+        let mut params = Params::new(SAMPLE_RATE);
+        params.lfo1.retrigger = lfo::LfoRetrigger::Free;
+        params.lfo1.one_shot = false;
+        params.lfo2.retrigger = lfo::LfoRetrigger::EveryNote;
+        let play = |note| {
+            let mut modulation = Modulation::new(SAMPLE_RATE);
+            modulation.lfo1.set_shape(lfo::LfoShape::SampleHold);
+            modulation.lfo2.set_shape(lfo::LfoShape::SampleHold);
+            modulation.note_on(&params, note, 1.0, true);
+            (modulation.lfo1.evaluate(0.0), modulation.lfo2.evaluate(0.0))
+        };
+        let ((free1, retriggered1), (free2, retriggered2)) = (play(60), play(72));
+        assert_eq!(free1, free2);
+        assert_ne!(retriggered1, retriggered2);
+    }
+
+    #[test]
+    fn env_pitch_is_voice_scoped() {
+        let meta = ParamsMeta::new();
+        let mut params = Params::new(SAMPLE_RATE);
+        params.mod_env_route.target = ModulationTarget::Osc1Pitch;
+        let mut params_modulated = params.clone();
+        let mut modulation = Modulation::new(SAMPLE_RATE);
+        modulation.on_param_update_before_mod_update(
+            &meta,
+            &params,
+            &params_modulated,
+            EParam::ModEnvRoute(EEnvRouteParams::Target),
+            2.0,
+        );
+
+        modulation.set_mod_envs(1.0, 0.0);
+        modulation.tick(1.0, &meta, &params, &mut params_modulated);
+        // The shared parameters are untouched; each voice follows its own envelope.
+        assert_eq!(params_modulated.osc1.detune, 0.0);
+        let full = modulation.voice_mod(&params, 1.0, 0.0);
+        let half = modulation.voice_mod(&params, 0.5, 0.0);
+        assert!(full.osc1_detune > 0.0);
+        assert!((half.osc1_detune * 2.0 - full.osc1_detune).abs() < 1e-9);
+        assert_eq!(full.filt1_cutoff, 0.0);
+    }
+
+    #[test]
+    fn negative_polarity_inverts_lfo_routes() {
+        let meta = ParamsMeta::new();
+        let cutoff_offset = |polarity| {
+            let mut params = Params::new(SAMPLE_RATE);
+            params.lfo1.routes[0].target = ModulationTarget::Filter1Cutoff;
+            params.lfo1.routes[0].mode = RouteMode::UnipolarUp;
+            params.lfo1.routes[0].polarity = polarity;
+            let mut params_modulated = params.clone();
+            let mut modulation = Modulation::new(SAMPLE_RATE);
+            let target = EParam::Lfo1(ELfoParams::Target);
+            modulation.on_param_update_before_mod_update(
+                &meta,
+                &params,
+                &params_modulated,
+                target,
+                2.0,
+            );
+            modulation.tick(0.1, &meta, &params, &mut params_modulated);
+            modulation.voice_mod(&params, 0.0, 0.0).filt1_cutoff
+        };
+        let raised = cutoff_offset(Polarity::Positive);
+        assert!(raised > 0.0);
+        assert_eq!(cutoff_offset(Polarity::Negative), -raised);
+    }
+
+    #[test]
+    fn mod_wheel_scales_its_routes() {
+        let meta = ParamsMeta::new();
+        let mut params = Params::new(SAMPLE_RATE);
+        params.mod_wheel.vibrato = 1.0;
+        params.mod_wheel.cutoff = 24.0;
+        let mut params_modulated = params.clone();
+        let mut modulation = Modulation::new(SAMPLE_RATE);
+
+        // At rest, the wheel does nothing.
+        modulation.tick(0.01, &meta, &params, &mut params_modulated);
+        assert_eq!(modulation.voice_mod(&params, 0.0, 0.0), VoiceMod::default());
+
+        modulation.set_mod_wheel(0.5);
+        modulation.tick(0.01, &meta, &params, &mut params_modulated);
+        let voice_mod = modulation.voice_mod(&params, 0.0, 0.0);
+        assert_eq!(voice_mod.filt1_cutoff, 12.0);
+        assert_eq!(voice_mod.filt2_cutoff, 12.0);
+        assert!(voice_mod.osc1_detune > 0.0 && voice_mod.osc1_detune <= 0.5);
+        assert_eq!(voice_mod.osc1_detune, voice_mod.osc2_detune);
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+use crate::util::enumerable::Enumerable;
+
+/// Direction a modulator moves its destination.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Polarity {
+    Positive,
+    Negative,
+}
+
+impl Polarity {
+    pub fn as_string(self) -> String {
+        match self {
+            Polarity::Positive => "Positive".to_string(),
+            Polarity::Negative => "Negative".to_string(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn sign(self) -> f64 {
+        match self {
+            Polarity::Positive => 1.0,
+            Polarity::Negative => -1.0,
+        }
+    }
+}
+
+impl Enumerable<Polarity> for Polarity {
+    fn enumerate() -> Vec<Polarity> {
+        vec![Polarity::Positive, Polarity::Negative]
+    }
+}
+
+impl From<Polarity> for String {
+    fn from(f: Polarity) -> String {
+        f.as_string()
+    }
+}
+
+impl From<String> for Polarity {
+    fn from(s: String) -> Polarity {
+        match s.as_ref() {
+            "Positive" => Polarity::Positive,
+            "Negative" => Polarity::Negative,
+            _ => Polarity::Positive,
+        }
+    }
+}
+
+/// How an LFO route maps the (bipolar) LFO output onto its destination.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum RouteMode {
+    /// Swing both above and below the destination's value.
+    Bipolar,
+    /// Only raise the destination.
+    UnipolarUp,
+    /// Only lower the destination.
+    UnipolarDown,
+}
+
+impl RouteMode {
+    pub fn as_string(self) -> String {
+        match self {
+            RouteMode::Bipolar => "Bipolar".to_string(),
+            RouteMode::UnipolarUp => "Unipolar Up".to_string(),
+            RouteMode::UnipolarDown => "Unipolar Down".to_string(),
+        }
+    }
+
+    /// Map a bipolar (-1.0 to 1.0) modulator value according to the mode.
+    #[inline(always)]
+    pub fn apply(self, value: f64) -> f64 {
+        match self {
+            RouteMode::Bipolar => value,
+            RouteMode::UnipolarUp => 0.5 * (value + 1.0),
+            RouteMode::UnipolarDown => -0.5 * (value + 1.0),
+        }
+    }
+}
+
+impl Enumerable<RouteMode> for RouteMode {
+    fn enumerate() -> Vec<RouteMode> {
+        vec![
+            RouteMode::Bipolar,
+            RouteMode::UnipolarUp,
+            RouteMode::UnipolarDown,
+        ]
+    }
+}
+
+impl From<RouteMode> for String {
+    fn from(f: RouteMode) -> String {
+        f.as_string()
+    }
+}
+
+impl From<String> for RouteMode {
+    fn from(s: String) -> RouteMode {
+        match s.as_ref() {
+            "Unipolar Up" => RouteMode::UnipolarUp,
+            "Unipolar Down" => RouteMode::UnipolarDown,
+            _ => RouteMode::Bipolar,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unipolar_modes_stay_on_one_side() {
+        for &value in [-1.0, -0.25, 0.0, 0.5, 1.0].iter() {
+            assert_eq!(RouteMode::Bipolar.apply(value), value);
+            let up = RouteMode::UnipolarUp.apply(value);
+            let down = RouteMode::UnipolarDown.apply(value);
+            assert!((0.0..=1.0).contains(&up));
+            assert!((-1.0..=0.0).contains(&down));
+            assert_eq!(up, -down);
+        }
+    }
+}
@@ -13,6 +13,7 @@ pub enum ModulationTarget {
 
     Filter1Cutoff,
     Filter1Resonance,
+    Filter1Vowel,
 
     Osc2Frequency,
     Osc2StereoWidth,
@@ -20,9 +21,40 @@ pub enum ModulationTarget {
 
     Filter2Cutoff,
     Filter2Resonance,
+    Filter2Vowel,
+
+    Lfo1Rate,
+    Lfo1Amount,
+    Lfo2Rate,
+    Lfo2Amount,
+
+    // Pitch, in semitones; unlike the frequency targets, the depth is the same across the
+    // keyboard.
+    Osc1Pitch,
+    Osc2Pitch,
+
+    Osc1Gain,
+    Osc2Gain,
+    Osc1Pan,
+    Osc2Pan,
+    Filter1EnvAmt,
+    Filter2EnvAmt,
+    OutputGain,
 }
 
 impl ModulationTarget {
+    /// Targets that are modulated separately for each voice (see `VoiceMod`), so a note's own
+    /// envelopes move only that note. Everything else is shared by all voices.
+    pub fn is_voice_scoped(self) -> bool {
+        matches!(
+            self,
+            ModulationTarget::Osc1Pitch
+                | ModulationTarget::Osc2Pitch
+                | ModulationTarget::Filter1Cutoff
+                | ModulationTarget::Filter2Cutoff
+        )
+    }
+
     pub fn value(self) -> u8 {
         match self {
             ModulationTarget::Off => 0,
@@ -39,6 +71,25 @@ impl ModulationTarget {
 
             ModulationTarget::Filter2Cutoff => 9,
             ModulationTarget::Filter2Resonance => 10,
+
+            ModulationTarget::Filter1Vowel => 11,
+            ModulationTarget::Filter2Vowel => 12,
+
+            ModulationTarget::Lfo1Rate => 13,
+            ModulationTarget::Lfo1Amount => 14,
+            ModulationTarget::Lfo2Rate => 15,
+            ModulationTarget::Lfo2Amount => 16,
+
+            ModulationTarget::Osc1Pitch => 17,
+            ModulationTarget::Osc2Pitch => 18,
+
+            ModulationTarget::Osc1Gain => 19,
+            ModulationTarget::Osc2Gain => 20,
+            ModulationTarget::Osc1Pan => 21,
+            ModulationTarget::Osc2Pan => 22,
+            ModulationTarget::Filter1EnvAmt => 23,
+            ModulationTarget::Filter2EnvAmt => 24,
+            ModulationTarget::OutputGain => 25,
         }
     }
 
@@ -51,6 +102,7 @@ impl ModulationTarget {
 
             ModulationTarget::Filter1Cutoff => "Filter1Cutoff",
             ModulationTarget::Filter1Resonance => "Filter1Resonance",
+            ModulationTarget::Filter1Vowel => "Filter1Vowel",
 
             ModulationTarget::Osc2Frequency => "Osc2Frequency",
             ModulationTarget::Osc2StereoWidth => "Osc2StereoWidth",
@@ -58,6 +110,23 @@ impl ModulationTarget {
 
             ModulationTarget::Filter2Cutoff => "Filter2Cutoff",
             ModulationTarget::Filter2Resonance => "Filter2Resonance",
+            ModulationTarget::Filter2Vowel => "Filter2Vowel",
+
+            ModulationTarget::Lfo1Rate => "Lfo1Rate",
+            ModulationTarget::Lfo1Amount => "Lfo1Amount",
+            ModulationTarget::Lfo2Rate => "Lfo2Rate",
+            ModulationTarget::Lfo2Amount => "Lfo2Amount",
+
+            ModulationTarget::Osc1Pitch => "Osc1Pitch",
+            ModulationTarget::Osc2Pitch => "Osc2Pitch",
+
+            ModulationTarget::Osc1Gain => "Osc1Gain",
+            ModulationTarget::Osc2Gain => "Osc2Gain",
+            ModulationTarget::Osc1Pan => "Osc1Pan",
+            ModulationTarget::Osc2Pan => "Osc2Pan",
+            ModulationTarget::Filter1EnvAmt => "Filter1EnvAmt",
+            ModulationTarget::Filter2EnvAmt => "Filter2EnvAmt",
+            ModulationTarget::OutputGain => "OutputGain",
         };
         as_str.to_string()
     }
@@ -79,6 +148,7 @@ impl From<String> for ModulationTarget {
 
             "Filter1Cutoff" => ModulationTarget::Filter1Cutoff,
             "Filter1Resonance" => ModulationTarget::Filter1Resonance,
+            "Filter1Vowel" => ModulationTarget::Filter1Vowel,
 
             "Osc2Frequency" => ModulationTarget::Osc2Frequency,
             "Osc2StereoWidth" => ModulationTarget::Osc2StereoWidth,
@@ -86,6 +156,23 @@ impl From<String> for ModulationTarget {
 
             "Filter2Cutoff" => ModulationTarget::Filter2Cutoff,
             "Filter2Resonance" => ModulationTarget::Filter2Resonance,
+            "Filter2Vowel" => ModulationTarget::Filter2Vowel,
+
+            "Lfo1Rate" => ModulationTarget::Lfo1Rate,
+            "Lfo1Amount" => ModulationTarget::Lfo1Amount,
+            "Lfo2Rate" => ModulationTarget::Lfo2Rate,
+            "Lfo2Amount" => ModulationTarget::Lfo2Amount,
+
+            "Osc1Pitch" => ModulationTarget::Osc1Pitch,
+            "Osc2Pitch" => ModulationTarget::Osc2Pitch,
+
+            "Osc1Gain" => ModulationTarget::Osc1Gain,
+            "Osc2Gain" => ModulationTarget::Osc2Gain,
+            "Osc1Pan" => ModulationTarget::Osc1Pan,
+            "Osc2Pan" => ModulationTarget::Osc2Pan,
+            "Filter1EnvAmt" => ModulationTarget::Filter1EnvAmt,
+            "Filter2EnvAmt" => ModulationTarget::Filter2EnvAmt,
+            "OutputGain" => ModulationTarget::OutputGain,
             _ => ModulationTarget::Off,
         }
     }
@@ -100,11 +187,26 @@ impl Enumerable<ModulationTarget> for ModulationTarget {
             ModulationTarget::Osc1UnisonAmt,
             ModulationTarget::Filter1Cutoff,
             ModulationTarget::Filter1Resonance,
+            ModulationTarget::Filter1Vowel,
             ModulationTarget::Osc2Frequency,
             ModulationTarget::Osc2StereoWidth,
             ModulationTarget::Osc2UnisonAmt,
             ModulationTarget::Filter2Cutoff,
             ModulationTarget::Filter2Resonance,
+            ModulationTarget::Filter2Vowel,
+            ModulationTarget::Lfo1Rate,
+            ModulationTarget::Lfo1Amount,
+            ModulationTarget::Lfo2Rate,
+            ModulationTarget::Lfo2Amount,
+            ModulationTarget::Osc1Pitch,
+            ModulationTarget::Osc2Pitch,
+            ModulationTarget::Osc1Gain,
+            ModulationTarget::Osc2Gain,
+            ModulationTarget::Osc1Pan,
+            ModulationTarget::Osc2Pan,
+            ModulationTarget::Filter1EnvAmt,
+            ModulationTarget::Filter2EnvAmt,
+            ModulationTarget::OutputGain,
         ]
     }
 }
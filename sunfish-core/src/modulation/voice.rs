@@ -0,0 +1,25 @@
+use crate::modulation::target::ModulationTarget;
+
+/// Modulation applied to a single voice, on top of the (global) modulated parameters. Offsets
+/// are in the target's own units: semitones for pitch and cutoff.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VoiceMod {
+    pub osc1_detune: f64,
+    pub osc2_detune: f64,
+    pub filt1_cutoff: f64,
+    pub filt2_cutoff: f64,
+}
+
+impl VoiceMod {
+    /// Add `offset` to `target`; targets that aren't voice-scoped are ignored.
+    #[inline]
+    pub fn add(&mut self, target: ModulationTarget, offset: f64) {
+        match target {
+            ModulationTarget::Osc1Pitch => self.osc1_detune += offset,
+            ModulationTarget::Osc2Pitch => self.osc2_detune += offset,
+            ModulationTarget::Filter1Cutoff => self.filt1_cutoff += offset,
+            ModulationTarget::Filter2Cutoff => self.filt2_cutoff += offset,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::params::Params;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CompareSlot {
+    A,
+    B,
+}
+
+/// Two patches to flip between. The live parameters are always the active slot; only the other
+/// one is kept here.
+#[derive(Clone, Debug)]
+pub struct Compare {
+    active: CompareSlot,
+    // Empty until first switched to, at which point it starts as a copy of the active slot.
+    other: Option<Params>,
+}
+
+impl Compare {
+    pub fn new() -> Self {
+        Compare {
+            active: CompareSlot::A,
+            other: None,
+        }
+    }
+
+    pub fn active(&self) -> CompareSlot {
+        self.active
+    }
+
+    /// Switch to the other slot, given the `live` parameters. Returns the parameters to load.
+    pub fn toggle(&mut self, live: Params) -> Params {
+        self.active = match self.active {
+            CompareSlot::A => CompareSlot::B,
+            CompareSlot::B => CompareSlot::A,
+        };
+        match self.other.replace(live.clone()) {
+            Some(other) => other,
+            None => live,
+        }
+    }
+
+    /// Make B a copy of A. Returns the parameters to load, if the live ones (B) changed.
+    pub fn copy_a_to_b(&mut self, live: &Params) -> Option<Params> {
+        match self.active {
+            CompareSlot::A => {
+                self.other = Some(live.clone());
+                None
+            }
+            CompareSlot::B => Some(self.other.clone().unwrap_or_else(|| live.clone())),
+        }
+    }
+}
+
+impl Default for Compare {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn toggles_between_slots() {
+        let mut compare = Compare::new();
+        let mut a = Params::new(44100.0);
+        a.osc1.gain = 0.5;
+
+        // B starts out as a copy of A, then is edited.
+        let mut b = compare.toggle(a.clone());
+        assert_eq!(compare.active(), CompareSlot::B);
+        assert_eq!(b.osc1.gain, 0.5);
+        b.osc1.gain = 0.25;
+
+        let live = compare.toggle(b);
+        assert_eq!(compare.active(), CompareSlot::A);
+        assert_eq!(live.osc1.gain, 0.5);
+
+        let live = compare.toggle(live);
+        assert_eq!(live.osc1.gain, 0.25);
+        let live = compare.copy_a_to_b(&live).unwrap();
+        assert_eq!(live.osc1.gain, 0.5);
+        assert_eq!(compare.toggle(live).osc1.gain, 0.5);
+    }
+}
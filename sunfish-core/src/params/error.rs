@@ -0,0 +1,17 @@
+use thiserror::Error;
+
+use crate::params::EParam;
+
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum ParamError {
+    #[error("no parameter at index {0}")]
+    UnknownIndex(usize),
+    #[error("unknown parameter: {0}")]
+    UnknownParam(String),
+    #[error("{value} is out of range for {eparam:?}; expected a value from 0.0 to 1.0")]
+    OutOfRange { eparam: EParam, value: f64 },
+    #[error("failed to format {0:?}")]
+    Format(EParam),
+    #[error("can't read {text:?} as a value for {eparam:?}")]
+    Parse { eparam: EParam, text: String },
+}
@@ -1,3 +1,6 @@
+use serde::Deserialize;
+
+use crate::dsp::formant;
 use crate::util;
 
 // Formatters: Useful for formatting parameters appropriately,
@@ -5,6 +8,32 @@ use crate::util;
 
 pub trait Formatter<T> {
     fn format_value(&self, value: T) -> String;
+
+    /// Read back a value typed in by the user, in the form `format_value` gives (a unit may be
+    /// left off). `None` if it doesn't make sense.
+    fn parse_value(&self, text: &str) -> Option<T>;
+}
+
+// Split text into its leading number and whatever follows it, as it is.
+fn split_number(text: &str) -> Option<(f64, &str)> {
+    let text = text.trim();
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(text.len());
+    let number = text[..end].parse().ok()?;
+    Some((number, &text[end..]))
+}
+
+// Split typed-in text into its leading number and the (lowercased) unit after it.
+fn number_and_unit(text: &str) -> Option<(f64, String)> {
+    let (number, unit) = split_number(text)?;
+    Some((number, unit.trim().to_lowercase()))
+}
+
+// A number with no unit, or one of the units given.
+fn number_in(text: &str, units: &[&str]) -> Option<f64> {
+    let (number, unit) = number_and_unit(text)?;
+    (unit.is_empty() || units.contains(&unit.as_str())).then_some(number)
 }
 
 #[derive(Clone, Debug)]
@@ -18,6 +47,14 @@ impl Formatter<f64> for FrequencyFormatter {
             format!("{:.2} KHz", value / 1000.0)
         }
     }
+
+    fn parse_value(&self, text: &str) -> Option<f64> {
+        match number_and_unit(text)? {
+            (hz, unit) if unit.is_empty() || unit == "hz" => Some(hz),
+            (khz, unit) if unit == "k" || unit == "khz" => Some(khz * 1000.0),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -31,6 +68,14 @@ impl Formatter<bool> for BoolOnOffFormatter {
             "off".to_string()
         }
     }
+
+    fn parse_value(&self, text: &str) -> Option<bool> {
+        match text.trim().to_lowercase().as_str() {
+            "on" | "1" | "true" => Some(true),
+            "off" | "0" | "false" => Some(false),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -38,12 +83,21 @@ pub struct StringFormatter();
 
 impl<T> Formatter<T> for StringFormatter
 where
-    T: Into<String>,
+    T: Clone + From<String> + Into<String>,
     String: From<T>,
 {
     fn format_value(&self, value: T) -> String {
         String::from(value)
     }
+
+    fn parse_value(&self, text: &str) -> Option<T> {
+        // Unknown names convert to a fallback, so check the name comes back out.
+        let text = text.trim();
+        let value = T::from(text.to_string());
+        String::from(value.clone())
+            .eq_ignore_ascii_case(text)
+            .then_some(value)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -53,12 +107,20 @@ impl Formatter<i32> for NumberFormatter {
     fn format_value(&self, value: i32) -> String {
         format!("{}", value)
     }
+
+    fn parse_value(&self, text: &str) -> Option<i32> {
+        number_in(text, &[]).map(|number| number.round() as i32)
+    }
 }
 
 impl Formatter<f64> for NumberFormatter {
     fn format_value(&self, value: f64) -> String {
         format!("{:.2}", value)
     }
+
+    fn parse_value(&self, text: &str) -> Option<f64> {
+        number_in(text, &[])
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -72,6 +134,15 @@ impl Formatter<f64> for TimeFormatter {
             format!("{:.1} s", value)
         }
     }
+
+    // A bare number is taken as milliseconds, like most times shown.
+    fn parse_value(&self, text: &str) -> Option<f64> {
+        match number_and_unit(text)? {
+            (ms, unit) if unit.is_empty() || unit == "ms" => Some(ms / 1000.0),
+            (s, unit) if unit == "s" => Some(s),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -81,6 +152,10 @@ impl Formatter<f64> for PercentFormatter {
     fn format_value(&self, value: f64) -> String {
         format!("{:.1}%", value * 100.0)
     }
+
+    fn parse_value(&self, text: &str) -> Option<f64> {
+        number_in(text, &["%"]).map(|percent| percent / 100.0)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -90,6 +165,13 @@ impl Formatter<f64> for DbFormatter {
     fn format_value(&self, value: f64) -> String {
         format!("{:.2} dB", util::gain_to_db(value))
     }
+
+    fn parse_value(&self, text: &str) -> Option<f64> {
+        if text.trim().to_lowercase().starts_with("-inf") {
+            return Some(0.0);
+        }
+        number_in(text, &["db"]).map(util::db_to_gain)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -105,4 +187,182 @@ impl Formatter<f64> for BalanceFormatter {
             format!("{:.2} L", -value)
         }
     }
+
+    fn parse_value(&self, text: &str) -> Option<f64> {
+        if text.trim().eq_ignore_ascii_case("c") {
+            return Some(0.0);
+        }
+        match number_and_unit(text)? {
+            (balance, unit) if unit.is_empty() || unit == "r" => Some(balance),
+            (balance, unit) if unit == "l" => Some(-balance),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VowelFormatter();
+
+impl Formatter<f64> for VowelFormatter {
+    fn format_value(&self, value: f64) -> String {
+        formant::vowel_name(value).to_string()
+    }
+
+    fn parse_value(&self, text: &str) -> Option<f64> {
+        let text = text.trim();
+        (0..=formant::MAX_VOWEL as usize)
+            .map(|vowel| vowel as f64)
+            .find(|vowel| formant::vowel_name(*vowel).eq_ignore_ascii_case(text))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CurveFormatter();
+
+impl Formatter<f64> for CurveFormatter {
+    fn format_value(&self, value: f64) -> String {
+        if value.abs() < 0.005 {
+            "Linear".to_string()
+        } else if value > 0.0 {
+            format!("Exp {:.2}", value)
+        } else {
+            format!("Log {:.2}", -value)
+        }
+    }
+
+    fn parse_value(&self, text: &str) -> Option<f64> {
+        let text = text.trim().to_lowercase();
+        if text == "linear" {
+            Some(0.0)
+        } else if let Some(exp) = text.strip_prefix("exp") {
+            number_in(exp, &[])
+        } else if let Some(log) = text.strip_prefix("log") {
+            number_in(log, &[]).map(|curve| -curve)
+        } else {
+            number_in(&text, &[])
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DegreeFormatter();
+
+impl Formatter<f64> for DegreeFormatter {
+    fn format_value(&self, value: f64) -> String {
+        format!("{:.0}°", value)
+    }
+
+    fn parse_value(&self, text: &str) -> Option<f64> {
+        number_in(text, &["°"])
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CentsFormatter();
+
+impl Formatter<f64> for CentsFormatter {
+    fn format_value(&self, value: f64) -> String {
+        format!("{:+.0} ct", value * 100.0)
+    }
+
+    fn parse_value(&self, text: &str) -> Option<f64> {
+        number_in(text, &["ct"]).map(|cents| cents / 100.0)
+    }
+}
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// The name of the note nearest `freq_hz`, with its octave (A4 being 440 Hz).
+pub fn note_name(freq_hz: f64) -> String {
+    let note = (69.0 + 12.0 * (freq_hz / 440.0).log2()).round() as i32;
+    format!(
+        "{}{}",
+        NOTE_NAMES[note.rem_euclid(12) as usize],
+        note.div_euclid(12) - 1
+    )
+}
+
+/// Changes to how a value's shown, made to the text its formatter gives. Text that doesn't
+/// start with a number (e.g. "Linear") is left as it is.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ValueFormat {
+    // Digits after the decimal point; as many as the formatter gave if None.
+    pub decimals: Option<usize>,
+    // Shown after the number in place of the formatter's unit, as it is (so " kHz", not "kHz").
+    // Formatters that switch units (e.g. Hz to KHz) are best left with their own.
+    pub suffix: Option<String>,
+    // Show frequencies (in Hz or KHz) as the nearest note instead, e.g. "C#4".
+    pub note_name: bool,
+}
+
+impl ValueFormat {
+    pub fn apply(&self, formatted: &str) -> String {
+        let formatted = formatted.trim();
+        let (number, unit) = match split_number(formatted) {
+            Some(split) => split,
+            None => return formatted.to_string(),
+        };
+        if self.note_name {
+            match unit.trim().to_lowercase().as_str() {
+                "hz" => return note_name(number),
+                "khz" => return note_name(number * 1000.0),
+                _ => {}
+            }
+        }
+        let sign = if formatted.starts_with('+') && number >= 0.0 {
+            "+"
+        } else {
+            ""
+        };
+        let number = match self.decimals {
+            Some(decimals) => format!("{}{:.*}", sign, decimals, number),
+            None => formatted[..formatted.len() - unit.len()].to_string(),
+        };
+        format!("{}{}", number, self.suffix.as_deref().unwrap_or(unit))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn value_format_reworks_formatted_text() {
+        let khz = FrequencyFormatter().format_value(2500.0);
+        assert_eq!(ValueFormat::default().apply(&khz), khz);
+        let format = ValueFormat {
+            decimals: Some(1),
+            suffix: Some(" kHz".to_string()),
+            note_name: false,
+        };
+        assert_eq!(format.apply(&khz), "2.5 kHz");
+        let decimals = ValueFormat {
+            decimals: Some(0),
+            ..ValueFormat::default()
+        };
+        assert_eq!(
+            decimals.apply(&CentsFormatter().format_value(0.25)),
+            "+25 ct"
+        );
+        assert_eq!(
+            decimals.apply(&CurveFormatter().format_value(0.0)),
+            "Linear"
+        );
+
+        let notes = ValueFormat {
+            note_name: true,
+            ..ValueFormat::default()
+        };
+        assert_eq!(notes.apply(&FrequencyFormatter().format_value(440.0)), "A4");
+        assert_eq!(
+            notes.apply(&FrequencyFormatter().format_value(277.2)),
+            "C#4"
+        );
+        assert_eq!(notes.apply(&FrequencyFormatter().format_value(32.7)), "C1");
+        // Only frequencies have notes.
+        assert_eq!(notes.apply("12.0%"), "12.0%");
+    }
 }
@@ -1,22 +1,36 @@
+pub mod compare;
+pub mod error;
 pub mod fmt;
+pub mod randomize;
+pub mod set;
+pub mod smoothing;
+pub mod state;
 pub mod sync;
 pub mod types;
 
-use std::collections::HashMap;
+use std::str::FromStr;
 
 use copy_from::CopyFrom;
+use params_derive::{ParamFields, ParamNames};
 use serde::{Deserialize, Serialize};
 
-use crate::dsp::env::ADSR;
-use crate::dsp::filter::FilterMode;
-use crate::dsp::osc::{Unison, WaveShape};
-use crate::lfo::{LfoRateSync, LfoShape, Rate};
+use crate::dsp::env::{EnvSource, ADSR, MAX_CURVE, MIN_CURVE};
+use crate::dsp::filter::{FilterMode, FilterRoute};
+use crate::dsp::formant::MAX_VOWEL;
+use crate::dsp::osc::{SoundSource, Unison, WaveShape};
+use crate::lfo::{LfoRateSync, LfoRetrigger, LfoShape, Rate};
+use crate::modulation::matrix::{MatrixTarget, ModSlot, ModSource, MOD_MATRIX_SLOTS};
+use crate::modulation::polarity::{Polarity, RouteMode};
 use crate::modulation::target::ModulationTarget;
+use crate::params::error::ParamError;
 use crate::params::fmt::{
-    BalanceFormatter, BoolOnOffFormatter, DbFormatter, Formatter, FrequencyFormatter,
-    NumberFormatter, PercentFormatter, StringFormatter, TimeFormatter,
+    BalanceFormatter, BoolOnOffFormatter, CentsFormatter, CurveFormatter, DbFormatter,
+    DegreeFormatter, Formatter, FrequencyFormatter, NumberFormatter, PercentFormatter,
+    StringFormatter, TimeFormatter, VowelFormatter,
+};
+use crate::params::types::{
+    Boolean, Enum, Exponential, GradualTime, Linear, LinearDiscrete, LogFrequency, ParamType,
 };
-use crate::params::types::{Boolean, Enum, GradualTime, Linear, LinearDiscrete, ParamType};
 use crate::util::enumerable::Enumerable;
 
 // Used for converting semitones to frequency:
@@ -25,6 +39,19 @@ pub const MIN_CUTOFF_FREQ: f64 = 100.0;
 pub const MIN_CUTOFF_SEMI: f64 = 0.0;
 pub const MAX_CUTOFF_SEMI: f64 = 91.0;
 
+pub const MIN_RESONANCE: f64 = 0.5;
+pub const MAX_RESONANCE: f64 = 2.0;
+
+// Oscillator detune range, in semitones either way.
+pub const MAX_DETUNE_SEMI: f64 = 2.0;
+
+// Mod wheel depths at full travel: vibrato in semitones either way, cutoff in semitones.
+pub const MAX_MOD_WHEEL_VIBRATO_SEMI: f64 = 2.0;
+pub const MAX_MOD_WHEEL_CUTOFF_SEMI: f64 = 48.0;
+
+// Key tracking is relative to middle C.
+pub const KEY_TRACK_CENTER_NOTE: u8 = 60;
+
 const MIN_MOD_RATE_FREQ: f64 = 0.05; // ~20 seconds.
 const MAX_MOD_RATE_FREQ: f64 = 10.0; // Cap modulation to 10 Hz.
 
@@ -32,6 +59,12 @@ pub const DEFAULT_FILTER: FilterMode = FilterMode::LowPass;
 pub const DEFAULT_CUTOFF_SEMI: f64 = MAX_CUTOFF_SEMI;
 pub const DEFAULT_RESONANCE: f64 = 1.0;
 pub const DEFAULT_ENV_AMT: f64 = 0.2;
+pub const DEFAULT_VOWEL: f64 = 0.0;
+pub const DEFAULT_KEY_TRACK: f64 = 0.0;
+pub const DEFAULT_FM_AMT: f64 = 0.0;
+pub const DEFAULT_RES_COMP: bool = false;
+pub const DEFAULT_ENV_SOURCE: EnvSource = EnvSource::Mod;
+pub const DEFAULT_ENV_POLARITY: Polarity = Polarity::Positive;
 
 #[derive(Clone, CopyFrom, Debug, Deserialize, Serialize)]
 pub struct Params {
@@ -48,111 +81,50 @@ pub struct Params {
     // Envelopes
     pub amp_env: ADSR,
     pub mod_env: ADSR,
+    pub mod_env2: ADSR,
+    pub mod_env_route: EnvRoute,
+    pub mod_env2_route: EnvRoute,
 
     // Modulation
     pub lfo1: LfoParams,
     pub lfo2: LfoParams,
+    pub drift: DriftParams,
+    pub mod_matrix: [ModSlot; MOD_MATRIX_SLOTS],
+    pub mod_wheel: ModWheelParams,
+    pub input: InputParams,
 
     pub output_gain: f64,
 }
 
-#[derive(Clone, CopyFrom, Debug, Deserialize, Serialize)]
+#[derive(Clone, CopyFrom, Debug, Deserialize, ParamFields, Serialize)]
+#[params(EOscParams)]
 pub struct OscParams {
+    #[param(Enable, meta = "osc_enabled_meta")]
     pub enabled: bool,
+    #[param(Shape, meta = "osc_shape_meta")]
     pub shape: WaveShape,
+    #[param(FineOffset, meta = "osc_fine_offset_meta")]
     pub fine_offset: f64,
+    // Pitch offset in (fractional) semitones.
+    #[param(Detune, meta = "osc_detune_meta")]
+    pub detune: f64,
+    #[param(SemitonesOffset, meta = "osc_semitones_offset_meta")]
     pub semitones_offset: i32,
+    #[param(OctaveOffset, meta = "osc_octave_offset_meta")]
     pub octave_offset: i32,
+    #[param(StereoWidth, meta = "osc_stereo_width_meta")]
     pub stereo_width: f64,
+    #[param(Unison, meta = "osc_unison_meta")]
     pub unison: Unison,
+    #[param(UnisonAmt, meta = "osc_unison_amt_meta")]
     pub unison_amt: f64,
+    #[param(Gain, meta = "osc_gain_meta")]
     pub gain: f64,
-}
-
-impl OscParams {
-    fn update_param(&mut self, meta: &ParamsMeta, eparam: EOscParams, new_value: f64) {
-        match eparam {
-            EOscParams::Enable => {
-                self.enabled = meta.osc_enabled_meta.0.vst_float_to_value(new_value);
-            }
-            EOscParams::Shape => {
-                self.shape = meta.osc_shape_meta.0.vst_float_to_value(new_value);
-            }
-            EOscParams::FineOffset => {
-                self.fine_offset = meta.osc_fine_offset_meta.0.vst_float_to_value(new_value);
-            }
-            EOscParams::SemitonesOffset => {
-                self.semitones_offset = meta
-                    .osc_semitones_offset_meta
-                    .0
-                    .vst_float_to_value(new_value);
-            }
-            EOscParams::OctaveOffset => {
-                self.octave_offset = meta.osc_octave_offset_meta.0.vst_float_to_value(new_value);
-            }
-            EOscParams::StereoWidth => {
-                self.stereo_width = meta.osc_stereo_width_meta.0.vst_float_to_value(new_value);
-            }
-            EOscParams::Unison => {
-                self.unison = meta.osc_unison_meta.0.vst_float_to_value(new_value);
-            }
-            EOscParams::UnisonAmt => {
-                self.unison_amt = meta.osc_unison_amt_meta.0.vst_float_to_value(new_value);
-            }
-            EOscParams::Gain => {
-                self.gain = meta.osc_gain_meta.0.vst_float_to_value(new_value);
-            }
-        }
-    }
-
-    fn read_parameter(&self, meta: &ParamsMeta, eparam: EOscParams) -> f64 {
-        match eparam {
-            EOscParams::Enable => meta.osc_enabled_meta.0.value_to_vst_float(self.enabled),
-            EOscParams::Shape => meta.osc_shape_meta.0.value_to_vst_float(self.shape),
-            EOscParams::FineOffset => meta
-                .osc_fine_offset_meta
-                .0
-                .value_to_vst_float(self.fine_offset),
-            EOscParams::SemitonesOffset => meta
-                .osc_semitones_offset_meta
-                .0
-                .value_to_vst_float(self.semitones_offset),
-            EOscParams::OctaveOffset => meta
-                .osc_octave_offset_meta
-                .0
-                .value_to_vst_float(self.octave_offset),
-            EOscParams::StereoWidth => meta
-                .osc_stereo_width_meta
-                .0
-                .value_to_vst_float(self.stereo_width),
-            EOscParams::Unison => meta.osc_unison_meta.0.value_to_vst_float(self.unison),
-            EOscParams::UnisonAmt => meta
-                .osc_unison_amt_meta
-                .0
-                .value_to_vst_float(self.unison_amt),
-            EOscParams::Gain => meta.osc_gain_meta.0.value_to_vst_float(self.gain),
-        }
-    }
-
-    fn format_value(&self, meta: &ParamsMeta, eparam: EOscParams) -> String {
-        match eparam {
-            EOscParams::Enable => meta.osc_enabled_meta.1.format_value(self.enabled),
-            EOscParams::Shape => meta.osc_shape_meta.1.format_value(self.shape),
-            EOscParams::FineOffset => meta.osc_fine_offset_meta.1.format_value(self.fine_offset),
-            EOscParams::SemitonesOffset => meta
-                .osc_semitones_offset_meta
-                .1
-                .format_value(self.semitones_offset),
-            EOscParams::OctaveOffset => meta
-                .osc_octave_offset_meta
-                .1
-                .format_value(self.octave_offset),
-            EOscParams::StereoWidth => meta.osc_stereo_width_meta.1.format_value(self.stereo_width),
-            EOscParams::Unison => meta.osc_unison_meta.1.format_value(self.unison),
-            EOscParams::UnisonAmt => meta.osc_unison_amt_meta.1.format_value(self.unison_amt),
-            EOscParams::Gain => meta.osc_gain_meta.1.format_value(self.gain),
-        }
-    }
+    // Balance between the left (-1.0) and right (1.0) channels.
+    #[param(Pan, meta = "osc_pan_meta")]
+    pub pan: f64,
+    #[param(FilterRoute, meta = "osc_filter_route_meta")]
+    pub filter_route: FilterRoute,
 }
 
 impl Default for OscParams {
@@ -161,65 +133,47 @@ impl Default for OscParams {
             enabled: true,
             shape: WaveShape::Sine,
             fine_offset: 0.0,
+            detune: 0.0,
             semitones_offset: 0,
             octave_offset: 0,
             stereo_width: 0.0,
             unison: Unison::Off,
             unison_amt: 1.0,
             gain: 1.0,
+            pan: 0.0,
+            filter_route: FilterRoute::Filter1,
         }
     }
 }
 
-#[derive(Clone, CopyFrom, Debug, Deserialize, Serialize)]
+#[derive(Clone, CopyFrom, Debug, Deserialize, ParamFields, Serialize)]
+#[params(EFiltParams)]
 pub struct FilterParams {
+    #[param(Enable, meta = "filter_enable_meta")]
     pub enable: bool,
+    #[param(Cutoff, meta = "cutoff_meta")]
     pub cutoff_semi: f64,
+    #[param(Resonance, meta = "resonance_meta")]
     pub resonance: f64,
+    #[param(Mode, meta = "mode_meta")]
     pub mode: FilterMode,
+    #[param(EnvAmt, meta = "env_amt_meta")]
     pub env_amt: f64,
-}
-
-impl FilterParams {
-    fn update_param(&mut self, meta: &ParamsMeta, eparam: EFiltParams, new_value: f64) {
-        match eparam {
-            EFiltParams::Enable => {
-                self.enable = meta.filter_enable_meta.0.vst_float_to_value(new_value);
-            }
-            EFiltParams::Cutoff => {
-                self.cutoff_semi = meta.cutoff_meta.0.vst_float_to_value(new_value);
-            }
-            EFiltParams::Resonance => {
-                self.resonance = meta.resonance_meta.0.vst_float_to_value(new_value);
-            }
-            EFiltParams::Mode => {
-                self.mode = meta.mode_meta.0.vst_float_to_value(new_value);
-            }
-            EFiltParams::EnvAmt => {
-                self.env_amt = meta.env_amt_meta.0.vst_float_to_value(new_value);
-            }
-        };
-    }
-
-    fn read_parameter(&self, meta: &ParamsMeta, eparam: EFiltParams) -> f64 {
-        match eparam {
-            EFiltParams::Enable => meta.filter_enable_meta.0.value_to_vst_float(self.enable),
-            EFiltParams::Cutoff => meta.cutoff_meta.0.value_to_vst_float(self.cutoff_semi),
-            EFiltParams::Resonance => meta.resonance_meta.0.value_to_vst_float(self.resonance),
-            EFiltParams::Mode => meta.mode_meta.0.value_to_vst_float(self.mode),
-            EFiltParams::EnvAmt => meta.env_amt_meta.0.value_to_vst_float(self.env_amt),
-        }
-    }
-
-    fn format_value(&self, meta: &ParamsMeta, eparam: EFiltParams) -> String {
-        match eparam {
-            EFiltParams::Enable => meta.filter_enable_meta.1.format_value(self.enable),
-            EFiltParams::Cutoff => meta.cutoff_meta.1.format_value(self.cutoff_semi),
-            EFiltParams::Resonance => meta.resonance_meta.1.format_value(self.resonance),
-            EFiltParams::Mode => meta.mode_meta.1.format_value(self.mode),
-            EFiltParams::EnvAmt => meta.env_amt_meta.1.format_value(self.env_amt),
-        }
-    }
+    /// Envelope driving the cutoff, and in which direction.
+    #[param(EnvSource, meta = "env_source_meta")]
+    pub env_source: EnvSource,
+    #[param(EnvPolarity, meta = "env_polarity_meta")]
+    pub env_polarity: Polarity,
+    #[param(Vowel, meta = "vowel_meta")]
+    pub vowel: f64,
+    #[param(KeyTrack, meta = "key_track_meta")]
+    pub key_track: f64,
+    /// Audio-rate cutoff modulation by the other oscillator.
+    #[param(FmAmt, meta = "fm_amt_meta")]
+    pub fm_amt: f64,
+    /// Resonance loudness compensation and self-oscillation limiting.
+    #[param(ResComp, meta = "res_comp_meta")]
+    pub res_comp: bool,
 }
 
 impl Default for FilterParams {
@@ -230,59 +184,63 @@ impl Default for FilterParams {
             resonance: DEFAULT_RESONANCE,
             mode: DEFAULT_FILTER,
             env_amt: DEFAULT_ENV_AMT,
+            env_source: DEFAULT_ENV_SOURCE,
+            env_polarity: DEFAULT_ENV_POLARITY,
+            vowel: DEFAULT_VOWEL,
+            key_track: DEFAULT_KEY_TRACK,
+            fm_amt: DEFAULT_FM_AMT,
+            res_comp: DEFAULT_RES_COMP,
         }
     }
 }
 
-impl ADSR {
-    fn update_param(&mut self, meta: &ParamsMeta, eparam: EAdsrParams, new_value: f64) {
-        match eparam {
-            EAdsrParams::Attack => {
-                self.attack = meta.attack_meta.0.vst_float_to_value(new_value);
-            }
-            EAdsrParams::Decay => {
-                self.decay = meta.decay_meta.0.vst_float_to_value(new_value);
-            }
-            EAdsrParams::Sustain => {
-                self.sustain = meta.sustain_meta.0.vst_float_to_value(new_value);
-            }
-            EAdsrParams::Release => {
-                self.release = meta.release_meta.0.vst_float_to_value(new_value);
-            }
-        }
-    }
-    fn read_parameter(&self, meta: &ParamsMeta, eparam: EAdsrParams) -> f64 {
-        match eparam {
-            EAdsrParams::Attack => meta.attack_meta.0.value_to_vst_float(self.attack),
-            EAdsrParams::Decay => meta.decay_meta.0.value_to_vst_float(self.decay),
-            EAdsrParams::Sustain => meta.sustain_meta.0.value_to_vst_float(self.sustain),
-            EAdsrParams::Release => meta.release_meta.0.value_to_vst_float(self.release),
-        }
-    }
-    fn format_value(&self, meta: &ParamsMeta, eparam: EAdsrParams) -> String {
-        match eparam {
-            EAdsrParams::Attack => meta.attack_meta.1.format_value(self.attack),
-            EAdsrParams::Decay => meta.decay_meta.1.format_value(self.decay),
-            EAdsrParams::Sustain => meta.sustain_meta.1.format_value(self.sustain),
-            EAdsrParams::Release => meta.release_meta.1.format_value(self.release),
+/// Number of targets each LFO can modulate at once.
+pub const LFO_ROUTES: usize = 2;
+
+/// An LFO destination, and how far the LFO moves it. A negative polarity
+/// inverts the LFO.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct LfoRoute {
+    pub target: ModulationTarget,
+    pub amt: f64,
+    pub mode: RouteMode,
+    pub polarity: Polarity,
+}
+
+impl Default for LfoRoute {
+    fn default() -> Self {
+        Self {
+            target: ModulationTarget::Off,
+            amt: 1.0,
+            mode: RouteMode::Bipolar,
+            polarity: Polarity::Positive,
         }
     }
 }
 
 #[derive(Clone, CopyFrom, Debug, Deserialize, Serialize)]
 pub struct LfoParams {
-    pub target: ModulationTarget,
+    pub routes: [LfoRoute; LFO_ROUTES],
     pub shape: LfoShape,
     pub sync: bool,
-    pub amt: f64,
     pub rate: Rate,
+    // Start phase, in degrees.
+    pub phase: f64,
+    pub retrigger: LfoRetrigger,
+    // Time after note-on before the LFO starts, and how long it takes to reach full depth.
+    pub delay: f64,
+    pub fade: f64,
+    pub one_shot: bool,
 }
 
 impl LfoParams {
     fn update_param(&mut self, meta: &ParamsMeta, eparam: ELfoParams, new_value: f64) {
         match eparam {
             ELfoParams::Target => {
-                self.target = meta.mod_target_meta.0.vst_float_to_value(new_value);
+                self.routes[0].target = meta.mod_target_meta.0.vst_float_to_value(new_value);
+            }
+            ELfoParams::Target2 => {
+                self.routes[1].target = meta.mod_target_meta.0.vst_float_to_value(new_value);
             }
             ELfoParams::Shape => {
                 self.shape = meta.mod_shape_meta.0.vst_float_to_value(new_value);
@@ -299,13 +257,44 @@ impl LfoParams {
                 }
             }
             ELfoParams::Amt => {
-                self.amt = meta.mod_amt_meta.0.vst_float_to_value(new_value);
+                self.routes[0].amt = meta.mod_amt_meta.0.vst_float_to_value(new_value);
+            }
+            ELfoParams::Amt2 => {
+                self.routes[1].amt = meta.mod_amt_meta.0.vst_float_to_value(new_value);
+            }
+            ELfoParams::Mode => {
+                self.routes[0].mode = meta.mod_route_mode_meta.0.vst_float_to_value(new_value);
+            }
+            ELfoParams::Mode2 => {
+                self.routes[1].mode = meta.mod_route_mode_meta.0.vst_float_to_value(new_value);
+            }
+            ELfoParams::Phase => {
+                self.phase = meta.mod_phase_meta.0.vst_float_to_value(new_value);
+            }
+            ELfoParams::Retrigger => {
+                self.retrigger = meta.mod_retrigger_meta.0.vst_float_to_value(new_value);
+            }
+            ELfoParams::Delay => {
+                self.delay = meta.mod_delay_meta.0.vst_float_to_value(new_value);
+            }
+            ELfoParams::Fade => {
+                self.fade = meta.mod_fade_meta.0.vst_float_to_value(new_value);
+            }
+            ELfoParams::OneShot => {
+                self.one_shot = meta.mod_one_shot_meta.0.vst_float_to_value(new_value);
             }
         };
     }
     fn read_parameter(&self, meta: &ParamsMeta, eparam: ELfoParams) -> f64 {
         match eparam {
-            ELfoParams::Target => meta.mod_target_meta.0.value_to_vst_float(self.target),
+            ELfoParams::Target => meta
+                .mod_target_meta
+                .0
+                .value_to_vst_float(self.routes[0].target),
+            ELfoParams::Target2 => meta
+                .mod_target_meta
+                .0
+                .value_to_vst_float(self.routes[1].target),
             ELfoParams::Shape => meta.mod_shape_meta.0.value_to_vst_float(self.shape),
             ELfoParams::Synced => meta.mod_sync_meta.0.value_to_vst_float(self.sync),
             ELfoParams::Rate => match self.rate {
@@ -314,36 +303,184 @@ impl LfoParams {
                     meta.mod_rate_synced_meta.0.value_to_vst_float(rate_synced)
                 }
             },
-            ELfoParams::Amt => meta.mod_amt_meta.0.value_to_vst_float(self.amt),
+            ELfoParams::Amt => meta.mod_amt_meta.0.value_to_vst_float(self.routes[0].amt),
+            ELfoParams::Amt2 => meta.mod_amt_meta.0.value_to_vst_float(self.routes[1].amt),
+            ELfoParams::Mode => meta
+                .mod_route_mode_meta
+                .0
+                .value_to_vst_float(self.routes[0].mode),
+            ELfoParams::Mode2 => meta
+                .mod_route_mode_meta
+                .0
+                .value_to_vst_float(self.routes[1].mode),
+            ELfoParams::Phase => meta.mod_phase_meta.0.value_to_vst_float(self.phase),
+            ELfoParams::Retrigger => meta.mod_retrigger_meta.0.value_to_vst_float(self.retrigger),
+            ELfoParams::Delay => meta.mod_delay_meta.0.value_to_vst_float(self.delay),
+            ELfoParams::Fade => meta.mod_fade_meta.0.value_to_vst_float(self.fade),
+            ELfoParams::OneShot => meta.mod_one_shot_meta.0.value_to_vst_float(self.one_shot),
         }
     }
     fn format_value(&self, meta: &ParamsMeta, eparam: ELfoParams) -> String {
         match eparam {
-            ELfoParams::Target => meta.mod_target_meta.1.format_value(self.target),
+            ELfoParams::Target => meta.mod_target_meta.1.format_value(self.routes[0].target),
+            ELfoParams::Target2 => meta.mod_target_meta.1.format_value(self.routes[1].target),
             ELfoParams::Shape => meta.mod_shape_meta.1.format_value(self.shape),
             ELfoParams::Synced => meta.mod_sync_meta.1.format_value(self.sync),
             ELfoParams::Rate => match self.rate {
                 Rate::Hz(rate_hz) => meta.mod_rate_hz_meta.1.format_value(rate_hz),
                 Rate::Synced(rate_synced) => meta.mod_rate_synced_meta.1.format_value(rate_synced),
             },
-            ELfoParams::Amt => meta.mod_amt_meta.1.format_value(self.amt),
+            ELfoParams::Amt => meta.mod_amt_meta.1.format_value(self.routes[0].amt),
+            ELfoParams::Amt2 => meta.mod_amt_meta.1.format_value(self.routes[1].amt),
+            ELfoParams::Mode => meta.mod_route_mode_meta.1.format_value(self.routes[0].mode),
+            ELfoParams::Mode2 => meta.mod_route_mode_meta.1.format_value(self.routes[1].mode),
+            ELfoParams::Phase => meta.mod_phase_meta.1.format_value(self.phase),
+            ELfoParams::Retrigger => meta.mod_retrigger_meta.1.format_value(self.retrigger),
+            ELfoParams::Delay => meta.mod_delay_meta.1.format_value(self.delay),
+            ELfoParams::Fade => meta.mod_fade_meta.1.format_value(self.fade),
+            ELfoParams::OneShot => meta.mod_one_shot_meta.1.format_value(self.one_shot),
+        }
+    }
+    fn format_normalized(&self, meta: &ParamsMeta, eparam: ELfoParams, value: f64) -> String {
+        fn format<T, P: ParamType<T>, F: Formatter<T>>(meta: &(P, F), value: f64) -> String {
+            meta.1.format_value(meta.0.vst_float_to_value(value))
+        }
+        match eparam {
+            ELfoParams::Target | ELfoParams::Target2 => format(&meta.mod_target_meta, value),
+            ELfoParams::Shape => format(&meta.mod_shape_meta, value),
+            ELfoParams::Synced => format(&meta.mod_sync_meta, value),
+            // As `update_param` takes it: in the units the sync setting calls for.
+            ELfoParams::Rate if self.sync => format(&meta.mod_rate_synced_meta, value),
+            ELfoParams::Rate => format(&meta.mod_rate_hz_meta, value),
+            ELfoParams::Amt | ELfoParams::Amt2 => format(&meta.mod_amt_meta, value),
+            ELfoParams::Mode | ELfoParams::Mode2 => format(&meta.mod_route_mode_meta, value),
+            ELfoParams::Phase => format(&meta.mod_phase_meta, value),
+            ELfoParams::Retrigger => format(&meta.mod_retrigger_meta, value),
+            ELfoParams::Delay => format(&meta.mod_delay_meta, value),
+            ELfoParams::Fade => format(&meta.mod_fade_meta, value),
+            ELfoParams::OneShot => format(&meta.mod_one_shot_meta, value),
+        }
+    }
+    fn parse_value(&self, meta: &ParamsMeta, eparam: ELfoParams, text: &str) -> Option<f64> {
+        fn parse<T, P: ParamType<T>, F: Formatter<T>>(meta: &(P, F), text: &str) -> Option<f64> {
+            meta.1
+                .parse_value(text)
+                .map(|value| meta.0.value_to_vst_float(value))
+        }
+        match eparam {
+            ELfoParams::Target | ELfoParams::Target2 => parse(&meta.mod_target_meta, text),
+            ELfoParams::Shape => parse(&meta.mod_shape_meta, text),
+            ELfoParams::Synced => parse(&meta.mod_sync_meta, text),
+            ELfoParams::Rate => match self.rate {
+                Rate::Hz(_) => parse(&meta.mod_rate_hz_meta, text),
+                Rate::Synced(_) => parse(&meta.mod_rate_synced_meta, text),
+            },
+            ELfoParams::Amt | ELfoParams::Amt2 => parse(&meta.mod_amt_meta, text),
+            ELfoParams::Mode | ELfoParams::Mode2 => parse(&meta.mod_route_mode_meta, text),
+            ELfoParams::Phase => parse(&meta.mod_phase_meta, text),
+            ELfoParams::Retrigger => parse(&meta.mod_retrigger_meta, text),
+            ELfoParams::Delay => parse(&meta.mod_delay_meta, text),
+            ELfoParams::Fade => parse(&meta.mod_fade_meta, text),
+            ELfoParams::OneShot => parse(&meta.mod_one_shot_meta, text),
         }
     }
 }
 
-impl Default for LfoParams {
+/// The random-walk modulation source.
+#[derive(Clone, CopyFrom, Debug, Deserialize, ParamFields, Serialize)]
+#[params(EDriftParams)]
+pub struct DriftParams {
+    // Steps per second.
+    #[param(Rate, meta = "drift_rate_meta")]
+    pub rate: f64,
+    // How gradually it moves to each step, from 0.0 (jump) to 1.0.
+    #[param(Smooth, meta = "drift_smooth_meta")]
+    pub smooth: f64,
+}
+
+impl Default for DriftParams {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            smooth: 1.0,
+        }
+    }
+}
+
+/// How far the mod wheel, at full travel, moves its fixed destinations.
+#[derive(Clone, CopyFrom, Debug, Deserialize, ParamFields, Serialize)]
+#[params(EModWheelParams)]
+pub struct ModWheelParams {
+    // Vibrato depth of both oscillators, in semitones.
+    #[param(Vibrato, meta = "mod_wheel_vibrato_meta")]
+    pub vibrato: f64,
+    // Offset of both filter cutoffs, in semitones.
+    #[param(Cutoff, meta = "mod_wheel_cutoff_meta")]
+    pub cutoff: f64,
+}
+
+impl Default for ModWheelParams {
+    fn default() -> Self {
+        Self {
+            vibrato: 0.5,
+            cutoff: 0.0,
+        }
+    }
+}
+
+/// Audio input handling.
+#[derive(Clone, CopyFrom, Debug, Deserialize, ParamFields, Serialize)]
+#[params(EInputParams)]
+pub struct InputParams {
+    #[param(Source, meta = "input_source_meta")]
+    pub source: SoundSource,
+}
+
+impl Default for InputParams {
+    fn default() -> Self {
+        Self {
+            source: SoundSource::Oscillators,
+        }
+    }
+}
+
+/// A mod envelope destination, and how far the envelope moves it. A negative
+/// amount inverts the envelope.
+#[derive(Clone, Copy, Debug, Deserialize, ParamFields, PartialEq, Serialize)]
+#[params(EEnvRouteParams)]
+pub struct EnvRoute {
+    #[param(Target, meta = "mod_target_meta")]
+    pub target: ModulationTarget,
+    #[param(Amt, meta = "env_route_amt_meta")]
+    pub amt: f64,
+}
+
+impl Default for EnvRoute {
     fn default() -> Self {
         Self {
             target: ModulationTarget::Off,
+            amt: 1.0,
+        }
+    }
+}
+
+impl Default for LfoParams {
+    fn default() -> Self {
+        Self {
+            routes: [LfoRoute::default(); LFO_ROUTES],
             shape: LfoShape::Triangle,
             sync: true,
-            amt: 1.0,
             rate: Rate::Synced(LfoRateSync::R1_4),
+            phase: 0.0,
+            retrigger: LfoRetrigger::Free,
+            delay: 0.0,
+            fade: 0.0,
+            one_shot: false,
         }
     }
 }
 
-// Enums (TODO: Maybe figure out how to macroize these?)
+// Enums
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum EParam {
     // Oscillators
@@ -357,54 +494,190 @@ pub enum EParam {
     // Envelopes
     AmpEnv(EAdsrParams),
     ModEnv(EAdsrParams),
+    ModEnv2(EAdsrParams),
+    // Where each mod envelope is routed, besides the filters.
+    ModEnvRoute(EEnvRouteParams),
+    ModEnv2Route(EEnvRouteParams),
 
     // Modulation
     Lfo1(ELfoParams),
     Lfo2(ELfoParams),
+    Drift(EDriftParams),
+    // Modulation matrix route, by index.
+    ModSlot(usize, EModSlotParams),
+    ModWheel(EModWheelParams),
+    Input(EInputParams),
+    // Each LFO route's polarity.
+    Lfo1Polarity(ELfoPolarityParams),
+    Lfo2Polarity(ELfoPolarityParams),
 
     // Global Gain
     OutputGain,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, ParamNames, PartialEq, Serialize)]
 pub enum EOscParams {
     Enable,
     Shape,
+    #[param(name = "Offset (Fine)")]
     FineOffset,
+    #[param(name = "Offset (Semitones)")]
     SemitonesOffset,
+    #[param(name = "Offset (Octave)")]
     OctaveOffset,
+    #[param(name = "Stereo Width")]
     StereoWidth,
     Unison,
+    #[param(name = "Unison Amount")]
     UnisonAmt,
     Gain,
+    #[param(name = "Filter Route")]
+    FilterRoute,
+    Detune,
+    Pan,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, ParamNames, PartialEq, Serialize)]
 pub enum EFiltParams {
     Enable,
     Cutoff,
     Resonance,
     Mode,
+    #[param(name = "Env Amount", host_name = "EnvAmt")]
     EnvAmt,
+    #[param(name = "Env Source")]
+    EnvSource,
+    #[param(name = "Env Polarity")]
+    EnvPolarity,
+    Vowel,
+    #[param(name = "Key Tracking")]
+    KeyTrack,
+    #[param(name = "Filter FM")]
+    FmAmt,
+    #[param(name = "Res Comp")]
+    ResComp,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, ParamNames, PartialEq, Serialize)]
 pub enum EAdsrParams {
     Attack,
     Decay,
     Sustain,
     Release,
+    #[param(name = "Attack Curve")]
+    AttackCurve,
+    #[param(name = "Decay Curve")]
+    DecayCurve,
+    #[param(name = "Release Curve")]
+    ReleaseCurve,
+    Delay,
+    Hold,
+    Retrigger,
+    #[param(name = "Velocity > Level")]
+    VelToLevel,
+    #[param(name = "Velocity > Attack")]
+    VelToAttack,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, ParamNames, PartialEq, Serialize)]
 pub enum ELfoParams {
     Target,
     Shape,
+    #[param(name = "Sync")]
     Synced,
     Rate,
+    #[param(name = "Amount")]
     Amt,
+    // Second route.
+    #[param(name = "Target 2")]
+    Target2,
+    #[param(name = "Amount 2")]
+    Amt2,
+    Phase,
+    Retrigger,
+    Delay,
+    #[param(name = "Fade In")]
+    Fade,
+    #[param(name = "One Shot")]
+    OneShot,
+    Mode,
+    #[param(name = "Mode 2")]
+    Mode2,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, ParamNames, PartialEq, Serialize)]
+pub enum ELfoPolarityParams {
+    Polarity,
+    #[param(name = "Polarity 2")]
+    Polarity2,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, ParamNames, PartialEq, Serialize)]
+pub enum EDriftParams {
+    Rate,
+    Smooth,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, ParamNames, PartialEq, Serialize)]
+pub enum EModWheelParams {
+    Vibrato,
+    Cutoff,
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, ParamNames, PartialEq, Serialize)]
+pub enum EInputParams {
+    Source,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, ParamNames, PartialEq, Serialize)]
+pub enum EEnvRouteParams {
+    Target,
+    #[param(name = "Amount")]
+    Amt,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, ParamNames, PartialEq, Serialize)]
+pub enum EModSlotParams {
+    Source,
+    Target,
+    Depth,
+    Polarity,
+}
+
+/// Parse a parameter from its JSON form, e.g. `{"Osc1":"Gain"}` or `"OutputGain"`.
+impl FromStr for EParam {
+    type Err = ParamError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || ParamError::UnknownParam(s.to_string());
+        let eparam: EParam = serde_json::from_str(s).map_err(|_| unknown())?;
+        match eparam {
+            EParam::ModSlot(slot, _) if slot >= MOD_MATRIX_SLOTS => Err(unknown()),
+            eparam => Ok(eparam),
+        }
+    }
+}
+
+// Where each section starts in the host's parameter list.
+const OSC_BASE: usize = 0;
+const FILT_BASE: usize = OSC_BASE + 2 * EOscParams::COUNT;
+const ENV_BASE: usize = FILT_BASE + 2 * EFiltParams::COUNT;
+const ENV_ROUTE_BASE: usize = ENV_BASE + 3 * EAdsrParams::COUNT;
+const LFO_BASE: usize = ENV_ROUTE_BASE + 2 * EEnvRouteParams::COUNT;
+const DRIFT_BASE: usize = LFO_BASE + 2 * ELfoParams::COUNT;
+const MOD_SLOT_BASE: usize = DRIFT_BASE + EDriftParams::COUNT;
+const OUTPUT_GAIN_INDEX: usize = MOD_SLOT_BASE + MOD_MATRIX_SLOTS * EModSlotParams::COUNT;
+// Sections added later go after the output gain, so earlier indices stay put.
+const MOD_WHEEL_BASE: usize = OUTPUT_GAIN_INDEX + 1;
+const INPUT_BASE: usize = MOD_WHEEL_BASE + EModWheelParams::COUNT;
+const LFO_POLARITY_BASE: usize = INPUT_BASE + EInputParams::COUNT;
+
+/// Number of parameters exposed to the host.
+pub const EPARAM_COUNT: usize = LFO_POLARITY_BASE + 2 * ELfoPolarityParams::COUNT;
+
+// How finely `Params::options` tries a parameter's range; enough for any enum's steps.
+const OPTION_PROBES: usize = 1024;
+
 // Names.
 impl EParam {
     pub fn as_string(&self, short: bool) -> String {
@@ -416,8 +689,16 @@ impl EParam {
             Self::Filt2(e) => e.as_string(short),
             Self::AmpEnv(e) => e.as_string(short),
             Self::ModEnv(e) => e.as_string(short),
+            Self::ModEnv2(e) => e.as_string(short),
+            Self::ModEnvRoute(e) => e.as_string(short),
+            Self::ModEnv2Route(e) => e.as_string(short),
             Self::Lfo1(e) => e.as_string(short),
             Self::Lfo2(e) => e.as_string(short),
+            Self::Drift(e) => e.as_string(short),
+            Self::ModSlot(_, e) => e.as_string(short),
+            Self::ModWheel(e) => e.as_string(short),
+            Self::Input(e) => e.as_string(short),
+            Self::Lfo1Polarity(e) | Self::Lfo2Polarity(e) => e.as_string(short),
             Self::OutputGain => "Output Gain".to_string(),
         };
         if short {
@@ -430,145 +711,134 @@ impl EParam {
                 Self::Filt2(_) => "Filt2",
                 Self::AmpEnv(_) => "AmpEnv",
                 Self::ModEnv(_) => "ModEnv",
+                Self::ModEnv2(_) => "ModEnv2",
+                Self::ModEnvRoute(_) => "ModEnv",
+                Self::ModEnv2Route(_) => "ModEnv2",
                 Self::Lfo1(_) => "Osc1",
                 Self::Lfo2(_) => "Osc1",
-                Self::OutputGain => "",
+                Self::Drift(_) => "Drift",
+                Self::ModSlot(slot, _) => return format!("ModSlot{}:{}", slot + 1, param_name),
+                Self::ModWheel(_) => "ModWheel",
+                Self::Input(_) => "Input",
+                Self::Lfo1Polarity(_) => "Lfo1",
+                Self::Lfo2Polarity(_) => "Lfo2",
+                Self::OutputGain => return param_name,
             };
             format!("{}:{}", prefix, param_name)
         }
     }
-    fn get_names() -> Vec<(EParam, String)> {
-        let mut names = vec![];
-        // Oscillators.
-        for (param, name) in EOscParams::get_names() {
-            names.push((EParam::Osc1(param), format!("Osc1:{}", name)));
-            names.push((EParam::Osc2(param), format!("Osc2:{}", name)));
-        }
-        // Filters.
-        for (param, name) in EFiltParams::get_names() {
-            names.push((EParam::Filt1(param), format!("Filt1:{}", name)));
-            names.push((EParam::Filt2(param), format!("Filt2:{}", name)));
-        }
-        // Envelopes
-        for (param, name) in EAdsrParams::get_names() {
-            names.push((EParam::AmpEnv(param), format!("Amp Env:{}", name)));
-            names.push((EParam::ModEnv(param), format!("Mod Env:{}", name)));
-        }
-        // Modulation
-        for (param, name) in ELfoParams::get_names() {
-            names.push((EParam::Lfo1(param), format!("Mod LFO1:{}", name)));
-            names.push((EParam::Lfo2(param), format!("Mod LFO2:{}", name)));
+    /// Name shown to the host.
+    pub fn host_name(&self) -> String {
+        match self {
+            Self::Osc1(e) => format!("Osc1:{}", e.host_name()),
+            Self::Osc2(e) => format!("Osc2:{}", e.host_name()),
+            Self::Filt1(e) => format!("Filt1:{}", e.host_name()),
+            Self::Filt2(e) => format!("Filt2:{}", e.host_name()),
+            Self::AmpEnv(e) => format!("Amp Env:{}", e.host_name()),
+            Self::ModEnv(e) => format!("Mod Env:{}", e.host_name()),
+            Self::ModEnv2(e) => format!("Mod Env 2:{}", e.host_name()),
+            Self::ModEnvRoute(e) => format!("Mod Env:{}", e.host_name()),
+            Self::ModEnv2Route(e) => format!("Mod Env 2:{}", e.host_name()),
+            Self::Lfo1(e) => format!("Mod LFO1:{}", e.host_name()),
+            Self::Lfo2(e) => format!("Mod LFO2:{}", e.host_name()),
+            Self::Drift(e) => format!("Mod Drift:{}", e.host_name()),
+            Self::ModSlot(slot, e) => format!("Mod Slot {}:{}", slot + 1, e.host_name()),
+            Self::ModWheel(e) => format!("Mod Wheel:{}", e.host_name()),
+            Self::Input(e) => format!("Input:{}", e.host_name()),
+            Self::Lfo1Polarity(e) => format!("Mod LFO1:{}", e.host_name()),
+            Self::Lfo2Polarity(e) => format!("Mod LFO2:{}", e.host_name()),
+            Self::OutputGain => "Output Gain".to_string(),
         }
-        // Output Gain
-        names.push((EParam::OutputGain, "Output Gain".to_string()));
-        names
-    }
-}
-impl EOscParams {
-    fn as_string(&self, _short: bool) -> String {
-        let s = match self {
-            Self::Enable => "Enable",
-            Self::Shape => "Shape",
-            Self::FineOffset => "Offset (Fine)",
-            Self::SemitonesOffset => "Offset (Semitones)",
-            Self::OctaveOffset => "Offset (Octave)",
-            Self::StereoWidth => "Stereo Width",
-            Self::Unison => "Unison",
-            Self::UnisonAmt => "Unison Amount",
-            Self::Gain => "Gain",
-        };
-        s.to_string()
-    }
-    fn get_names() -> Vec<(EOscParams, String)> {
-        vec![
-            (Self::Enable, "Enable".to_owned()),
-            (Self::Shape, "Shape".to_owned()),
-            (Self::FineOffset, "Offset (Fine)".to_owned()),
-            (Self::SemitonesOffset, "Offset (Semitones)".to_owned()),
-            (Self::OctaveOffset, "Offset (Octave)".to_string()),
-            (Self::StereoWidth, "Stereo Width".to_string()),
-            (Self::Unison, "Unison".to_string()),
-            (Self::UnisonAmt, "Unison Amount".to_string()),
-            (Self::Gain, "Gain".to_string()),
-        ]
-    }
-}
-impl EFiltParams {
-    fn as_string(&self, _short: bool) -> String {
-        let s = match self {
-            Self::Enable => "Enable",
-            Self::Cutoff => "Cutoff",
-            Self::Resonance => "Resonance",
-            Self::Mode => "Mode",
-            Self::EnvAmt => "Env Amount",
-        };
-        s.to_string()
-    }
-    fn get_names() -> Vec<(EFiltParams, String)> {
-        vec![
-            (Self::Enable, "Enable".to_string()),
-            (Self::Cutoff, "Cutoff".to_string()),
-            (Self::Resonance, "Resonance".to_string()),
-            (Self::Mode, "Mode".to_string()),
-            (Self::EnvAmt, "EnvAmt".to_string()),
-        ]
-    }
-}
-
-impl EAdsrParams {
-    fn as_string(&self, _short: bool) -> String {
-        let s = match self {
-            Self::Attack => "Attack",
-            Self::Decay => "Decay",
-            Self::Sustain => "Sustain",
-            Self::Release => "Release",
-        };
-        s.to_string()
-    }
-    fn get_names() -> Vec<(EAdsrParams, String)> {
-        vec![
-            (Self::Attack, "Attack".to_string()),
-            (Self::Decay, "Decay".to_string()),
-            (Self::Sustain, "Sustain".to_string()),
-            (Self::Release, "Release".to_string()),
-        ]
-    }
-}
-
-impl ELfoParams {
-    fn as_string(&self, _short: bool) -> String {
-        let s = match self {
-            Self::Target => "Target",
-            Self::Shape => "Shape",
-            Self::Synced => "Sync",
-            Self::Rate => "Rate",
-            Self::Amt => "Amount",
+    }
+
+    /// Position in the host's parameter list, or `None` for a matrix slot that doesn't exist.
+    ///
+    /// Parameters shared by several sections are interleaved (Osc1 Enable, Osc2 Enable, Osc1
+    /// Shape, ...). Hosts save automation by index, so this order must not change.
+    pub fn index(self) -> Option<usize> {
+        let index = match self {
+            Self::Osc1(e) => OSC_BASE + e.index() * 2,
+            Self::Osc2(e) => OSC_BASE + e.index() * 2 + 1,
+            Self::Filt1(e) => FILT_BASE + e.index() * 2,
+            Self::Filt2(e) => FILT_BASE + e.index() * 2 + 1,
+            Self::AmpEnv(e) => ENV_BASE + e.index() * 3,
+            Self::ModEnv(e) => ENV_BASE + e.index() * 3 + 1,
+            Self::ModEnv2(e) => ENV_BASE + e.index() * 3 + 2,
+            Self::ModEnvRoute(e) => ENV_ROUTE_BASE + e.index() * 2,
+            Self::ModEnv2Route(e) => ENV_ROUTE_BASE + e.index() * 2 + 1,
+            Self::Lfo1(e) => LFO_BASE + e.index() * 2,
+            Self::Lfo2(e) => LFO_BASE + e.index() * 2 + 1,
+            Self::Drift(e) => DRIFT_BASE + e.index(),
+            Self::ModSlot(slot, e) if slot < MOD_MATRIX_SLOTS => {
+                MOD_SLOT_BASE + slot * EModSlotParams::COUNT + e.index()
+            }
+            Self::ModSlot(..) => return None,
+            Self::OutputGain => OUTPUT_GAIN_INDEX,
+            Self::ModWheel(e) => MOD_WHEEL_BASE + e.index(),
+            Self::Input(e) => INPUT_BASE + e.index(),
+            Self::Lfo1Polarity(e) => LFO_POLARITY_BASE + e.index() * 2,
+            Self::Lfo2Polarity(e) => LFO_POLARITY_BASE + e.index() * 2 + 1,
         };
-        s.to_string()
+        Some(index)
     }
-    fn get_names() -> Vec<(ELfoParams, String)> {
-        vec![
-            (Self::Target, "Target".to_string()),
-            (Self::Shape, "Shape".to_string()),
-            (Self::Synced, "Sync".to_string()),
-            (Self::Rate, "Rate".to_string()),
-            (Self::Amt, "Amount".to_string()),
-        ]
+
+    /// The parameter at `index` in the host's parameter list; see `index`.
+    pub fn from_index(index: usize) -> Option<EParam> {
+        let eparam = if index < FILT_BASE {
+            let i = index - OSC_BASE;
+            [Self::Osc1, Self::Osc2][i % 2](EOscParams::ALL[i / 2])
+        } else if index < ENV_BASE {
+            let i = index - FILT_BASE;
+            [Self::Filt1, Self::Filt2][i % 2](EFiltParams::ALL[i / 2])
+        } else if index < ENV_ROUTE_BASE {
+            let i = index - ENV_BASE;
+            [Self::AmpEnv, Self::ModEnv, Self::ModEnv2][i % 3](EAdsrParams::ALL[i / 3])
+        } else if index < LFO_BASE {
+            let i = index - ENV_ROUTE_BASE;
+            [Self::ModEnvRoute, Self::ModEnv2Route][i % 2](EEnvRouteParams::ALL[i / 2])
+        } else if index < DRIFT_BASE {
+            let i = index - LFO_BASE;
+            [Self::Lfo1, Self::Lfo2][i % 2](ELfoParams::ALL[i / 2])
+        } else if index < MOD_SLOT_BASE {
+            Self::Drift(EDriftParams::ALL[index - DRIFT_BASE])
+        } else if index < OUTPUT_GAIN_INDEX {
+            let i = index - MOD_SLOT_BASE;
+            Self::ModSlot(
+                i / EModSlotParams::COUNT,
+                EModSlotParams::ALL[i % EModSlotParams::COUNT],
+            )
+        } else if index == OUTPUT_GAIN_INDEX {
+            Self::OutputGain
+        } else if index < INPUT_BASE {
+            Self::ModWheel(EModWheelParams::ALL[index - MOD_WHEEL_BASE])
+        } else if index < LFO_POLARITY_BASE {
+            Self::Input(EInputParams::ALL[index - INPUT_BASE])
+        } else if index < EPARAM_COUNT {
+            let i = index - LFO_POLARITY_BASE;
+            [Self::Lfo1Polarity, Self::Lfo2Polarity][i % 2](ELfoPolarityParams::ALL[i / 2])
+        } else {
+            return None;
+        };
+        Some(eparam)
     }
-}
 
-// Metadata per parameter.
-#[derive(Clone, Debug)]
-struct ParamMeta {
-    name: String,
-}
+    /// Every parameter, in the host's order.
+    pub fn all() -> impl Iterator<Item = EParam> {
+        (0..EPARAM_COUNT).filter_map(EParam::from_index)
+    }
 
-impl ParamMeta {
-    fn new(name: String) -> Self {
-        ParamMeta { name }
+    /// Check that `value` is a valid normalized value for this parameter.
+    pub fn check_value(self, value: f64) -> Result<f64, ParamError> {
+        if (0.0..=1.0).contains(&value) {
+            Ok(value)
+        } else {
+            Err(ParamError::OutOfRange {
+                eparam: self,
+                value,
+            })
+        }
     }
 }
-
 // Stores all of the Metadata associated with the parameters.
 #[derive(Clone, Debug)]
 pub struct ParamsMeta {
@@ -584,6 +854,9 @@ pub struct ParamsMeta {
     pub osc_unison_meta: (Enum<Unison>, StringFormatter),
     pub osc_unison_amt_meta: (Linear, FrequencyFormatter),
     pub osc_gain_meta: (Linear, DbFormatter),
+    pub osc_pan_meta: (Linear, BalanceFormatter),
+    pub osc_filter_route_meta: (Enum<FilterRoute>, StringFormatter),
+    pub osc_detune_meta: (Linear, CentsFormatter),
 
     // Filters
     pub filter_enable_meta: (Boolean, BoolOnOffFormatter),
@@ -591,63 +864,67 @@ pub struct ParamsMeta {
     pub resonance_meta: (Linear, NumberFormatter),
     pub mode_meta: (Enum<FilterMode>, StringFormatter),
     pub env_amt_meta: (Linear, PercentFormatter),
+    pub env_source_meta: (Enum<EnvSource>, StringFormatter),
+    pub env_polarity_meta: (Enum<Polarity>, StringFormatter),
+    pub vowel_meta: (Linear, VowelFormatter),
+    pub key_track_meta: (Linear, PercentFormatter),
+    pub fm_amt_meta: (Linear, PercentFormatter),
+    pub res_comp_meta: (Boolean, BoolOnOffFormatter),
 
     // Envelopes
     pub attack_meta: (GradualTime, TimeFormatter),
     pub decay_meta: (GradualTime, TimeFormatter),
     pub sustain_meta: (Linear, PercentFormatter),
     pub release_meta: (Linear, TimeFormatter),
+    pub curve_meta: (Linear, CurveFormatter),
+    pub delay_meta: (GradualTime, TimeFormatter),
+    pub hold_meta: (GradualTime, TimeFormatter),
+    pub retrigger_meta: (Boolean, BoolOnOffFormatter),
+    pub vel_amt_meta: (Linear, PercentFormatter),
 
     // Modulation
     pub mod_target_meta: (Enum<ModulationTarget>, StringFormatter),
     pub mod_shape_meta: (Enum<LfoShape>, StringFormatter),
-    // TODO: Use a non-linear rate
     pub mod_sync_meta: (Boolean, BoolOnOffFormatter),
-    pub mod_rate_hz_meta: (Linear, NumberFormatter),
+    pub mod_rate_hz_meta: (LogFrequency, NumberFormatter),
     pub mod_rate_synced_meta: (Enum<LfoRateSync>, StringFormatter),
     pub mod_amt_meta: (Linear, NumberFormatter),
-
-    pub output_gain_meta: (Linear, DbFormatter),
-
-    pub paramlist: Vec<EParam>,
-    param_to_index: HashMap<EParam, usize>,
-    params: HashMap<EParam, ParamMeta>,
+    pub mod_phase_meta: (Linear, DegreeFormatter),
+    pub mod_retrigger_meta: (Enum<LfoRetrigger>, StringFormatter),
+    pub mod_delay_meta: (GradualTime, TimeFormatter),
+    pub mod_fade_meta: (GradualTime, TimeFormatter),
+    pub mod_one_shot_meta: (Boolean, BoolOnOffFormatter),
+    pub mod_route_mode_meta: (Enum<RouteMode>, StringFormatter),
+    pub mod_polarity_meta: (Enum<Polarity>, StringFormatter),
+    pub env_route_amt_meta: (Linear, NumberFormatter),
+
+    // Drift
+    pub drift_rate_meta: (LogFrequency, NumberFormatter),
+    pub drift_smooth_meta: (Linear, PercentFormatter),
+
+    // Modulation matrix
+    pub mod_slot_source_meta: (Enum<ModSource>, StringFormatter),
+    pub mod_slot_target_meta: (Enum<MatrixTarget>, StringFormatter),
+    pub mod_slot_depth_meta: (Linear, PercentFormatter),
+    pub mod_slot_polarity_meta: (Enum<Polarity>, StringFormatter),
+
+    // Mod wheel
+    pub mod_wheel_vibrato_meta: (Linear, CentsFormatter),
+    pub mod_wheel_cutoff_meta: (Linear, NumberFormatter),
+
+    // Input
+    pub input_source_meta: (Enum<SoundSource>, StringFormatter),
+
+    pub output_gain_meta: (Exponential, DbFormatter),
+
+    // Normalized default of every parameter, by index.
+    defaults: Vec<f64>,
 }
 
 impl ParamsMeta {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        /*
-         * The VST interface retrieves parameters by index. Additionally, we would like to store
-         * metadata per parameter (such as the name). All parameters are internal referenced
-         * through the EParam enum.
-         *
-         * We do two things in this function:
-         * 1. Create a mapping between EParam and ParamMeta (the metadata).
-         * 2. Create a list of all EParam choices to be able to look them up by index.
-         *    In theory, we could do this on initialization by iterating the hashmap's keys
-         *    but we would like the order to be consistent across executions and compilations.
-         *
-         */
-        let (paramlist, param_to_index, params) = {
-            // This is the authoritative source of per-param metadata (minus the type).
-            let param_metas: Vec<(EParam, String)> = EParam::get_names();
-
-            // Allow index to eparam lookup.
-            let mut param_to_index: HashMap<EParam, usize> = HashMap::new();
-
-            // Create the lookup between EParam and the associated metadata.
-            let mut m: HashMap<EParam, ParamMeta> = HashMap::new();
-            for (index, (eparam, name)) in param_metas.iter().enumerate() {
-                m.insert(*eparam, ParamMeta::new(name.to_string()));
-                param_to_index.insert(*eparam, index);
-            }
-
-            // And finally, VST index to EParam.
-            let paramlist: Vec<EParam> = param_metas.iter().map(|(eparam, _)| *eparam).collect();
-            (paramlist, param_to_index, m)
-        };
-        ParamsMeta {
+        let mut meta = ParamsMeta {
             // Oscillators
             osc_enabled_meta: (Boolean::new(), BoolOnOffFormatter()),
             osc_shape_meta: (Enum::new(WaveShape::enumerate()), StringFormatter()),
@@ -658,6 +935,12 @@ impl ParamsMeta {
             osc_unison_meta: (Enum::new(Unison::enumerate()), StringFormatter()),
             osc_unison_amt_meta: (Linear::new(0.0, 3.0), FrequencyFormatter()),
             osc_gain_meta: (Linear::new(0.0, 1.0), DbFormatter()),
+            osc_pan_meta: (Linear::new(-1.0, 1.0), BalanceFormatter()),
+            osc_filter_route_meta: (Enum::new(FilterRoute::enumerate()), StringFormatter()),
+            osc_detune_meta: (
+                Linear::new(-MAX_DETUNE_SEMI, MAX_DETUNE_SEMI),
+                CentsFormatter(),
+            ),
 
             // Filters
             filter_enable_meta: (Boolean::new(), BoolOnOffFormatter()),
@@ -665,51 +948,104 @@ impl ParamsMeta {
                 Linear::new(MIN_CUTOFF_SEMI, MAX_CUTOFF_SEMI),
                 NumberFormatter(),
             ),
-            resonance_meta: (Linear::new(0.5, 2.0), NumberFormatter()),
+            resonance_meta: (Linear::new(MIN_RESONANCE, MAX_RESONANCE), NumberFormatter()),
             mode_meta: (Enum::new(FilterMode::enumerate()), StringFormatter()),
             env_amt_meta: (Linear::new(0.0, 1.0), PercentFormatter()),
+            env_source_meta: (Enum::new(EnvSource::enumerate()), StringFormatter()),
+            env_polarity_meta: (Enum::new(Polarity::enumerate()), StringFormatter()),
+            vowel_meta: (Linear::new(0.0, MAX_VOWEL), VowelFormatter()),
+            key_track_meta: (Linear::new(0.0, 1.0), PercentFormatter()),
+            fm_amt_meta: (Linear::new(0.0, 1.0), PercentFormatter()),
+            res_comp_meta: (Boolean::new(), BoolOnOffFormatter()),
 
             // Envelopes
             attack_meta: (GradualTime::for_attack(), TimeFormatter()),
             decay_meta: (GradualTime::for_decay(), TimeFormatter()),
             sustain_meta: (Linear::new(0.0, 1.0), PercentFormatter()),
             release_meta: (Linear::new(0.0, 5.0), TimeFormatter()),
+            curve_meta: (Linear::new(MIN_CURVE, MAX_CURVE), CurveFormatter()),
+            delay_meta: (GradualTime::for_delay_hold(), TimeFormatter()),
+            hold_meta: (GradualTime::for_delay_hold(), TimeFormatter()),
+            retrigger_meta: (Boolean::new(), BoolOnOffFormatter()),
+            vel_amt_meta: (Linear::new(0.0, 1.0), PercentFormatter()),
 
             // Modulation
             mod_target_meta: (Enum::new(ModulationTarget::enumerate()), StringFormatter()),
             mod_shape_meta: (Enum::new(LfoShape::enumerate()), StringFormatter()),
             mod_sync_meta: (Boolean::new(), BoolOnOffFormatter()),
             mod_rate_hz_meta: (
-                Linear::new(MIN_MOD_RATE_FREQ, MAX_MOD_RATE_FREQ),
+                LogFrequency::new(MIN_MOD_RATE_FREQ, MAX_MOD_RATE_FREQ, 1.0),
                 NumberFormatter(),
             ),
             mod_rate_synced_meta: (Enum::new(LfoRateSync::enumerate()), StringFormatter()),
             mod_amt_meta: (Linear::new(0.0, 1.0), NumberFormatter()),
+            mod_phase_meta: (Linear::new(0.0, 360.0), DegreeFormatter()),
+            mod_retrigger_meta: (Enum::new(LfoRetrigger::enumerate()), StringFormatter()),
+            mod_delay_meta: (GradualTime::for_delay_hold(), TimeFormatter()),
+            mod_fade_meta: (GradualTime::for_delay_hold(), TimeFormatter()),
+            mod_one_shot_meta: (Boolean::new(), BoolOnOffFormatter()),
+            mod_route_mode_meta: (Enum::new(RouteMode::enumerate()), StringFormatter()),
+            mod_polarity_meta: (Enum::new(Polarity::enumerate()), StringFormatter()),
+            env_route_amt_meta: (Linear::new(-1.0, 1.0), NumberFormatter()),
+
+            // Drift
+            drift_rate_meta: (
+                LogFrequency::new(MIN_MOD_RATE_FREQ, MAX_MOD_RATE_FREQ, 1.0),
+                NumberFormatter(),
+            ),
+            drift_smooth_meta: (Linear::new(0.0, 1.0), PercentFormatter()),
+
+            // Modulation matrix
+            mod_slot_source_meta: (Enum::new(ModSource::enumerate()), StringFormatter()),
+            mod_slot_target_meta: (Enum::new(MatrixTarget::enumerate()), StringFormatter()),
+            mod_slot_depth_meta: (Linear::new(0.0, 1.0), PercentFormatter()),
+            mod_slot_polarity_meta: (Enum::new(Polarity::enumerate()), StringFormatter()),
+
+            // Mod wheel
+            mod_wheel_vibrato_meta: (
+                Linear::new(0.0, MAX_MOD_WHEEL_VIBRATO_SEMI),
+                CentsFormatter(),
+            ),
+            mod_wheel_cutoff_meta: (
+                Linear::new(-MAX_MOD_WHEEL_CUTOFF_SEMI, MAX_MOD_WHEEL_CUTOFF_SEMI),
+                NumberFormatter(),
+            ),
+
+            // Input
+            input_source_meta: (Enum::new(SoundSource::enumerate()), StringFormatter()),
 
             // Global Gain
-            output_gain_meta: (Linear::new(0.0, 2.0), DbFormatter()),
+            // Unity gain sits at ~70% of the travel.
+            output_gain_meta: (Exponential::new(0.0, 2.0, 2.0), DbFormatter()),
 
-            paramlist,
-            param_to_index,
-            params,
-        }
+            defaults: vec![],
+        };
+        // The sample rate doesn't affect any parameter.
+        let defaults = Params::new(44100.0);
+        meta.defaults = EParam::all()
+            .map(|eparam| defaults.read_parameter(&meta, eparam))
+            .collect();
+        meta
     }
 
     pub fn count(&self) -> usize {
-        self.paramlist.len()
+        EPARAM_COUNT
     }
 
     pub fn param_to_index(&self, param: &EParam) -> Option<usize> {
-        self.param_to_index.get(param).copied()
+        param.index()
     }
 
-    pub fn parameter_index(&self, index: usize) -> EParam {
-        if index < self.paramlist.len() {
-            self.paramlist[index]
-        } else {
-            log::error!("Invalid parameter index: {}", index);
-            EParam::Osc1(EOscParams::Enable)
-        }
+    pub fn parameter_index(&self, index: usize) -> Result<EParam, ParamError> {
+        EParam::from_index(index).ok_or(ParamError::UnknownIndex(index))
+    }
+
+    /// The parameter's (normalized) value in the init patch.
+    pub fn default_value(&self, eparam: EParam) -> Result<f64, ParamError> {
+        eparam
+            .index()
+            .map(|index| self.defaults[index])
+            .ok_or_else(|| ParamError::UnknownParam(format!("{:?}", eparam)))
     }
 }
 
@@ -718,13 +1054,24 @@ impl Params {
         Params {
             sample_rate,
             osc1: OscParams::default(),
-            osc2: OscParams::default(),
+            // By default, each oscillator feeds its own filter.
+            osc2: OscParams {
+                filter_route: FilterRoute::Filter2,
+                ..OscParams::default()
+            },
             filt1: FilterParams::default(),
             filt2: FilterParams::default(),
             amp_env: ADSR::default(),
             mod_env: ADSR::default(),
+            mod_env2: ADSR::default(),
+            mod_env_route: EnvRoute::default(),
+            mod_env2_route: EnvRoute::default(),
             lfo1: LfoParams::default(),
             lfo2: LfoParams::default(),
+            drift: DriftParams::default(),
+            mod_matrix: [ModSlot::default(); MOD_MATRIX_SLOTS],
+            mod_wheel: ModWheelParams::default(),
+            input: InputParams::default(),
             output_gain: 1.0,
         }
     }
@@ -732,24 +1079,56 @@ impl Params {
     pub fn update_sample_rate(&mut self, sample_rate: f64) {
         self.sample_rate = sample_rate;
     }
+
+    /// Put a single parameter back to its init patch value.
+    pub fn reset_to_default(
+        &mut self,
+        meta: &ParamsMeta,
+        eparam: EParam,
+    ) -> Result<(), ParamError> {
+        let value = meta.default_value(eparam)?;
+        self.write_parameter(meta, eparam, value);
+        Ok(())
+    }
+
+    /// The values a stepped parameter (e.g. an enum) can take, normalized, each with its
+    /// formatted text. They're found by trying values across the whole range, so this only
+    /// makes sense for parameters with a handful of steps.
+    pub fn options(&self, meta: &ParamsMeta, eparam: EParam) -> Vec<(f64, String)> {
+        let mut probe = self.clone();
+        let mut options: Vec<(f64, String)> = vec![];
+        for step in 0..=OPTION_PROBES {
+            probe.write_parameter(meta, eparam, step as f64 / OPTION_PROBES as f64);
+            let value = probe.read_parameter(meta, eparam);
+            if options.last().map(|(last, _)| *last) != Some(value) {
+                let text = probe.formatted_value(meta, eparam).unwrap_or_default();
+                options.push((value, text));
+            }
+        }
+        options
+    }
 }
 
-#[allow(clippy::result_unit_err)]
 pub trait NormalizedParams {
     fn write_parameter(&mut self, meta: &ParamsMeta, eparam: EParam, new_value: f64);
     fn read_parameter(&self, meta: &ParamsMeta, eparam: EParam) -> f64;
-    fn parameter_name(&self, meta: &ParamsMeta, index: usize) -> String;
-    fn formatted_value(&self, meta: &ParamsMeta, eparam: EParam) -> String;
+    fn parameter_name(&self, meta: &ParamsMeta, index: usize) -> Result<String, ParamError>;
+    fn formatted_value(&self, meta: &ParamsMeta, eparam: EParam) -> Result<String, ParamError>;
+    /// The text `formatted_value` would give if `eparam` were set to the normalized `value`.
+    fn formatted_normalized(
+        &self,
+        meta: &ParamsMeta,
+        eparam: EParam,
+        value: f64,
+    ) -> Result<String, ParamError>;
+    /// The normalized value for text typed in the form `formatted_value` gives.
+    fn parse_value(&self, meta: &ParamsMeta, eparam: EParam, text: &str)
+        -> Result<f64, ParamError>;
 }
 
 impl NormalizedParams for Params {
-    fn parameter_name(&self, meta: &ParamsMeta, index: usize) -> String {
-        let eparam = meta.parameter_index(index);
-        if let Some(param_meta) = meta.params.get(&eparam) {
-            param_meta.name.clone()
-        } else {
-            "(error)".to_string()
-        }
+    fn parameter_name(&self, meta: &ParamsMeta, index: usize) -> Result<String, ParamError> {
+        Ok(meta.parameter_index(index)?.host_name())
     }
 
     fn write_parameter(&mut self, meta: &ParamsMeta, eparam: EParam, new_value: f64) {
@@ -773,12 +1152,45 @@ impl NormalizedParams for Params {
             EParam::ModEnv(env_param) => {
                 self.mod_env.update_param(meta, env_param, new_value);
             }
+            EParam::ModEnv2(env_param) => {
+                self.mod_env2.update_param(meta, env_param, new_value);
+            }
+            EParam::ModEnvRoute(route_param) => {
+                self.mod_env_route
+                    .update_param(meta, route_param, new_value);
+            }
+            EParam::ModEnv2Route(route_param) => {
+                self.mod_env2_route
+                    .update_param(meta, route_param, new_value);
+            }
             EParam::Lfo1(lfo_param) => {
                 self.lfo1.update_param(meta, lfo_param, new_value);
             }
             EParam::Lfo2(lfo_param) => {
                 self.lfo2.update_param(meta, lfo_param, new_value);
             }
+            EParam::Drift(drift_param) => {
+                self.drift.update_param(meta, drift_param, new_value);
+            }
+            EParam::ModSlot(slot, slot_param) => {
+                if let Some(mod_slot) = self.mod_matrix.get_mut(slot) {
+                    mod_slot.update_param(meta, slot_param, new_value);
+                }
+            }
+            EParam::ModWheel(wheel_param) => {
+                self.mod_wheel.update_param(meta, wheel_param, new_value);
+            }
+            EParam::Input(input_param) => {
+                self.input.update_param(meta, input_param, new_value);
+            }
+            EParam::Lfo1Polarity(polarity_param) => {
+                self.lfo1.routes[polarity_param.index()].polarity =
+                    meta.mod_polarity_meta.0.vst_float_to_value(new_value);
+            }
+            EParam::Lfo2Polarity(polarity_param) => {
+                self.lfo2.routes[polarity_param.index()].polarity =
+                    meta.mod_polarity_meta.0.vst_float_to_value(new_value);
+            }
             EParam::OutputGain => {
                 self.output_gain = meta.output_gain_meta.0.vst_float_to_value(new_value);
             }
@@ -794,23 +1206,350 @@ impl NormalizedParams for Params {
             EParam::Filt2(filt_param) => self.filt2.read_parameter(meta, filt_param),
             EParam::AmpEnv(env_param) => self.amp_env.read_parameter(meta, env_param),
             EParam::ModEnv(env_param) => self.mod_env.read_parameter(meta, env_param),
+            EParam::ModEnv2(env_param) => self.mod_env2.read_parameter(meta, env_param),
+            EParam::ModEnvRoute(route_param) => {
+                self.mod_env_route.read_parameter(meta, route_param)
+            }
+            EParam::ModEnv2Route(route_param) => {
+                self.mod_env2_route.read_parameter(meta, route_param)
+            }
             EParam::Lfo1(lfo_param) => self.lfo1.read_parameter(meta, lfo_param),
             EParam::Lfo2(lfo_param) => self.lfo2.read_parameter(meta, lfo_param),
+            EParam::Drift(drift_param) => self.drift.read_parameter(meta, drift_param),
+            EParam::ModSlot(slot, slot_param) => self
+                .mod_matrix
+                .get(slot)
+                .map_or(0.0, |mod_slot| mod_slot.read_parameter(meta, slot_param)),
+            EParam::ModWheel(wheel_param) => self.mod_wheel.read_parameter(meta, wheel_param),
+            EParam::Input(input_param) => self.input.read_parameter(meta, input_param),
+            EParam::Lfo1Polarity(polarity_param) => meta
+                .mod_polarity_meta
+                .0
+                .value_to_vst_float(self.lfo1.routes[polarity_param.index()].polarity),
+            EParam::Lfo2Polarity(polarity_param) => meta
+                .mod_polarity_meta
+                .0
+                .value_to_vst_float(self.lfo2.routes[polarity_param.index()].polarity),
             EParam::OutputGain => meta.output_gain_meta.0.value_to_vst_float(self.output_gain),
         }
     }
 
-    fn formatted_value(&self, meta: &ParamsMeta, eparam: EParam) -> String {
-        match eparam {
+    fn formatted_value(&self, meta: &ParamsMeta, eparam: EParam) -> Result<String, ParamError> {
+        let formatted = match eparam {
             EParam::Osc1(osc_param) => self.osc1.format_value(meta, osc_param),
             EParam::Osc2(osc_param) => self.osc2.format_value(meta, osc_param),
             EParam::Filt1(filt_param) => self.filt1.format_value(meta, filt_param),
             EParam::Filt2(filt_param) => self.filt2.format_value(meta, filt_param),
             EParam::AmpEnv(env_param) => self.amp_env.format_value(meta, env_param),
             EParam::ModEnv(env_param) => self.mod_env.format_value(meta, env_param),
+            EParam::ModEnv2(env_param) => self.mod_env2.format_value(meta, env_param),
+            EParam::ModEnvRoute(route_param) => self.mod_env_route.format_value(meta, route_param),
+            EParam::ModEnv2Route(route_param) => {
+                self.mod_env2_route.format_value(meta, route_param)
+            }
             EParam::Lfo1(lfo_param) => self.lfo1.format_value(meta, lfo_param),
             EParam::Lfo2(lfo_param) => self.lfo2.format_value(meta, lfo_param),
+            EParam::Drift(drift_param) => self.drift.format_value(meta, drift_param),
+            EParam::ModSlot(slot, slot_param) => self
+                .mod_matrix
+                .get(slot)
+                .ok_or(ParamError::Format(eparam))?
+                .format_value(meta, slot_param),
+            EParam::ModWheel(wheel_param) => self.mod_wheel.format_value(meta, wheel_param),
+            EParam::Input(input_param) => self.input.format_value(meta, input_param),
+            EParam::Lfo1Polarity(polarity_param) => meta
+                .mod_polarity_meta
+                .1
+                .format_value(self.lfo1.routes[polarity_param.index()].polarity),
+            EParam::Lfo2Polarity(polarity_param) => meta
+                .mod_polarity_meta
+                .1
+                .format_value(self.lfo2.routes[polarity_param.index()].polarity),
             EParam::OutputGain => meta.output_gain_meta.1.format_value(self.output_gain),
+        };
+        Ok(formatted)
+    }
+
+    fn formatted_normalized(
+        &self,
+        meta: &ParamsMeta,
+        eparam: EParam,
+        value: f64,
+    ) -> Result<String, ParamError> {
+        let formatted = match eparam {
+            EParam::Osc1(osc_param) => self.osc1.format_normalized(meta, osc_param, value),
+            EParam::Osc2(osc_param) => self.osc2.format_normalized(meta, osc_param, value),
+            EParam::Filt1(filt_param) => self.filt1.format_normalized(meta, filt_param, value),
+            EParam::Filt2(filt_param) => self.filt2.format_normalized(meta, filt_param, value),
+            EParam::AmpEnv(env_param) => self.amp_env.format_normalized(meta, env_param, value),
+            EParam::ModEnv(env_param) => self.mod_env.format_normalized(meta, env_param, value),
+            EParam::ModEnv2(env_param) => self.mod_env2.format_normalized(meta, env_param, value),
+            EParam::ModEnvRoute(route_param) => {
+                self.mod_env_route
+                    .format_normalized(meta, route_param, value)
+            }
+            EParam::ModEnv2Route(route_param) => {
+                self.mod_env2_route
+                    .format_normalized(meta, route_param, value)
+            }
+            EParam::Lfo1(lfo_param) => self.lfo1.format_normalized(meta, lfo_param, value),
+            EParam::Lfo2(lfo_param) => self.lfo2.format_normalized(meta, lfo_param, value),
+            EParam::Drift(drift_param) => self.drift.format_normalized(meta, drift_param, value),
+            EParam::ModSlot(slot, slot_param) => self
+                .mod_matrix
+                .get(slot)
+                .ok_or(ParamError::Format(eparam))?
+                .format_normalized(meta, slot_param, value),
+            EParam::ModWheel(wheel_param) => {
+                self.mod_wheel.format_normalized(meta, wheel_param, value)
+            }
+            EParam::Input(input_param) => self.input.format_normalized(meta, input_param, value),
+            EParam::Lfo1Polarity(_) | EParam::Lfo2Polarity(_) => meta
+                .mod_polarity_meta
+                .1
+                .format_value(meta.mod_polarity_meta.0.vst_float_to_value(value)),
+            EParam::OutputGain => meta
+                .output_gain_meta
+                .1
+                .format_value(meta.output_gain_meta.0.vst_float_to_value(value)),
+        };
+        Ok(formatted)
+    }
+
+    fn parse_value(
+        &self,
+        meta: &ParamsMeta,
+        eparam: EParam,
+        text: &str,
+    ) -> Result<f64, ParamError> {
+        let parsed = match eparam {
+            EParam::Osc1(osc_param) => self.osc1.parse_value(meta, osc_param, text),
+            EParam::Osc2(osc_param) => self.osc2.parse_value(meta, osc_param, text),
+            EParam::Filt1(filt_param) => self.filt1.parse_value(meta, filt_param, text),
+            EParam::Filt2(filt_param) => self.filt2.parse_value(meta, filt_param, text),
+            EParam::AmpEnv(env_param) => self.amp_env.parse_value(meta, env_param, text),
+            EParam::ModEnv(env_param) => self.mod_env.parse_value(meta, env_param, text),
+            EParam::ModEnv2(env_param) => self.mod_env2.parse_value(meta, env_param, text),
+            EParam::ModEnvRoute(route_param) => {
+                self.mod_env_route.parse_value(meta, route_param, text)
+            }
+            EParam::ModEnv2Route(route_param) => {
+                self.mod_env2_route.parse_value(meta, route_param, text)
+            }
+            EParam::Lfo1(lfo_param) => self.lfo1.parse_value(meta, lfo_param, text),
+            EParam::Lfo2(lfo_param) => self.lfo2.parse_value(meta, lfo_param, text),
+            EParam::Drift(drift_param) => self.drift.parse_value(meta, drift_param, text),
+            EParam::ModSlot(slot, slot_param) => self
+                .mod_matrix
+                .get(slot)
+                .and_then(|mod_slot| mod_slot.parse_value(meta, slot_param, text)),
+            EParam::ModWheel(wheel_param) => self.mod_wheel.parse_value(meta, wheel_param, text),
+            EParam::Input(input_param) => self.input.parse_value(meta, input_param, text),
+            EParam::Lfo1Polarity(_) | EParam::Lfo2Polarity(_) => meta
+                .mod_polarity_meta
+                .1
+                .parse_value(text)
+                .map(|polarity| meta.mod_polarity_meta.0.value_to_vst_float(polarity)),
+            EParam::OutputGain => meta
+                .output_gain_meta
+                .1
+                .parse_value(text)
+                .map(|gain| meta.output_gain_meta.0.value_to_vst_float(gain)),
+        };
+        // Values past either end of the range go to that end.
+        parsed
+            .filter(|value| !value.is_nan())
+            .map(|value| value.clamp(0.0, 1.0))
+            .ok_or_else(|| ParamError::Parse {
+                eparam,
+                text: text.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::modulation::matrix::DEFAULT_MOD_DEPTH;
+
+    #[test]
+    fn derived_names_and_values() {
+        let meta = ParamsMeta::new();
+        let mut params = Params::new(44100.0);
+        let index = meta
+            .param_to_index(&EParam::Filt1(EFiltParams::EnvAmt))
+            .unwrap();
+        assert_eq!(params.parameter_name(&meta, index).unwrap(), "Filt1:EnvAmt");
+        assert_eq!(
+            EParam::Filt1(EFiltParams::EnvAmt).as_string(true),
+            "Env Amount"
+        );
+        assert_eq!(
+            EParam::Osc2(EOscParams::FineOffset).as_string(false),
+            "Osc2:Offset (Fine)"
+        );
+
+        let release = EParam::ModEnv2(EAdsrParams::ReleaseCurve);
+        params.write_parameter(&meta, release, 1.0);
+        assert_eq!(params.mod_env2.release_curve, MAX_CURVE);
+        assert_eq!(params.read_parameter(&meta, release), 1.0);
+
+        let depth = EParam::ModSlot(1, EModSlotParams::Depth);
+        params.write_parameter(&meta, depth, 0.0);
+        assert_eq!(params.read_parameter(&meta, depth), 0.0);
+        assert_eq!(params.mod_matrix[0].depth, DEFAULT_MOD_DEPTH);
+    }
+
+    #[test]
+    fn reports_param_errors() {
+        let meta = ParamsMeta::new();
+        let params = Params::new(44100.0);
+        assert_eq!(
+            meta.parameter_index(meta.count()),
+            Err(ParamError::UnknownIndex(meta.count()))
+        );
+        assert!(params.parameter_name(&meta, meta.count()).is_err());
+
+        let gain: EParam = r#"{"Osc1":"Gain"}"#.parse().unwrap();
+        assert_eq!(gain, EParam::Osc1(EOscParams::Gain));
+        assert!(matches!(
+            "Osc1:Gain".parse::<EParam>(),
+            Err(ParamError::UnknownParam(_))
+        ));
+        let slot = format!(r#"{{"ModSlot":[{},"Depth"]}}"#, MOD_MATRIX_SLOTS);
+        assert!(slot.parse::<EParam>().is_err());
+
+        assert_eq!(gain.check_value(0.5), Ok(0.5));
+        assert!(matches!(
+            gain.check_value(1.5),
+            Err(ParamError::OutOfRange { value, .. }) if value == 1.5
+        ));
+        assert!(gain.check_value(f64::NAN).is_err());
+
+        let missing_slot = EParam::ModSlot(MOD_MATRIX_SLOTS, EModSlotParams::Depth);
+        assert_eq!(
+            params.formatted_value(&meta, missing_slot),
+            Err(ParamError::Format(missing_slot))
+        );
+    }
+
+    #[test]
+    fn formats_normalized_values_as_if_written() {
+        let meta = ParamsMeta::new();
+        let mut params = Params::new(44100.0);
+        params.lfo2.sync = true;
+        for eparam in EParam::all() {
+            for value in [0.0, 0.3, 1.0] {
+                let mut written = params.clone();
+                written.write_parameter(&meta, eparam, value);
+                assert_eq!(
+                    params.formatted_normalized(&meta, eparam, value),
+                    written.formatted_value(&meta, eparam),
+                    "{:?} at {}",
+                    eparam,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn indices_round_trip() {
+        assert_eq!(EParam::all().count(), EPARAM_COUNT);
+        for (index, eparam) in EParam::all().enumerate() {
+            assert_eq!(eparam.index(), Some(index), "{:?}", eparam);
+        }
+        assert_eq!(
+            EParam::from_index(0),
+            Some(EParam::Osc1(EOscParams::Enable))
+        );
+        assert_eq!(
+            EParam::from_index(OUTPUT_GAIN_INDEX),
+            Some(EParam::OutputGain)
+        );
+        assert_eq!(
+            EParam::from_index(EPARAM_COUNT - 1),
+            Some(EParam::Lfo2Polarity(ELfoPolarityParams::Polarity2))
+        );
+        assert_eq!(EParam::from_index(EPARAM_COUNT), None);
+        assert_eq!(
+            EParam::ModSlot(MOD_MATRIX_SLOTS, EModSlotParams::Source).index(),
+            None
+        );
+    }
+
+    #[test]
+    fn resets_to_default() {
+        let meta = ParamsMeta::new();
+        let mut params = Params::new(44100.0);
+        let route = EParam::Osc2(EOscParams::FilterRoute);
+        params.write_parameter(&meta, route, 0.0);
+        params.osc1.gain = 0.25;
+        assert_eq!(params.osc2.filter_route, FilterRoute::Filter1);
+
+        params.reset_to_default(&meta, route).unwrap();
+        assert_eq!(params.osc2.filter_route, FilterRoute::Filter2);
+        assert_eq!(params.osc1.gain, 0.25);
+        assert!(params
+            .reset_to_default(
+                &meta,
+                EParam::ModSlot(MOD_MATRIX_SLOTS, EModSlotParams::Depth)
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn typed_values_round_trip() {
+        let meta = ParamsMeta::new();
+        let mut params = Params::new(44100.0);
+        let attack = EParam::AmpEnv(EAdsrParams::Attack);
+        let value = params.parse_value(&meta, attack, "250 ms").unwrap();
+        params.write_parameter(&meta, attack, value);
+        assert_eq!(params.formatted_value(&meta, attack).unwrap(), "250.0 ms");
+        let value = params.parse_value(&meta, attack, "1.5s").unwrap();
+        params.write_parameter(&meta, attack, value);
+        assert_eq!(params.formatted_value(&meta, attack).unwrap(), "1.5 s");
+
+        // Every parameter reads back what it shows.
+        for eparam in EParam::all() {
+            let text = params.formatted_value(&meta, eparam).unwrap();
+            let value = params
+                .parse_value(&meta, eparam, &text)
+                .unwrap_or_else(|err| panic!("{}: {:?}", err, text));
+            let mut reparsed = params.clone();
+            reparsed.write_parameter(&meta, eparam, value);
+            assert_eq!(
+                reparsed.formatted_value(&meta, eparam).unwrap(),
+                text,
+                "{:?}",
+                eparam
+            );
+        }
+
+        assert!(matches!(
+            params.parse_value(&meta, attack, "loud"),
+            Err(ParamError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn options_list_every_enum_value() {
+        let meta = ParamsMeta::new();
+        let params = Params::new(44100.0);
+        let shape = EParam::Osc1(EOscParams::Shape);
+        let options = params.options(&meta, shape);
+        let names: Vec<String> = options.iter().map(|(_, text)| text.clone()).collect();
+        let expected: Vec<String> = WaveShape::enumerate()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(names, expected);
+
+        // Each value selects its option.
+        let mut params = params;
+        for (value, text) in options {
+            params.write_parameter(&meta, shape, value);
+            assert_eq!(params.formatted_value(&meta, shape).unwrap(), text);
         }
     }
 }
@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+
+use crate::params::{
+    EAdsrParams, EFiltParams, EInputParams, ELfoParams, EOscParams, EParam, NormalizedParams,
+    Params, ParamsMeta,
+};
+use crate::util::rng::Rng;
+
+/// Parameters the randomizer leaves alone unless told otherwise.
+pub fn default_locks() -> HashSet<EParam> {
+    [EParam::OutputGain].into_iter().collect()
+}
+
+/// The part of each parameter's (normalized) range worth exploring; outside of it patches tend
+/// to be silent, harsh or out of tune. `None` leaves the parameter as it is.
+fn random_range(eparam: EParam) -> Option<(f64, f64)> {
+    let range = match eparam {
+        // Keep the first oscillator and both filters on, so there's always something to hear.
+        EParam::Osc1(EOscParams::Enable) => (1.0, 1.0),
+        EParam::Osc1(e) | EParam::Osc2(e) => match e {
+            EOscParams::Gain => (0.5, 1.0),
+            // Stay in tune: at most an octave either way, and no semitone shifts.
+            EOscParams::OctaveOffset => (1.0 / 3.0, 2.0 / 3.0),
+            EOscParams::SemitonesOffset => (0.5, 0.5),
+            EOscParams::FineOffset | EOscParams::Detune => (0.4, 0.6),
+            _ => (0.0, 1.0),
+        },
+        EParam::Filt1(e) | EParam::Filt2(e) => match e {
+            EFiltParams::Enable => (1.0, 1.0),
+            EFiltParams::Cutoff => (0.4, 1.0),
+            EFiltParams::Resonance => (0.0, 0.6),
+            _ => (0.0, 1.0),
+        },
+        EParam::AmpEnv(e) => match e {
+            EAdsrParams::Delay | EAdsrParams::Hold => (0.0, 0.0),
+            EAdsrParams::Attack | EAdsrParams::Release => (0.0, 0.3),
+            EAdsrParams::Sustain => (0.5, 1.0),
+            _ => (0.0, 1.0),
+        },
+        // One-shot LFOs mostly sound like nothing is happening.
+        EParam::Lfo1(ELfoParams::OneShot) | EParam::Lfo2(ELfoParams::OneShot) => (0.0, 0.0),
+        // Matrix routings are too easily destructive; leave them to the user.
+        EParam::ModSlot(..) => return None,
+        // Switching to the host's input would replace the oscillators, often with silence.
+        EParam::Input(EInputParams::Source) => return None,
+        _ => (0.0, 1.0),
+    };
+    Some(range)
+}
+
+/// Pick a random (normalized) value for every parameter that isn't locked.
+pub fn random_values(rng: &mut Rng, locks: &HashSet<EParam>) -> Vec<(EParam, f64)> {
+    EParam::all()
+        .filter(|eparam| !locks.contains(eparam))
+        .filter_map(|eparam| {
+            let (low, high) = random_range(eparam)?;
+            let unit = (rng.next_bipolar() + 1.0) / 2.0;
+            Some((eparam, low + (high - low) * unit))
+        })
+        .collect()
+}
+
+impl Params {
+    /// Replace every parameter that isn't locked with a random (but playable) value.
+    pub fn randomize(&mut self, meta: &ParamsMeta, rng: &mut Rng, locks: &HashSet<EParam>) {
+        for (eparam, value) in random_values(rng, locks) {
+            self.write_parameter(meta, eparam, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dsp::osc::SoundSource;
+
+    #[test]
+    fn randomize_respects_locks_and_ranges() {
+        let meta = ParamsMeta::new();
+        let mut params = Params::new(44100.0);
+        params.output_gain = 0.25;
+        params.randomize(&meta, &mut Rng::new(3), &default_locks());
+
+        assert_eq!(params.output_gain, 0.25);
+        assert!(params.osc1.enabled && params.filt1.enable && params.filt2.enable);
+        assert_eq!(params.osc1.semitones_offset, 0);
+        assert!(params.osc2.octave_offset.abs() <= 1);
+        assert_ne!(
+            params.filt1.cutoff_semi,
+            Params::new(44100.0).filt1.cutoff_semi
+        );
+
+        let mut again = Params::new(44100.0);
+        again.randomize(&meta, &mut Rng::new(3), &default_locks());
+        assert_eq!(again.filt1.cutoff_semi, params.filt1.cutoff_semi);
+    }
+
+    #[test]
+    fn randomize_keeps_the_sound_source() {
+        let meta = ParamsMeta::new();
+        for source in [SoundSource::Oscillators, SoundSource::ExternalInput] {
+            for seed in 0..16 {
+                let mut params = Params::new(44100.0);
+                params.input.source = source;
+                params.randomize(&meta, &mut Rng::new(seed), &HashSet::new());
+                assert_eq!(params.input.source, source);
+            }
+        }
+    }
+}
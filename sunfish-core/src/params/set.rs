@@ -0,0 +1,78 @@
+use crate::params::{EParam, EPARAM_COUNT};
+
+const WORDS: usize = EPARAM_COUNT.div_ceil(64);
+
+/// A set of parameters, one bit per parameter in the host's order (see `EParam::index`).
+///
+/// Inserting and removing never allocates, so it's safe to use on the audio thread.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ParamSet {
+    bits: [u64; WORDS],
+}
+
+impl ParamSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `eparam`; parameters without an index (see `EParam::index`) are ignored.
+    pub fn insert(&mut self, eparam: EParam) {
+        if let Some(index) = eparam.index() {
+            self.bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+
+    pub fn remove(&mut self, eparam: EParam) {
+        if let Some(index) = eparam.index() {
+            self.bits[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    pub fn contains(&self, eparam: EParam) -> bool {
+        match eparam.index() {
+            Some(index) => self.bits[index / 64] & (1 << (index % 64)) != 0,
+            None => false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|word| *word == 0)
+    }
+
+    /// The parameters in the set, in the host's order.
+    pub fn iter(&self) -> impl Iterator<Item = EParam> + '_ {
+        self.bits.iter().enumerate().flat_map(|(word_index, word)| {
+            let word = *word;
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .filter_map(move |bit| EParam::from_index(word_index * 64 + bit))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::{EFiltParams, EOscParams};
+
+    #[test]
+    fn inserts_and_removes() {
+        let cutoff = EParam::Filt2(EFiltParams::Cutoff);
+        let gain = EParam::Osc1(EOscParams::Gain);
+        let mut set = ParamSet::new();
+        assert!(set.is_empty());
+        set.insert(cutoff);
+        set.insert(gain);
+        set.insert(EParam::OutputGain);
+        assert!(set.contains(cutoff));
+        assert!(!set.contains(EParam::Filt1(EFiltParams::Cutoff)));
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            vec![gain, cutoff, EParam::OutputGain]
+        );
+        set.remove(gain);
+        set.remove(cutoff);
+        set.remove(EParam::OutputGain);
+        assert!(set.is_empty());
+    }
+}
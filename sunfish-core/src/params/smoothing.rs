@@ -0,0 +1,150 @@
+use crate::dsp::smoothing::SlewRateLimiter;
+use crate::params::{EFiltParams, EOscParams, EParam, NormalizedParams, Params, ParamsMeta};
+
+/// Default time for smoothed parameters to reach a new value.
+pub const DEFAULT_SMOOTHING_TIME_SEC: f64 = 0.02;
+
+// Continuous parameters that are audible as "zipper" noise when they jump.
+const SMOOTHED_PARAMS: [EParam; 13] = [
+    EParam::Osc1(EOscParams::Gain),
+    EParam::Osc1(EOscParams::Pan),
+    EParam::Osc1(EOscParams::FineOffset),
+    EParam::Osc1(EOscParams::Detune),
+    EParam::Osc2(EOscParams::Gain),
+    EParam::Osc2(EOscParams::Pan),
+    EParam::Osc2(EOscParams::FineOffset),
+    EParam::Osc2(EOscParams::Detune),
+    EParam::Filt1(EFiltParams::Cutoff),
+    EParam::Filt1(EFiltParams::Resonance),
+    EParam::Filt2(EFiltParams::Cutoff),
+    EParam::Filt2(EFiltParams::Resonance),
+    EParam::OutputGain,
+];
+
+// Once this close (in normalized terms) to the new value, jump the rest of the way.
+const SETTLE_THRESHOLD: f64 = 1e-5;
+
+#[derive(Clone, Debug)]
+struct Ramp {
+    eparam: EParam,
+    limiter: SlewRateLimiter,
+    // Normalized values: where the ramp is now, and where it's heading.
+    value: f64,
+    target: f64,
+}
+
+/// Ramps continuous parameters towards new values set by the host or GUI, rather than jumping,
+/// to avoid zipper noise.
+#[derive(Clone, Debug)]
+pub struct ParamSmoother {
+    ramps: Vec<Ramp>,
+    // Ramps that have moved during the last `tick`, with their new values.
+    updated: Vec<(EParam, f64)>,
+}
+
+impl ParamSmoother {
+    /// Smooth over `time_sec`; at zero, parameters change instantly.
+    pub fn new(meta: &ParamsMeta, params: &Params, sample_rate: f64, time_sec: f64) -> Self {
+        let ramps = if time_sec > 0.0 {
+            SMOOTHED_PARAMS
+                .iter()
+                .map(|eparam| {
+                    let value = params.read_parameter(meta, *eparam);
+                    Ramp {
+                        eparam: *eparam,
+                        limiter: SlewRateLimiter::new(value, sample_rate, time_sec, 0.0),
+                        value,
+                        target: value,
+                    }
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        ParamSmoother {
+            ramps,
+            updated: Vec::with_capacity(SMOOTHED_PARAMS.len()),
+        }
+    }
+
+    pub fn is_smoothed(&self, eparam: EParam) -> bool {
+        self.ramps.iter().any(|ramp| ramp.eparam == eparam)
+    }
+
+    /// Take new targets from freshly updated `params`, and put back the values the ramps have
+    /// reached so far.
+    pub fn retarget(&mut self, meta: &ParamsMeta, params: &mut Params) {
+        for ramp in self.ramps.iter_mut() {
+            let target = params.read_parameter(meta, ramp.eparam);
+            #[allow(clippy::float_cmp)]
+            if target != ramp.target {
+                ramp.target = target;
+                ramp.limiter.update(target);
+            }
+            params.write_parameter(meta, ramp.eparam, ramp.value);
+        }
+    }
+
+    /// Advance the ramps by `samples`, writing their values into `params`. Returns the
+    /// parameters that changed, with their new (normalized) values.
+    pub fn tick(
+        &mut self,
+        samples: usize,
+        meta: &ParamsMeta,
+        params: &mut Params,
+    ) -> &[(EParam, f64)] {
+        self.updated.clear();
+        for ramp in self.ramps.iter_mut() {
+            #[allow(clippy::float_cmp)]
+            if ramp.value == ramp.target {
+                continue;
+            }
+            for _ in 0..samples {
+                ramp.limiter.step();
+            }
+            ramp.value = ramp.limiter.filtered_value;
+            if (ramp.value - ramp.target).abs() < SETTLE_THRESHOLD {
+                ramp.value = ramp.target;
+            }
+            params.write_parameter(meta, ramp.eparam, ramp.value);
+            self.updated.push((ramp.eparam, ramp.value));
+        }
+        &self.updated
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ramps_to_new_values() {
+        let meta = ParamsMeta::new();
+        let mut params = Params::new(44100.0);
+        let mut smoother = ParamSmoother::new(&meta, &params, 44100.0, 0.01);
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+        assert!(smoother.is_smoothed(cutoff));
+        assert!(!smoother.is_smoothed(EParam::Filt1(EFiltParams::Mode)));
+
+        // The new value is held back, then approached gradually.
+        params.write_parameter(&meta, cutoff, 0.0);
+        smoother.retarget(&meta, &mut params);
+        assert_eq!(params.read_parameter(&meta, cutoff), 1.0);
+        let mut last = 1.0;
+        for _ in 0..4 {
+            let updated = smoother.tick(32, &meta, &mut params).to_vec();
+            assert_eq!(updated.len(), 1);
+            assert!(updated[0].1 < last && updated[0].1 > 0.0);
+            last = updated[0].1;
+        }
+        for _ in 0..100 {
+            smoother.tick(256, &meta, &mut params);
+        }
+        assert_eq!(params.read_parameter(&meta, cutoff), 0.0);
+        assert!(smoother.tick(256, &meta, &mut params).is_empty());
+
+        // Without a smoothing time, nothing is smoothed.
+        let instant = ParamSmoother::new(&meta, &params, 44100.0, 0.0);
+        assert!(!instant.is_smoothed(cutoff));
+    }
+}
@@ -0,0 +1,294 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::midi::mapping::CcMap;
+use crate::params::Params;
+use crate::presets::{Preset, PresetMeta};
+
+/// Version of the saved parameter format; bump whenever `Params` changes shape in a way that
+/// needs a migration (new fields alone don't: they pick up their defaults).
+pub const STATE_VERSION: u32 = 1;
+
+/// Upgrades from each older version to the next: `MIGRATIONS[0]` takes version 1 to 2, and so
+/// on. Each works on the serialized parameters, so it can rename or convert fields.
+const MIGRATIONS: &[fn(&mut Value)] = &[];
+
+/// The full plugin state, as stored by hosts in their projects.
+#[derive(Clone, Debug)]
+pub struct PluginState {
+    pub params: Params,
+    pub cc_map: CcMap,
+    // Whether MIDI program changes switch programs.
+    pub program_change: bool,
+    // The editor's logical width and height, if it's been resized.
+    pub editor_size: Option<(u32, u32)>,
+    // The selected program.
+    pub program: usize,
+    // Every program, when the host saves the whole bank; the selected one's live settings are
+    // `params`.
+    pub programs: Option<Vec<Preset>>,
+}
+
+// On-disk layout of `PluginState`.
+#[derive(Serialize)]
+struct State<'a> {
+    version: u32,
+    params: &'a Params,
+    cc_map: &'a CcMap,
+    program_change: bool,
+    editor_size: Option<(u32, u32)>,
+    program: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    programs: Option<Vec<Program<'a>>>,
+}
+
+// On-disk layout of a program in the bank.
+#[derive(Serialize)]
+struct Program<'a> {
+    meta: &'a PresetMeta,
+    params: &'a Params,
+}
+
+/// Serialize the plugin state into a (versioned) blob.
+pub fn save(state: &PluginState) -> Result<Vec<u8>> {
+    let state = State {
+        version: STATE_VERSION,
+        params: &state.params,
+        cc_map: &state.cc_map,
+        program_change: state.program_change,
+        editor_size: state.editor_size,
+        program: state.program,
+        programs: state.programs.as_ref().map(|programs| {
+            programs
+                .iter()
+                .map(|preset| Program {
+                    meta: &preset.meta,
+                    params: &preset.params,
+                })
+                .collect()
+        }),
+    };
+    Ok(serde_json::to_vec(&state)?)
+}
+
+/// Restore the plugin state from a blob written by `save`, from this or any earlier version.
+pub fn load(data: &[u8]) -> Result<PluginState> {
+    let mut state: Value = serde_json::from_slice(data)?;
+    let version = read_version(&state)?;
+    let params = params_from_value(version, state["params"].take())?;
+    let cc_map = match state.get_mut("cc_map") {
+        Some(cc_map) => serde_json::from_value(cc_map.take())?,
+        None => CcMap::default(),
+    };
+    let program_change = state["program_change"].as_bool().unwrap_or(true);
+    let editor_size = match state.get_mut("editor_size") {
+        Some(editor_size) => serde_json::from_value(editor_size.take())?,
+        None => None,
+    };
+    let program = state["program"].as_u64().unwrap_or(0) as usize;
+    let programs = match state.get_mut("programs") {
+        Some(programs) => Some(programs_from_value(version, programs.take())?),
+        None => None,
+    };
+    Ok(PluginState {
+        params,
+        cc_map,
+        program_change,
+        editor_size,
+        program,
+        programs,
+    })
+}
+
+// The programs of a saved bank, with their parameters brought up to date.
+fn programs_from_value(version: u32, programs: Value) -> Result<Vec<Preset>> {
+    let programs = match programs {
+        Value::Array(programs) => programs,
+        _ => bail!("Programs aren't a list"),
+    };
+    programs
+        .into_iter()
+        .map(|mut program| {
+            let meta = serde_json::from_value(program["meta"].take())?;
+            let params = params_from_value(version, program["params"].take())?;
+            Ok(Preset::new(meta, params))
+        })
+        .collect()
+}
+
+/// Read the `version` field of a saved state or preset.
+pub fn read_version(value: &Value) -> Result<u32> {
+    match value["version"].as_u64() {
+        Some(version) => Ok(version as u32),
+        None => bail!("Missing version"),
+    }
+}
+
+/// Bring parameters saved at `version` up to date. Anything the saved parameters don't mention
+/// (such as parameters added since) keeps its default.
+pub fn params_from_value(version: u32, mut saved: Value) -> Result<Params> {
+    if version == 0 || version > STATE_VERSION {
+        bail!(
+            "Unsupported version {} (expected 1 to {})",
+            version,
+            STATE_VERSION
+        );
+    }
+    for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+        migration(&mut saved);
+    }
+    let mut params = serde_json::to_value(Params::new(0.0))?;
+    merge(&mut params, saved);
+    Ok(serde_json::from_value(params)?)
+}
+
+/// Overlay `saved` onto `defaults`, field by field.
+fn merge(defaults: &mut Value, saved: Value) {
+    match (defaults, saved) {
+        (Value::Object(defaults), Value::Object(saved)) => {
+            // Enums serialize as a single-key object; a different variant replaces the default
+            // outright.
+            let other_variant = saved.len() == 1 && !saved.keys().all(|k| defaults.contains_key(k));
+            if other_variant {
+                *defaults = saved;
+                return;
+            }
+            for (key, value) in saved {
+                match defaults.get_mut(&key) {
+                    Some(default) => merge(default, value),
+                    None => {
+                        defaults.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(defaults), Value::Array(saved)) => {
+            // Fixed-size arrays (routes, slots) may have grown; keep the extra defaults.
+            for (default, value) in defaults.iter_mut().zip(saved) {
+                merge(default, value);
+            }
+        }
+        (defaults, saved) => *defaults = saved,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lfo::{LfoRateSync, Rate};
+    use crate::modulation::polarity::Polarity;
+    use crate::params::{EFiltParams, EParam, NormalizedParams, ParamsMeta};
+
+    #[test]
+    fn round_trips_every_parameter() {
+        let meta = ParamsMeta::new();
+        let mut params = Params::new(44100.0);
+        params.filt1.cutoff_semi = 12.0;
+        params.osc2.gain = 0.25;
+        params.lfo1.routes[1].polarity = Polarity::Negative;
+        params.lfo2.rate = Rate::Synced(LfoRateSync::R1_8);
+
+        let mut cc_map = CcMap::new();
+        cc_map.bind(74, EParam::Filt1(EFiltParams::Cutoff));
+        let state = PluginState {
+            params,
+            cc_map,
+            program_change: false,
+            editor_size: Some((1200, 870)),
+            program: 3,
+            programs: None,
+        };
+
+        let loaded = load(&save(&state).unwrap()).unwrap();
+        assert_eq!(loaded.cc_map, state.cc_map);
+        assert!(!loaded.program_change);
+        assert_eq!(loaded.editor_size, Some((1200, 870)));
+        assert_eq!(loaded.program, 3);
+        assert!(loaded.programs.is_none());
+        let (loaded, params) = (loaded.params, state.params);
+        for eparam in EParam::all() {
+            assert_eq!(
+                loaded.read_parameter(&meta, eparam),
+                params.read_parameter(&meta, eparam),
+                "{:?}",
+                eparam
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_programs() {
+        let mut params = Params::new(44100.0);
+        params.osc1.gain = 0.25;
+        let meta = PresetMeta {
+            name: "Quiet".to_string(),
+            ..PresetMeta::default()
+        };
+        let state = PluginState {
+            params: Params::new(44100.0),
+            cc_map: CcMap::new(),
+            program_change: true,
+            editor_size: None,
+            program: 1,
+            programs: Some(vec![
+                Preset::new(PresetMeta::default(), Params::new(44100.0)),
+                Preset::new(meta.clone(), params),
+            ]),
+        };
+        let programs = load(&save(&state).unwrap()).unwrap().programs.unwrap();
+        assert_eq!(programs.len(), 2);
+        assert_eq!(programs[1].meta, meta);
+        assert_eq!(programs[1].params.osc1.gain, 0.25);
+    }
+
+    #[test]
+    fn missing_fields_keep_defaults() {
+        let mut params = Params::new(44100.0);
+        params.osc1.gain = 0.5;
+        params.lfo1.rate = Rate::Hz(2.0);
+        let mut saved = serde_json::to_value(&params).unwrap();
+        // As if saved before these existed.
+        saved["osc1"].as_object_mut().unwrap().remove("pan");
+        saved.as_object_mut().unwrap().remove("drift");
+
+        let loaded = params_from_value(STATE_VERSION, saved).unwrap();
+        assert_eq!(loaded.osc1.gain, 0.5);
+        assert_eq!(loaded.osc1.pan, 0.0);
+        assert!(matches!(loaded.lfo1.rate, Rate::Hz(rate) if rate == 2.0));
+    }
+
+    #[test]
+    fn program_change_defaults_to_enabled() {
+        let data = format!(
+            "{{\"version\":{},\"params\":{}}}",
+            STATE_VERSION,
+            serde_json::to_string(&Params::new(44100.0)).unwrap()
+        );
+        let loaded = load(data.as_bytes()).unwrap();
+        assert!(loaded.program_change);
+        assert_eq!(loaded.program, 0);
+        assert!(loaded.programs.is_none());
+    }
+
+    #[test]
+    fn editor_size_is_optional() {
+        let data = format!(
+            "{{\"version\":{},\"params\":{}}}",
+            STATE_VERSION,
+            serde_json::to_string(&Params::new(44100.0)).unwrap()
+        );
+        assert_eq!(load(data.as_bytes()).unwrap().editor_size, None);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let data = format!(
+            "{{\"version\":{},\"params\":{}}}",
+            STATE_VERSION + 1,
+            serde_json::to_string(&Params::new(44100.0)).unwrap()
+        );
+        assert!(load(data.as_bytes()).is_err());
+        assert_eq!(MIGRATIONS.len(), STATE_VERSION as usize - 1);
+    }
+}
@@ -1,51 +1,265 @@
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use copy_from::CopyFrom;
-use crossbeam::atomic::AtomicCell;
 
-use crate::params::{EParam, NormalizedParams, Params, ParamsMeta};
+use crate::midi::mapping::{CcMap, MAX_CC_TARGETS};
+use crate::params::compare::{Compare, CompareSlot};
+use crate::params::error::ParamError;
+use crate::params::set::ParamSet;
+use crate::params::{EParam, NormalizedParams, Params, ParamsMeta, EPARAM_COUNT};
+use crate::util::spsc::{self, Consumer, Producer};
+use crate::util::triple_buffer::{self, triple_buffer};
 
 pub type EnqueuedParams = HashMap<EParam, f64>;
 
+/// How many parameter writes a queue holds: enough for a few full preset loads.
+const QUEUE_CAPACITY: usize = 4 * EPARAM_COUNT;
+
+// Until the host reports its tempo.
+const DEFAULT_TEMPO_BPM: f64 = 120.0;
+
+/// The start or end of an edit in the GUI (e.g. a knob drag), so the host can record the
+/// values in between as one automation pass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    Begin(EParam),
+    End(EParam),
+}
+
 #[derive(Clone)]
 pub struct Subscriber {
-    // Parameters that have changed.
+    // Parameters that have changed since the subscriber last took them.
     pub changes: Arc<Mutex<EnqueuedParams>>,
-    // Last acknowledged epoch.
-    last_epoch: Arc<AtomicU32>,
 }
 
+/// Routes parameter changes, from whichever handle made them, to one party that mirrors the
+/// parameters: the host (as automation) or the GUI (as widget values). Each change reaches it
+/// once, coalesced to the parameter's latest value.
+pub struct ParamDeltaRouter {
+    subscriber: Subscriber,
+    // Changes taken from the subscriber, passed on outside its lock. Kept to avoid allocating.
+    flushing: Vec<(EParam, f64)>,
+}
+
+impl ParamDeltaRouter {
+    pub fn new(subscriber: Subscriber) -> Self {
+        Self {
+            subscriber,
+            flushing: Vec::with_capacity(EPARAM_COUNT),
+        }
+    }
+
+    /// Whether there are changes for the next `flush`.
+    pub fn has_pending(&self) -> bool {
+        match self.subscriber.changes.try_lock() {
+            Ok(changes) => !changes.is_empty(),
+            Err(_) => false,
+        }
+    }
+
+    /// Hand every change since the last flush to `f`. Doesn't wait: if the changes are being
+    /// added to right now, they stay pending until the next call. `f` may write parameters; its
+    /// writes come back on a later flush.
+    pub fn flush<F>(&mut self, mut f: F)
+    where
+        F: FnMut(EParam, f64),
+    {
+        if let Ok(mut changes) = self.subscriber.changes.try_lock() {
+            self.flushing.extend(changes.drain());
+        }
+        for (eparam, value) in self.flushing.drain(..) {
+            f(eparam, value);
+        }
+    }
+}
+
+/// A handle on the shared parameters, one per thread that reads or writes them.
+///
+/// Writes go into the handle's own lock-free queue (its "outbox") and are applied to the
+/// authoritative parameters by whichever handle next holds the hub: right away if it's free, or
+/// else on the next `refresh_maybe`, `refresh` or `clone_inner`. The core reads through a
+/// `MailboxReceiver`, which never blocks.
 pub struct Synchronizer {
     pub meta: ParamsMeta,
 
-    /// Authoritative source of parameters, along with epoch number.
-    pub params: Arc<Mutex<(Params, u32)>>,
-    /// Local copy in case we cannot get the mutex lock. Of course, this is *not* consistent.
+    /// Authoritative parameters, and the queues around them.
+    hub: Arc<Mutex<Hub>>,
+    /// This handle's view of the parameters, including its own writes.
     params_copy: Params,
+    /// This handle's writes, waiting for the hub.
+    outbox: Producer<(EParam, f64)>,
+    /// Writes that didn't fit in the outbox, with their values in `params_copy`; they follow
+    /// once the hub catches up.
+    overflow: ParamSet,
+
+    /// A/B compare slots, shared so the GUI and plugin agree on which is active.
+    compare: Arc<Mutex<Compare>>,
+
+    /// MIDI controller assignments, shared so the GUI can start "MIDI learn".
+    cc_map: Arc<Mutex<CcMap>>,
+
+    /// The controller (in bits 8 to 15) and value (the low byte) received while "MIDI learn"
+    /// was waiting, with bit 16 set, or 0; the binding is left to `finish_learn`.
+    learned_cc: Arc<AtomicU32>,
+
+    /// Edit gestures from the GUI, waiting to be passed on to the host.
+    gestures: Arc<Mutex<Vec<Gesture>>>,
+
+    /// Set while processing is stopped after a panic, so the GUI can say so.
+    faulted: Arc<AtomicBool>,
+
+    /// The host's tempo in BPM, as bits of an `f64`, so the GUI can preview synced rates.
+    tempo_bpm: Arc<AtomicU64>,
+
+    /// Each parameter's modulated value as bits of an `f64`, or NaN where it isn't modulated,
+    /// so the GUI can show modulation depth.
+    modulated: Arc<[AtomicU64]>,
+
+    /// How many voices are playing, so the GUI can show it.
+    active_voices: Arc<AtomicUsize>,
 
-    /// Parameters that need to be written to the authoritative, useful for when we cannot
-    /// get the mutex lock.
-    on_deck: EnqueuedParams,
-
-    /// Store mailboxes & subscribers in the same mutex:
-    /// - Mailboxes get a copy of the parameters, along with changes
-    ///   included in that copy.
-    /// - Subscribers only get the changes. They are responsible for
-    ///   synchronizing that information.
-    #[allow(clippy::type_complexity)]
-    mailboxes_and_subs: Arc<Mutex<(Vec<MailboxWriter<(Params, u32)>>, Vec<Subscriber>)>>,
+    /// The editor's logical size as last resized, width in the high 32 bits and height in the
+    /// low ones, or 0 if it hasn't been; saved with the plugin state.
+    editor_size: Arc<AtomicU64>,
+}
+
+struct Hub {
+    params: Params,
+    epoch: u32,
+    /// Every handle's outbox; each handle is the single producer of its own.
+    outboxes: Vec<Consumer<(EParam, f64)>>,
+    /// Outboxes from this index on belong to dropped handles and have been emptied; they're
+    /// left for `remove_closed` to free.
+    open_outboxes: usize,
+    /// Subscribers only get the changes. They are responsible for synchronizing that
+    /// information.
+    subscribers: Vec<SubscriberSlot>,
+    /// Mailboxes get the changes, and a snapshot of the parameters including them.
+    mailboxes: Vec<MailboxWriter>,
+    /// Writes taken from the outboxes, kept to avoid allocating.
+    updates: Vec<(EParam, f64)>,
+}
+
+impl Hub {
+    fn outbox(&mut self) -> Producer<(EParam, f64)> {
+        let (producer, consumer) = spsc::channel(QUEUE_CAPACITY);
+        self.outboxes.push(consumer);
+        let last = self.outboxes.len() - 1;
+        self.outboxes.swap(self.open_outboxes, last);
+        self.open_outboxes += 1;
+        producer
+    }
+
+    /// Apply every handle's queued writes, and pass them on as a single epoch. This runs on the
+    /// audio thread, so what dropped handles and mailboxes leave behind isn't freed here.
+    fn drain(&mut self, meta: &ParamsMeta) {
+        self.updates.clear();
+        let mut index = 0;
+        while index < self.open_outboxes {
+            let outbox = &mut self.outboxes[index];
+            // Check first, so nothing written before the handle was dropped is missed.
+            let closed = outbox.is_closed();
+            while let Some(update) = outbox.pop() {
+                self.updates.push(update);
+            }
+            if closed {
+                self.open_outboxes -= 1;
+                self.outboxes.swap(index, self.open_outboxes);
+            } else {
+                index += 1;
+            }
+        }
+        // Subscribers may have changes left from before, so they're tried even without updates.
+        for subscriber in self.subscribers.iter_mut() {
+            subscriber.pass_on(&self.updates);
+        }
+        if self.updates.is_empty() {
+            return;
+        }
+
+        self.epoch += 1;
+        for (eparam, value) in self.updates.iter() {
+            self.params.write_parameter(meta, *eparam, *value);
+        }
+        for mailbox in self.mailboxes.iter_mut() {
+            if !mailbox.changes_closed() {
+                mailbox.update(&self.params, &self.updates);
+            }
+        }
+    }
+
+    /// Free the outboxes of dropped handles and the writers of dropped mailboxes. Only called
+    /// where the hub is locked outright, which the audio thread never does.
+    fn remove_closed(&mut self) {
+        self.outboxes.truncate(self.open_outboxes);
+        self.mailboxes.retain(|mailbox| !mailbox.changes_closed());
+    }
+}
+
+// A subscriber as the hub sees it, with the changes it couldn't hand over yet.
+struct SubscriberSlot {
+    subscriber: Subscriber,
+    // The latest value of each pending parameter, by index.
+    values: Vec<f64>,
+    pending: ParamSet,
+}
+
+impl SubscriberSlot {
+    fn new(subscriber: Subscriber) -> Self {
+        Self {
+            subscriber,
+            values: vec![0.0; EPARAM_COUNT],
+            pending: ParamSet::new(),
+        }
+    }
+
+    // Hand `updates`, and whatever is pending, to the subscriber. Called from the audio thread,
+    // so if the subscriber is taking its changes right now they stay pending until next time.
+    fn pass_on(&mut self, updates: &[(EParam, f64)]) {
+        for (eparam, value) in updates {
+            if let Some(index) = eparam.index() {
+                self.values[index] = *value;
+                self.pending.insert(*eparam);
+            }
+        }
+        if self.pending.is_empty() {
+            return;
+        }
+        if let Ok(mut changes) = self.subscriber.changes.try_lock() {
+            for eparam in self.pending.iter() {
+                if let Some(index) = eparam.index() {
+                    changes.insert(eparam, self.values[index]);
+                }
+            }
+            self.pending = ParamSet::new();
+        }
+    }
 }
 
 impl std::clone::Clone for Synchronizer {
     fn clone(&self) -> Self {
+        let outbox = {
+            let mut hub = self.hub.lock().expect("Access parameters");
+            hub.remove_closed();
+            hub.outbox()
+        };
         Self {
             meta: self.meta.clone(),
-            params: Arc::clone(&self.params),
+            hub: Arc::clone(&self.hub),
             params_copy: self.params_copy.clone(),
-            on_deck: HashMap::new(),
-            mailboxes_and_subs: Arc::clone(&self.mailboxes_and_subs),
+            outbox,
+            overflow: ParamSet::new(),
+            compare: Arc::clone(&self.compare),
+            cc_map: Arc::clone(&self.cc_map),
+            learned_cc: Arc::clone(&self.learned_cc),
+            gestures: Arc::clone(&self.gestures),
+            faulted: Arc::clone(&self.faulted),
+            tempo_bpm: Arc::clone(&self.tempo_bpm),
+            modulated: Arc::clone(&self.modulated),
+            active_voices: Arc::clone(&self.active_voices),
+            editor_size: Arc::clone(&self.editor_size),
         }
     }
 }
@@ -53,121 +267,356 @@ impl std::clone::Clone for Synchronizer {
 impl Synchronizer {
     pub fn new(meta: ParamsMeta, params: Params) -> Self {
         let params_copy = params.clone();
+        let mut hub = Hub {
+            params,
+            epoch: 0,
+            outboxes: vec![],
+            open_outboxes: 0,
+            subscribers: vec![],
+            mailboxes: vec![],
+            updates: Vec::with_capacity(QUEUE_CAPACITY),
+        };
+        let outbox = hub.outbox();
         Synchronizer {
             meta,
-            params: Arc::new(Mutex::new((params, 0))),
+            hub: Arc::new(Mutex::new(hub)),
             params_copy,
-            mailboxes_and_subs: Arc::new(Mutex::new((vec![], vec![]))),
-            on_deck: HashMap::new(),
+            outbox,
+            overflow: ParamSet::new(),
+            compare: Arc::new(Mutex::new(Compare::new())),
+            cc_map: Arc::new(Mutex::new(CcMap::new())),
+            learned_cc: Arc::new(AtomicU32::new(0)),
+            gestures: Arc::new(Mutex::new(vec![])),
+            faulted: Arc::new(AtomicBool::new(false)),
+            tempo_bpm: Arc::new(AtomicU64::new(DEFAULT_TEMPO_BPM.to_bits())),
+            modulated: (0..EPARAM_COUNT)
+                .map(|_| AtomicU64::new(f64::NAN.to_bits()))
+                .collect(),
+            active_voices: Arc::new(AtomicUsize::new(0)),
+            editor_size: Arc::new(AtomicU64::new(0)),
         }
     }
 
     pub fn subscriber(&mut self) -> Subscriber {
+        // Room for every parameter, so passing changes on never allocates.
         let subscriber = Subscriber {
-            changes: Arc::new(Mutex::new(HashMap::new())),
-            last_epoch: Arc::new(AtomicU32::new(0)),
+            changes: Arc::new(Mutex::new(HashMap::with_capacity(EPARAM_COUNT))),
         };
-        let (_mailboxes, subscribers) = &mut (*self
-            .mailboxes_and_subs
+        self.hub
             .lock()
-            .expect("Access mailboxes and subscribers"));
-        subscribers.push(Subscriber {
-            changes: Arc::clone(&subscriber.changes),
-            last_epoch: Arc::clone(&subscriber.last_epoch),
-        });
-        // TODO: Return subscriber client, tie locking to other mutex.
+            .expect("Access parameters")
+            .subscribers
+            .push(SubscriberSlot::new(subscriber.clone()));
         subscriber
     }
 
+    /// A subscriber, wrapped to pass its changes on.
+    pub fn delta_router(&mut self) -> ParamDeltaRouter {
+        ParamDeltaRouter::new(self.subscriber())
+    }
+
     pub fn mailbox(&mut self) -> MailboxReceiver {
-        let last_epoch = Arc::new(AtomicU32::new(0));
-        let changes = Arc::new(Mutex::new(HashMap::new()));
-        let (mailbox_writer, mailbox_reader) = mailbox();
-        let reader = MailboxReceiver {
-            reader: mailbox_reader,
-            subscriber: Subscriber {
-                changes,
-                last_epoch: Arc::clone(&last_epoch),
-            },
-        };
-        let (mailboxes, subscribers) = &mut (*self
-            .mailboxes_and_subs
-            .lock()
-            .expect("Access mailboxes and subscribers"));
-        mailboxes.push(mailbox_writer);
-        subscribers.push(reader.subscriber.clone());
-        reader
+        let mut hub = self.hub.lock().expect("Access parameters");
+        hub.remove_closed();
+        let (changes_writer, changes) = spsc::channel(QUEUE_CAPACITY);
+        let (snapshot_writer, snapshot) = triple_buffer(&hub.params);
+        let resync = Arc::new(AtomicBool::new(false));
+        hub.mailboxes.push(MailboxWriter {
+            changes: changes_writer,
+            snapshot: snapshot_writer,
+            resync: Arc::clone(&resync),
+        });
+        MailboxReceiver {
+            meta: self.meta.clone(),
+            changes,
+            snapshot,
+            resync,
+            pending: Vec::with_capacity(QUEUE_CAPACITY),
+        }
     }
 
     pub fn write_parameter(&mut self, eparam: EParam, value: f64) {
-        if let Ok(mut guard) = self.params.try_lock() {
-            let (shared_params, epoch) = &mut *guard;
-
-            let (mailboxes, subscribers) = &mut (*self
-                .mailboxes_and_subs
-                .lock()
-                .expect("Access mailboxes and subscribers"));
-            for subscriber in (*subscribers).iter_mut() {
-                if let Ok(mut guard) = subscriber.changes.try_lock() {
-                    let changes = &mut (*guard);
-                    // Reset the shared queue when we have the lock, before
-                    // we add any new updates to it.
-                    if subscriber.last_epoch.load(Ordering::Acquire) >= *epoch {
-                        changes.clear();
-                    }
-                    for (enq_param, enq_value) in &self.on_deck {
-                        changes.insert(*enq_param, *enq_value);
-                    }
-                    changes.insert(eparam, value);
-                }
+        self.write_parameters(std::iter::once((eparam, value)));
+    }
+
+    /// Write several parameters at once: readers see them all change together, in a single
+    /// epoch, unless another thread is passing on writes at the same time.
+    pub fn write_parameters<I>(&mut self, updates: I)
+    where
+        I: IntoIterator<Item = (EParam, f64)>,
+    {
+        for (eparam, value) in updates {
+            self.queue(eparam, value);
+        }
+        self.try_flush();
+    }
+
+    /// Write every parameter, e.g. to load a preset. Doesn't allocate, so the audio thread
+    /// can switch programs.
+    pub fn write_params(&mut self, params: &Params) {
+        for eparam in EParam::all() {
+            let value = params.read_parameter(&self.meta, eparam);
+            self.queue(eparam, value);
+        }
+        self.try_flush();
+    }
+
+    /// Reset every parameter to its default (the "init patch").
+    pub fn reset_to_defaults(&mut self) {
+        for eparam in EParam::all() {
+            if let Ok(value) = self.meta.default_value(eparam) {
+                self.queue(eparam, value);
             }
-            *epoch += 1;
-            // Apply all "on deck" changes.
-            for (enq_param, enq_value) in self.on_deck.drain() {
-                shared_params.write_parameter(&self.meta, enq_param, enq_value);
+        }
+        self.try_flush();
+    }
+
+    // Put a write in the outbox, to be passed on with the others. If the hub is far behind,
+    // the write waits in `overflow` rather than blocking (or being dropped); once something
+    // has, later writes follow it there to keep their order.
+    fn queue(&mut self, eparam: EParam, value: f64) {
+        self.params_copy.write_parameter(&self.meta, eparam, value);
+        if !self.overflow.is_empty() || self.outbox.push((eparam, value)).is_err() {
+            self.overflow.insert(eparam);
+        }
+    }
+
+    // Pass on queued writes from every handle, then this handle's overflow now that there's
+    // room for it.
+    fn pass_on(&mut self, hub: &mut Hub) {
+        hub.drain(&self.meta);
+        if self.overflow.is_empty() {
+            return;
+        }
+        let overflow = self.overflow;
+        for eparam in overflow.iter() {
+            let value = self.params_copy.read_parameter(&self.meta, eparam);
+            if self.outbox.push((eparam, value)).is_err() {
+                break;
             }
-            // Finally write the parameter we intend to write.
-            shared_params.write_parameter(&self.meta, eparam, value);
-            // Since we have access to the parameters, we take the opportunity to refresh our view
-            // of parameters.
-            self.params_copy.copy_from(shared_params);
-            for mailbox in mailboxes {
-                let next = guard.clone();
-                mailbox.update(next);
+            self.overflow.remove(eparam);
+        }
+        hub.drain(&self.meta);
+    }
+
+    /// Reset a single parameter to its default, e.g. on double-click.
+    pub fn reset_to_default(&mut self, eparam: EParam) -> Result<(), ParamError> {
+        let value = self.meta.default_value(eparam)?;
+        self.write_parameter(eparam, value);
+        Ok(())
+    }
+
+    /// Flip between the A and B compare slots.
+    pub fn toggle_compare(&mut self) {
+        let live = self
+            .clone_inner()
+            .unwrap_or_else(|| self.params_copy.clone());
+        let next = self
+            .compare
+            .lock()
+            .expect("Access compare slots")
+            .toggle(live);
+        self.write_params(&next);
+    }
+
+    /// Copy the A compare slot over B.
+    pub fn copy_compare_a_to_b(&mut self) {
+        let live = self
+            .clone_inner()
+            .unwrap_or_else(|| self.params_copy.clone());
+        let next = self
+            .compare
+            .lock()
+            .expect("Access compare slots")
+            .copy_a_to_b(&live);
+        if let Some(next) = next {
+            self.write_params(&next);
+        }
+    }
+
+    pub fn compare_slot(&self) -> CompareSlot {
+        self.compare.lock().expect("Access compare slots").active()
+    }
+
+    /// Apply a MIDI controller change to the parameters mapped to it.
+    pub fn handle_cc(&mut self, cc: u8, value: u8) {
+        // Called from the audio thread, so don't wait on the GUI or allocate.
+        let mut updates = [(EParam::OutputGain, 0.0); MAX_CC_TARGETS];
+        let count = match self.cc_map.try_lock() {
+            Ok(cc_map) => {
+                if cc_map.learning().is_some() {
+                    let learned = 1 << 16 | (cc as u32) << 8 | value as u32;
+                    self.learned_cc.store(learned, Ordering::Relaxed);
+                }
+                cc_map.handle(cc, value, &mut updates).len()
             }
-        } else {
-            self.on_deck.insert(eparam, value);
-            self.params_copy.write_parameter(&self.meta, eparam, value);
+            Err(_) => return,
+        };
+        if count > 0 {
+            self.write_parameters(updates[..count].iter().copied());
         }
     }
 
-    pub fn refresh_maybe(&mut self) {
-        if let Ok(guard) = self.params.try_lock() {
-            let (shared_params, _shared_queue) = &*guard;
-            // Since we have access to the parameters, we take the opportunity to refresh our view
-            // of parameters.
-            self.params_copy.copy_from(shared_params);
+    /// Bind the next MIDI controller received to `eparam`.
+    pub fn learn_cc(&self, eparam: EParam) {
+        self.cc_map.lock().expect("Access CC map").learn(eparam);
+    }
+
+    /// Bind the controller received during "MIDI learn", if any, and apply its value. Called
+    /// from the GUI, since binding allocates.
+    pub fn finish_learn(&mut self) {
+        let learned = self.learned_cc.swap(0, Ordering::Relaxed);
+        if learned == 0 {
+            return;
         }
+        let (cc, value) = ((learned >> 8) as u8, learned as u8);
+        let mut updates = [(EParam::OutputGain, 0.0); MAX_CC_TARGETS];
+        let count = {
+            let mut cc_map = self.cc_map.lock().expect("Access CC map");
+            if cc_map.finish_learn(cc).is_none() {
+                return;
+            }
+            cc_map.handle(cc, value, &mut updates).len()
+        };
+        self.write_parameters(updates[..count].iter().copied());
     }
 
-    pub fn refresh(&mut self) {
-        if let Ok(guard) = self.params.lock() {
-            let (shared_params, _shared_queue) = &*guard;
-            // Since we have access to the parameters, we take the opportunity to refresh our view
-            // of parameters.
-            self.params_copy.copy_from(shared_params);
+    pub fn cc_map(&self) -> CcMap {
+        self.cc_map.lock().expect("Access CC map").clone()
+    }
+
+    pub fn set_cc_map(&self, cc_map: CcMap) {
+        *self.cc_map.lock().expect("Access CC map") = cc_map;
+    }
+
+    /// The GUI started editing `eparam`; values written until `end_edit` belong together.
+    pub fn begin_edit(&self, eparam: EParam) {
+        self.gestures
+            .lock()
+            .expect("Access gestures")
+            .push(Gesture::Begin(eparam));
+    }
+
+    pub fn end_edit(&self, eparam: EParam) {
+        self.gestures
+            .lock()
+            .expect("Access gestures")
+            .push(Gesture::End(eparam));
+    }
+
+    /// Hand queued gestures, oldest first, to `f`. Called from the audio thread, so gestures
+    /// stay queued if the GUI holds the lock.
+    pub fn drain_gestures<F>(&self, mut f: F)
+    where
+        F: FnMut(Gesture),
+    {
+        if let Ok(mut gestures) = self.gestures.try_lock() {
+            gestures.drain(..).for_each(&mut f);
+        }
+    }
+
+    pub fn set_faulted(&self, faulted: bool) {
+        self.faulted.store(faulted, Ordering::Relaxed);
+    }
+
+    pub fn is_faulted(&self) -> bool {
+        self.faulted.load(Ordering::Relaxed)
+    }
+
+    pub fn set_tempo_bpm(&self, tempo_bpm: f64) {
+        self.tempo_bpm.store(tempo_bpm.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The tempo last reported by the host.
+    pub fn tempo_bpm(&self) -> f64 {
+        f64::from_bits(self.tempo_bpm.load(Ordering::Relaxed))
+    }
+
+    pub fn set_active_voices(&self, active_voices: usize) {
+        self.active_voices.store(active_voices, Ordering::Relaxed);
+    }
+
+    /// How many voices the core was playing as of its last rendered buffer.
+    pub fn active_voices(&self) -> usize {
+        self.active_voices.load(Ordering::Relaxed)
+    }
+
+    pub fn set_editor_size(&self, (width, height): (u32, u32)) {
+        let bits = (width as u64) << 32 | height as u64;
+        self.editor_size.store(bits, Ordering::Relaxed);
+    }
+
+    /// The editor's logical width and height, if it's been resized (or restored) since the
+    /// plugin started.
+    pub fn editor_size(&self) -> Option<(u32, u32)> {
+        let bits = self.editor_size.load(Ordering::Relaxed);
+        (bits != 0).then_some(((bits >> 32) as u32, bits as u32))
+    }
+
+    /// Publish a parameter's modulated (normalized) value, or `None` once it's no longer
+    /// modulated.
+    pub fn set_modulated_value(&self, eparam: EParam, value: Option<f64>) {
+        if let Some(index) = eparam.index() {
+            let bits = value.unwrap_or(f64::NAN).to_bits();
+            self.modulated[index].store(bits, Ordering::Relaxed);
         }
     }
 
+    /// The modulated (normalized) value last published by the core, if the parameter is
+    /// modulated.
+    pub fn modulated_value(&self, eparam: EParam) -> Option<f64> {
+        let value = f64::from_bits(self.modulated[eparam.index()?].load(Ordering::Relaxed));
+        (!value.is_nan()).then_some(value)
+    }
+
+    /// Pass on queued writes from every handle, unless another thread holds the hub (it'll pass
+    /// them on itself, or leave them for the next call).
+    pub fn try_flush(&mut self) {
+        // A reference of our own, so holding the hub doesn't borrow `self`.
+        let shared = Arc::clone(&self.hub);
+        let Ok(mut hub) = shared.try_lock() else {
+            return;
+        };
+        self.pass_on(&mut hub);
+    }
+
+    pub fn refresh_maybe(&mut self) {
+        let shared = Arc::clone(&self.hub);
+        let Ok(mut hub) = shared.try_lock() else {
+            return;
+        };
+        self.pass_on(&mut hub);
+        // Since we have access to the parameters, we take the opportunity to refresh our view
+        // of parameters.
+        self.params_copy.copy_from(&hub.params);
+    }
+
+    pub fn refresh(&mut self) {
+        let shared = Arc::clone(&self.hub);
+        let Ok(mut hub) = shared.lock() else {
+            return;
+        };
+        self.pass_on(&mut hub);
+        hub.remove_closed();
+        self.params_copy.copy_from(&hub.params);
+    }
+
     pub fn read_parameter(&mut self, eparam: EParam) -> f64 {
         self.params_copy.read_parameter(&self.meta, eparam)
     }
 
     pub fn clone_inner(&self) -> Option<Params> {
-        match self.params.lock() {
-            Ok(guard) => {
-                let (shared_params, _) = &*guard;
-                Some(shared_params.clone())
+        match self.hub.lock() {
+            Ok(mut hub) => {
+                hub.drain(&self.meta);
+                hub.remove_closed();
+                let mut params = hub.params.clone();
+                // Writes still in the overflow are newer than the hub's values.
+                for eparam in self.overflow.iter() {
+                    let value = self.params_copy.read_parameter(&self.meta, eparam);
+                    params.write_parameter(&self.meta, eparam, value);
+                }
+                Some(params)
             }
             Err(err) => {
                 log::error!(
@@ -179,80 +628,324 @@ impl Synchronizer {
         }
     }
 
-    pub fn formatted_value(&self, eparam: EParam) -> String {
+    pub fn formatted_value(&self, eparam: EParam) -> Result<String, ParamError> {
         self.params_copy.formatted_value(&self.meta, eparam)
     }
+
+    pub fn parse_value(&self, eparam: EParam, text: &str) -> Result<f64, ParamError> {
+        self.params_copy.parse_value(&self.meta, eparam, text)
+    }
+
+    /// This handle's view of the parameters, as of its last refresh.
+    pub fn params(&self) -> &Params {
+        &self.params_copy
+    }
+}
+
+/******************************************************************************
+ ** Mailbox                                                                  **
+ ******************************************************************************/
+
+/// The hub's end of a mailbox.
+struct MailboxWriter {
+    changes: Producer<(EParam, f64)>,
+    snapshot: triple_buffer::Writer<Params>,
+    // Set when `changes` overflowed, so the receiver knows some are missing.
+    resync: Arc<AtomicBool>,
 }
 
-/// Exclusive parameter "reader"; this is designed for the core render
-/// loop, used to query the authoritative source of parameters, copy it into
-/// its local parameters, then flag to the synchronizer that it's up-to-date.
+impl MailboxWriter {
+    fn update(&mut self, params: &Params, updates: &[(EParam, f64)]) {
+        // Changes go first, so the receiver never sees a snapshot without them.
+        for update in updates {
+            if self.changes.push(*update).is_err() {
+                self.resync.store(true, Ordering::Release);
+                break;
+            }
+        }
+        self.snapshot.write(|snapshot| snapshot.copy_from(params));
+    }
+
+    fn changes_closed(&self) -> bool {
+        // Only the receiver shares `resync`.
+        Arc::strong_count(&self.resync) == 1
+    }
+}
+
+/// Exclusive parameter "reader"; this is designed for the core render loop, which takes the
+/// latest snapshot of the parameters along with the changes that led to it. It never blocks.
 pub struct MailboxReceiver {
-    reader: MailboxReader<(Params, u32)>,
-    subscriber: Subscriber,
+    meta: ParamsMeta,
+    changes: Consumer<(EParam, f64)>,
+    snapshot: triple_buffer::Reader<Params>,
+    resync: Arc<AtomicBool>,
+    // Changes taken from the queue, kept to avoid allocating.
+    pending: Vec<(EParam, f64)>,
 }
 
 impl MailboxReceiver {
-    pub fn check_and_update<F>(&self, last_epoch_recorded: &mut u32, update: F)
+    /// If the parameters changed since the last call, hand `update` the latest snapshot and the
+    /// changes since then, oldest first.
+    pub fn check_and_update<F>(&mut self, update: F)
     where
-        F: FnOnce(Params, &EnqueuedParams),
+        F: FnOnce(&Params, &[(EParam, f64)]),
     {
-        if let Some((params, epoch)) = self.reader.get_updated() {
-            if epoch > *last_epoch_recorded {
-                if let Ok(guard) = self.subscriber.changes.lock() {
-                    let changes = &*guard;
-                    update(params, changes);
-                }
-            }
-            self.subscriber.last_epoch.store(epoch, Ordering::Release);
-            *last_epoch_recorded = epoch;
+        let params = match self.snapshot.read() {
+            Some(params) => params,
+            None => return,
+        };
+        self.pending.clear();
+        while let Some(change) = self.changes.pop() {
+            self.pending.push(change);
         }
+        if self.resync.swap(false, Ordering::Acquire) {
+            // Some changes didn't fit in the queue; report every parameter instead.
+            let meta = &self.meta;
+            self.pending.clear();
+            self.pending
+                .extend(EParam::all().map(|eparam| (eparam, params.read_parameter(meta, eparam))));
+        }
+        update(params, &self.pending);
+    }
+
+    /// The snapshot last handed to `check_and_update` (or the parameters when the mailbox was
+    /// made).
+    pub fn latest(&self) -> &Params {
+        self.snapshot.latest()
     }
 }
 
-/******************************************************************************
- ** Mailbox                                                                  **
- ******************************************************************************/
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::EFiltParams;
 
-pub fn mailbox<T: Clone>() -> (MailboxWriter<T>, MailboxReader<T>) {
-    let slot = Arc::new(AtomicCell::new(None));
-    let ready = Arc::new(AtomicBool::new(false));
-
-    (
-        MailboxWriter {
-            slot: Arc::clone(&slot),
-            ready: Arc::clone(&ready),
-        },
-        MailboxReader { slot, ready },
-    )
-}
+    #[test]
+    fn reset_is_a_single_update() {
+        let meta = ParamsMeta::new();
+        let mut params = Params::new(44100.0);
+        params.osc1.gain = 0.5;
+        params.filt2.resonance = 1.5;
+        let mut sync = Synchronizer::new(meta.clone(), params);
+        let subscriber = sync.subscriber();
 
-#[derive(Clone)]
-pub struct MailboxWriter<T: Clone> {
-    slot: Arc<AtomicCell<Option<T>>>,
-    ready: Arc<AtomicBool>,
-}
+        let mut receiver = sync.mailbox();
 
-impl<T: Clone> MailboxWriter<T> {
-    fn update(&self, next: T) {
-        self.slot.store(Some(next));
-        self.ready.store(true, Ordering::Release);
+        sync.reset_to_defaults();
+
+        assert_eq!(sync.hub.lock().unwrap().epoch, 1);
+        assert_eq!(subscriber.changes.lock().unwrap().len(), meta.count());
+        let mut updates = 0;
+        receiver.check_and_update(|params, changes| {
+            assert_eq!(params.osc1.gain, 1.0);
+            assert_eq!(params.filt2.resonance, 1.0);
+            assert_eq!(changes.len(), meta.count());
+            updates += 1;
+        });
+        receiver.check_and_update(|_, _| updates += 1);
+        assert_eq!(updates, 1);
     }
-}
 
-pub struct MailboxReader<T: Clone> {
-    slot: Arc<AtomicCell<Option<T>>>,
-    ready: Arc<AtomicBool>,
-}
+    #[test]
+    fn writes_from_any_handle_reach_the_receiver_in_order() {
+        let meta = ParamsMeta::new();
+        let mut sync = Synchronizer::new(meta, Params::new(44100.0));
+        let mut receiver = sync.mailbox();
+        let mut gui = sync.clone();
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+
+        // Another thread holds the hub, so the writes wait in the GUI's outbox.
+        let hub = Arc::clone(&sync.hub);
+        let guard = hub.lock().unwrap();
+        gui.write_parameter(cutoff, 0.25);
+        gui.write_parameter(cutoff, 0.75);
+        assert!((gui.read_parameter(cutoff) - 0.75).abs() < 1e-9);
+        receiver.check_and_update(|_, _| panic!("Nothing was passed on"));
+        drop(guard);
+
+        sync.refresh_maybe();
+        assert!((sync.read_parameter(cutoff) - 0.75).abs() < 1e-9);
+        let mut seen = vec![];
+        receiver.check_and_update(|params, changes| {
+            assert!((params.read_parameter(&sync.meta, cutoff) - 0.75).abs() < 1e-9);
+            seen.extend_from_slice(changes);
+        });
+        assert_eq!(seen, [(cutoff, 0.25), (cutoff, 0.75)]);
+    }
+
+    #[test]
+    fn learned_controllers_are_bound_by_the_gui() {
+        let meta = ParamsMeta::new();
+        let mut audio = Synchronizer::new(meta, Params::new(44100.0));
+        let mut gui = audio.clone();
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
 
-impl<T: Clone> MailboxReader<T> {
-    pub fn get_updated(&self) -> Option<T> {
-        // Check if there's a new value.
-        if self.ready.swap(false, Ordering::Acquire) {
-            // If so, swap it with None.
-            self.slot.swap(None)
-        } else {
-            None
+        gui.learn_cc(cutoff);
+        audio.handle_cc(74, 127);
+        assert_eq!(gui.cc_map().binding(cutoff), None);
+        gui.finish_learn();
+        gui.refresh();
+        assert_eq!(gui.cc_map().binding(cutoff), Some(74));
+        assert_eq!(gui.cc_map().learning(), None);
+        assert!((gui.read_parameter(cutoff) - 1.0).abs() < 1e-9);
+
+        audio.handle_cc(74, 0);
+        gui.refresh();
+        assert!(gui.read_parameter(cutoff).abs() < 1e-9);
+    }
+
+    #[test]
+    fn overflowing_changes_report_every_parameter() {
+        let meta = ParamsMeta::new();
+        let mut sync = Synchronizer::new(meta.clone(), Params::new(44100.0));
+        let mut receiver = sync.mailbox();
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+
+        for step in 0..=QUEUE_CAPACITY {
+            sync.write_parameter(cutoff, step as f64 / QUEUE_CAPACITY as f64);
         }
+        let mut seen = vec![];
+        receiver.check_and_update(|_, changes| seen.extend_from_slice(changes));
+        assert_eq!(seen.len(), meta.count());
+    }
+
+    #[test]
+    fn gestures_are_shared_and_ordered() {
+        let meta = ParamsMeta::new();
+        let sync = Synchronizer::new(meta, Params::new(44100.0));
+        let gui = sync.clone();
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+
+        gui.begin_edit(cutoff);
+        gui.end_edit(cutoff);
+        let mut gestures = vec![];
+        sync.drain_gestures(|gesture| gestures.push(gesture));
+        assert_eq!(gestures, [Gesture::Begin(cutoff), Gesture::End(cutoff)]);
+
+        gestures.clear();
+        sync.drain_gestures(|gesture| gestures.push(gesture));
+        assert!(gestures.is_empty());
+    }
+
+    #[test]
+    fn deltas_are_flushed_once_at_their_latest_value() {
+        let meta = ParamsMeta::new();
+        let mut sync = Synchronizer::new(meta, Params::new(44100.0));
+        let mut router = sync.delta_router();
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+        let resonance = EParam::Filt1(EFiltParams::Resonance);
+
+        sync.write_parameter(cutoff, 0.25);
+        sync.write_parameter(cutoff, 0.5);
+        sync.write_parameter(resonance, 0.75);
+        assert!(router.has_pending());
+        let mut flushed = vec![];
+        router.flush(|eparam, value| flushed.push((eparam, value)));
+        flushed.sort_by_key(|(eparam, _)| eparam.index());
+        assert_eq!(flushed, [(cutoff, 0.5), (resonance, 0.75)]);
+
+        assert!(!router.has_pending());
+        router.flush(|_, _| panic!("Already flushed"));
+    }
+
+    #[test]
+    fn deltas_stay_pending_while_locked() {
+        let meta = ParamsMeta::new();
+        let mut sync = Synchronizer::new(meta, Params::new(44100.0));
+        let subscriber = sync.subscriber();
+        let mut router = ParamDeltaRouter::new(subscriber.clone());
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+        sync.write_parameter(cutoff, 0.25);
+
+        let guard = subscriber.changes.lock().unwrap();
+        router.flush(|_, _| panic!("Changes are locked"));
+        drop(guard);
+
+        let mut flushed = vec![];
+        router.flush(|eparam, value| flushed.push((eparam, value)));
+        assert_eq!(flushed, [(cutoff, 0.25)]);
+    }
+
+    #[test]
+    fn busy_subscribers_get_their_changes_later() {
+        let meta = ParamsMeta::new();
+        let mut sync = Synchronizer::new(meta, Params::new(44100.0));
+        let subscriber = sync.subscriber();
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+
+        let guard = subscriber.changes.lock().unwrap();
+        sync.write_parameter(cutoff, 0.25);
+        drop(guard);
+        assert!(subscriber.changes.lock().unwrap().is_empty());
+
+        sync.try_flush();
+        assert_eq!(subscriber.changes.lock().unwrap().get(&cutoff), Some(&0.25));
+    }
+
+    #[test]
+    fn full_outboxes_hold_writes_without_blocking() {
+        let meta = ParamsMeta::new();
+        let mut sync = Synchronizer::new(meta, Params::new(44100.0));
+        let mut audio = sync.clone();
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+        let resonance = EParam::Filt1(EFiltParams::Resonance);
+
+        let hub = Arc::clone(&sync.hub);
+        let guard = hub.lock().unwrap();
+        for step in 0..=QUEUE_CAPACITY {
+            audio.write_parameter(cutoff, step as f64 / QUEUE_CAPACITY as f64);
+        }
+        audio.write_parameter(resonance, 0.5);
+        assert!(audio.overflow.contains(cutoff) && audio.overflow.contains(resonance));
+        drop(guard);
+
+        audio.try_flush();
+        assert!(audio.overflow.is_empty());
+        sync.refresh();
+        assert!((sync.read_parameter(cutoff) - 1.0).abs() < 1e-9);
+        assert!((sync.read_parameter(resonance) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn writes_while_flushing_come_back_later() {
+        let meta = ParamsMeta::new();
+        let mut sync = Synchronizer::new(meta, Params::new(44100.0));
+        let mut router = sync.delta_router();
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+        sync.write_parameter(cutoff, 0.25);
+
+        // E.g. a host setting the parameter again as it records automation.
+        router.flush(|eparam, _| sync.write_parameter(eparam, 0.5));
+        let mut flushed = vec![];
+        router.flush(|eparam, value| flushed.push((eparam, value)));
+        assert_eq!(flushed, [(cutoff, 0.5)]);
+    }
+
+    #[test]
+    fn dropped_handles_are_freed_off_the_audio_thread() {
+        let meta = ParamsMeta::new();
+        let mut sync = Synchronizer::new(meta, Params::new(44100.0));
+        let mut audio = sync.clone();
+        let mut gui = sync.clone();
+        let mailbox = sync.mailbox();
+        let cutoff = EParam::Filt1(EFiltParams::Cutoff);
+
+        gui.write_parameter(cutoff, 0.25);
+        let hub = Arc::clone(&sync.hub);
+        let guard = hub.lock().unwrap();
+        gui.write_parameter(cutoff, 0.5);
+        drop(guard);
+        drop(gui);
+        drop(mailbox);
+
+        // The audio thread passes the dropped handle's last write on, but frees nothing.
+        audio.try_flush();
+        assert_eq!(hub.lock().unwrap().outboxes.len(), 3);
+        assert_eq!(hub.lock().unwrap().mailboxes.len(), 1);
+
+        sync.refresh();
+        assert!((sync.read_parameter(cutoff) - 0.5).abs() < 1e-9);
+        assert_eq!(hub.lock().unwrap().outboxes.len(), 2);
+        assert!(hub.lock().unwrap().mailboxes.is_empty());
     }
 }
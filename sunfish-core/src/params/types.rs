@@ -70,6 +70,21 @@ impl GradualTime {
             piece2_minmax: (0.750, 10.000),
         }
     }
+    pub fn for_delay_hold() -> Self {
+        // Piecewise delay/hold time (starting at zero, i.e. off):
+        // norm:       actual:
+        // 0.0-0.3     0ms-100ms
+        // 0.3-0.6     100ms-500ms
+        // 0.6-1.0     500ms-5seconds
+        Self {
+            piece0_point: 0.3,
+            piece0_minmax: (0.0, 0.100),
+            piece1_point: 0.6,
+            piece1_minmax: (0.100, 0.500),
+            piece2_point: 1.0,
+            piece2_minmax: (0.500, 5.000),
+        }
+    }
 }
 
 impl ParamType<f64> for GradualTime {
@@ -113,7 +128,7 @@ impl ParamType<f64> for GradualTime {
             let value_norm = value / (max - min);
             let source_range = self.piece1_point - self.piece0_point;
             // map to source range
-            self.piece1_point + (value_norm * source_range)
+            self.piece0_point + (value_norm * source_range)
         } else {
             let (min, max) = self.piece2_minmax;
             // subtract the target min
@@ -122,7 +137,7 @@ impl ParamType<f64> for GradualTime {
             let value_norm = value / (max - min);
             let source_range = self.piece2_point - self.piece1_point;
             // map to source range
-            self.piece2_point + (value_norm * source_range)
+            self.piece1_point + (value_norm * source_range)
         };
 
         mapped_value.max(0.0).min(1.0)
@@ -244,3 +259,87 @@ impl ParamType<f64> for Linear {
         result.max(0.0).min(1.0)
     }
 }
+
+/// Maps evenly across octaves, so each part of the knob covers the same musical range. A `skew`
+/// above 1.0 spends more of the travel on the low end.
+#[derive(Clone, Debug)]
+pub struct LogFrequency {
+    pub min: f64,
+    pub max: f64,
+    pub skew: f64,
+}
+
+impl LogFrequency {
+    pub fn new(min: f64, max: f64, skew: f64) -> Self {
+        assert!(min > 0.0 && max > min, "LogFrequency needs 0 < min < max");
+        LogFrequency { min, max, skew }
+    }
+}
+
+impl ParamType<f64> for LogFrequency {
+    fn vst_float_to_value(&self, value_unit: f64) -> f64 {
+        let value_unit = value_unit.clamp(0.0, 1.0).powf(self.skew);
+        self.min * (self.max / self.min).powf(value_unit)
+    }
+
+    fn value_to_vst_float(&self, value_full: f64) -> f64 {
+        let value_full = value_full.clamp(self.min, self.max);
+        let octaves = (value_full / self.min).ln() / (self.max / self.min).ln();
+        octaves.powf(1.0 / self.skew)
+    }
+}
+
+/// A power curve from `min` to `max`: a `skew` above 1.0 gives finer control at the low end
+/// (e.g. for gains), below 1.0 at the high end.
+#[derive(Clone, Debug)]
+pub struct Exponential {
+    pub min: f64,
+    pub max: f64,
+    pub skew: f64,
+}
+
+impl Exponential {
+    pub fn new(min: f64, max: f64, skew: f64) -> Self {
+        Exponential { min, max, skew }
+    }
+}
+
+impl ParamType<f64> for Exponential {
+    fn vst_float_to_value(&self, value_unit: f64) -> f64 {
+        let value_unit = value_unit.clamp(0.0, 1.0).powf(self.skew);
+        self.min + value_unit * (self.max - self.min)
+    }
+
+    fn value_to_vst_float(&self, value_full: f64) -> f64 {
+        let value_unit = ((value_full - self.min) / (self.max - self.min)).clamp(0.0, 1.0);
+        value_unit.powf(1.0 / self.skew)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn curved_types_round_trip() {
+        let rate = LogFrequency::new(0.05, 10.0, 1.0);
+        assert!((rate.vst_float_to_value(0.0) - 0.05).abs() < 1e-12);
+        assert!((rate.vst_float_to_value(1.0) - 10.0).abs() < 1e-9);
+        // Halfway is the geometric mean, not the arithmetic one.
+        assert!((rate.vst_float_to_value(0.5) - (0.05f64 * 10.0).sqrt()).abs() < 1e-9);
+
+        let attack = GradualTime::for_attack();
+        assert!((attack.vst_float_to_value(0.45) - 0.3).abs() < 1e-12);
+
+        let gain = Exponential::new(0.0, 2.0, 2.0);
+        assert!((gain.vst_float_to_value(0.5) - 0.5).abs() < 1e-12);
+        assert_eq!(gain.value_to_vst_float(3.0), 1.0);
+
+        for unit in [0.0, 0.1, 0.25, 0.5, 0.8, 1.0] {
+            for param in [&rate as &dyn ParamType<f64>, &attack, &gain] {
+                let value = param.vst_float_to_value(unit);
+                assert!((param.value_to_vst_float(value) - unit).abs() < 1e-9);
+            }
+        }
+    }
+}
@@ -1,12 +1,21 @@
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
 use std::time::{Duration, Instant};
 
+use num_traits::Float;
 use vst::host::Host;
 use vst::plugin::HostCallback;
 
 use crate::core::{Sunfish, Tempo};
+use crate::midi::{self, MidiReceiver};
 use crate::modulation;
 use crate::params;
-use crate::params::sync::{Subscriber, Synchronizer};
+use crate::params::error::ParamError;
+use crate::params::state::{self, PluginState};
+use crate::params::sync::{Gesture, ParamDeltaRouter, Synchronizer};
+use crate::params::{NormalizedParams, Params};
+use crate::presets::bank::{Bank, BANK_SIZE};
+use crate::shell::{PluginShell, Transport};
 use crate::ui::editor::SunfishEditor;
 use crate::util;
 
@@ -15,7 +24,17 @@ pub struct SunfishPlugin {
     pub core: Sunfish,
     pub editor: SunfishEditor,
     pub host: HostCallback,
-    pub host_subscriber: Subscriber,
+    pub host_deltas: ParamDeltaRouter,
+    // Notes played on the GUI's keyboard.
+    gui_midi: MidiReceiver,
+    // Parameters whose GUI edit has ended, reported after their final values.
+    ending_edits: Vec<usize>,
+    // Programs the host can switch between.
+    pub bank: Bank,
+    // Whether MIDI program changes switch programs.
+    pub program_change: bool,
+    // Set when processing panics; see `guarded`.
+    faulted: bool,
     pub last_host_param_update: Instant,
     pub host_param_update_tick: Duration,
 }
@@ -32,12 +51,13 @@ impl SunfishPlugin {
         let meta = params::ParamsMeta::new();
 
         let mut synchronizer = Synchronizer::new(meta.clone(), params);
-        let gui_subscriber = synchronizer.subscriber();
-        let host_subscriber = synchronizer.subscriber();
+        let gui_deltas = synchronizer.delta_router();
+        let host_deltas = synchronizer.delta_router();
 
         let core_mailbox = synchronizer.mailbox();
 
         let gui_synchronizer = synchronizer.clone();
+        let (gui_midi_sender, gui_midi) = midi::gui_channel();
 
         // How often to update host with new param values.
         let host_param_update_tick = Duration::from_micros(500);
@@ -55,24 +75,279 @@ impl SunfishPlugin {
 
         SunfishPlugin {
             core,
-            editor: SunfishEditor::new(gui_synchronizer, gui_subscriber),
+            editor: SunfishEditor::new(gui_synchronizer, gui_deltas, gui_midi_sender),
             host: HostCallback::default(),
 
-            host_subscriber,
+            host_deltas,
+            gui_midi,
+            ending_edits: Vec::with_capacity(params::EPARAM_COUNT),
+            bank: Bank::default(),
+            program_change: true,
+            faulted: false,
             last_host_param_update: Instant::now() - host_param_update_tick,
             host_param_update_tick,
         }
     }
 
+    /// The live parameters, as last written by the GUI or host.
+    pub fn current_params(&self) -> Params {
+        self.core
+            .params_sync
+            .clone_inner()
+            .unwrap_or_else(|| self.core.params.clone())
+    }
+
+    /// Serialize the state along with every program in the bank, for hosts that save banks.
+    pub fn save_bank(&self) -> Vec<u8> {
+        let params = self.current_params();
+        let programs = self.bank.programs(&params);
+        self.save(PluginState {
+            programs: Some(programs),
+            ..self.plugin_state(params)
+        })
+    }
+
+    fn plugin_state(&self, params: Params) -> PluginState {
+        PluginState {
+            params,
+            cc_map: self.core.params_sync.cc_map(),
+            program_change: self.program_change,
+            editor_size: self.core.params_sync.editor_size(),
+            program: self.bank.current(),
+            programs: None,
+        }
+    }
+
+    fn save(&self, state: PluginState) -> Vec<u8> {
+        state::save(&state).unwrap_or_else(|err| {
+            log::error!("Failed to save state: {:?}", err);
+            vec![]
+        })
+    }
+
+    /// Switch to another program in the bank.
+    pub fn select_program(&mut self, index: usize) {
+        if index == self.bank.current() {
+            return;
+        }
+        let params = self.current_params();
+        if let Some(preset) = self.bank.select(index, &params) {
+            self.core.params_sync.write_params(&preset.params);
+        }
+    }
+
+    // Switch programs from the audio thread (i.e. on a MIDI program change) without waiting
+    // on the GUI or allocating: the outgoing program keeps the parameters the core last
+    // received, which misses only edits it hasn't picked up yet.
+    fn select_program_from_midi(&mut self, index: usize) {
+        if index == self.bank.current() {
+            return;
+        }
+        if let Some(preset) = self.bank.select(index, self.core.received_params()) {
+            self.core.params_sync.write_params(&preset.params);
+        }
+    }
+
+    /// Write every parameter through the synchronizer, so the core and GUI both pick them up.
+    pub fn apply_params(&mut self, params: &Params) {
+        self.core.params_sync.write_params(params);
+    }
+
     pub fn update_host_parameters(&mut self) {
-        if let Ok(guard) = self.host_subscriber.changes.lock() {
-            let changes = &(*guard);
-            for (updated_eparam, updated_value) in changes {
-                let index = self.core.meta.param_to_index(updated_eparam).unwrap();
-                self.host.automate(index as i32, *updated_value as f32);
+        // Wrap the new values in any gestures started or ended since the last update.
+        let host = &self.host;
+        let meta = &self.core.meta;
+        let ending_edits = &mut self.ending_edits;
+        self.core
+            .params_sync
+            .drain_gestures(|gesture| match gesture {
+                Gesture::Begin(eparam) => {
+                    if let Some(index) = meta.param_to_index(&eparam) {
+                        host.begin_edit(index as i32);
+                    }
+                }
+                Gesture::End(eparam) => ending_edits.extend(meta.param_to_index(&eparam)),
+            });
+        self.host_deltas.flush(|updated_eparam, updated_value| {
+            if let Some(index) = meta.param_to_index(&updated_eparam) {
+                host.automate(index as i32, updated_value as f32);
+            }
+        });
+        for index in self.ending_edits.drain(..) {
+            self.host.end_edit(index as i32);
+        }
+    }
+
+    /// Run `f`, catching any panic in it so a DSP bug can't take down the host. After a panic
+    /// (which the panic hook logs) the synth stays silent until the host resumes it, and the GUI
+    /// shows an error. Returns `None` if `f` didn't run to completion.
+    pub fn guarded<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> Option<R> {
+        if self.faulted {
+            return None;
+        }
+        match panic::catch_unwind(AssertUnwindSafe(|| f(self))) {
+            Ok(result) => Some(result),
+            Err(_) => {
+                log::error!("Silencing output until the host resumes processing");
+                self.faulted = true;
+                self.core.params_sync.set_faulted(true);
+                None
             }
         }
     }
+
+    fn handle_midi(&mut self, data: [u8; 3], sample_offset: usize) {
+        match data[0] & 0xF0 {
+            // Program change, unless turned off so a controller can't clobber edits.
+            0xC0 => {
+                if self.program_change {
+                    self.select_program_from_midi(data[1] as usize);
+                }
+            }
+            _ => self.core.handle_midi(data, sample_offset),
+        }
+    }
+}
+
+impl PluginShell for SunfishPlugin {
+    fn param_count(&self) -> usize {
+        self.core.meta.count()
+    }
+
+    fn param_name(&self, index: usize) -> Result<String, ParamError> {
+        self.core.params.parameter_name(&self.core.meta, index)
+    }
+
+    fn param_text(&self, index: usize, value: f64) -> Result<String, ParamError> {
+        let eparam = self.core.meta.parameter_index(index)?;
+        self.core
+            .params
+            .formatted_normalized(&self.core.meta, eparam, eparam.check_value(value)?)
+    }
+
+    fn param_value(&self, index: usize) -> Result<f64, ParamError> {
+        let eparam = self.core.meta.parameter_index(index)?;
+        Ok(self.core.params.read_parameter(&self.core.meta, eparam))
+    }
+
+    fn param_default(&self, index: usize) -> Result<f64, ParamError> {
+        let eparam = self.core.meta.parameter_index(index)?;
+        self.core.meta.default_value(eparam)
+    }
+
+    fn set_param_value(&mut self, index: usize, value: f64) -> Result<(), ParamError> {
+        let eparam = self.core.meta.parameter_index(index)?;
+        let value = eparam.check_value(value)?;
+        self.core.params_sync.write_parameter(eparam, value);
+        Ok(())
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        self.save(self.plugin_state(self.current_params()))
+    }
+
+    fn load_state(&mut self, data: &[u8]) {
+        match state::load(data) {
+            Ok(state) => {
+                self.apply_params(&state.params);
+                self.core.params_sync.set_cc_map(state.cc_map);
+                self.program_change = state.program_change;
+                // Projects saved before the editor was resized open it at its default size.
+                if let Some(editor_size) = state.editor_size {
+                    self.core.params_sync.set_editor_size(editor_size);
+                }
+                // Only whole banks bring their programs; otherwise just the selection changes.
+                if let Some(programs) = state.programs {
+                    self.bank = Bank::with_presets(BANK_SIZE, programs);
+                }
+                self.bank.set_current(state.program);
+            }
+            Err(err) => log::error!("Failed to load state: {:?}", err),
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.core.update_sample_rate(sample_rate);
+        self.core.params.update_sample_rate(sample_rate);
+        self.core.dt = 1.0 / sample_rate;
+    }
+
+    fn midi_event(&mut self, data: [u8; 3], sample_offset: usize) {
+        self.guarded(|plugin| plugin.handle_midi(data, sample_offset));
+    }
+
+    fn suspend(&mut self) {
+        // Nothing should keep sounding once the host resumes processing.
+        self.core.all_sound_off();
+    }
+
+    fn resume(&mut self) {
+        // Don't carry voices or filter state across a transport stop, nor whatever state a
+        // panic left behind.
+        self.core.reset();
+        self.faulted = false;
+        self.core.params_sync.set_faulted(false);
+    }
+
+    fn tail_samples(&self) -> usize {
+        self.core.tail_samples()
+    }
+
+    fn process<F: Float>(
+        &mut self,
+        inputs: &[&[F]],
+        outputs: &mut [&mut [F]],
+        transport: &Transport,
+    ) {
+        // Take the input for this buffer.
+        self.core.write_input(inputs);
+        // We need to zero out the buffers, since render assumes they
+        // are zero. There may be a faster way to do this in the future.
+        silence(outputs);
+        let rendered = self.guarded(|plugin| {
+            if let Some(tempo_bpm) = transport.tempo_bpm {
+                plugin.core.tempo.update(tempo_bpm);
+                plugin.core.params_sync.set_tempo_bpm(tempo_bpm);
+            }
+            plugin
+                .core
+                .update_transport(transport.ppq_pos, transport.playing);
+            // Notes from the GUI start with the buffer.
+            while let Some(data) = plugin.gui_midi.pop() {
+                plugin.handle_midi(data, 0);
+            }
+            // Pass on host and MIDI writes that found the parameters busy, if they're free now.
+            plugin.core.params_sync.try_flush();
+
+            if !outputs.is_empty() {
+                plugin.core.render(outputs);
+            }
+        });
+        // Don't pass on whatever a panicking render left half-written.
+        if rendered.is_none() {
+            silence(outputs);
+        }
+    }
+
+    fn editor_size(&self) -> (i32, i32) {
+        self.editor.window_size()
+    }
+
+    fn open_editor(&mut self, parent: *mut c_void) -> bool {
+        self.editor.open_parented(parent)
+    }
+
+    fn close_editor(&mut self) {
+        self.editor.close_window();
+    }
+}
+
+fn silence<F: Float>(outputs: &mut [&mut [F]]) {
+    for output_channel in outputs.iter_mut() {
+        for output_sample in output_channel.iter_mut() {
+            *output_sample = F::zero();
+        }
+    }
 }
 
 impl Default for SunfishPlugin {
@@ -80,3 +355,90 @@ impl Default for SunfishPlugin {
         SunfishPlugin::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn panics_silence_the_plugin_until_resumed() {
+        let mut plugin = SunfishPlugin::new();
+        plugin.midi_event([144, 60, 100], 0);
+        let result: Option<()> = plugin.guarded(|_| panic!("DSP bug"));
+        assert_eq!(result, None);
+        assert!(plugin.core.params_sync.is_faulted());
+
+        let (mut left, mut right) = (vec![1.0f32; 64], vec![1.0f32; 64]);
+        plugin.process(&[], &mut [&mut left, &mut right], &Transport::default());
+        assert!(left.iter().chain(right.iter()).all(|sample| *sample == 0.0));
+
+        PluginShell::resume(&mut plugin);
+        assert!(!plugin.core.params_sync.is_faulted());
+        assert_eq!(plugin.guarded(|_| 1), Some(1));
+    }
+
+    #[test]
+    fn gui_notes_reach_the_core() {
+        let mut plugin = SunfishPlugin::new();
+        let (mut gui_midi_sender, gui_midi) = midi::gui_channel();
+        plugin.gui_midi = gui_midi;
+        let (mut left, mut right) = (vec![0.0f32; 64], vec![0.0f32; 64]);
+
+        gui_midi_sender.push(midi::note_on(60, 100)).unwrap();
+        plugin.process(&[], &mut [&mut left, &mut right], &Transport::default());
+        assert_eq!(plugin.core.voices.len(), 1);
+        assert!(left.iter().any(|sample| *sample != 0.0));
+    }
+
+    #[test]
+    fn editor_size_is_saved_with_the_state() {
+        let plugin = SunfishPlugin::new();
+        plugin.core.params_sync.set_editor_size((1200, 870));
+        let data = PluginShell::save_state(&plugin);
+
+        let mut restored = SunfishPlugin::new();
+        assert_eq!(restored.core.params_sync.editor_size(), None);
+        PluginShell::load_state(&mut restored, &data);
+        assert_eq!(restored.core.params_sync.editor_size(), Some((1200, 870)));
+    }
+
+    #[test]
+    fn banks_keep_every_program() {
+        let mut plugin = SunfishPlugin::new();
+        plugin.select_program(2);
+        plugin.bank.rename_current("Renamed".to_string());
+        let mut params = plugin.current_params();
+        params.osc1.gain = 0.25;
+        plugin.apply_params(&params);
+        let data = plugin.save_bank();
+
+        let mut restored = SunfishPlugin::new();
+        PluginShell::load_state(&mut restored, &data);
+        assert_eq!(restored.bank.current(), 2);
+        assert_eq!(restored.bank.get(2).unwrap().meta.name, "Renamed");
+        assert_eq!(restored.current_params().osc1.gain, 0.25);
+        // Switching away keeps the edits with their program.
+        restored.select_program(0);
+        assert_eq!(restored.bank.get(2).unwrap().params.osc1.gain, 0.25);
+    }
+
+    #[test]
+    fn program_changes_switch_programs() {
+        let mut plugin = SunfishPlugin::new();
+        let mut params = plugin.current_params();
+        params.osc1.gain = 0.25;
+        plugin.apply_params(&params);
+        let (mut left, mut right) = (vec![0.0f32; 64], vec![0.0f32; 64]);
+        plugin.process(&[], &mut [&mut left, &mut right], &Transport::default());
+
+        plugin.midi_event([192, 1, 0], 0);
+        assert_eq!(plugin.bank.current(), 1);
+        assert_eq!(plugin.bank.get(0).unwrap().params.osc1.gain, 0.25);
+        let incoming = plugin.bank.get(1).unwrap().params.osc1.gain;
+        assert_eq!(plugin.current_params().osc1.gain, incoming);
+
+        plugin.program_change = false;
+        plugin.midi_event([192, 2, 0], 0);
+        assert_eq!(plugin.bank.current(), 1);
+    }
+}
@@ -0,0 +1,137 @@
+use copy_from::CopyFrom;
+
+use crate::params::Params;
+use crate::presets::{factory, Preset, PresetMeta};
+
+/// Number of programs the host sees.
+pub const BANK_SIZE: usize = 16;
+
+const INIT_NAME: &str = "Init";
+
+/// The programs offered to the host, and which one is selected. Edits made while a program is
+/// selected stay with it when switching away.
+#[derive(Clone, Debug)]
+pub struct Bank {
+    presets: Vec<Preset>,
+    current: usize,
+}
+
+impl Bank {
+    /// A bank of `size` programs, all at their default settings.
+    pub fn init(size: usize) -> Self {
+        let presets = (0..size)
+            .map(|_| {
+                Preset::new(
+                    PresetMeta {
+                        name: INIT_NAME.to_string(),
+                        ..PresetMeta::default()
+                    },
+                    Params::new(0.0),
+                )
+            })
+            .collect();
+        Bank {
+            presets,
+            current: 0,
+        }
+    }
+
+    /// The factory presets, followed by `Init` programs up to `size`.
+    pub fn factory(size: usize) -> Self {
+        Self::with_presets(size, factory::load_all())
+    }
+
+    /// `presets`, followed by `Init` programs up to `size`; any past `size` are dropped.
+    pub fn with_presets(size: usize, presets: Vec<Preset>) -> Self {
+        let mut bank = Self::init(size);
+        for (slot, preset) in bank.presets.iter_mut().zip(presets) {
+            *slot = preset;
+        }
+        bank
+    }
+
+    pub fn len(&self) -> usize {
+        self.presets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.presets.is_empty()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Preset> {
+        self.presets.get(index)
+    }
+
+    /// Every program, with `params` (the live settings) in place of the selected one's.
+    pub fn programs(&self, params: &Params) -> Vec<Preset> {
+        let mut presets = self.presets.clone();
+        presets[self.current].params = params.clone();
+        presets
+    }
+
+    /// Mark `index` as the selected program without switching to it, e.g. when restoring the
+    /// live settings along with it. Out of range indices are ignored.
+    pub fn set_current(&mut self, index: usize) {
+        if index < self.presets.len() {
+            self.current = index;
+        }
+    }
+
+    /// Switch programs, storing `params` (the live settings) in the outgoing one. Returns the
+    /// incoming program, unless `index` is out of range. Doesn't allocate.
+    pub fn select(&mut self, index: usize, params: &Params) -> Option<&Preset> {
+        if index >= self.presets.len() {
+            return None;
+        }
+        self.presets[self.current].params.copy_from(params);
+        self.current = index;
+        self.presets.get(index)
+    }
+
+    pub fn rename_current(&mut self, name: String) {
+        if let Some(preset) = self.presets.get_mut(self.current) {
+            preset.meta.name = name;
+        }
+    }
+}
+
+impl Default for Bank {
+    fn default() -> Self {
+        Self::factory(BANK_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn programs_keep_their_edits() {
+        let mut bank = Bank::init(2);
+        let mut edited = Params::new(0.0);
+        edited.osc1.gain = 0.5;
+
+        let incoming = bank.select(1, &edited).unwrap();
+        assert_eq!(incoming.params.osc1.gain, 1.0);
+        assert_eq!(bank.current(), 1);
+        bank.rename_current("Second".to_string());
+
+        let outgoing = bank.get(1).unwrap().params.clone();
+        let back = bank.select(0, &outgoing).unwrap();
+        assert_eq!(back.params.osc1.gain, 0.5);
+        assert_eq!(bank.get(1).unwrap().meta.name, "Second");
+        assert!(bank.select(2, &edited).is_none());
+    }
+
+    #[test]
+    fn factory_bank_fills_remaining_slots() {
+        let bank = Bank::factory(BANK_SIZE);
+        assert_eq!(bank.len(), BANK_SIZE);
+        assert_eq!(bank.get(1).unwrap().meta.name, "Round Bass");
+        assert_eq!(bank.get(BANK_SIZE - 1).unwrap().meta.name, INIT_NAME);
+    }
+}
@@ -0,0 +1,60 @@
+use lazy_static::lazy_static;
+
+use crate::presets::{Preset, PresetMeta};
+
+// Bundled into the binary, in program order; the first is the default patch.
+const FACTORY_PRESETS: &[&[u8]] = &[
+    include_bytes!("../../../assets/presets/init.json"),
+    include_bytes!("../../../assets/presets/round_bass.json"),
+    include_bytes!("../../../assets/presets/saw_lead.json"),
+    include_bytes!("../../../assets/presets/glass_pad.json"),
+    include_bytes!("../../../assets/presets/wobble.json"),
+    include_bytes!("../../../assets/presets/vowel_choir.json"),
+    include_bytes!("../../../assets/presets/metal_drops.json"),
+];
+
+/// Number of factory presets.
+pub fn count() -> usize {
+    FACTORY_PRESETS.len()
+}
+
+/// Parse the factory preset at `index`.
+pub fn load(index: usize) -> Option<Preset> {
+    let data = FACTORY_PRESETS.get(index)?;
+    match Preset::from_slice(data) {
+        Ok(preset) => Some(preset),
+        Err(err) => {
+            log::error!("Invalid factory preset {}: {:?}", index, err);
+            None
+        }
+    }
+}
+
+/// All factory presets, in order.
+pub fn load_all() -> Vec<Preset> {
+    (0..count()).filter_map(load).collect()
+}
+
+lazy_static! {
+    static ref METAS: Vec<PresetMeta> = load_all().into_iter().map(|preset| preset.meta).collect();
+}
+
+/// Metadata of all factory presets, in order. They're only parsed the first time.
+pub fn metas() -> &'static [PresetMeta] {
+    &METAS
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn every_factory_preset_loads() {
+        let presets = load_all();
+        assert_eq!(presets.len(), count());
+        assert_eq!(presets[0].meta.name, "Init");
+        assert!(presets.iter().all(|preset| !preset.meta.name.is_empty()));
+        assert!(load(count()).is_none());
+        assert_eq!(metas()[0].name, "Init");
+    }
+}
@@ -0,0 +1,231 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::presets::{factory, Category, Preset, PresetMeta, PRESET_EXTENSION};
+use crate::util::enumerable::Enumerable;
+
+/// Where a preset is kept.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Folder {
+    Factory,
+    User,
+}
+
+impl Folder {
+    pub fn as_string(self) -> String {
+        match self {
+            Folder::Factory => "Factory".to_string(),
+            Folder::User => "User".to_string(),
+        }
+    }
+}
+
+impl Enumerable<Folder> for Folder {
+    fn enumerate() -> Vec<Folder> {
+        vec![Folder::Factory, Folder::User]
+    }
+}
+
+// Where to load a preset's parameters from.
+#[derive(Clone, Debug)]
+enum Source {
+    Factory(usize),
+    File(PathBuf),
+}
+
+/// A preset that can be browsed; only its metadata is kept until it's loaded.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub folder: Folder,
+    pub meta: PresetMeta,
+    source: Source,
+}
+
+/// Which presets to list: those in `folder` and `category` (any, if None) matching `query`
+/// (see `PresetMeta::matches`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    pub folder: Option<Folder>,
+    pub category: Option<Category>,
+    pub query: String,
+}
+
+impl Filter {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        self.folder.is_none_or(|folder| folder == entry.folder)
+            && self
+                .category
+                .is_none_or(|category| entry.meta.category == Some(category))
+            && entry.meta.matches(&self.query)
+    }
+}
+
+/// Every preset there is to browse: the factory presets, then those saved in the user's folder.
+#[derive(Clone, Debug, Default)]
+pub struct Library {
+    entries: Vec<Entry>,
+}
+
+impl Library {
+    /// Find the presets, reading those in `user_dir` (if any) by file name. Files that can't
+    /// be read are left out.
+    pub fn load(user_dir: Option<&Path>) -> Self {
+        let factory_entries = factory::metas()
+            .iter()
+            .enumerate()
+            .map(|(index, meta)| Entry {
+                folder: Folder::Factory,
+                meta: meta.clone(),
+                source: Source::Factory(index),
+            });
+        let user_entries = user_dir
+            .map(preset_files)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|path| match Preset::load(&path) {
+                Ok(preset) => Some(Entry {
+                    folder: Folder::User,
+                    meta: preset.meta,
+                    source: Source::File(path),
+                }),
+                Err(err) => {
+                    log::warn!("Skipping preset {:?}: {}", path, err);
+                    None
+                }
+            });
+        Library {
+            entries: factory_entries.chain(user_entries).collect(),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(index)
+    }
+
+    /// Indices of the entries `filter` lets through, in library order.
+    pub fn filter(&self, filter: &Filter) -> Vec<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| filter.matches(entry))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Read the preset at `index` in full. User presets are read afresh, in case the file
+    /// changed since the library was loaded.
+    pub fn load_preset(&self, index: usize) -> Result<Preset> {
+        let entry = self
+            .entries
+            .get(index)
+            .ok_or_else(|| anyhow!("No preset at {}", index))?;
+        match &entry.source {
+            Source::Factory(index) => {
+                factory::load(*index).ok_or_else(|| anyhow!("No factory preset at {}", index))
+            }
+            Source::File(path) => Preset::load(path),
+        }
+    }
+}
+
+// The presets in `dir`, sorted by name; none if it can't be read (e.g. it doesn't exist yet).
+fn preset_files(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == PRESET_EXTENSION))
+            .collect(),
+        Err(_) => return vec![],
+    };
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::Params;
+
+    fn names(library: &Library, filter: &Filter) -> Vec<String> {
+        library
+            .filter(filter)
+            .into_iter()
+            .map(|index| library.get(index).unwrap().meta.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn filters_by_folder_category_and_query() {
+        let dir = std::env::temp_dir().join(format!("sunfish-library-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let meta = PresetMeta {
+            name: "Deep Sub".to_string(),
+            category: Some(Category::Bass),
+            ..PresetMeta::default()
+        };
+        Preset::new(meta, Params::new(44100.0))
+            .save(&dir.join(format!("deep_sub.{}", PRESET_EXTENSION)))
+            .unwrap();
+        fs::write(dir.join(format!("broken.{}", PRESET_EXTENSION)), "{").unwrap();
+        fs::write(dir.join("notes.txt"), "not a preset").unwrap();
+
+        let library = Library::load(Some(&dir));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(library.count(), factory::count() + 1);
+
+        let user = Filter {
+            folder: Some(Folder::User),
+            ..Filter::default()
+        };
+        assert_eq!(names(&library, &user), vec!["Deep Sub"]);
+        let bass = Filter {
+            category: Some(Category::Bass),
+            ..Filter::default()
+        };
+        assert_eq!(
+            names(&library, &bass),
+            vec!["Round Bass", "Wobble", "Deep Sub"]
+        );
+        let query = Filter {
+            folder: Some(Folder::Factory),
+            category: Some(Category::Bass),
+            query: "wob".to_string(),
+        };
+        assert_eq!(names(&library, &query), vec!["Wobble"]);
+
+        // The file's gone, but the factory presets don't need one.
+        let deep_sub = library.filter(&user)[0];
+        assert!(library.load_preset(deep_sub).is_err());
+        let init = library.load_preset(0).unwrap();
+        assert_eq!(init.meta.name, "Init");
+    }
+
+    #[test]
+    fn loads_user_presets() {
+        let dir = std::env::temp_dir().join(format!("sunfish-user-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut params = Params::new(44100.0);
+        params.osc1.gain = 0.25;
+        Preset::new(
+            PresetMeta {
+                name: "Quiet".to_string(),
+                ..PresetMeta::default()
+            },
+            params,
+        )
+        .save(&dir.join(format!("quiet.{}", PRESET_EXTENSION)))
+        .unwrap();
+
+        let library = Library::load(Some(&dir));
+        let loaded = library.load_preset(factory::count());
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.unwrap().params.osc1.gain, 0.25);
+        assert_eq!(Library::load(None).count(), factory::count());
+    }
+}
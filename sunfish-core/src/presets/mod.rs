@@ -0,0 +1,198 @@
+pub mod bank;
+pub mod factory;
+pub mod library;
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::params::state::{self, STATE_VERSION};
+use crate::params::Params;
+use crate::presets::library::{Filter, Library};
+use crate::util::enumerable::Enumerable;
+
+/// File extension for presets on disk.
+pub const PRESET_EXTENSION: &str = "json";
+
+/// The kind of sound a preset makes, for browsing.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Category {
+    Bass,
+    Lead,
+    Pad,
+    Fx,
+}
+
+impl Category {
+    pub fn as_string(self) -> String {
+        match self {
+            Category::Bass => "Bass".to_string(),
+            Category::Lead => "Lead".to_string(),
+            Category::Pad => "Pad".to_string(),
+            Category::Fx => "FX".to_string(),
+        }
+    }
+}
+
+impl Enumerable<Category> for Category {
+    fn enumerate() -> Vec<Category> {
+        vec![Category::Bass, Category::Lead, Category::Pad, Category::Fx]
+    }
+}
+
+/// Descriptive information about a preset.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PresetMeta {
+    pub name: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub category: Option<Category>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl PresetMeta {
+    /// Whether every word of `query` appears (ignoring case) in the name, author, category or
+    /// tags. An empty query matches everything.
+    pub fn matches(&self, query: &str) -> bool {
+        let category = self.category.map(Category::as_string).unwrap_or_default();
+        let fields: Vec<String> = [&self.name, &self.author, &category]
+            .into_iter()
+            .chain(self.tags.iter())
+            .map(|field| field.to_lowercase())
+            .collect();
+        query
+            .to_lowercase()
+            .split_whitespace()
+            .all(|word| fields.iter().any(|field| field.contains(word)))
+    }
+}
+
+/// Metadata of the presets matching `query` (see `PresetMeta::matches`): the factory presets in
+/// bank order, then those in `user_dir` (if any).
+pub fn search(query: &str, user_dir: Option<&Path>) -> Vec<PresetMeta> {
+    let library = Library::load(user_dir);
+    let filter = Filter {
+        query: query.to_string(),
+        ..Filter::default()
+    };
+    library
+        .filter(&filter)
+        .into_iter()
+        .filter_map(|index| library.get(index))
+        .map(|entry| entry.meta.clone())
+        .collect()
+}
+
+/// A named set of parameters.
+#[derive(Clone, Debug)]
+pub struct Preset {
+    pub meta: PresetMeta,
+    pub params: Params,
+}
+
+// On-disk layout. The version covers the parameters; see `params::state`.
+#[derive(Serialize)]
+struct PresetFile<'a> {
+    version: u32,
+    meta: &'a PresetMeta,
+    params: &'a Params,
+}
+
+impl Preset {
+    pub fn new(meta: PresetMeta, params: Params) -> Self {
+        Preset { meta, params }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        let file = PresetFile {
+            version: STATE_VERSION,
+            meta: &self.meta,
+            params: &self.params,
+        };
+        Ok(serde_json::to_string_pretty(&file)?)
+    }
+
+    /// Parse a preset, migrating parameters saved by older versions.
+    pub fn from_json(json: &str) -> Result<Self> {
+        Self::from_slice(json.as_bytes())
+    }
+
+    /// As `from_json`, from raw bytes.
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        let mut file: Value = serde_json::from_slice(data)?;
+        let version = state::read_version(&file)?;
+        let meta = serde_json::from_value(file["meta"].take())?;
+        let params = state::params_from_value(version, file["params"].take())?;
+        Ok(Preset { meta, params })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_json() {
+        let mut params = Params::new(44100.0);
+        params.filt2.resonance = 0.75;
+        let preset = Preset::new(
+            PresetMeta {
+                name: "Glass Pad".to_string(),
+                author: "sunfish".to_string(),
+                category: Some(Category::Pad),
+                tags: vec!["bright".to_string(), "wide".to_string()],
+            },
+            params,
+        );
+
+        let loaded = Preset::from_json(&preset.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.meta, preset.meta);
+        assert_eq!(loaded.params.filt2.resonance, 0.75);
+    }
+
+    #[test]
+    fn search_matches_every_word() {
+        let names = |query, user_dir| {
+            search(query, user_dir)
+                .into_iter()
+                .map(|meta| meta.name)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(names("", None).len(), factory::count());
+        assert_eq!(names("bass", None), vec!["Round Bass", "Wobble"]);
+        assert_eq!(
+            names("PAD evolving", None),
+            vec!["Glass Pad", "Vowel Choir"]
+        );
+        assert_eq!(names("fx metallic", None), vec!["Metal Drops"]);
+        assert!(names("bass pad", None).is_empty());
+
+        let dir = std::env::temp_dir().join(format!("sunfish-search-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let meta = PresetMeta {
+            name: "Deep Sub".to_string(),
+            category: Some(Category::Bass),
+            ..PresetMeta::default()
+        };
+        Preset::new(meta, Params::new(44100.0))
+            .save(&dir.join(format!("deep_sub.{}", PRESET_EXTENSION)))
+            .unwrap();
+        let found = names("bass", Some(&dir));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(found, vec!["Round Bass", "Wobble", "Deep Sub"]);
+    }
+}
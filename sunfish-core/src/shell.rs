@@ -0,0 +1,59 @@
+use std::os::raw::c_void;
+
+use num_traits::Float;
+
+use crate::params::error::ParamError;
+
+/// The host's transport, as of the start of a buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Transport {
+    pub tempo_bpm: Option<f64>,
+    // Song position, in quarter notes.
+    pub ppq_pos: Option<f64>,
+    pub playing: bool,
+}
+
+/// Everything a plugin format (VST2, VST3, ...) needs from the synth, independent of any host
+/// API. Each format's bindings translate their host's calls into these.
+///
+/// Parameters are addressed by their index in the host's parameter list, and take normalized
+/// (0.0 to 1.0) values.
+pub trait PluginShell {
+    fn param_count(&self) -> usize;
+    fn param_name(&self, index: usize) -> Result<String, ParamError>;
+    /// A (normalized) value of the parameter, formatted for display.
+    fn param_text(&self, index: usize, value: f64) -> Result<String, ParamError>;
+    fn param_value(&self, index: usize) -> Result<f64, ParamError>;
+    fn param_default(&self, index: usize) -> Result<f64, ParamError>;
+    fn set_param_value(&mut self, index: usize, value: f64) -> Result<(), ParamError>;
+
+    /// Serialize the parameters and MIDI mappings, for the host to store in its project.
+    fn save_state(&self) -> Vec<u8>;
+    /// Restore a state written by `save_state`.
+    fn load_state(&mut self, data: &[u8]);
+
+    fn set_sample_rate(&mut self, sample_rate: f64);
+    /// A three-byte MIDI message, `sample_offset` samples into the next buffer.
+    fn midi_event(&mut self, data: [u8; 3], sample_offset: usize);
+    /// Processing has stopped; silence everything.
+    fn suspend(&mut self);
+    /// Processing is about to start again, possibly after a jump in the song.
+    fn resume(&mut self);
+    /// How long the synth can keep sounding after its last note is released, in samples.
+    fn tail_samples(&self) -> usize;
+    /// Render the next buffer into `outputs`, overwriting their contents. `inputs` is the host's
+    /// audio input; it can be empty.
+    fn process<F: Float>(
+        &mut self,
+        inputs: &[&[F]],
+        outputs: &mut [&mut [F]],
+        transport: &Transport,
+    );
+
+    /// Logical size of the editor window.
+    fn editor_size(&self) -> (i32, i32);
+    /// Open the editor inside `parent`, a platform window handle (`HWND`, `NSView` or X11
+    /// `Window`). Returns false if it's already open.
+    fn open_editor(&mut self, parent: *mut c_void) -> bool;
+    fn close_editor(&mut self);
+}
@@ -9,6 +9,11 @@ pub type Buffers<V> = tessellation::VertexBuffers<V, u16>;
 
 pub trait GpuVertex: bytemuck::Zeroable + bytemuck::Pod + Clone + std::fmt::Debug {
     fn descriptor<'a>() -> wgpu::VertexBufferLayout<'a>;
+
+    /// The vertex as drawn in a dimmed shape; unchanged unless it has a color to fade.
+    fn dimmed(&self) -> Self {
+        self.clone()
+    }
 }
 
 /// A Shape captures the CPU side of a single polygon with a
@@ -92,11 +97,18 @@ impl<V: GpuVertex> GpuShapeCollectionBuilder<V> {
         index
     }
 
+    /// How many shapes have been added so far.
+    pub fn count(&self) -> usize {
+        self.shapes.len()
+    }
+
     pub fn build(self) -> GpuShapeCollection<V> {
         let shape_count = self.shapes.len();
         GpuShapeCollection {
             shapes: self.shapes,
             shapes_to_update: HashSet::with_capacity(shape_count),
+            hidden: vec![false; shape_count],
+            dimmed: vec![false; shape_count],
         }
     }
 }
@@ -104,6 +116,10 @@ impl<V: GpuVertex> GpuShapeCollectionBuilder<V> {
 pub struct GpuShapeCollection<V: GpuVertex> {
     shapes: Vec<GpuShape<V>>,
     shapes_to_update: HashSet<usize>,
+    // Hidden shapes keep their vertices but aren't drawn.
+    hidden: Vec<bool>,
+    // Dimmed shapes are drawn with their vertices' `dimmed()` instead.
+    dimmed: Vec<bool>,
 }
 
 impl<V: GpuVertex> GpuShapeCollection<V> {
@@ -115,6 +131,28 @@ impl<V: GpuVertex> GpuShapeCollection<V> {
             log::warn!("Bad GpuShapeCollection index: {}", index);
         }
     }
+
+    pub fn set_hidden(&mut self, index: usize, hidden: bool) {
+        if let Some(shape_hidden) = self.hidden.get_mut(index) {
+            if *shape_hidden != hidden {
+                *shape_hidden = hidden;
+                self.shapes_to_update.insert(index);
+            }
+        } else {
+            log::warn!("Bad GpuShapeCollection index: {}", index);
+        }
+    }
+
+    pub fn set_dimmed(&mut self, index: usize, dimmed: bool) {
+        if let Some(shape_dimmed) = self.dimmed.get_mut(index) {
+            if *shape_dimmed != dimmed {
+                *shape_dimmed = dimmed;
+                self.shapes_to_update.insert(index);
+            }
+        } else {
+            log::warn!("Bad GpuShapeCollection index: {}", index);
+        }
+    }
 }
 
 struct VerRanges(Vec<std::ops::Range<u32>>);
@@ -212,11 +250,14 @@ pub fn update<V: GpuVertex>(
     for shape_index in shapes.shapes_to_update.drain() {
         let shape = &shapes.shapes[shape_index];
 
+        // A hidden shape gets empty ranges, so render() skips it.
+        let (ver_size, ind_size) = if shapes.hidden[shape_index] {
+            (0, 0)
+        } else {
+            (shape.vertices.len() as u64, shape.indices.len() as u64)
+        };
         let ver_offset = bufmem.ver_ranges.0[shape_index].start as u64;
-        let ver_size = shape.vertices.len() as u64;
-
         let ind_offset = bufmem.ind_ranges.0[shape_index].start as u64;
-        let ind_size = shape.indices.len() as u64;
 
         // TODO: These calls dig into the guts of buffers; could probably
         // benefit from a refactor.
@@ -225,15 +266,19 @@ pub fn update<V: GpuVertex>(
         let ver_elm_size = bufmem.buffers.vertices.element_size() as u64;
         let ver_buf_size = ver_elm_size * ver_size as u64;
         if ver_buf_size > 0 {
-            staging_belt
-                .write_buffer(
-                    encoder,
-                    &bufmem.buffers.vertices.buf,
-                    ver_offset * ver_elm_size,
-                    wgpu::BufferSize::new(ver_buf_size).unwrap(),
-                    device,
-                )
-                .copy_from_slice(bytemuck::cast_slice(&shape.vertices));
+            let mut view = staging_belt.write_buffer(
+                encoder,
+                &bufmem.buffers.vertices.buf,
+                ver_offset * ver_elm_size,
+                wgpu::BufferSize::new(ver_buf_size).unwrap(),
+                device,
+            );
+            if shapes.dimmed[shape_index] {
+                let dimmed: Vec<V> = shape.vertices.iter().map(V::dimmed).collect();
+                view.copy_from_slice(bytemuck::cast_slice(&dimmed));
+            } else {
+                view.copy_from_slice(bytemuck::cast_slice(&shape.vertices));
+            }
         }
         let start = bufmem.ver_ranges.0[shape_index].start;
         bufmem.ver_ranges.0[shape_index].end = start + ver_size as u32;
@@ -0,0 +1,97 @@
+use crate::params::EParam;
+use crate::ui::coords::Rect;
+use crate::ui::widgets::WidgetId;
+
+const CONTEXT_MENU_WIDTH: f32 = 0.16;
+const CONTEXT_MENU_ITEM_HEIGHT: f32 = 0.03;
+// Space between an item's left edge and its text.
+pub const CONTEXT_MENU_TEXT_PADDING: f32 = 0.01;
+pub const CONTEXT_MENU_SCALE: f32 = 0.014;
+pub const CONTEXT_MENU_OUTLINE_COLOR: [f32; 3] = [0.30039, 0.30039, 0.3019];
+pub const CONTEXT_MENU_OUTLINE_WIDTH: f32 = 0.0015;
+
+/// What can be done from a widget's context menu.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuItem {
+    ResetToDefault,
+    EnterValue,
+    MidiLearn,
+    CopyValue,
+    PasteValue,
+}
+
+/// A list of things to do with a widget's parameter, opened by right-clicking it.
+#[derive(Clone, Debug)]
+pub struct ContextMenu {
+    pub id: WidgetId,
+    pub eparam: EParam,
+    items: Vec<(MenuItem, String)>,
+    // Top-left corner.
+    x: f32,
+    y: f32,
+    hovered: Option<usize>,
+}
+
+impl ContextMenu {
+    /// Open at `x`, `y`, moved back if need be so the whole menu is on screen.
+    pub fn new(
+        id: WidgetId,
+        eparam: EParam,
+        items: Vec<(MenuItem, String)>,
+        x: f32,
+        y: f32,
+    ) -> Self {
+        let height = items.len() as f32 * CONTEXT_MENU_ITEM_HEIGHT;
+        ContextMenu {
+            id,
+            eparam,
+            items,
+            x: x.min(1.0 - CONTEXT_MENU_WIDTH).max(0.0),
+            y: y.min(1.0 - height).max(0.0),
+            hovered: None,
+        }
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::new(
+            self.x,
+            self.y,
+            self.x + CONTEXT_MENU_WIDTH,
+            self.y + self.items.len() as f32 * CONTEXT_MENU_ITEM_HEIGHT,
+        )
+    }
+
+    pub fn item_rect(&self, index: usize) -> Rect {
+        let y1 = self.y + index as f32 * CONTEXT_MENU_ITEM_HEIGHT;
+        Rect::new(
+            self.x,
+            y1,
+            self.x + CONTEXT_MENU_WIDTH,
+            y1 + CONTEXT_MENU_ITEM_HEIGHT,
+        )
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &(MenuItem, String)> {
+        self.items.iter()
+    }
+
+    fn index_at(&self, x: f32, y: f32) -> Option<usize> {
+        (0..self.items.len()).find(|index| self.item_rect(*index).in_bounds(x, y))
+    }
+
+    pub fn item_at(&self, x: f32, y: f32) -> Option<MenuItem> {
+        self.index_at(x, y).map(|index| self.items[index].0)
+    }
+
+    pub fn hovered(&self) -> Option<usize> {
+        self.hovered
+    }
+
+    /// Track the item under the mouse; returns whether it changed.
+    pub fn hover(&mut self, x: f32, y: f32) -> bool {
+        let hovered = self.index_at(x, y);
+        let changed = hovered != self.hovered;
+        self.hovered = hovered;
+        changed
+    }
+}
@@ -1,58 +1,70 @@
 use std::os::raw::c_void;
 
-use baseview::WindowScalePolicy;
+use baseview::{WindowHandle, WindowScalePolicy};
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 use vst::editor::{Editor, KeyCode, KnobMode};
 
-use crate::params::sync::{Subscriber, Synchronizer};
+use crate::midi::MidiSender;
+use crate::params::sync::{ParamDeltaRouter, Synchronizer};
+use crate::ui::preferences::Preferences;
 use crate::ui::styling;
 use crate::ui::window;
 use crate::util::borrow_return::Owner;
 
 pub struct SunfishEditor {
     open: bool,
+    // The open window, to close when the host asks.
+    window: Option<WindowHandle>,
 
     parameters: Owner<Synchronizer>,
-    subscriber: Owner<Subscriber>,
+    deltas: Owner<ParamDeltaRouter>,
+    midi: Owner<MidiSender>,
+    /// Reads the size the window was last resized to, even while the window has the parameters.
+    size_sync: Synchronizer,
     /// Metadata/GUI layout.
     styling: styling::Styling,
+    /// As of the last time the window opened; the window can change them.
+    preferences: Preferences,
 }
 
 impl SunfishEditor {
-    pub fn new(parameters: Synchronizer, subscriber: Subscriber) -> SunfishEditor {
+    pub fn new(
+        parameters: Synchronizer,
+        deltas: ParamDeltaRouter,
+        midi: MidiSender,
+    ) -> SunfishEditor {
         let styling = styling::load_default();
         SunfishEditor {
             open: false,
+            window: None,
+            size_sync: parameters.clone(),
             parameters: Owner::new(parameters),
-            subscriber: Owner::new(subscriber),
+            deltas: Owner::new(deltas),
+            midi: Owner::new(midi),
             styling,
+            preferences: Preferences::load(),
         }
     }
-}
-
-impl Editor for SunfishEditor {
-    /// Get the size of the editor window.
-    fn size(&self) -> (i32, i32) {
-        self.styling.size
-    }
-
-    /// Get the coordinates of the editor window.
-    fn position(&self) -> (i32, i32) {
-        (100, 100)
-    }
-
-    /// Editor idle call. Called by host.
-    fn idle(&mut self) {}
 
-    /// Called when the editor window is closed.
-    fn close(&mut self) {
-        // we have to manually drop the objects we allocated.
-        self.open = false;
+    /// Logical size of the editor window: as last resized (or restored with the plugin state),
+    /// or else the layout's own, times the UI scale.
+    pub fn window_size(&self) -> (i32, i32) {
+        let (width, height) = self
+            .size_sync
+            .editor_size()
+            .map_or(self.styling.size, |(width, height)| {
+                (width as i32, height as i32)
+            });
+        let ui_scale = self.preferences.ui_scale;
+        (
+            (width as f64 * ui_scale).round() as i32,
+            (height as f64 * ui_scale).round() as i32,
+        )
     }
 
-    /// Called when the editor window is opened. `window` is a platform dependent window pointer
-    /// (e.g. `HWND` on Windows, `NSView` (64-bit Cocoa) on OSX, `Window` on X11/Linux).
-    fn open(&mut self, parent: *mut c_void) -> bool {
+    /// Open the editor inside `parent`, a platform dependent window pointer (e.g. `HWND` on
+    /// Windows, `NSView` (64-bit Cocoa) on OSX, `Window` on X11/Linux).
+    pub fn open_parented(&mut self, parent: *mut c_void) -> bool {
         if self.open {
             return false;
         }
@@ -60,7 +72,9 @@ impl Editor for SunfishEditor {
 
         // TODO: Consolidate with standalone options.
         // Logical size.
-        let size = baseview::Size::new(self.styling.size.0 as f64, self.styling.size.1 as f64);
+        self.preferences = Preferences::load();
+        let (width, height) = self.window_size();
+        let size = baseview::Size::new(width as f64, height as f64);
 
         let options = baseview::WindowOpenOptions {
             title: "Sunfish Synthesizer".into(),
@@ -73,27 +87,83 @@ impl Editor for SunfishEditor {
             WindowScalePolicy::SystemScaleFactor => 1.0,
         };
 
+        // A window that was just closed may still be shutting down on its own thread (as on
+        // X11), holding on to the parameters until it's gone.
+        let (param_borrow, deltas_borrow, midi_borrow) = match (
+            self.parameters.try_borrow(),
+            self.deltas.try_borrow(),
+            self.midi.try_borrow(),
+        ) {
+            (Some(param_borrow), Some(deltas_borrow), Some(midi_borrow)) => {
+                (param_borrow, deltas_borrow, midi_borrow)
+            }
+            _ => {
+                log::warn!("Sunfish: the last editor window hasn't closed yet");
+                return false;
+            }
+        };
         let styling = self.styling.clone();
-        let param_borrow = self.parameters.borrow();
-        let subscriber_borrow = self.subscriber.borrow();
-
-        baseview::Window::open_parented(&ParentWindow(parent), options, move |window| {
-            window::SynthGui::create(
-                window,
-                &styling,
-                param_borrow,
-                subscriber_borrow,
-                size,
-                scaling,
-            )
-            .expect("Cannot create synth GUI")
-        });
+        let preferences = self.preferences.clone();
+
+        let window =
+            baseview::Window::open_parented(&ParentWindow(parent), options, move |window| {
+                window::EditorWindow::new(window::SynthGui::create(
+                    window,
+                    &styling,
+                    param_borrow,
+                    deltas_borrow,
+                    midi_borrow,
+                    preferences,
+                    size,
+                    scaling,
+                ))
+            });
+        self.window = Some(window);
+        self.open = true;
         true
     }
 
+    pub fn close_window(&mut self) {
+        // Hosts destroy the parent window after this, so close ours first; closing drops the
+        // GUI, which hands back the parameters for the next window.
+        if let Some(mut window) = self.window.take() {
+            window.close();
+        }
+        self.open = false;
+    }
+
+    pub fn is_window_open(&self) -> bool {
+        self.open
+    }
+}
+
+impl Editor for SunfishEditor {
+    /// Get the size of the editor window.
+    fn size(&self) -> (i32, i32) {
+        self.window_size()
+    }
+
+    /// Get the coordinates of the editor window.
+    fn position(&self) -> (i32, i32) {
+        (100, 100)
+    }
+
+    /// Editor idle call. Called by host.
+    fn idle(&mut self) {}
+
+    /// Called when the editor window is closed.
+    fn close(&mut self) {
+        self.close_window();
+    }
+
+    /// Called when the editor window is opened.
+    fn open(&mut self, parent: *mut c_void) -> bool {
+        self.open_parented(parent)
+    }
+
     /// Return whether the window is currently open.
     fn is_open(&mut self) -> bool {
-        self.open
+        self.is_window_open()
     }
 
     /// Set the knob mode for this editor (if supported by host).
@@ -1,23 +1,27 @@
 pub mod alignment;
 pub mod buffer_memory;
 pub mod buffers;
+pub mod context_menu;
 pub mod controls;
 pub mod coords;
 pub mod editor;
+pub mod preferences;
 pub mod shape_util;
 pub mod shapes;
 pub mod sprites;
 pub mod styling;
+pub mod text_entry;
 pub mod texture;
+pub mod tooltip;
 pub mod widgets;
 pub mod window;
 
-#[cfg(target_os = "macos")]
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
 pub fn editor_supported() -> bool {
     true
 }
 
-#[cfg(not(target_os = "macos"))]
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
 pub fn editor_supported() -> bool {
     false
 }
@@ -0,0 +1,168 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use iced_wgpu::wgpu;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+/// Bounds of the UI scale, relative to the layout's own size.
+pub const MIN_UI_SCALE: f64 = 0.75;
+pub const MAX_UI_SCALE: f64 = 2.0;
+/// How far the UI scale moves per step.
+pub const UI_SCALE_STEP: f64 = 0.25;
+/// Bounds of the drag sensitivity, relative to the usual.
+pub const MIN_DRAG_SENSITIVITY: f64 = 0.25;
+pub const MAX_DRAG_SENSITIVITY: f64 = 4.0;
+/// How far the drag sensitivity moves per step.
+pub const DRAG_SENSITIVITY_STEP: f64 = 0.25;
+
+const PREFERENCES_FILENAME: &str = "sunfish.ron";
+const PRESETS_FOLDER: &str = "presets";
+
+/// How often the editor may draw, at most.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum FrameRate {
+    Fps30,
+    Fps60,
+    Uncapped,
+}
+
+impl FrameRate {
+    /// The least time between frames, if there's a cap.
+    pub fn min_interval(self) -> Option<Duration> {
+        match self {
+            FrameRate::Fps30 => Some(Duration::from_secs_f64(1.0 / 30.0)),
+            FrameRate::Fps60 => Some(Duration::from_secs_f64(1.0 / 60.0)),
+            FrameRate::Uncapped => None,
+        }
+    }
+}
+
+/// How drawn frames reach the screen.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum PresentMode {
+    // Wait for the display's refresh (vsync).
+    Fifo,
+    // Show the newest frame at the refresh, without waiting for it to draw the next.
+    Mailbox,
+}
+
+impl PresentMode {
+    pub fn to_wgpu(self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        }
+    }
+}
+
+/// Settings that belong to the user rather than to a project, kept between sessions.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// How much larger (or smaller) to draw the editor, on top of the system's scale factor.
+    pub ui_scale: f64,
+    /// The name of the styling's color theme picked, if one has been.
+    pub theme: Option<String>,
+    pub frame_rate: FrameRate,
+    pub present_mode: PresentMode,
+    /// How far parameters move for the distance dragged (or scrolled), relative to the usual.
+    pub drag_sensitivity: f64,
+    /// Where the user's presets are kept, if not next to the preferences.
+    pub presets_folder: Option<PathBuf>,
+    /// Whether to show the FPS, voices playing and such in the editor's corner.
+    pub show_stats: bool,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences {
+            ui_scale: 1.0,
+            theme: None,
+            frame_rate: FrameRate::Fps60,
+            present_mode: PresentMode::Fifo,
+            drag_sensitivity: 1.0,
+            presets_folder: None,
+            show_stats: true,
+        }
+    }
+}
+
+impl Preferences {
+    /// Where the preferences are saved, if there's a config folder to put them in.
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join(PREFERENCES_FILENAME))
+    }
+
+    /// Where the user's presets are kept: their chosen folder, or next to the preferences.
+    pub fn presets_dir(&self) -> Option<PathBuf> {
+        self.presets_folder
+            .clone()
+            .or_else(|| config_dir().map(|dir| dir.join(PRESETS_FOLDER)))
+    }
+
+    /// The saved preferences, or the defaults if there are none (or they can't be read).
+    pub fn load() -> Self {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Self::default(),
+        };
+        let mut preferences: Self = match fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Ok(ron::de::from_str(&text)?))
+        {
+            Ok(preferences) => preferences,
+            Err(err) => {
+                log::warn!("Ignoring preferences in {:?}: {}", path, err);
+                return Self::default();
+            }
+        };
+        preferences.ui_scale = preferences.ui_scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE);
+        preferences.drag_sensitivity = preferences
+            .drag_sensitivity
+            .clamp(MIN_DRAG_SENSITIVITY, MAX_DRAG_SENSITIVITY);
+        preferences
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow!("No config folder"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, ron::ser::to_string_pretty(self, PrettyConfig::new())?)?;
+        Ok(())
+    }
+
+    /// Move the UI scale `steps` steps up (or down, if negative), within its bounds.
+    pub fn step_ui_scale(&mut self, steps: i32) {
+        self.ui_scale =
+            (self.ui_scale + steps as f64 * UI_SCALE_STEP).clamp(MIN_UI_SCALE, MAX_UI_SCALE);
+    }
+
+    /// Move the drag sensitivity `steps` steps up (or down, if negative), within its bounds.
+    pub fn step_drag_sensitivity(&mut self, steps: i32) {
+        self.drag_sensitivity = (self.drag_sensitivity + steps as f64 * DRAG_SENSITIVITY_STEP)
+            .clamp(MIN_DRAG_SENSITIVITY, MAX_DRAG_SENSITIVITY);
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("Sunfish"))
+}
+
+#[cfg(target_os = "macos")]
+fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join("Library/Application Support/Sunfish"))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("sunfish"))
+}
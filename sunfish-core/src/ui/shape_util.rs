@@ -101,6 +101,21 @@ pub fn rectangle_outline(
     screen_metrics: &ScreenMetrics,
     stroke_width: f32,
     color: &[f32; 3],
+) -> Buffers {
+    rectangles_outline(
+        std::slice::from_ref(rect),
+        screen_metrics,
+        stroke_width,
+        color,
+    )
+}
+
+/// The outlines of several rectangles, as one shape.
+pub fn rectangles_outline(
+    rects: &[Rect],
+    screen_metrics: &ScreenMetrics,
+    stroke_width: f32,
+    color: &[f32; 3],
 ) -> Buffers {
     let mut buffers: Buffers = tessellation::VertexBuffers::new();
 
@@ -117,31 +132,37 @@ pub fn rectangle_outline(
             screen_metrics,
         },
     );
-    stroke_tess
-        .tessellate_polygon(
-            Polygon {
-                points: &[
-                    point(rect.x1(), rect.y1()),
-                    point(rect.x2(), rect.y1()),
-                    point(rect.x2(), rect.y2()),
-                    point(rect.x1(), rect.y2()),
-                    point(rect.x1(), rect.y1()),
-                ],
-                closed: true,
-            },
-            &opts,
-            &mut vertex_builder,
-        )
-        .unwrap();
+    for rect in rects {
+        stroke_tess
+            .tessellate_polygon(
+                Polygon {
+                    points: &[
+                        point(rect.x1(), rect.y1()),
+                        point(rect.x2(), rect.y1()),
+                        point(rect.x2(), rect.y2()),
+                        point(rect.x1(), rect.y2()),
+                        point(rect.x1(), rect.y1()),
+                    ],
+                    closed: true,
+                },
+                &opts,
+                &mut vertex_builder,
+            )
+            .unwrap();
+    }
     buffers
 }
 
 pub fn rectangle_solid(rect: &Rect, screen_metrics: &ScreenMetrics) -> Buffers {
-    let box2d = Box2D::new(
-        euclid::point2(rect.x1(), rect.y1()),
-        euclid::point2(rect.x2(), rect.y2()),
-    );
+    rectangles_filled(std::slice::from_ref(rect), screen_metrics, &[1.0, 1.0, 1.0])
+}
 
+/// Several rectangles filled with `color`, as one shape.
+pub fn rectangles_filled(
+    rects: &[Rect],
+    screen_metrics: &ScreenMetrics,
+    color: &[f32; 3],
+) -> Buffers {
     let mut buffers: Buffers = tessellation::VertexBuffers::new();
 
     let mut fill_tess = FillTessellator::new();
@@ -150,14 +171,20 @@ pub fn rectangle_solid(rect: &Rect, screen_metrics: &ScreenMetrics) -> Buffers {
     let mut vertex_builder = BuffersBuilder::new(
         &mut buffers,
         ShapeVertexBuilder {
-            color: [1.0, 1.0, 1.0],
+            color: *color,
             screen_metrics,
         },
     );
     use lyon::tessellation::geometry_builder::*;
-    fill_tess
-        .tessellate_rectangle(&box2d.to_rect(), &opts, &mut vertex_builder)
-        .unwrap();
+    for rect in rects {
+        let box2d = Box2D::new(
+            euclid::point2(rect.x1(), rect.y1()),
+            euclid::point2(rect.x2(), rect.y2()),
+        );
+        fill_tess
+            .tessellate_rectangle(&box2d.to_rect(), &opts, &mut vertex_builder)
+            .unwrap();
+    }
     buffers
 }
 
@@ -196,6 +223,43 @@ pub fn line_segment(
     buffers
 }
 
+/// An open path through `points`. Miter joins keep the vertex count the same however the
+/// points move, so the shape can be updated in place.
+pub fn polyline(
+    points: &[(f32, f32)],
+    screen_metrics: &ScreenMetrics,
+    stroke_width: f32,
+    color: &[f32; 3],
+) -> Buffers {
+    let mut buffers: Buffers = tessellation::VertexBuffers::new();
+
+    let mut stroke_tess = StrokeTessellator::new();
+
+    let opts = StrokeOptions::default()
+        .with_tolerance(0.00005)
+        .with_line_width(stroke_width)
+        .with_line_join(LineJoin::MiterClip);
+    let mut vertex_builder = BuffersBuilder::new(
+        &mut buffers,
+        ShapeVertexBuilder {
+            color: *color,
+            screen_metrics,
+        },
+    );
+    let points: Vec<Point> = points.iter().map(|(x, y)| point(*x, *y)).collect();
+    stroke_tess
+        .tessellate_polygon(
+            Polygon {
+                points: &points,
+                closed: false,
+            },
+            &opts,
+            &mut vertex_builder,
+        )
+        .unwrap();
+    buffers
+}
+
 pub fn ellipse_outline(rect: &Rect, screen_metrics: &ScreenMetrics, stroke_width: f32) -> Buffers {
     let mut buffers: Buffers = tessellation::VertexBuffers::new();
 
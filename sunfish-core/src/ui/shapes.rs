@@ -76,7 +76,9 @@ impl ScreenMetrics {
         self.norm_y_to_corrected(y) * self.height_f32
     }
 
-    pub fn constrain_resize(&self, new_width: u32, new_height: u32, _ratio: f32) -> (u32, u32) {
+    /// The size nearest `new_width` x `new_height` with the given (width / height) `ratio`,
+    /// going by whichever side changed from this size.
+    pub fn constrain_resize(&self, new_width: u32, new_height: u32, ratio: f32) -> (u32, u32) {
         let new_width_f32 = new_width as f32;
         let new_height_f32 = new_height as f32;
         let new_ratio = new_width_f32 / new_height_f32;
@@ -101,9 +103,9 @@ impl ScreenMetrics {
         // preserve the ratio.
         // ratio = w / h
         // thus h = w / ratio
-        let correct_height = !same_width || new_ratio > self.ratio;
+        let correct_height = !same_width || new_ratio > ratio;
         if correct_height {
-            let corrected_height = (new_width_f32 / self.ratio).round() as u32;
+            let corrected_height = (new_width_f32 / ratio).round() as u32;
             let (w, h) = (new_width, corrected_height);
             log::info!(
                 "Corrected height: {}, new ratio: {}",
@@ -112,7 +114,7 @@ impl ScreenMetrics {
             );
             (w, h)
         } else {
-            let corrected_width = (new_height_f32 * self.ratio).round() as u32;
+            let corrected_width = (new_height_f32 * ratio).round() as u32;
             let (w, h) = (corrected_width, new_height);
             log::info!(
                 "Corrected width: {}, new ratio: {}",
@@ -124,7 +126,7 @@ impl ScreenMetrics {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct Color {
     pub r: f32,
     pub g: f32,
@@ -132,6 +134,12 @@ pub struct Color {
 }
 
 impl Color {
+    /// The color faded toward gray, as dimmed widgets are drawn.
+    pub fn dimmed(&self) -> Color {
+        let [r, g, b] = dim(self.to_array3());
+        Color { r, g, b }
+    }
+
     pub fn to_array3(&self) -> [f32; 3] {
         [self.r, self.g, self.b]
     }
@@ -153,6 +161,14 @@ impl ShapeVertex {
     }
 }
 
+/// The gray dimmed colors fade toward, and how far they go.
+const DIM_GRAY: f32 = 0.35;
+const DIM_AMOUNT: f32 = 0.6;
+
+fn dim(color: [f32; 3]) -> [f32; 3] {
+    color.map(|channel| channel + (DIM_GRAY - channel) * DIM_AMOUNT)
+}
+
 impl buffer_memory::GpuVertex for ShapeVertex {
     fn descriptor<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
@@ -172,6 +188,10 @@ impl buffer_memory::GpuVertex for ShapeVertex {
             ],
         }
     }
+
+    fn dimmed(&self) -> Self {
+        ShapeVertex::new(self.position, dim(self.color))
+    }
 }
 
 pub struct ShapeVertexBuilder<'a> {
@@ -206,6 +226,7 @@ impl<'a> lyon::tessellation::FillVertexConstructor<ShapeVertex> for ShapeVertexB
 pub fn create_pipeline(
     device: &wgpu::Device,
     swapchain_format: &wgpu::TextureFormat,
+    sample_count: u32,
 ) -> wgpu::RenderPipeline {
     let shape_vs_module =
         device.create_shader_module(&wgpu::include_spirv!("shader_shape.vert.spv"));
@@ -245,7 +266,7 @@ pub fn create_pipeline(
         },
         depth_stencil: None,
         multisample: wgpu::MultisampleState {
-            count: 1,
+            count: sample_count,
             mask: !0,
             alpha_to_coverage_enabled: false,
         },
@@ -257,6 +278,7 @@ pub struct ShapesBuilder<'a> {
     pub builder: buffer_memory::GpuShapeCollectionBuilder<ShapeVertex>,
     device: &'a wgpu::Device,
     swapchain_format: &'a wgpu::TextureFormat,
+    sample_count: u32,
 }
 
 impl<'a> ShapesBuilder<'a> {
@@ -264,11 +286,13 @@ impl<'a> ShapesBuilder<'a> {
         capacity: usize,
         device: &'a wgpu::Device,
         swapchain_format: &'a wgpu::TextureFormat,
+        sample_count: u32,
     ) -> Self {
         ShapesBuilder {
             builder: buffer_memory::GpuShapeCollectionBuilder::with_capacity(capacity),
             device,
             swapchain_format,
+            sample_count,
         }
     }
 
@@ -276,8 +300,13 @@ impl<'a> ShapesBuilder<'a> {
         self.builder.add(shape)
     }
 
+    /// How many shapes have been added so far.
+    pub fn count(&self) -> usize {
+        self.builder.count()
+    }
+
     pub fn build(self) -> Shapes {
-        let pipeline = create_pipeline(self.device, self.swapchain_format);
+        let pipeline = create_pipeline(self.device, self.swapchain_format, self.sample_count);
         let shapes = self.builder.build();
         let bufmem = buffer_memory::BufferMemory::new(self.device, pipeline, &shapes);
         Shapes { shapes, bufmem }
@@ -294,6 +323,14 @@ impl Shapes {
         self.shapes.update(index, vertices, indices)
     }
 
+    pub fn set_hidden(&mut self, index: usize, hidden: bool) {
+        self.shapes.set_hidden(index, hidden)
+    }
+
+    pub fn set_dimmed(&mut self, index: usize, dimmed: bool) {
+        self.shapes.set_dimmed(index, dimmed)
+    }
+
     pub fn render<'a>(&'a self, rpass: wgpu::RenderPass<'a>) -> wgpu::RenderPass<'a> {
         buffer_memory::render(&self.bufmem, rpass, None)
     }
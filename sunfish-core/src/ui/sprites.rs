@@ -1,11 +1,14 @@
+use anyhow::{anyhow, Result};
 use bytemuck::{Pod, Zeroable};
 use iced_wgpu::wgpu;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::mem;
 
 use crate::ui::buffer_memory::{
     self, BufferMemory, GpuShape, GpuShapeCollection, GpuShapeCollectionBuilder, GpuVertex,
 };
-use crate::ui::coords::UserVec2;
+use crate::ui::coords::{Rect, UserVec2};
 use crate::ui::shapes::ScreenMetrics;
 use crate::ui::texture;
 
@@ -58,6 +61,40 @@ impl GpuVertex for SpriteVertex {
     }
 }
 
+/// Where each image `atlas_packer.py` packed into a sprite image went, in pixels, by the
+/// image's file name without its extension.
+pub type SpriteRects = HashMap<String, Rect>;
+
+/// Where a sprite is in the sprite image, in pixels: a rect, or the name of an image packed
+/// into it, found in the styling's `sprite_rects`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SpriteRect {
+    Px(Rect),
+    Named(String),
+}
+
+impl SpriteRect {
+    /// Swap a name for the rect it stands for.
+    pub fn resolve(&mut self, rects: &SpriteRects) -> Result<()> {
+        if let SpriteRect::Named(name) = self {
+            let rect = rects
+                .get(name)
+                .ok_or_else(|| anyhow!("No sprite named {:?} in the sprite rects", name))?;
+            *self = SpriteRect::Px(rect.clone());
+        }
+        Ok(())
+    }
+
+    /// The rect in pixels; a name that wasn't resolved shows nothing.
+    pub fn pos(&self) -> [f32; 4] {
+        match self {
+            SpriteRect::Px(rect) => rect.pos,
+            SpriteRect::Named(_) => [0.0; 4],
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SpriteSource {
     pub src_rect: [f32; 4],
@@ -148,6 +185,7 @@ pub struct SpriteSheetBuilder<'a> {
     sprites: Vec<SpriteBuilder>,
     device: &'a wgpu::Device,
     swapchain_format: &'a wgpu::TextureFormat,
+    sample_count: u32,
     queue: &'a wgpu::Queue,
     filename: &'a str,
 }
@@ -156,6 +194,7 @@ impl<'a> SpriteSheetBuilder<'a> {
     pub fn new(
         device: &'a wgpu::Device,
         swapchain_format: &'a wgpu::TextureFormat,
+        sample_count: u32,
         queue: &'a wgpu::Queue,
         filename: &'a str,
     ) -> Self {
@@ -163,6 +202,7 @@ impl<'a> SpriteSheetBuilder<'a> {
             sprites: Vec::new(),
             device,
             swapchain_format,
+            sample_count,
             queue,
             filename,
         }
@@ -173,14 +213,41 @@ impl<'a> SpriteSheetBuilder<'a> {
         index
     }
 
-    pub fn build(mut self, screen_metrics: &ScreenMetrics) -> SpriteSheet {
-        let texture_bytes = std::fs::read(self.filename).unwrap();
+    /// How many sprites have been added so far.
+    pub fn count(&self) -> usize {
+        self.sprites.len()
+    }
+
+    /// Read the sheet's image.
+    pub fn load_texture(&self) -> Result<texture::Texture> {
+        let texture_bytes = std::fs::read(self.filename)?;
         log::info!("Loading spritesheet...");
-        let texture =
-            texture::Texture::from_bytes(self.device, self.queue, &texture_bytes, self.filename)
-                .unwrap();
-        let (pipeline, bind_group) =
-            create_pipeline_and_bind_group(self.device, self.swapchain_format, &texture);
+        texture::Texture::from_bytes(self.device, self.queue, &texture_bytes, self.filename)
+    }
+
+    /// Build the sheet with its image, as `load_texture` gave it. Without one (e.g. it
+    /// couldn't be read), the sheet draws nothing, but the rest of the editor still can.
+    pub fn build(
+        mut self,
+        texture: Option<texture::Texture>,
+        screen_metrics: &ScreenMetrics,
+    ) -> Result<SpriteSheet> {
+        let missing_image = texture.is_none();
+        let texture = match texture {
+            Some(texture) => texture,
+            None => texture::Texture::from_image(
+                self.device,
+                self.queue,
+                &image::DynamicImage::new_rgba8(1, 1),
+                Some("missing_spritesheet"),
+            )?,
+        };
+        let (pipeline, bind_group) = create_pipeline_and_bind_group(
+            self.device,
+            self.swapchain_format,
+            self.sample_count,
+            &texture,
+        );
 
         let mut gpu_shape_builder = GpuShapeCollectionBuilder::with_capacity(self.sprites.len());
         let mut sprites = Vec::with_capacity(self.sprites.len());
@@ -210,13 +277,14 @@ impl<'a> SpriteSheetBuilder<'a> {
         let shapes = gpu_shape_builder.build();
 
         let bufmem = BufferMemory::new(self.device, pipeline, &shapes);
-        SpriteSheet {
+        Ok(SpriteSheet {
             sprites,
             texture,
             shapes,
             bufmem,
             bind_group,
-        }
+            missing_image,
+        })
     }
 }
 
@@ -233,6 +301,8 @@ pub struct SpriteSheet {
     pub bufmem: BufferMemory<SpriteVertex>,
     // TODO: move this into BufferMemory?
     bind_group: wgpu::BindGroup,
+    // Built without its image, so there's nothing to draw.
+    missing_image: bool,
 }
 
 impl SpriteSheet {
@@ -269,7 +339,18 @@ impl SpriteSheet {
         }
     }
 
+    pub fn set_hidden(&mut self, index: usize, hidden: bool) {
+        if let Some(sprite) = self.sprites.get(index) {
+            self.shapes.set_hidden(sprite.shape_index, hidden);
+        } else {
+            log::warn!("Bad sprite index: {}", index);
+        }
+    }
+
     pub fn render<'a>(&'a self, rpass: wgpu::RenderPass<'a>) -> wgpu::RenderPass<'a> {
+        if self.missing_image {
+            return rpass;
+        }
         buffer_memory::render(&self.bufmem, rpass, Some(&self.bind_group))
     }
 }
@@ -277,6 +358,7 @@ impl SpriteSheet {
 pub fn create_pipeline_and_bind_group(
     device: &wgpu::Device,
     swapchain_format: &wgpu::TextureFormat,
+    sample_count: u32,
     texture: &texture::Texture,
 ) -> (wgpu::RenderPipeline, wgpu::BindGroup) {
     let vs_module = device.create_shader_module(&wgpu::include_spirv!("shader_sprite.vert.spv"));
@@ -357,7 +439,7 @@ pub fn create_pipeline_and_bind_group(
         },
         depth_stencil: None,
         multisample: wgpu::MultisampleState {
-            count: 1,
+            count: sample_count,
             mask: !0,
             alpha_to_coverage_enabled: false,
         },
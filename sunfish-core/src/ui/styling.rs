@@ -1,13 +1,21 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::SystemTime;
 
+use anyhow::{Context, Result};
 use ron::de::from_str;
 use serde::Deserialize;
 
-use crate::params::ParamsMeta;
+use crate::params::sync::Synchronizer;
+use crate::params::{EParam, NormalizedParams, ParamsMeta};
 use crate::ui::coords::Rect;
 use crate::ui::shapes::{Color, Polarity};
+use crate::ui::sprites::{SpriteRect, SpriteRects};
 use crate::ui::widgets;
-use crate::ui::widgets::{knob, panel, spinner, toggle, vslider};
+use crate::ui::widgets::{
+    dropdown, envelope, keyboard, knob, lfo_preview, panel, preset_browser, spinner, tab_bar,
+    toggle, vslider,
+};
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Styling {
@@ -15,13 +23,114 @@ pub struct Styling {
     pub background: Background,
     pub padding: (f32, f32),
     pub stylesheet_image: Option<String>,
+    // The rects file `atlas_packer.py` wrote along with the sprite image, in the assets folder.
+    // With it, sprites can give the name of the image they show instead of where it is.
+    #[serde(default)]
+    pub sprite_rects: Option<String>,
+    // A font file in the assets folder to draw texts in, instead of the bundled one.
+    #[serde(default)]
+    pub font: Option<String>,
+    // Samples per pixel for sprites and shapes, to smooth their edges; 1 (none) if unset.
+    #[serde(default)]
+    pub msaa_samples: Option<u32>,
+    // The user picks one; the first is the default.
+    #[serde(default)]
+    pub themes: Vec<Theme>,
+    // Shown on every page.
+    elements: Vec<Element>,
+    #[serde(default)]
+    pub pages: Vec<Page>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+/// Changes how widgets are drawn while a parameter's on (or off), e.g. dimming an oscillator's
+/// knobs while it's switched off. The widgets are redrawn whenever the parameter changes.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Rule {
+    pub param: EParam,
+    pub when: Switch,
+    pub effect: widgets::Effect,
+    pub widgets: Vec<widgets::WidgetId>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub enum Switch {
+    On,
+    Off,
+}
+
+impl Rule {
+    /// Whether the rule's effect applies, with the parameters as they are.
+    pub fn applies(&self, params: &Synchronizer) -> bool {
+        let on = params.params().read_parameter(&params.meta, self.param) >= 0.5;
+        on == (self.when == Switch::On)
+    }
+}
+
+/// The colors widgets are drawn in.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    // Knobs' value arcs, and the notch marking their value.
+    pub arc: Color,
+    pub notch: Color,
+    // Widgets' labels.
+    pub text: Color,
+    // The values shown on widgets, and dropdowns' options.
+    pub value_text: Color,
+    // Envelope and LFO curves.
+    pub line: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: "Default".to_string(),
+            arc: Color {
+                r: 0.7,
+                g: 0.7,
+                b: 0.7,
+            },
+            notch: Color {
+                r: 0.0429,
+                g: 0.0468,
+                b: 0.0507,
+            },
+            text: widgets::DEFAULT_TEXT_COLOR,
+            value_text: Color {
+                r: 0.30039,
+                g: 0.30039,
+                b: 0.3019,
+            },
+            line: Color {
+                r: 0.7,
+                g: 0.7,
+                b: 0.7,
+            },
+        }
+    }
+}
+
+/// A set of elements shown together, picked with a tab bar.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Page {
+    pub name: String,
+    // Pages without one use the styling's background.
+    #[serde(default)]
+    pub background: Option<Background>,
     elements: Vec<Element>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub enum Background {
-    Solid { color: Color },
-    Sprite { dest_rect: Rect, src_rect: Rect },
+    Solid {
+        color: Color,
+    },
+    Sprite {
+        dest_rect: Rect,
+        src_rect: SpriteRect,
+    },
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -29,11 +138,8 @@ pub enum Element {
     Knob {
         widget_id: widgets::WidgetId,
         rect: Rect,
-        arc_color: Color,
-        notch_color: Color,
         label: Option<widgets::Text>,
         value_text: widgets::Text,
-        value_text_color: Color,
         polarity: Option<Polarity>,
     },
     VSlider {
@@ -41,14 +147,19 @@ pub enum Element {
         rect: Rect,
         sprite: Option<vslider::VSliderSprite>,
         value_text: widgets::Text,
-        value_text_color: Color,
     },
     Spinner {
         widget_id: widgets::WidgetId,
         rect: Rect,
         label: Option<widgets::Text>,
         value_text: widgets::Text,
-        value_text_color: Color,
+    },
+    // Lists an enum parameter's options to pick from.
+    Dropdown {
+        widget_id: widgets::WidgetId,
+        rect: Rect,
+        label: Option<widgets::Text>,
+        value_text: widgets::Text,
     },
     Toggle {
         widget_id: widgets::WidgetId,
@@ -61,58 +172,265 @@ pub enum Element {
         color: Color,
         label: Option<widgets::Text>,
     },
+    // An envelope's A/D/S/R curve, edited by dragging its breakpoints.
+    Envelope {
+        envelope: envelope::AdsrEnv,
+        rect: Rect,
+        label: Option<widgets::Text>,
+        value_text: widgets::Text,
+    },
+    // An LFO's shape, scrolling by at its rate.
+    LfoPreview {
+        lfo: lfo_preview::LfoSlot,
+        rect: Rect,
+    },
+    // A momentary toggle that runs an action when pressed.
+    Button {
+        action: widgets::Action,
+        rect: Rect,
+        label: Option<widgets::Text>,
+        sprite: Option<toggle::ToggleSprite>,
+    },
+    // A piano keyboard to play notes with the mouse, starting at `first_note` (a C).
+    Keyboard {
+        rect: Rect,
+        first_note: u8,
+        octaves: u8,
+    },
+    // A tab for each page, to switch between them.
+    TabBar {
+        rect: Rect,
+        text_scale: f32,
+    },
+    // Lists the presets to load, `rows` at a time, with folders, categories and a search box
+    // above them.
+    PresetBrowser {
+        rect: Rect,
+        rows: usize,
+        text_scale: f32,
+    },
+}
+
+impl Styling {
+    /// The theme called `name`, or the first if there's none by that name (or no name).
+    pub fn theme(&self, name: Option<&str>) -> Theme {
+        self.themes
+            .iter()
+            .find(|theme| Some(theme.name.as_str()) == name)
+            .or_else(|| self.themes.first())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The theme after the one called `name`, going back to the first after the last.
+    pub fn next_theme(&self, name: Option<&str>) -> Option<&Theme> {
+        let next = self
+            .themes
+            .iter()
+            .position(|theme| Some(theme.name.as_str()) == name)
+            .map_or(0, |index| index + 1);
+        self.themes.get(next % self.themes.len().max(1))
+    }
+
+    /// How many samples per pixel to draw sprites and shapes with. Counts GPUs can't be relied
+    /// on to support fall back to 1.
+    pub fn sample_count(&self) -> u32 {
+        match self.msaa_samples {
+            None | Some(1) => 1,
+            Some(4) => 4,
+            Some(samples) => {
+                log::warn!("Unsupported MSAA sample count {}; using 1", samples);
+                1
+            }
+        }
+    }
+
+    /// Where the sprite image is, if the styling has one.
+    pub fn stylesheet_path(&self) -> Option<PathBuf> {
+        self.stylesheet_image
+            .as_ref()
+            .map(|filename| assets_folder().join(filename))
+    }
+
+    /// Where the sprite rects are, if the styling has them.
+    pub fn sprite_rects_path(&self) -> Option<PathBuf> {
+        self.sprite_rects
+            .as_ref()
+            .map(|filename| assets_folder().join(filename))
+    }
+
+    /// Swap the names sprites give for the rects in `sprite_rects`, so they're drawn from
+    /// wherever the atlas packer last put them.
+    fn resolve_sprite_rects(&mut self) -> Result<()> {
+        let rects: SpriteRects = match self.sprite_rects_path() {
+            Some(path) => {
+                let definitions = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Can't read the sprite rects {:?}", path))?;
+                from_str(&definitions)
+                    .with_context(|| format!("Can't parse the sprite rects {:?}", path))?
+            }
+            None => SpriteRects::new(),
+        };
+        let backgrounds = std::iter::once(&mut self.background).chain(
+            self.pages
+                .iter_mut()
+                .filter_map(|page| page.background.as_mut()),
+        );
+        for background in backgrounds {
+            if let Background::Sprite { src_rect, .. } = background {
+                src_rect.resolve(&rects)?;
+            }
+        }
+        let elements = self.elements.iter_mut().chain(
+            self.pages
+                .iter_mut()
+                .flat_map(|page| page.elements.iter_mut()),
+        );
+        for element in elements {
+            match element {
+                Element::Toggle {
+                    sprite: Some(sprite),
+                    ..
+                }
+                | Element::Button {
+                    sprite: Some(sprite),
+                    ..
+                } => sprite.resolve(&rects)?,
+                Element::VSlider {
+                    sprite: Some(sprite),
+                    ..
+                } => sprite.resolve(&rects)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Where the font is, if the styling has one.
+    pub fn font_path(&self) -> Option<PathBuf> {
+        self.font
+            .as_ref()
+            .map(|filename| assets_folder().join(filename))
+    }
+}
+
+fn assets_folder() -> PathBuf {
+    // Go up one folder
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .join("assets")
+}
+
+pub fn default_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("styling.ron")
 }
 
 pub fn load_default() -> Styling {
-    let styling_filename = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("styling.ron");
-    load_widgets_from_file(styling_filename.to_str().unwrap())
+    load_widgets_from_file(default_path().to_str().unwrap())
 }
 
 pub fn load_widgets_from_file(filename: &str) -> Styling {
-    let definitions = std::fs::read(filename).unwrap();
-    let definitions = std::str::from_utf8(&definitions).unwrap();
-    let styling: Styling = match from_str(definitions) {
-        Ok(x) => x,
+    match try_load_from_file(Path::new(filename)) {
+        Ok(styling) => styling,
         Err(e) => {
             panic!("Failed to load config: {}", e);
         }
-    };
+    }
+}
+
+/// Like `load_widgets_from_file`, but a styling that can't be read is an error rather than a
+/// panic; for reloading one that's being edited.
+pub fn try_load_from_file(path: &Path) -> Result<Styling> {
+    let definitions = std::fs::read_to_string(path)?;
+    let mut styling: Styling = from_str(&definitions)?;
+    styling.resolve_sprite_rects()?;
     println!(
         "GUI styles width={}, height={}",
         styling.size.0, styling.size.1
     );
-    styling
+    Ok(styling)
+}
+
+/// Spots changes to the styling, its sprite image and sprite rects, by when they were last
+/// modified.
+#[derive(Debug)]
+pub struct StylingWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl StylingWatcher {
+    pub fn new(styling_path: PathBuf, styling: &Styling) -> Self {
+        let files = std::iter::once(styling_path)
+            .chain(styling.stylesheet_path())
+            .chain(styling.sprite_rects_path())
+            .map(|path| {
+                let modified = modified_time(&path);
+                (path, modified)
+            })
+            .collect();
+        StylingWatcher { files }
+    }
+
+    /// Whether any of the files changed since the last call (or since being created).
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, last_modified) in self.files.iter_mut() {
+            let modified = modified_time(path);
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+// None if the file's missing, e.g. while an editor replaces it.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
 }
 
-pub fn create_widgets(def: &Styling, meta: Arc<ParamsMeta>) -> Vec<widgets::Widget> {
+pub fn create_widgets(
+    def: &Styling,
+    theme: &Theme,
+    presets_dir: Option<&Path>,
+    meta: Arc<ParamsMeta>,
+) -> Vec<widgets::Widget> {
     let mut widgets = vec![];
     let mut uniq_id = 0;
 
-    for elm in &def.elements {
-        match elm {
+    let page_elements =
+        def.pages.iter().enumerate().flat_map(|(page, page_def)| {
+            page_def.elements.iter().map(move |elm| (Some(page), elm))
+        });
+    for (page, elm) in def
+        .elements
+        .iter()
+        .map(|elm| (None, elm))
+        .chain(page_elements)
+    {
+        let mut widget = match elm {
             Element::Knob {
                 widget_id,
                 rect,
-                arc_color,
-                notch_color,
                 label,
                 value_text,
-                value_text_color,
                 polarity,
-            } => {
-                widgets.push(knob::Knob::new_widget(
-                    Arc::clone(&meta),
-                    *widget_id,
-                    rect.clone(),
-                    polarity.clone().unwrap_or(Polarity::Unipolar),
-                    0.0,
-                    arc_color.clone(),
-                    notch_color.clone(),
-                    label.clone(),
-                    value_text.clone(),
-                    value_text_color.clone(),
-                ));
-            }
+            } => knob::Knob::new_widget(
+                Arc::clone(&meta),
+                *widget_id,
+                rect.clone(),
+                polarity.clone().unwrap_or(Polarity::Unipolar),
+                0.0,
+                theme.arc.clone(),
+                theme.notch.clone(),
+                label.clone(),
+                value_text.clone(),
+                theme.value_text.clone(),
+            ),
             Element::Panel {
                 rect,
                 label,
@@ -120,63 +438,152 @@ pub fn create_widgets(def: &Styling, meta: Arc<ParamsMeta>) -> Vec<widgets::Widg
                 ..
             } => {
                 uniq_id += 1;
-                widgets.push(panel::Panel::new_widget(
+                panel::Panel::new_widget(
                     Arc::clone(&meta),
                     widgets::WidgetId::Unspecified { id: uniq_id },
                     rect.clone(),
                     label.clone(),
-                ));
+                )
             }
             Element::Spinner {
                 widget_id,
                 rect,
                 label,
                 value_text,
-                value_text_color,
-            } => {
-                widgets.push(spinner::Spinner::new_widget(
-                    Arc::clone(&meta),
-                    *widget_id,
-                    rect.clone(),
-                    0.0,
-                    label.clone(),
-                    value_text.clone(),
-                    value_text_color.clone(),
-                ));
-            }
+            } => spinner::Spinner::new_widget(
+                Arc::clone(&meta),
+                *widget_id,
+                rect.clone(),
+                0.0,
+                label.clone(),
+                value_text.clone(),
+                theme.value_text.clone(),
+            ),
+            Element::Dropdown {
+                widget_id,
+                rect,
+                label,
+                value_text,
+            } => dropdown::Dropdown::new_widget(
+                Arc::clone(&meta),
+                *widget_id,
+                rect.clone(),
+                0.0,
+                label.clone(),
+                value_text.clone(),
+                theme.value_text.clone(),
+            ),
             Element::Toggle {
                 widget_id,
                 rect,
                 label,
                 sprite,
+            } => toggle::Toggle::new_widget(
+                Arc::clone(&meta),
+                *widget_id,
+                rect.clone(),
+                0.0,
+                label.clone(),
+                sprite.clone(),
+            ),
+            Element::Button {
+                action,
+                rect,
+                label,
+                sprite,
+            } => toggle::Toggle::new_widget(
+                Arc::clone(&meta),
+                widgets::WidgetId::Action { action: *action },
+                rect.clone(),
+                0.0,
+                label.clone(),
+                sprite.clone(),
+            ),
+            Element::Envelope {
+                envelope,
+                rect,
+                label,
+                value_text,
+            } => envelope::Envelope::new_widget(
+                Arc::clone(&meta),
+                *envelope,
+                rect.clone(),
+                theme.line.clone(),
+                label.clone(),
+                value_text.clone(),
+                theme.value_text.clone(),
+            ),
+            Element::LfoPreview { lfo, rect } => {
+                uniq_id += 1;
+                lfo_preview::LfoPreview::new_widget(
+                    Arc::clone(&meta),
+                    widgets::WidgetId::Unspecified { id: uniq_id },
+                    rect.clone(),
+                    *lfo,
+                    theme.line.clone(),
+                )
+            }
+            Element::Keyboard {
+                rect,
+                first_note,
+                octaves,
             } => {
-                widgets.push(toggle::Toggle::new_widget(
+                uniq_id += 1;
+                keyboard::Keyboard::new_widget(
                     Arc::clone(&meta),
-                    *widget_id,
+                    widgets::WidgetId::Unspecified { id: uniq_id },
                     rect.clone(),
-                    0.0,
-                    label.clone(),
-                    sprite.clone(),
-                ));
+                    *first_note,
+                    *octaves,
+                )
             }
             Element::VSlider {
                 widget_id,
                 rect,
                 sprite,
                 value_text,
-                value_text_color,
+            } => vslider::VSlider::new_widget(
+                Arc::clone(&meta),
+                *widget_id,
+                rect.clone(),
+                0.0,
+                sprite.clone(),
+                value_text.clone(),
+                theme.value_text.clone(),
+            ),
+            Element::TabBar { rect, text_scale } => {
+                uniq_id += 1;
+                tab_bar::TabBar::new_widget(
+                    Arc::clone(&meta),
+                    widgets::WidgetId::Unspecified { id: uniq_id },
+                    rect.clone(),
+                    def.pages
+                        .iter()
+                        .map(|page_def| page_def.name.clone())
+                        .collect(),
+                    *text_scale,
+                )
+            }
+            Element::PresetBrowser {
+                rect,
+                rows,
+                text_scale,
             } => {
-                widgets.push(vslider::VSlider::new_widget(
+                uniq_id += 1;
+                preset_browser::PresetBrowser::new_widget(
                     Arc::clone(&meta),
-                    *widget_id,
+                    widgets::WidgetId::Unspecified { id: uniq_id },
                     rect.clone(),
-                    0.0,
-                    sprite.clone(),
-                    value_text.clone(),
-                    value_text_color.clone(),
-                ));
+                    *rows,
+                    *text_scale,
+                    theme.text.clone(),
+                    presets_dir,
+                )
             }
-        }
+        };
+        widget.page = page;
+        widget.label_color = theme.text.clone();
+        widgets.push(widget);
     }
 
     widgets
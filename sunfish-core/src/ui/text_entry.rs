@@ -0,0 +1,114 @@
+use keyboard_types::Key;
+
+use crate::params::EParam;
+use crate::ui::coords::Rect;
+use crate::ui::shapes::Color;
+use crate::ui::widgets::WidgetId;
+
+// The field is at least this wide, so a long value fits over a small knob.
+const TEXT_ENTRY_MIN_WIDTH: f32 = 0.1;
+const TEXT_ENTRY_HEIGHT: f32 = 0.03;
+pub const TEXT_ENTRY_SCALE: f32 = 0.016;
+pub const TEXT_ENTRY_COLOR: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+};
+// The color of text that the next key typed replaces.
+pub const TEXT_ENTRY_SELECTED_COLOR: Color = Color {
+    r: 0.2,
+    g: 0.4,
+    b: 0.9,
+};
+
+/// What a key press did to a text entry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntryKey {
+    Edited,
+    Commit,
+    Cancel,
+}
+
+/// A value being typed in over a widget.
+#[derive(Clone, Debug)]
+pub struct TextEntry {
+    pub id: WidgetId,
+    pub eparam: EParam,
+    pub text: String,
+    // Whether the text is the value shown when the entry opened, which typing replaces.
+    pub selected: bool,
+}
+
+impl TextEntry {
+    /// Start with the widget's current value, to be edited or typed over.
+    pub fn with_value(id: WidgetId, eparam: EParam, value: String) -> Self {
+        TextEntry {
+            id,
+            eparam,
+            text: value,
+            selected: true,
+        }
+    }
+
+    /// Start with the first character typed.
+    pub fn with_typed(id: WidgetId, eparam: EParam, typed: &str) -> Self {
+        TextEntry {
+            id,
+            eparam,
+            text: typed.to_string(),
+            selected: false,
+        }
+    }
+
+    pub fn on_key(&mut self, key: &Key) -> Option<EntryKey> {
+        match key {
+            Key::Character(typed) => {
+                if self.selected {
+                    self.text.clear();
+                    self.selected = false;
+                }
+                self.text.push_str(typed);
+                Some(EntryKey::Edited)
+            }
+            Key::Backspace => {
+                if self.selected {
+                    self.text.clear();
+                    self.selected = false;
+                } else {
+                    self.text.pop();
+                }
+                Some(EntryKey::Edited)
+            }
+            Key::Enter => Some(EntryKey::Commit),
+            Key::Escape => Some(EntryKey::Cancel),
+            _ => None,
+        }
+    }
+
+    /// The text shown, with a cursor at the end while typing.
+    pub fn display_text(&self) -> String {
+        if self.selected {
+            self.text.clone()
+        } else {
+            format!("{}|", self.text)
+        }
+    }
+
+    pub fn color(&self) -> &Color {
+        if self.selected {
+            &TEXT_ENTRY_SELECTED_COLOR
+        } else {
+            &TEXT_ENTRY_COLOR
+        }
+    }
+
+    /// Where the field goes, over the middle of the widget at `widget_rect`.
+    pub fn field_rect(widget_rect: &Rect) -> Rect {
+        Rect::centered_at(
+            widget_rect.mid_x(),
+            widget_rect.mid_y(),
+            widget_rect.width().max(TEXT_ENTRY_MIN_WIDTH),
+            TEXT_ENTRY_HEIGHT,
+        )
+    }
+}
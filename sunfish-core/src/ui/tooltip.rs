@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use crate::ui::coords::Rect;
+use crate::ui::shapes::Color;
+
+/// How long the mouse has to rest on a widget before its tooltip shows.
+pub const TOOLTIP_DELAY: Duration = Duration::from_millis(600);
+pub const TOOLTIP_SCALE: f32 = 0.014;
+pub const TOOLTIP_COLOR: Color = Color {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+};
+pub const TOOLTIP_OUTLINE_COLOR: [f32; 3] = [0.30039, 0.30039, 0.3019];
+pub const TOOLTIP_OUTLINE_WIDTH: f32 = 0.0015;
+// Space between the text and the tooltip's edge.
+pub const TOOLTIP_PADDING: f32 = 0.005;
+// Space between the tooltip and the widget below it.
+const TOOLTIP_GAP: f32 = 0.005;
+// Space between the value shown while dragging and the cursor below it, so it isn't covered.
+const DRAG_VALUE_GAP: f32 = 0.012;
+
+/// The full name and value of the parameter under the mouse, or the value being dragged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tooltip {
+    pub text: String,
+    // Middle of the bottom edge.
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Tooltip {
+    /// A tooltip just above the widget at `widget_rect`.
+    pub fn above(widget_rect: &Rect, text: String) -> Self {
+        Tooltip {
+            text,
+            x: widget_rect.mid_x(),
+            y: widget_rect.y1() - TOOLTIP_GAP,
+        }
+    }
+
+    /// A tooltip just above the cursor at `x`, `y`.
+    pub fn above_cursor(x: f32, y: f32, text: String) -> Self {
+        Tooltip {
+            text,
+            x,
+            y: y - DRAG_VALUE_GAP,
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::sync::Arc;
+
+use crate::params::ParamsMeta;
+use crate::ui::alignment::{HorizontalAlign, VerticalAlign};
+use crate::ui::buffer_memory::GpuShape;
+use crate::ui::coords::Rect;
+use crate::ui::shape_util;
+use crate::ui::shapes;
+use crate::ui::shapes::{Color, ScreenMetrics};
+use crate::ui::sprites;
+use crate::ui::window::ActiveMouseState;
+
+use crate::ui::widgets::{
+    LabelPosition, ShapeIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId,
+};
+
+const DROPDOWN_OUTLINE_COLOR: [f32; 3] = [0.30039, 0.30039, 0.3019];
+const DROPDOWN_OUTLINE_WIDTH: f32 = 0.0015;
+// Longer lists wrap into more columns, so they stay on screen.
+const DROPDOWN_MAX_ROWS: usize = 12;
+
+/// A parameter's current option; clicking opens a list of every option to pick from.
+#[derive(Debug)]
+pub struct Dropdown {
+    outline_index: ShapeIndex,
+    list_index: ShapeIndex,
+    selection_index: ShapeIndex,
+    open: bool,
+    // Normalized value and text of each option, found when the list opens.
+    options: Vec<(f64, String)>,
+    option_texts: Vec<Text>,
+    // The value picked by the last click.
+    picked: f64,
+    label: Option<Text>,
+    value_text: Text,
+    value_text_color: Color,
+}
+
+impl Dropdown {
+    pub fn new(label: Option<Text>, value_text: Text, value_text_color: Color) -> Self {
+        Dropdown {
+            outline_index: ShapeIndex(0),
+            list_index: ShapeIndex(0),
+            selection_index: ShapeIndex(0),
+            open: false,
+            options: vec![],
+            option_texts: vec![],
+            picked: 0.0,
+            label,
+            value_text,
+            value_text_color,
+        }
+    }
+
+    pub fn new_widget(
+        meta: Arc<ParamsMeta>,
+        id: WidgetId,
+        rect: Rect,
+        value: f64,
+        label: Option<Text>,
+        value_text: Text,
+        value_text_color: Color,
+    ) -> Widget {
+        let dropdown = Self::new(label, value_text, value_text_color);
+        Widget::new(meta, id, rect, value, WidgetClass::Dropdown(dropdown))
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // Where the option at `index` is listed, below the dropdown itself.
+    fn option_rect(rect: &Rect, index: usize) -> Rect {
+        let (column, row) = (index / DROPDOWN_MAX_ROWS, index % DROPDOWN_MAX_ROWS);
+        let x1 = rect.x1() + column as f32 * rect.width();
+        let y1 = rect.y2() + row as f32 * rect.height();
+        Rect::new(x1, y1, x1 + rect.width(), y1 + rect.height())
+    }
+
+    fn list_rect(rect: &Rect, count: usize) -> Rect {
+        let columns = count.div_ceil(DROPDOWN_MAX_ROWS);
+        let rows = count.min(DROPDOWN_MAX_ROWS);
+        Rect::new(
+            rect.x1(),
+            rect.y2(),
+            rect.x1() + columns as f32 * rect.width(),
+            rect.y2() + rows as f32 * rect.height(),
+        )
+    }
+
+    pub fn initialize(
+        &mut self,
+        rect: &Rect,
+        screen_metrics: &ScreenMetrics,
+        _spritesheet_builder: &mut sprites::SpriteSheetBuilder,
+        shapes_builder: &mut shapes::ShapesBuilder,
+    ) {
+        let mut add = |buffers: shapes::Buffers| {
+            let max_v_count = buffers.vertices.len();
+            let max_i_count = buffers.indices.len();
+            ShapeIndex(shapes_builder.add(GpuShape::from_lyon(buffers, max_v_count, max_i_count)))
+        };
+        self.outline_index = add(shape_util::rectangle_outline(
+            rect,
+            screen_metrics,
+            DROPDOWN_OUTLINE_WIDTH,
+            &DROPDOWN_OUTLINE_COLOR,
+        ));
+        // The list starts out closed; these only size its shapes.
+        self.list_index = add(shape_util::rectangle_solid(rect, screen_metrics));
+        self.selection_index = add(shape_util::rectangle_outline(
+            rect,
+            screen_metrics,
+            DROPDOWN_OUTLINE_WIDTH,
+            &DROPDOWN_OUTLINE_COLOR,
+        ));
+    }
+
+    /// Open the list, or if it's open, pick the option clicked (if any) and close it.
+    pub fn on_press(&mut self, rect: &Rect, mouse_state: &ActiveMouseState, value: f64) {
+        self.picked = value;
+        if !self.open {
+            self.open = true;
+            return;
+        }
+        let (x, y) = (mouse_state.start.x, mouse_state.start.y);
+        if let Some((option_value, _)) = self
+            .options
+            .iter()
+            .enumerate()
+            .find(|(index, _)| Self::option_rect(rect, *index).in_bounds(x, y))
+            .map(|(_, option)| option)
+        {
+            self.picked = *option_value;
+        }
+        self.open = false;
+    }
+
+    pub fn on_dragging(&self) -> f64 {
+        self.picked
+    }
+
+    pub fn update(&mut self, ctx: &mut UpdateContext, value: f64) {
+        if let WidgetId::Bound { eparam } = ctx.id {
+            let formatted = ctx.params.formatted_value(*eparam).unwrap_or_else(|err| {
+                log::error!("Failed to format value: {}", err);
+                String::new()
+            });
+            self.value_text.value = self.value_text.formatted(formatted);
+            // Options can depend on other parameters (e.g. synced LFO rates), so find them
+            // afresh each time the list opens.
+            if self.open && self.options.is_empty() {
+                self.options = ctx.params.params().options(ctx.meta, *eparam);
+            }
+        }
+        if !self.open {
+            self.options.clear();
+        }
+
+        let rect = ctx.rect;
+        self.option_texts = self
+            .options
+            .iter()
+            .enumerate()
+            .map(|(index, (_, text))| {
+                let option_rect = Self::option_rect(rect, index);
+                Text {
+                    value: text.clone(),
+                    pos: LabelPosition::Relative {
+                        x: option_rect.mid_x() - rect.x1(),
+                        y: option_rect.mid_y() - rect.y1(),
+                        h_align: HorizontalAlign::Center,
+                        v_align: VerticalAlign::Center,
+                    },
+                    scale: self.value_text.scale,
+                    color: self.value_text.color.clone(),
+                    format: None,
+                }
+            })
+            .collect();
+
+        if self.open {
+            let list = shape_util::rectangle_solid(
+                &Self::list_rect(rect, self.options.len()),
+                ctx.screen_metrics,
+            );
+            ctx.shapes
+                .update(self.list_index.0, &list.vertices, &list.indices);
+            match self.options.iter().position(|(option, _)| *option == value) {
+                Some(selected) => {
+                    let selection = shape_util::rectangle_outline(
+                        &Self::option_rect(rect, selected),
+                        ctx.screen_metrics,
+                        DROPDOWN_OUTLINE_WIDTH,
+                        &DROPDOWN_OUTLINE_COLOR,
+                    );
+                    ctx.shapes.update(
+                        self.selection_index.0,
+                        &selection.vertices,
+                        &selection.indices,
+                    );
+                }
+                None => ctx.shapes.update(self.selection_index.0, &[], &[]),
+            }
+        } else {
+            ctx.shapes.update(self.list_index.0, &[], &[]);
+            ctx.shapes.update(self.selection_index.0, &[], &[]);
+        }
+    }
+
+    pub fn on_resize(&mut self, ctx: &mut UpdateContext, value: f64) {
+        let outline = shape_util::rectangle_outline(
+            ctx.rect,
+            ctx.screen_metrics,
+            DROPDOWN_OUTLINE_WIDTH,
+            &DROPDOWN_OUTLINE_COLOR,
+        );
+        ctx.shapes
+            .update(self.outline_index.0, &outline.vertices, &outline.indices);
+        self.update(ctx, value);
+    }
+
+    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, label_color: &Color, mut f: F) {
+        if let Some(label) = &self.label {
+            f(label, label_color);
+        }
+        f(&self.value_text, &self.value_text_color);
+        for text in self.option_texts.iter() {
+            f(text, &self.value_text_color);
+        }
+    }
+}
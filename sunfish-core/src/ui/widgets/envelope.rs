@@ -0,0 +1,331 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::params::{EAdsrParams, EParam, ParamsMeta};
+use crate::ui::buffer_memory::GpuShape;
+use crate::ui::coords::Rect;
+use crate::ui::shape_util;
+use crate::ui::shapes;
+use crate::ui::shapes::{Color, ScreenMetrics};
+use crate::ui::sprites;
+use crate::ui::window::ActiveMouseState;
+
+use crate::ui::widgets::{ShapeIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId};
+
+const ENVELOPE_LINE_WIDTH: f32 = 0.002;
+const ENVELOPE_HANDLE_SIZE: f32 = 0.008;
+// How near (in window-relative units) a click has to be to grab a breakpoint.
+const ENVELOPE_GRAB_DISTANCE: f32 = 0.02;
+
+// The stages drawn, in the order their values are kept.
+const STAGES: [EAdsrParams; 4] = [
+    EAdsrParams::Attack,
+    EAdsrParams::Decay,
+    EAdsrParams::Sustain,
+    EAdsrParams::Release,
+];
+const ATTACK: usize = 0;
+const DECAY: usize = 1;
+const SUSTAIN: usize = 2;
+const RELEASE: usize = 3;
+
+/// Which envelope an envelope widget edits.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
+pub enum AdsrEnv {
+    Amp,
+    Mod,
+    Mod2,
+}
+
+impl AdsrEnv {
+    pub fn eparam(&self, param: EAdsrParams) -> EParam {
+        match self {
+            Self::Amp => EParam::AmpEnv(param),
+            Self::Mod => EParam::ModEnv(param),
+            Self::Mod2 => EParam::ModEnv2(param),
+        }
+    }
+
+    /// The envelope whose curve shows `eparam`, if any.
+    pub fn showing(eparam: &EParam) -> Option<Self> {
+        let (env, param) = match eparam {
+            EParam::AmpEnv(param) => (Self::Amp, param),
+            EParam::ModEnv(param) => (Self::Mod, param),
+            EParam::ModEnv2(param) => (Self::Mod2, param),
+            _ => return None,
+        };
+        stage_index(param).map(|_| env)
+    }
+}
+
+fn stage_index(param: &EAdsrParams) -> Option<usize> {
+    STAGES.iter().position(|stage| stage == param)
+}
+
+/// The points of the curve that can be dragged.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Breakpoint {
+    // End of the attack; drags sideways to set the attack time.
+    Peak,
+    // End of the decay; drags sideways to set the decay time, and up and down for the sustain.
+    DecayEnd,
+    // End of the release; drags sideways to set the release time.
+    ReleaseEnd,
+}
+
+impl Breakpoint {
+    const ALL: [Breakpoint; 3] = [Self::Peak, Self::DecayEnd, Self::ReleaseEnd];
+
+    fn stages(&self) -> &'static [usize] {
+        match self {
+            Self::Peak => &[ATTACK],
+            Self::DecayEnd => &[DECAY, SUSTAIN],
+            Self::ReleaseEnd => &[RELEASE],
+        }
+    }
+
+    // Index into the curve's points.
+    fn point_index(&self) -> usize {
+        match self {
+            Self::Peak => 1,
+            Self::DecayEnd => 2,
+            Self::ReleaseEnd => 4,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Envelope {
+    env: AdsrEnv,
+    curve_index: ShapeIndex,
+    handle_indices: [ShapeIndex; 3],
+    line_color: Color,
+    // Normalized attack, decay, sustain and release, as last synchronized.
+    values: [f64; 4],
+    // The values being dragged to, until the drag is done.
+    tentative_values: Option<[f64; 4]>,
+    grabbed: Option<Breakpoint>,
+    label: Option<Text>,
+    value_text: Text,
+    value_text_color: Color,
+}
+
+impl Envelope {
+    pub fn new(
+        env: AdsrEnv,
+        line_color: Color,
+        label: Option<Text>,
+        value_text: Text,
+        value_text_color: Color,
+    ) -> Self {
+        Envelope {
+            env,
+            curve_index: ShapeIndex(0),
+            handle_indices: [ShapeIndex(0); 3],
+            line_color,
+            values: [0.0; 4],
+            tentative_values: None,
+            grabbed: None,
+            label,
+            value_text,
+            value_text_color,
+        }
+    }
+
+    pub fn new_widget(
+        meta: Arc<ParamsMeta>,
+        env: AdsrEnv,
+        rect: Rect,
+        line_color: Color,
+        label: Option<Text>,
+        value_text: Text,
+        value_text_color: Color,
+    ) -> Widget {
+        let envelope = Self::new(env, line_color, label, value_text, value_text_color);
+        Widget::new(
+            meta,
+            WidgetId::Envelope { env },
+            rect,
+            0.0,
+            WidgetClass::Envelope(envelope),
+        )
+    }
+
+    /// The parameters the curve shows.
+    pub fn params(&self) -> impl Iterator<Item = EParam> + '_ {
+        STAGES.iter().map(move |stage| self.env.eparam(*stage))
+    }
+
+    pub fn set_value(&mut self, eparam: EParam, value: f64) {
+        if let Some(index) = self.params().position(|shown| shown == eparam) {
+            self.values[index] = value;
+        }
+    }
+
+    /// The parameters edited by the current (or last) drag.
+    pub fn edited_params(&self) -> impl Iterator<Item = EParam> + '_ {
+        self.grabbed
+            .iter()
+            .flat_map(|breakpoint| breakpoint.stages())
+            .map(move |index| self.env.eparam(STAGES[*index]))
+    }
+
+    /// The parameters edited by the current (or last) drag, with the values dragged to.
+    pub fn edited_values(&self) -> impl Iterator<Item = (EParam, f64)> + '_ {
+        let values = self.tentative_values.unwrap_or(self.values);
+        self.grabbed
+            .iter()
+            .flat_map(|breakpoint| breakpoint.stages())
+            .map(move |index| (self.env.eparam(STAGES[*index]), values[*index]))
+    }
+
+    // Attack, decay and release each take up to a quarter of the width, and sustain holds for
+    // the quarter between decay and release.
+    fn curve_points(rect: &Rect, values: &[f64; 4]) -> [(f32, f32); 5] {
+        let quarter = rect.width() / 4.0;
+        let peak_x = rect.x1() + values[ATTACK] as f32 * quarter;
+        let decay_x = peak_x + values[DECAY] as f32 * quarter;
+        let sustain_y = rect.y2() - values[SUSTAIN] as f32 * rect.height();
+        let release_x = decay_x + quarter;
+        let end_x = release_x + values[RELEASE] as f32 * quarter;
+        [
+            (rect.x1(), rect.y2()),
+            (peak_x, rect.y1()),
+            (decay_x, sustain_y),
+            (release_x, sustain_y),
+            (end_x, rect.y2()),
+        ]
+    }
+
+    fn handle_rect(point: &(f32, f32)) -> Rect {
+        Rect::centered_at(point.0, point.1, ENVELOPE_HANDLE_SIZE, ENVELOPE_HANDLE_SIZE)
+    }
+
+    fn shapes(
+        &self,
+        rect: &Rect,
+        screen_metrics: &ScreenMetrics,
+    ) -> (shapes::Buffers, Vec<shapes::Buffers>) {
+        let values = self.tentative_values.unwrap_or(self.values);
+        let points = Self::curve_points(rect, &values);
+        let color = self.line_color.to_array3();
+        let curve = shape_util::polyline(&points, screen_metrics, ENVELOPE_LINE_WIDTH, &color);
+        let handles = Breakpoint::ALL
+            .iter()
+            .map(|breakpoint| {
+                shape_util::rectangle_outline(
+                    &Self::handle_rect(&points[breakpoint.point_index()]),
+                    screen_metrics,
+                    ENVELOPE_LINE_WIDTH,
+                    &color,
+                )
+            })
+            .collect();
+        (curve, handles)
+    }
+
+    pub fn initialize(
+        &mut self,
+        rect: &Rect,
+        screen_metrics: &ScreenMetrics,
+        _spritesheet_builder: &mut sprites::SpriteSheetBuilder,
+        shapes_builder: &mut shapes::ShapesBuilder,
+    ) {
+        let (vmargin, imargin) = (10, 10);
+        let (curve, handles) = self.shapes(rect, screen_metrics);
+        self.curve_index = ShapeIndex({
+            let max_v_count = curve.vertices.len() + vmargin;
+            let max_i_count = curve.indices.len() + imargin;
+            shapes_builder.add(GpuShape::from_lyon(curve, max_v_count, max_i_count))
+        });
+        for (handle_index, handle) in self.handle_indices.iter_mut().zip(handles) {
+            let max_v_count = handle.vertices.len();
+            let max_i_count = handle.indices.len();
+            *handle_index = ShapeIndex(shapes_builder.add(GpuShape::from_lyon(
+                handle,
+                max_v_count,
+                max_i_count,
+            )));
+        }
+    }
+
+    /// Pick the breakpoint nearest the start of a drag, if it's near enough.
+    pub fn grab(&mut self, rect: &Rect, mouse_state: &ActiveMouseState) {
+        let points = Self::curve_points(rect, &self.values);
+        let distance = |breakpoint: &Breakpoint| {
+            let (x, y) = points[breakpoint.point_index()];
+            (x - mouse_state.start.x).hypot(y - mouse_state.start.y)
+        };
+        self.grabbed = Breakpoint::ALL
+            .into_iter()
+            .filter(|breakpoint| distance(breakpoint) <= ENVELOPE_GRAB_DISTANCE)
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)));
+    }
+
+    pub fn on_dragging(&mut self, rect: &Rect, mouse_state: &ActiveMouseState) {
+        let breakpoint = match self.grabbed {
+            Some(breakpoint) => breakpoint,
+            None => return,
+        };
+        let dx = ((mouse_state.pos.x - mouse_state.start.x) / (rect.width() / 4.0)) as f64;
+        let dy = ((mouse_state.start.y - mouse_state.pos.y) / rect.height()) as f64;
+        let mut values = self.values;
+        let mut nudge = |index: usize, delta: f64| {
+            values[index] = (self.values[index] + delta).min(1.0).max(0.0);
+        };
+        match breakpoint {
+            Breakpoint::Peak => nudge(ATTACK, dx),
+            Breakpoint::DecayEnd => {
+                nudge(DECAY, dx);
+                nudge(SUSTAIN, dy);
+            }
+            Breakpoint::ReleaseEnd => nudge(RELEASE, dx),
+        }
+        self.tentative_values = Some(values);
+    }
+
+    pub fn on_drag_done(&mut self) {
+        if let Some(values) = self.tentative_values.take() {
+            self.values = values;
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut UpdateContext) {
+        let (curve, handles) = self.shapes(ctx.rect, ctx.screen_metrics);
+        ctx.shapes
+            .update(self.curve_index.0, &curve.vertices, &curve.indices);
+        for (handle_index, handle) in self.handle_indices.iter().zip(handles) {
+            ctx.shapes
+                .update(handle_index.0, &handle.vertices, &handle.indices);
+        }
+
+        // Show the values being dragged.
+        self.value_text.value = if self.tentative_values.is_some() {
+            let texts: Vec<String> = self
+                .edited_params()
+                .map(|eparam| {
+                    let formatted = ctx.params.formatted_value(eparam).unwrap_or_else(|err| {
+                        log::error!("Failed to format value: {}", err);
+                        String::new()
+                    });
+                    self.value_text.formatted(formatted)
+                })
+                .collect();
+            texts.join(" / ")
+        } else {
+            String::new()
+        };
+    }
+
+    pub fn on_resize(&mut self, ctx: &mut UpdateContext) {
+        self.update(ctx);
+    }
+
+    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, mut f: F) {
+        if let Some(label) = &self.label {
+            f(label, &self.value_text_color);
+        }
+        f(&self.value_text, &self.value_text_color);
+    }
+}
@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use crate::params::ParamsMeta;
+use crate::ui::buffer_memory::GpuShape;
+use crate::ui::coords::Rect;
+use crate::ui::shape_util;
+use crate::ui::shapes;
+use crate::ui::shapes::{Color, ScreenMetrics};
+use crate::ui::sprites;
+
+use crate::ui::widgets::{ShapeIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId};
+
+const KEYBOARD_OUTLINE_COLOR: [f32; 3] = [0.30039, 0.30039, 0.3019];
+const KEYBOARD_OUTLINE_WIDTH: f32 = 0.0015;
+const KEYBOARD_WHITE_KEY_COLOR: [f32; 3] = [0.95, 0.95, 0.95];
+const KEYBOARD_BLACK_KEY_COLOR: [f32; 3] = [0.1, 0.1, 0.1];
+const KEYBOARD_HELD_KEY_COLOR: [f32; 3] = [0.95, 0.6, 0.1];
+// Black keys' size, relative to the white keys.
+const BLACK_KEY_WIDTH: f32 = 0.6;
+const BLACK_KEY_HEIGHT: f32 = 0.6;
+// Semitones above C of each of an octave's white keys.
+const WHITE_KEY_SEMITONES: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+// Which white keys have a black key just above them (C, D, F, G and A).
+const BLACK_KEY_ABOVE: [bool; 7] = [true, true, false, true, true, true, false];
+
+/// A piano keyboard for auditioning sounds with the mouse; the window plays the key held.
+#[derive(Debug)]
+pub struct Keyboard {
+    // The lowest key, which should be a C.
+    first_note: u8,
+    octaves: u8,
+    white_index: ShapeIndex,
+    outline_index: ShapeIndex,
+    black_index: ShapeIndex,
+    held_index: ShapeIndex,
+    held: Option<u8>,
+}
+
+impl Keyboard {
+    pub fn new(first_note: u8, octaves: u8) -> Self {
+        Keyboard {
+            first_note,
+            octaves,
+            white_index: ShapeIndex(0),
+            outline_index: ShapeIndex(0),
+            black_index: ShapeIndex(0),
+            held_index: ShapeIndex(0),
+            held: None,
+        }
+    }
+
+    pub fn new_widget(
+        meta: Arc<ParamsMeta>,
+        id: WidgetId,
+        rect: Rect,
+        first_note: u8,
+        octaves: u8,
+    ) -> Widget {
+        let keyboard = Self::new(first_note, octaves);
+        Widget::new(meta, id, rect, 0.0, WidgetClass::Keyboard(keyboard))
+    }
+
+    // Every octave, and the C that tops off the last one.
+    fn white_key_count(&self) -> usize {
+        self.octaves as usize * WHITE_KEY_SEMITONES.len() + 1
+    }
+
+    fn white_keys(&self, rect: &Rect) -> Vec<(u8, Rect)> {
+        let count = self.white_key_count();
+        let width = rect.width() / count as f32;
+        (0..count)
+            .map(|index| {
+                let octave = (index / WHITE_KEY_SEMITONES.len()) as u8;
+                let note = self.first_note
+                    + 12 * octave
+                    + WHITE_KEY_SEMITONES[index % WHITE_KEY_SEMITONES.len()];
+                let x1 = rect.x1() + index as f32 * width;
+                (note, Rect::new(x1, rect.y1(), x1 + width, rect.y2()))
+            })
+            .collect()
+    }
+
+    fn black_keys(&self, rect: &Rect) -> Vec<(u8, Rect)> {
+        let white_keys = self.white_keys(rect);
+        let width = rect.width() / white_keys.len() as f32 * BLACK_KEY_WIDTH;
+        let y2 = rect.y1() + rect.height() * BLACK_KEY_HEIGHT;
+        white_keys
+            .iter()
+            .take(white_keys.len() - 1)
+            .enumerate()
+            .filter(|(index, _)| BLACK_KEY_ABOVE[index % BLACK_KEY_ABOVE.len()])
+            .map(|(_, (note, white_rect))| {
+                let x = white_rect.x2();
+                (
+                    note + 1,
+                    Rect::new(x - width / 2.0, rect.y1(), x + width / 2.0, y2),
+                )
+            })
+            .collect()
+    }
+
+    /// The key at `x`, `y`, if any. Black keys sit on top of the white ones.
+    pub fn note_at(&self, rect: &Rect, x: f32, y: f32) -> Option<u8> {
+        if !rect.in_bounds(x, y) {
+            return None;
+        }
+        self.black_keys(rect)
+            .into_iter()
+            .chain(self.white_keys(rect))
+            .find(|(_, key_rect)| key_rect.in_bounds(x, y))
+            .map(|(note, _)| note)
+    }
+
+    pub fn set_held(&mut self, note: Option<u8>) {
+        self.held = note;
+    }
+
+    // Where to mark the held key: a black key whole, or a white key's part below the black keys.
+    fn held_rect(&self, rect: &Rect) -> Option<Rect> {
+        let held = self.held?;
+        if let Some((_, black_rect)) = self
+            .black_keys(rect)
+            .into_iter()
+            .find(|(note, _)| *note == held)
+        {
+            return Some(black_rect);
+        }
+        self.white_keys(rect)
+            .into_iter()
+            .find(|(note, _)| *note == held)
+            .map(|(_, white_rect)| {
+                let y1 = rect.y1() + rect.height() * BLACK_KEY_HEIGHT;
+                Rect::new(white_rect.x1(), y1, white_rect.x2(), white_rect.y2())
+            })
+    }
+
+    fn key_rects(keys: Vec<(u8, Rect)>) -> Vec<Rect> {
+        keys.into_iter().map(|(_, key_rect)| key_rect).collect()
+    }
+
+    pub fn initialize(
+        &mut self,
+        rect: &Rect,
+        screen_metrics: &ScreenMetrics,
+        _spritesheet_builder: &mut sprites::SpriteSheetBuilder,
+        shapes_builder: &mut shapes::ShapesBuilder,
+    ) {
+        let mut add = |buffers: shapes::Buffers| {
+            let max_v_count = buffers.vertices.len();
+            let max_i_count = buffers.indices.len();
+            ShapeIndex(shapes_builder.add(GpuShape::from_lyon(buffers, max_v_count, max_i_count)))
+        };
+        let white_rects = Self::key_rects(self.white_keys(rect));
+        self.white_index = add(shape_util::rectangles_filled(
+            &white_rects,
+            screen_metrics,
+            &KEYBOARD_WHITE_KEY_COLOR,
+        ));
+        self.outline_index = add(shape_util::rectangles_outline(
+            &white_rects,
+            screen_metrics,
+            KEYBOARD_OUTLINE_WIDTH,
+            &KEYBOARD_OUTLINE_COLOR,
+        ));
+        self.black_index = add(shape_util::rectangles_filled(
+            &Self::key_rects(self.black_keys(rect)),
+            screen_metrics,
+            &KEYBOARD_BLACK_KEY_COLOR,
+        ));
+        // No key is held yet; this only sizes the shape.
+        self.held_index = add(shape_util::rectangles_filled(
+            std::slice::from_ref(rect),
+            screen_metrics,
+            &KEYBOARD_HELD_KEY_COLOR,
+        ));
+    }
+
+    pub fn update(&mut self, ctx: &mut UpdateContext) {
+        match self.held_rect(ctx.rect) {
+            Some(held_rect) => {
+                let held = shape_util::rectangles_filled(
+                    std::slice::from_ref(&held_rect),
+                    ctx.screen_metrics,
+                    &KEYBOARD_HELD_KEY_COLOR,
+                );
+                ctx.shapes
+                    .update(self.held_index.0, &held.vertices, &held.indices);
+            }
+            None => ctx.shapes.update(self.held_index.0, &[], &[]),
+        }
+    }
+
+    pub fn on_resize(&mut self, ctx: &mut UpdateContext) {
+        let white_rects = Self::key_rects(self.white_keys(ctx.rect));
+        let white = shape_util::rectangles_filled(
+            &white_rects,
+            ctx.screen_metrics,
+            &KEYBOARD_WHITE_KEY_COLOR,
+        );
+        ctx.shapes
+            .update(self.white_index.0, &white.vertices, &white.indices);
+        let outline = shape_util::rectangles_outline(
+            &white_rects,
+            ctx.screen_metrics,
+            KEYBOARD_OUTLINE_WIDTH,
+            &KEYBOARD_OUTLINE_COLOR,
+        );
+        ctx.shapes
+            .update(self.outline_index.0, &outline.vertices, &outline.indices);
+        let black = shape_util::rectangles_filled(
+            &Self::key_rects(self.black_keys(ctx.rect)),
+            ctx.screen_metrics,
+            &KEYBOARD_BLACK_KEY_COLOR,
+        );
+        ctx.shapes
+            .update(self.black_index.0, &black.vertices, &black.indices);
+        self.update(ctx);
+    }
+
+    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, _f: F) {}
+}
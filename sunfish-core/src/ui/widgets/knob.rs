@@ -10,7 +10,7 @@ use crate::ui::sprites;
 use crate::ui::window::ActiveMouseState;
 
 use crate::ui::widgets::{
-    self, ShapeIndex, SpriteIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId,
+    ShapeIndex, SpriteIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId,
 };
 
 const KNOB_DEBUG_OUTLINE: bool = false;
@@ -18,6 +18,14 @@ const KNOB_DEBUG_OUTLINE_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
 
 const KNOB_OUTLINE_WIDTH: f32 = 0.001;
 const KNOB_ARC_WIDTH: f32 = 0.001;
+// The modulation arc is drawn just outside the value arc.
+const KNOB_MOD_ARC_RADIUS: f32 = 1.1;
+const KNOB_MOD_ARC_WIDTH: f32 = 0.002;
+const KNOB_MOD_ARC_COLOR: Color = Color {
+    r: 0.95,
+    g: 0.6,
+    b: 0.1,
+};
 
 #[derive(Debug)]
 pub struct Knob {
@@ -27,6 +35,9 @@ pub struct Knob {
     notch_color: Color,
     _sprite_index: SpriteIndex,
     arc_index: ShapeIndex,
+    mod_arc_index: ShapeIndex,
+    // The modulated value last drawn, if the parameter is modulated.
+    modulated_value: Option<f64>,
     inner_notch_index: ShapeIndex,
     outline_index: ShapeIndex,
     _circle_index: ShapeIndex,
@@ -55,6 +66,8 @@ impl Knob {
             notch_color,
             _sprite_index: SpriteIndex(0),
             arc_index: ShapeIndex(0),
+            mod_arc_index: ShapeIndex(0),
+            modulated_value: None,
             inner_notch_index: ShapeIndex(0),
             outline_index: ShapeIndex(0),
             _circle_index: ShapeIndex(0),
@@ -90,9 +103,9 @@ impl Knob {
         Widget::new(meta, id, rect, value, WidgetClass::Knob(knob))
     }
 
-    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, mut f: F) {
+    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, label_color: &Color, mut f: F) {
         if let Some(label) = &self.label {
-            f(label, &widgets::DEFAULT_TEXT_COLOR);
+            f(label, label_color);
         }
         f(&self.value_text, &self.value_text_color);
     }
@@ -124,6 +137,34 @@ impl Knob {
         }
     }
 
+    /// An arc from the user's value to the modulated value, showing how far modulation moves
+    /// the parameter.
+    fn create_mod_arc(arc: &shape_util::Arc, value: f64, modulated_value: f64) -> shape_util::Arc {
+        let arc_range = arc.max_angle - arc.min_angle;
+        let (from, to) = if modulated_value < value {
+            (modulated_value, value)
+        } else {
+            (value, modulated_value)
+        };
+        shape_util::Arc {
+            x: arc.x,
+            y: arc.y,
+            radius: arc.radius * KNOB_MOD_ARC_RADIUS,
+            amount: 1.0,
+            min_angle: arc.min_angle + from as f32 * arc_range,
+            max_angle: arc.min_angle + to as f32 * arc_range,
+            color: KNOB_MOD_ARC_COLOR,
+            stroke_width: KNOB_MOD_ARC_WIDTH,
+            polarity: Polarity::Unipolar,
+        }
+    }
+
+    /// Whether the knob shows modulation, or needs to stop showing it, and so has to be
+    /// redrawn every frame.
+    pub fn follows_modulation(&self, modulated_value: Option<f64>) -> bool {
+        self.modulated_value.is_some() || modulated_value.is_some()
+    }
+
     fn create_notch(
         screen_metrics: &ScreenMetrics,
         arc: &shape_util::Arc,
@@ -201,6 +242,17 @@ impl Knob {
                 shapes_builder.add(GpuShape::from_lyon(buffers, max_v_count, max_i_count))
             });
         }
+        self.mod_arc_index = ShapeIndex({
+            let max_mod_arc_buf = Self::create_mod_arc(&max_arc, 0.0, 1.0).render(screen_metrics);
+            let max_v_count = max_mod_arc_buf.vertices.len() + vmargin;
+            let max_i_count = max_mod_arc_buf.indices.len() + imargin;
+            // Hidden until the parameter is modulated; this only sizes the shape.
+            shapes_builder.add(GpuShape::from_lyon(
+                max_mod_arc_buf,
+                max_v_count,
+                max_i_count,
+            ))
+        });
         self.inner_notch_index = ShapeIndex({
             let line_segment = Self::create_notch(screen_metrics, &max_arc, 1.0, &self.notch_color);
             let max_v_count = line_segment.vertices.len() + vmargin;
@@ -264,9 +316,27 @@ impl Knob {
             &line_segment.indices,
         );
 
-        // Update value label.
+        // Update value label and modulation.
         if let WidgetId::Bound { eparam } = ctx.id {
-            self.value_text.value = ctx.params.formatted_value(*eparam);
+            self.modulated_value = ctx.params.modulated_value(*eparam);
+            match self.modulated_value {
+                Some(modulated_value) => {
+                    let mod_arc_bufs = Self::create_mod_arc(&self.arc, value, modulated_value)
+                        .render(ctx.screen_metrics);
+                    ctx.shapes.update(
+                        self.mod_arc_index.0,
+                        &mod_arc_bufs.vertices,
+                        &mod_arc_bufs.indices,
+                    );
+                }
+                None => ctx.shapes.update(self.mod_arc_index.0, &[], &[]),
+            }
+
+            let formatted = ctx.params.formatted_value(*eparam).unwrap_or_else(|err| {
+                log::error!("Failed to format value: {}", err);
+                String::new()
+            });
+            self.value_text.value = self.value_text.formatted(formatted);
         }
     }
 
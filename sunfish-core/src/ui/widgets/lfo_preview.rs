@@ -0,0 +1,150 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde::Deserialize;
+
+use crate::lfo::{Lfo, LfoShape};
+use crate::params::{LfoParams, Params, ParamsMeta};
+use crate::ui::buffer_memory::GpuShape;
+use crate::ui::coords::Rect;
+use crate::ui::shape_util;
+use crate::ui::shapes;
+use crate::ui::shapes::{Color, ScreenMetrics};
+use crate::ui::sprites;
+
+use crate::ui::widgets::{ShapeIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId};
+
+const LFO_PREVIEW_LINE_WIDTH: f32 = 0.0015;
+// How many points the drawn shape is made of.
+const LFO_PREVIEW_POINTS: usize = 96;
+// How long a stretch of the LFO is shown, in seconds.
+const LFO_PREVIEW_SECONDS: f64 = 1.0;
+// Faster rates are drawn at this one; past it, the shape would blur into a smear anyway.
+const LFO_PREVIEW_MAX_DRAWN_HZ: f64 = 8.0;
+
+/// Which LFO a preview shows.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
+pub enum LfoSlot {
+    Lfo1,
+    Lfo2,
+}
+
+impl LfoSlot {
+    fn params<'a>(&self, params: &'a Params) -> &'a LfoParams {
+        match self {
+            Self::Lfo1 => &params.lfo1,
+            Self::Lfo2 => &params.lfo2,
+        }
+    }
+}
+
+/// Draws an LFO's shape scrolling by at its current rate, so its speed shows at a glance.
+#[derive(Debug)]
+pub struct LfoPreview {
+    lfo: LfoSlot,
+    shape_index: ShapeIndex,
+    line_color: Color,
+    started: Instant,
+}
+
+impl LfoPreview {
+    pub fn new(lfo: LfoSlot, line_color: Color) -> Self {
+        LfoPreview {
+            lfo,
+            shape_index: ShapeIndex(0),
+            line_color,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn new_widget(
+        meta: Arc<ParamsMeta>,
+        id: WidgetId,
+        rect: Rect,
+        lfo: LfoSlot,
+        line_color: Color,
+    ) -> Widget {
+        let preview = Self::new(lfo, line_color);
+        Widget::new(meta, id, rect, 0.0, WidgetClass::LfoPreview(preview))
+    }
+
+    // The points of the shape `elapsed` seconds in, scrolling from right to left.
+    fn points(
+        rect: &Rect,
+        shape: LfoShape,
+        rate_hz: f64,
+        phase: f64,
+        elapsed: f64,
+    ) -> Vec<(f32, f32)> {
+        let drawn_hz = rate_hz.min(LFO_PREVIEW_MAX_DRAWN_HZ);
+        let start = elapsed * drawn_hz + phase;
+        (0..LFO_PREVIEW_POINTS)
+            .map(|index| {
+                let along = index as f64 / (LFO_PREVIEW_POINTS - 1) as f64;
+                let value = shape.preview_value(start + along * LFO_PREVIEW_SECONDS * drawn_hz);
+                let x = rect.x1() + along as f32 * rect.width();
+                let y = rect.mid_y() - value as f32 * rect.height() / 2.0;
+                (x, y)
+            })
+            .collect()
+    }
+
+    fn shape(
+        &self,
+        rect: &Rect,
+        screen_metrics: &ScreenMetrics,
+        params: &Params,
+        tempo_bpm: f64,
+    ) -> shapes::Buffers {
+        let lfo = self.lfo.params(params);
+        let (_, rate_hz) = Lfo::compute_period_sec(&lfo.rate, tempo_bpm / 60.0);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let points = Self::points(rect, lfo.shape, rate_hz, lfo.phase / 360.0, elapsed);
+        shape_util::polyline(
+            &points,
+            screen_metrics,
+            LFO_PREVIEW_LINE_WIDTH,
+            &self.line_color.to_array3(),
+        )
+    }
+
+    pub fn initialize(
+        &mut self,
+        rect: &Rect,
+        screen_metrics: &ScreenMetrics,
+        _spritesheet_builder: &mut sprites::SpriteSheetBuilder,
+        shapes_builder: &mut shapes::ShapesBuilder,
+    ) {
+        // Every shape has the same number of points, but sharper corners take more vertices.
+        let (vmargin, imargin) = (2 * LFO_PREVIEW_POINTS, 6 * LFO_PREVIEW_POINTS);
+        let points = Self::points(rect, LfoShape::Saw, 1.0, 0.0, 0.0);
+        let buffers = shape_util::polyline(
+            &points,
+            screen_metrics,
+            LFO_PREVIEW_LINE_WIDTH,
+            &self.line_color.to_array3(),
+        );
+        self.shape_index = ShapeIndex({
+            let max_v_count = buffers.vertices.len() + vmargin;
+            let max_i_count = buffers.indices.len() + imargin;
+            shapes_builder.add(GpuShape::from_lyon(buffers, max_v_count, max_i_count))
+        });
+    }
+
+    pub fn update(&mut self, ctx: &mut UpdateContext) {
+        let buffers = self.shape(
+            ctx.rect,
+            ctx.screen_metrics,
+            ctx.params.params(),
+            ctx.params.tempo_bpm(),
+        );
+        ctx.shapes
+            .update(self.shape_index.0, &buffers.vertices, &buffers.indices);
+    }
+
+    pub fn on_resize(&mut self, ctx: &mut UpdateContext) {
+        self.update(ctx);
+    }
+
+    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, _f: F) {}
+}
@@ -1,23 +1,42 @@
+pub mod dropdown;
+pub mod envelope;
+pub mod keyboard;
 pub mod knob;
+pub mod lfo_preview;
 pub mod panel;
+pub mod preset_browser;
 pub mod spinner;
+pub mod tab_bar;
 pub mod toggle;
 pub mod vslider;
 
+use std::iter;
+use std::ops::Range;
 use std::sync::Arc;
 
 use serde::Deserialize;
 
+use crate::params::fmt::ValueFormat;
 use crate::params::sync::Synchronizer;
 use crate::params::{EParam, ParamsMeta};
 
 use crate::ui::alignment::{HorizontalAlign, VerticalAlign};
-use crate::ui::coords::Rect;
+use crate::ui::coords::{Coord2, Rect};
 use crate::ui::shapes;
 use crate::ui::shapes::{Color, ScreenMetrics};
 use crate::ui::sprites;
 use crate::ui::widgets::{
-    knob::Knob, panel::Panel, spinner::Spinner, toggle::Toggle, vslider::VSlider,
+    dropdown::Dropdown,
+    envelope::{AdsrEnv, Envelope},
+    keyboard::Keyboard,
+    knob::Knob,
+    lfo_preview::LfoPreview,
+    panel::Panel,
+    preset_browser::{BrowserHit, PresetBrowser},
+    spinner::Spinner,
+    tab_bar::TabBar,
+    toggle::Toggle,
+    vslider::VSlider,
 };
 use crate::ui::window::ActiveMouseState;
 
@@ -27,13 +46,25 @@ pub const DEFAULT_TEXT_COLOR: Color = Color {
     b: 0.0,
 };
 
+/// What a styling rule does to the widgets it names while it applies. Where several apply, the
+/// last listed here wins.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Effect {
+    // Nothing but redrawing the widget whenever the rule's parameter changes, e.g. for a rate
+    // shown in Hz or in beats depending on whether it's synced.
+    Redraw,
+    // Drawn faded, and left alone by the mouse.
+    Dim,
+    Hide,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct SpriteIndex(usize);
 
 #[derive(Copy, Clone, Debug)]
 pub struct ShapeIndex(usize);
 
-#[derive(Copy, Clone, Debug, Deserialize)]
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
 pub enum LabelPosition {
     Below {
         offset_relative: Option<f32>,
@@ -50,10 +81,37 @@ pub enum LabelPosition {
     },
 }
 
+/// Commands triggered by buttons, rather than bound to a parameter.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
+pub enum Action {
+    Randomize,
+    InitPatch,
+    // Flip between the A and B compare slots.
+    CompareAB,
+    CopyAToB,
+    // Bind the next MIDI controller received to the last parameter touched.
+    MidiLearn,
+    // Step the UI scale down or up, for the next time the editor opens.
+    ShrinkUi,
+    GrowUi,
+    // Switch to the styling's next color theme.
+    NextTheme,
+    // Load the preset before or after the one loaded in the preset browser.
+    PreviousPreset,
+    NextPreset,
+    // Step how far parameters move for the distance dragged down or up.
+    SlowerDrag,
+    FasterDrag,
+    // Show or hide the FPS and such in the corner.
+    ToggleStats,
+}
+
 #[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq)]
 pub enum WidgetId {
     Unspecified { id: usize }, // Assign a unique ID as we use this as a hashmap key
     Bound { eparam: EParam },
+    Action { action: Action },
+    Envelope { env: AdsrEnv },
 }
 
 impl WidgetId {
@@ -61,12 +119,19 @@ impl WidgetId {
         match self {
             Self::Unspecified { id } => format!("Unspecified ({})", id),
             Self::Bound { eparam } => eparam.as_string(true),
+            Self::Action { action } => format!("{:?}", action),
+            Self::Envelope { env } => format!("{:?} Envelope", env),
         }
     }
+
+    /// The widgets that could be showing `eparam`.
+    pub fn showing(eparam: EParam) -> impl Iterator<Item = WidgetId> {
+        iter::once(Self::Bound { eparam })
+            .chain(AdsrEnv::showing(&eparam).map(|env| Self::Envelope { env }))
+    }
 }
 
 pub struct UpdateContext<'a> {
-    #[allow(dead_code)]
     pub meta: &'a Arc<ParamsMeta>,
     pub params: &'a Synchronizer,
     pub id: &'a WidgetId,
@@ -87,6 +152,17 @@ pub struct Widget {
     pub tentative_value: Option<f64>,
     pub wt: WidgetClass,
     pub interactive: bool,
+    // The page the widget is on, or None if it's on every page.
+    pub page: Option<usize>,
+    // Of the widget's label, if it has one; from the theme.
+    pub label_color: Color,
+    on_page: bool,
+    // From the styling's rules that apply to the widget.
+    effect: Option<Effect>,
+    visible: bool,
+    // The shapes and sprites the widget added, hidden along with it.
+    shape_indices: Range<usize>,
+    sprite_indices: Range<usize>,
 }
 
 impl Widget {
@@ -97,7 +173,13 @@ impl Widget {
         value: f64,
         wt: WidgetClass,
     ) -> Self {
-        let interactive = !matches!(wt, WidgetClass::Panel(_));
+        let interactive = !matches!(
+            wt,
+            WidgetClass::Panel(_)
+                | WidgetClass::LfoPreview(_)
+                | WidgetClass::TabBar(_)
+                | WidgetClass::PresetBrowser(_)
+        );
         Self {
             meta,
             id,
@@ -107,25 +189,181 @@ impl Widget {
             tentative_value: None,
             wt,
             interactive,
+            page: None,
+            label_color: DEFAULT_TEXT_COLOR,
+            on_page: true,
+            effect: None,
+            visible: true,
+            shape_indices: 0..0,
+            sprite_indices: 0..0,
         }
     }
 
-    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, f: F) {
+    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, mut f: F) {
+        let label_color = &self.label_color;
+        let dimmed = self.is_dimmed();
+        let f = |text: &Text, color: &Color| {
+            let color = text.color.as_ref().unwrap_or(color);
+            if dimmed {
+                f(text, &color.dimmed())
+            } else {
+                f(text, color)
+            }
+        };
         match &self.wt {
-            WidgetClass::Knob(knob) => knob.apply_to_texts(f),
-            WidgetClass::Spinner(spinner) => spinner.apply_to_texts(f),
+            WidgetClass::Knob(knob) => knob.apply_to_texts(label_color, f),
+            WidgetClass::Spinner(spinner) => spinner.apply_to_texts(label_color, f),
             WidgetClass::VSlider(vslider) => vslider.apply_to_texts(f),
             WidgetClass::Panel(_panel) => { /* TODO */ }
-            WidgetClass::Toggle(toggle) => toggle.apply_to_texts(f),
+            WidgetClass::Toggle(toggle) => toggle.apply_to_texts(label_color, f),
+            WidgetClass::Envelope(envelope) => envelope.apply_to_texts(f),
+            WidgetClass::LfoPreview(preview) => preview.apply_to_texts(f),
+            WidgetClass::Dropdown(dropdown) => dropdown.apply_to_texts(label_color, f),
+            WidgetClass::Keyboard(keyboard) => keyboard.apply_to_texts(f),
+            WidgetClass::TabBar(tab_bar) => tab_bar.apply_to_texts(label_color, f),
+            WidgetClass::PresetBrowser(browser) => browser.apply_to_texts(f),
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn is_dimmed(&self) -> bool {
+        self.effect == Some(Effect::Dim)
+    }
+
+    /// Show the widget if it's on `page` (or on every page), and hide it otherwise.
+    pub fn show_page(
+        &mut self,
+        page: usize,
+        spritesheet: &mut sprites::SpriteSheet,
+        shapes: &mut shapes::Shapes,
+    ) {
+        self.on_page = self.page.is_none() || self.page == Some(page);
+        self.show(spritesheet, shapes);
+        if let WidgetClass::TabBar(tab_bar) = &mut self.wt {
+            tab_bar.set_selected(page);
+        }
+    }
+
+    /// Apply the effect of the styling's rules (if any).
+    pub fn set_effect(
+        &mut self,
+        effect: Option<Effect>,
+        spritesheet: &mut sprites::SpriteSheet,
+        shapes: &mut shapes::Shapes,
+    ) {
+        if effect != self.effect {
+            self.effect = effect;
+            self.show(spritesheet, shapes);
+        }
+    }
+
+    // Hide, dim or show the widget's shapes and sprites as it stands. Sprites aren't dimmed.
+    fn show(&mut self, spritesheet: &mut sprites::SpriteSheet, shapes: &mut shapes::Shapes) {
+        self.visible = self.on_page && self.effect != Some(Effect::Hide);
+        let dimmed = self.is_dimmed();
+        for index in self.shape_indices.clone() {
+            shapes.set_hidden(index, !self.visible);
+            shapes.set_dimmed(index, dimmed);
+        }
+        for index in self.sprite_indices.clone() {
+            spritesheet.set_hidden(index, !self.visible);
+        }
+    }
+
+    /// The page of the tab at `x`, `y` if this is a tab bar.
+    pub fn tab_at(&self, x: f32, y: f32) -> Option<usize> {
+        match &self.wt {
+            WidgetClass::TabBar(tab_bar) if self.visible => tab_bar.tab_at(&self.rect, x, y),
+            _ => None,
+        }
+    }
+
+    /// What's at `x`, `y` if this is a preset browser.
+    pub fn browser_at(&self, x: f32, y: f32) -> Option<BrowserHit> {
+        match &self.wt {
+            WidgetClass::PresetBrowser(browser) if self.visible => browser.hit(&self.rect, x, y),
+            _ => None,
+        }
+    }
+
+    pub fn preset_browser(&self) -> Option<&PresetBrowser> {
+        match &self.wt {
+            WidgetClass::PresetBrowser(browser) => Some(browser),
+            _ => None,
+        }
+    }
+
+    pub fn preset_browser_mut(&mut self) -> Option<&mut PresetBrowser> {
+        match &mut self.wt {
+            WidgetClass::PresetBrowser(browser) => Some(browser),
+            _ => None,
+        }
+    }
+
+    /// Whether the widget changes every frame, not just when parameters do.
+    pub fn is_animated(&self) -> bool {
+        matches!(self.wt, WidgetClass::LfoPreview(_))
+    }
+
+    /// Whether the widget shows a modulated parameter (or just stopped), and so changes every
+    /// frame for now.
+    pub fn follows_modulation(&self, params: &Synchronizer) -> bool {
+        match (&self.id, &self.wt) {
+            (WidgetId::Bound { eparam }, WidgetClass::Knob(knob)) => {
+                knob.follows_modulation(params.modulated_value(*eparam))
+            }
+            _ => false,
+        }
+    }
+
+    /// The key at `x`, `y` if this is a keyboard.
+    pub fn note_at(&self, x: f32, y: f32) -> Option<u8> {
+        match &self.wt {
+            WidgetClass::Keyboard(keyboard) => keyboard.note_at(&self.rect, x, y),
+            _ => None,
+        }
+    }
+
+    /// Show `note` as held down, if this is a keyboard.
+    pub fn set_held_note(&mut self, note: Option<u8>) {
+        if let WidgetClass::Keyboard(keyboard) = &mut self.wt {
+            keyboard.set_held(note);
+        }
+    }
+
+    /// Whether the widget has a list open, which takes the next click wherever it lands.
+    pub fn is_open(&self) -> bool {
+        matches!(&self.wt, WidgetClass::Dropdown(dropdown) if dropdown.is_open())
+    }
+
+    /// The parameter a knob, slider or spinner sets, which can be reset or typed in. Lists and
+    /// switches are quicker to click.
+    pub fn value_param(&self) -> Option<EParam> {
+        match (&self.id, &self.wt) {
+            (
+                WidgetId::Bound { eparam },
+                WidgetClass::Knob(_) | WidgetClass::VSlider(_) | WidgetClass::Spinner(_),
+            ) => Some(*eparam),
+            _ => None,
         }
     }
 
     pub fn in_bounds_rel(&self, x: f32, y: f32) -> bool {
-        self.rect.in_bounds(x, y)
+        self.visible && !self.is_dimmed() && self.rect.in_bounds(x, y)
     }
 
     pub fn on_drag_start(&mut self, mouse_state: &ActiveMouseState, drag_factor: &f32) -> f64 {
         self.baseline_value = Some(self.value);
+        match &mut self.wt {
+            WidgetClass::Envelope(envelope) => envelope.grab(&self.rect, mouse_state),
+            WidgetClass::Dropdown(dropdown) => {
+                dropdown.on_press(&self.rect, mouse_state, self.value)
+            }
+            _ => (),
+        }
         self.on_dragging(mouse_state, drag_factor)
     }
 
@@ -138,14 +376,47 @@ impl Widget {
                 spinner.on_dragging(mouse_state, drag_factor, baseline_value)
             }
             WidgetClass::Toggle(toggle) => toggle.on_dragging(baseline_value),
-            WidgetClass::Panel(_) => 0.0,
+            WidgetClass::Dropdown(dropdown) => dropdown.on_dragging(),
+            WidgetClass::Panel(_)
+            | WidgetClass::LfoPreview(_)
+            | WidgetClass::Keyboard(_)
+            | WidgetClass::TabBar(_)
+            | WidgetClass::PresetBrowser(_) => 0.0,
+            WidgetClass::Envelope(envelope) => {
+                envelope.on_dragging(&self.rect, mouse_state);
+                0.0
+            }
         };
         self.tentative_value = Some(tentative_value);
         tentative_value
     }
 
+    /// The value a drag of `distance` (window-relative, upward) would give, for nudging it with
+    /// the mouse wheel. Only knobs, sliders and spinners scroll.
+    pub fn on_scroll(&mut self, distance: f32, drag_factor: &f32) -> Option<f64> {
+        let mouse_state = ActiveMouseState {
+            start: Coord2::new(0.0, 0.0),
+            pos: Coord2::new(0.0, -distance),
+        };
+        let value = match &mut self.wt {
+            WidgetClass::Knob(knob) => knob.on_dragging(&mouse_state, drag_factor, self.value),
+            WidgetClass::Spinner(spinner) => {
+                spinner.on_dragging(&mouse_state, drag_factor, self.value)
+            }
+            // Sliders follow the mouse, so move by the distance itself.
+            WidgetClass::VSlider(_) => {
+                (self.value + (distance / self.rect.height()) as f64).clamp(0.0, 1.0)
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
     pub fn on_drag_done(&mut self) -> Option<f64> {
         self.baseline_value = None;
+        if let WidgetClass::Envelope(envelope) = &mut self.wt {
+            envelope.on_drag_done();
+        }
         if let Some(new_value) = self.tentative_value {
             self.value = new_value;
             self.tentative_value = None;
@@ -155,12 +426,41 @@ impl Widget {
         }
     }
 
+    /// The parameters this widget shows.
+    pub fn params(&self) -> Vec<EParam> {
+        match (&self.id, &self.wt) {
+            (_, WidgetClass::Envelope(envelope)) => envelope.params().collect(),
+            (WidgetId::Bound { eparam }, _) => vec![*eparam],
+            _ => vec![],
+        }
+    }
+
+    /// Set the value shown for `eparam`, one of this widget's parameters.
+    pub fn set_param_value(&mut self, eparam: EParam, value: f64) {
+        match &mut self.wt {
+            WidgetClass::Envelope(envelope) => envelope.set_value(eparam, value),
+            _ => self.value = value,
+        }
+    }
+
+    /// The parameters written by dragging this widget, with the values they've been dragged to.
+    pub fn edited_values(&self) -> Vec<(EParam, f64)> {
+        match (&self.id, &self.wt) {
+            (_, WidgetClass::Envelope(envelope)) => envelope.edited_values().collect(),
+            (WidgetId::Bound { eparam }, _) => {
+                vec![(*eparam, self.tentative_value.unwrap_or(self.value))]
+            }
+            _ => vec![],
+        }
+    }
+
     pub fn initialize(
         &mut self,
         screen_metrics: &ScreenMetrics,
         spritesheet_builder: &mut sprites::SpriteSheetBuilder,
         shapes_builder: &mut shapes::ShapesBuilder,
     ) {
+        let (first_shape, first_sprite) = (shapes_builder.count(), spritesheet_builder.count());
         match &mut self.wt {
             WidgetClass::Knob(knob) => knob.initialize(
                 &self.rect,
@@ -195,7 +495,45 @@ impl Widget {
                 spritesheet_builder,
                 shapes_builder,
             ),
+            WidgetClass::Envelope(envelope) => envelope.initialize(
+                &self.rect,
+                screen_metrics,
+                spritesheet_builder,
+                shapes_builder,
+            ),
+            WidgetClass::LfoPreview(preview) => preview.initialize(
+                &self.rect,
+                screen_metrics,
+                spritesheet_builder,
+                shapes_builder,
+            ),
+            WidgetClass::Dropdown(dropdown) => dropdown.initialize(
+                &self.rect,
+                screen_metrics,
+                spritesheet_builder,
+                shapes_builder,
+            ),
+            WidgetClass::Keyboard(keyboard) => keyboard.initialize(
+                &self.rect,
+                screen_metrics,
+                spritesheet_builder,
+                shapes_builder,
+            ),
+            WidgetClass::TabBar(tab_bar) => tab_bar.initialize(
+                &self.rect,
+                screen_metrics,
+                spritesheet_builder,
+                shapes_builder,
+            ),
+            WidgetClass::PresetBrowser(browser) => browser.initialize(
+                &self.rect,
+                screen_metrics,
+                spritesheet_builder,
+                shapes_builder,
+            ),
         };
+        self.shape_indices = first_shape..shapes_builder.count();
+        self.sprite_indices = first_sprite..spritesheet_builder.count();
     }
 
     pub fn update(
@@ -231,6 +569,24 @@ impl Widget {
             WidgetClass::Toggle(toggle) => {
                 toggle.update(&mut ctx, value);
             }
+            WidgetClass::Envelope(envelope) => {
+                envelope.update(&mut ctx);
+            }
+            WidgetClass::LfoPreview(preview) => {
+                preview.update(&mut ctx);
+            }
+            WidgetClass::Dropdown(dropdown) => {
+                dropdown.update(&mut ctx, value);
+            }
+            WidgetClass::Keyboard(keyboard) => {
+                keyboard.update(&mut ctx);
+            }
+            WidgetClass::TabBar(tab_bar) => {
+                tab_bar.update(&mut ctx);
+            }
+            WidgetClass::PresetBrowser(browser) => {
+                browser.update(&mut ctx);
+            }
         };
     }
 
@@ -267,16 +623,49 @@ impl Widget {
             WidgetClass::Toggle(toggle) => {
                 toggle.on_resize(&mut ctx, value);
             }
+            WidgetClass::Envelope(envelope) => {
+                envelope.on_resize(&mut ctx);
+            }
+            WidgetClass::LfoPreview(preview) => {
+                preview.on_resize(&mut ctx);
+            }
+            WidgetClass::Dropdown(dropdown) => {
+                dropdown.on_resize(&mut ctx, value);
+            }
+            WidgetClass::Keyboard(keyboard) => {
+                keyboard.on_resize(&mut ctx);
+            }
+            WidgetClass::TabBar(tab_bar) => {
+                tab_bar.on_resize(&mut ctx);
+            }
+            WidgetClass::PresetBrowser(browser) => {
+                browser.on_resize(&mut ctx);
+            }
         };
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct Text {
     pub value: String,
     pub pos: LabelPosition,
     pub scale: f32,
-    // TODO: color
+    // Drawn in the widget's color for it (e.g. the theme's) if not given.
+    #[serde(default)]
+    pub color: Option<Color>,
+    // How values shown in the text are reworked, if at all.
+    #[serde(default)]
+    pub format: Option<ValueFormat>,
+}
+
+impl Text {
+    /// `value`, as its parameter's formatter gave it, the way this text shows values.
+    pub fn formatted(&self, value: String) -> String {
+        match &self.format {
+            Some(format) => format.apply(&value),
+            None => value,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -286,4 +675,10 @@ pub enum WidgetClass {
     Spinner(Spinner),
     Panel(Panel),
     Toggle(Toggle),
+    Envelope(Envelope),
+    LfoPreview(LfoPreview),
+    Dropdown(Dropdown),
+    Keyboard(Keyboard),
+    TabBar(TabBar),
+    PresetBrowser(PresetBrowser),
 }
@@ -8,7 +8,7 @@ use crate::ui::shapes;
 use crate::ui::shapes::{Color, ScreenMetrics};
 use crate::ui::sprites;
 
-use crate::ui::widgets::{self, ShapeIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId};
+use crate::ui::widgets::{ShapeIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId};
 
 const PANEL_OUTLINE_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
 
@@ -62,9 +62,9 @@ impl Panel {
             .update(self.outline_index.0, &buffers.vertices, &buffers.indices);
     }
 
-    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, mut f: F) {
+    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, label_color: &Color, mut f: F) {
         if let Some(label) = &self.label {
-            f(label, &widgets::DEFAULT_TEXT_COLOR);
+            f(label, label_color);
         }
     }
 }
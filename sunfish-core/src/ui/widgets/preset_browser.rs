@@ -0,0 +1,415 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Result;
+use keyboard_types::Key;
+
+use crate::params::ParamsMeta;
+use crate::presets::library::{Filter, Folder, Library};
+use crate::presets::{Category, Preset};
+use crate::ui::alignment::{HorizontalAlign, VerticalAlign};
+use crate::ui::buffer_memory::GpuShape;
+use crate::ui::coords::Rect;
+use crate::ui::shape_util;
+use crate::ui::shapes;
+use crate::ui::shapes::{Color, ScreenMetrics};
+use crate::ui::sprites;
+use crate::util::enumerable::Enumerable;
+
+use crate::ui::widgets::{
+    LabelPosition, ShapeIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId,
+};
+
+const PRESET_BROWSER_OUTLINE_COLOR: [f32; 3] = [0.30039, 0.30039, 0.3019];
+const PRESET_BROWSER_OUTLINE_WIDTH: f32 = 0.0015;
+const PRESET_BROWSER_SELECTED_COLOR: [f32; 3] = [0.95, 0.6, 0.1];
+// Space between a row's left edge and its text.
+const PRESET_BROWSER_TEXT_PADDING: f32 = 0.008;
+// The folder and category picked, the preset loaded and the search box while typing.
+const PRESET_BROWSER_MAX_SELECTED: usize = 4;
+
+/// What a click on the browser landed on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BrowserHit {
+    // None for every folder (or category).
+    Folder(Option<Folder>),
+    Category(Option<Category>),
+    Search,
+    // The library index of the preset clicked.
+    Preset(usize),
+}
+
+/// Lists the presets there are to load, narrowed down by folder, category and a search.
+#[derive(Debug)]
+pub struct PresetBrowser {
+    library: Library,
+    filter: Filter,
+    // Library indices of the presets listed.
+    listed: Vec<usize>,
+    // The preset last loaded, as a library index.
+    loaded: Option<usize>,
+    // The first preset shown, when there are more than fit.
+    scroll: usize,
+    // Whether typing goes to the search box.
+    searching: bool,
+    rows: usize,
+    text_scale: f32,
+    text_color: Color,
+    outline_index: ShapeIndex,
+    selected_index: ShapeIndex,
+    texts: Vec<Text>,
+}
+
+impl PresetBrowser {
+    pub fn new(
+        rows: usize,
+        text_scale: f32,
+        text_color: Color,
+        presets_dir: Option<&Path>,
+    ) -> Self {
+        let library = Library::load(presets_dir);
+        let filter = Filter::default();
+        let listed = library.filter(&filter);
+        PresetBrowser {
+            library,
+            filter,
+            listed,
+            loaded: None,
+            scroll: 0,
+            searching: false,
+            rows,
+            text_scale,
+            text_color,
+            outline_index: ShapeIndex(0),
+            selected_index: ShapeIndex(0),
+            texts: vec![],
+        }
+    }
+
+    pub fn new_widget(
+        meta: Arc<ParamsMeta>,
+        id: WidgetId,
+        rect: Rect,
+        rows: usize,
+        text_scale: f32,
+        text_color: Color,
+        presets_dir: Option<&Path>,
+    ) -> Widget {
+        let browser = Self::new(rows, text_scale, text_color, presets_dir);
+        Widget::new(meta, id, rect, 0.0, WidgetClass::PresetBrowser(browser))
+    }
+
+    // The folders and categories to pick from, with None for all of them.
+    fn folders() -> Vec<Option<Folder>> {
+        std::iter::once(None)
+            .chain(Folder::enumerate().into_iter().map(Some))
+            .collect()
+    }
+
+    fn categories() -> Vec<Option<Category>> {
+        std::iter::once(None)
+            .chain(Category::enumerate().into_iter().map(Some))
+            .collect()
+    }
+
+    // The folders and the search box share the first row, the categories have the second, and
+    // the presets are listed below.
+    fn row_height(&self, rect: &Rect) -> f32 {
+        rect.height() / (self.rows + 2) as f32
+    }
+
+    fn row_rect(&self, rect: &Rect, row: usize) -> Rect {
+        let y1 = rect.y1() + row as f32 * self.row_height(rect);
+        Rect::new(rect.x1(), y1, rect.x2(), y1 + self.row_height(rect))
+    }
+
+    // `count` cells splitting `row_rect` evenly.
+    fn cells(row_rect: &Rect, count: usize) -> Vec<Rect> {
+        let width = row_rect.width() / count as f32;
+        (0..count)
+            .map(|index| {
+                let x1 = row_rect.x1() + index as f32 * width;
+                Rect::new(x1, row_rect.y1(), x1 + width, row_rect.y2())
+            })
+            .collect()
+    }
+
+    fn folder_rects(&self, rect: &Rect) -> Vec<Rect> {
+        let row = self.row_rect(rect, 0);
+        let half = Rect::new(row.x1(), row.y1(), row.mid_x(), row.y2());
+        Self::cells(&half, Self::folders().len())
+    }
+
+    fn search_rect(&self, rect: &Rect) -> Rect {
+        let row = self.row_rect(rect, 0);
+        Rect::new(row.mid_x(), row.y1(), row.x2(), row.y2())
+    }
+
+    fn category_rects(&self, rect: &Rect) -> Vec<Rect> {
+        Self::cells(&self.row_rect(rect, 1), Self::categories().len())
+    }
+
+    // Where the preset listed at `position` is, if it's scrolled into view.
+    fn preset_rect(&self, rect: &Rect, position: usize) -> Option<Rect> {
+        let row = position.checked_sub(self.scroll)?;
+        (row < self.rows).then(|| self.row_rect(rect, row + 2))
+    }
+
+    /// What's at `x`, `y`, if anything.
+    pub fn hit(&self, rect: &Rect, x: f32, y: f32) -> Option<BrowserHit> {
+        if let Some(index) = self
+            .folder_rects(rect)
+            .iter()
+            .position(|cell| cell.in_bounds(x, y))
+        {
+            return Some(BrowserHit::Folder(Self::folders()[index]));
+        }
+        if let Some(index) = self
+            .category_rects(rect)
+            .iter()
+            .position(|cell| cell.in_bounds(x, y))
+        {
+            return Some(BrowserHit::Category(Self::categories()[index]));
+        }
+        if self.search_rect(rect).in_bounds(x, y) {
+            return Some(BrowserHit::Search);
+        }
+        self.listed
+            .iter()
+            .enumerate()
+            .find(|(position, _)| {
+                self.preset_rect(rect, *position)
+                    .is_some_and(|row| row.in_bounds(x, y))
+            })
+            .map(|(_, index)| BrowserHit::Preset(*index))
+    }
+
+    pub fn set_folder(&mut self, folder: Option<Folder>) {
+        self.filter.folder = folder;
+        self.refilter();
+    }
+
+    pub fn set_category(&mut self, category: Option<Category>) {
+        self.filter.category = category;
+        self.refilter();
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.searching
+    }
+
+    pub fn set_searching(&mut self, searching: bool) {
+        self.searching = searching;
+    }
+
+    /// Type into the search box. Enter and Escape stop the search, which Escape also clears.
+    pub fn on_key(&mut self, key: &Key) {
+        match key {
+            Key::Character(typed) => self.filter.query.push_str(typed),
+            Key::Backspace => {
+                self.filter.query.pop();
+            }
+            Key::Enter => self.searching = false,
+            Key::Escape => {
+                self.searching = false;
+                self.filter.query.clear();
+            }
+            _ => return,
+        }
+        self.refilter();
+    }
+
+    fn refilter(&mut self) {
+        self.listed = self.library.filter(&self.filter);
+        self.scroll = 0;
+        self.scroll_to_loaded();
+    }
+
+    // Scroll just far enough to show the preset loaded, if it's listed.
+    fn scroll_to_loaded(&mut self) {
+        let position = match self
+            .listed
+            .iter()
+            .position(|index| Some(*index) == self.loaded)
+        {
+            Some(position) => position,
+            None => return,
+        };
+        if position < self.scroll {
+            self.scroll = position;
+        } else if position >= self.scroll + self.rows {
+            self.scroll = position + 1 - self.rows;
+        }
+    }
+
+    /// Scroll the list by `rows` (down, if positive), within it.
+    pub fn scroll_by(&mut self, rows: i32) {
+        let max_scroll = self.listed.len().saturating_sub(self.rows);
+        self.scroll = (self.scroll as i64 + rows as i64).clamp(0, max_scroll as i64) as usize;
+    }
+
+    /// The preset `steps` after (or before, if negative) the one loaded, going around the list;
+    /// the first listed if none is loaded.
+    pub fn step(&self, steps: i32) -> Option<usize> {
+        let count = self.listed.len() as i64;
+        if count == 0 {
+            return None;
+        }
+        let position = match self
+            .listed
+            .iter()
+            .position(|index| Some(*index) == self.loaded)
+        {
+            Some(position) => (position as i64 + steps as i64).rem_euclid(count),
+            None => 0,
+        };
+        Some(self.listed[position as usize])
+    }
+
+    /// Read the preset at `index` in the library, marking it as loaded if it can be read.
+    pub fn load_preset(&mut self, index: usize) -> Result<Preset> {
+        let preset = self.library.load_preset(index)?;
+        self.loaded = Some(index);
+        self.scroll_to_loaded();
+        Ok(preset)
+    }
+
+    pub fn initialize(
+        &mut self,
+        rect: &Rect,
+        screen_metrics: &ScreenMetrics,
+        _spritesheet_builder: &mut sprites::SpriteSheetBuilder,
+        shapes_builder: &mut shapes::ShapesBuilder,
+    ) {
+        let mut add = |buffers: shapes::Buffers| {
+            let max_v_count = buffers.vertices.len();
+            let max_i_count = buffers.indices.len();
+            ShapeIndex(shapes_builder.add(GpuShape::from_lyon(buffers, max_v_count, max_i_count)))
+        };
+        // Filled under the outlines, so they stay visible. Rectangles take the same number of
+        // vertices whatever their size, so this sizes the shape for the most there can be.
+        self.selected_index = add(shape_util::rectangles_filled(
+            &vec![rect.clone(); PRESET_BROWSER_MAX_SELECTED],
+            screen_metrics,
+            &PRESET_BROWSER_SELECTED_COLOR,
+        ));
+        self.outline_index = add(shape_util::rectangles_outline(
+            &self.outline_rects(rect),
+            screen_metrics,
+            PRESET_BROWSER_OUTLINE_WIDTH,
+            &PRESET_BROWSER_OUTLINE_COLOR,
+        ));
+    }
+
+    fn outline_rects(&self, rect: &Rect) -> Vec<Rect> {
+        let list = Rect::new(rect.x1(), self.row_rect(rect, 2).y1(), rect.x2(), rect.y2());
+        self.folder_rects(rect)
+            .into_iter()
+            .chain(self.category_rects(rect))
+            .chain([self.search_rect(rect), list])
+            .collect()
+    }
+
+    fn selected_rects(&self, rect: &Rect) -> Vec<Rect> {
+        let folder = Self::folders()
+            .iter()
+            .position(|folder| *folder == self.filter.folder)
+            .map(|index| self.folder_rects(rect)[index].clone());
+        let category = Self::categories()
+            .iter()
+            .position(|category| *category == self.filter.category)
+            .map(|index| self.category_rects(rect)[index].clone());
+        let loaded = self
+            .listed
+            .iter()
+            .position(|index| Some(*index) == self.loaded)
+            .and_then(|position| self.preset_rect(rect, position));
+        let search = self.searching.then(|| self.search_rect(rect));
+        folder
+            .into_iter()
+            .chain(category)
+            .chain(loaded)
+            .chain(search)
+            .collect()
+    }
+
+    // A text centered in `cell`, or starting at its left edge.
+    fn text_in(&self, rect: &Rect, cell: &Rect, value: String, centered: bool) -> Text {
+        let (x, h_align) = if centered {
+            (cell.mid_x(), HorizontalAlign::Center)
+        } else {
+            (
+                cell.x1() + PRESET_BROWSER_TEXT_PADDING,
+                HorizontalAlign::Left,
+            )
+        };
+        Text {
+            value,
+            pos: LabelPosition::Relative {
+                x: x - rect.x1(),
+                y: cell.mid_y() - rect.y1(),
+                h_align,
+                v_align: VerticalAlign::Center,
+            },
+            scale: self.text_scale,
+            color: None,
+            format: None,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut UpdateContext) {
+        let rect = ctx.rect;
+        let folder_names = Self::folders()
+            .into_iter()
+            .map(|folder| folder.map_or("All".to_string(), Folder::as_string));
+        let category_names = Self::categories()
+            .into_iter()
+            .map(|category| category.map_or("All".to_string(), Category::as_string));
+        let mut texts: Vec<Text> = folder_names
+            .zip(self.folder_rects(rect))
+            .chain(category_names.zip(self.category_rects(rect)))
+            .map(|(name, cell)| self.text_in(rect, &cell, name, true))
+            .collect();
+
+        let search = match (self.searching, self.filter.query.is_empty()) {
+            (true, _) => format!("{}|", self.filter.query),
+            (false, true) => "Search".to_string(),
+            (false, false) => self.filter.query.clone(),
+        };
+        texts.push(self.text_in(rect, &self.search_rect(rect), search, false));
+        for (position, index) in self.listed.iter().enumerate() {
+            if let (Some(row), Some(entry)) =
+                (self.preset_rect(rect, position), self.library.get(*index))
+            {
+                texts.push(self.text_in(rect, &row, entry.meta.name.clone(), false));
+            }
+        }
+        self.texts = texts;
+
+        let selected = shape_util::rectangles_filled(
+            &self.selected_rects(rect),
+            ctx.screen_metrics,
+            &PRESET_BROWSER_SELECTED_COLOR,
+        );
+        ctx.shapes
+            .update(self.selected_index.0, &selected.vertices, &selected.indices);
+    }
+
+    pub fn on_resize(&mut self, ctx: &mut UpdateContext) {
+        let outline = shape_util::rectangles_outline(
+            &self.outline_rects(ctx.rect),
+            ctx.screen_metrics,
+            PRESET_BROWSER_OUTLINE_WIDTH,
+            &PRESET_BROWSER_OUTLINE_COLOR,
+        );
+        ctx.shapes
+            .update(self.outline_index.0, &outline.vertices, &outline.indices);
+        self.update(ctx);
+    }
+
+    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, mut f: F) {
+        for text in self.texts.iter() {
+            f(text, &self.text_color);
+        }
+    }
+}
@@ -9,7 +9,7 @@ use crate::ui::shapes::{Color, ScreenMetrics};
 use crate::ui::sprites;
 use crate::ui::window::ActiveMouseState;
 
-use crate::ui::widgets::{self, ShapeIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId};
+use crate::ui::widgets::{ShapeIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId};
 
 const SPINNER_OUTLINE: bool = false;
 const SPINNER_OUTLINE_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
@@ -68,7 +68,11 @@ impl Spinner {
 
     pub fn update(&mut self, ctx: &mut UpdateContext, _value: f64) {
         if let WidgetId::Bound { eparam } = ctx.id {
-            self.value_text.value = ctx.params.formatted_value(*eparam);
+            let formatted = ctx.params.formatted_value(*eparam).unwrap_or_else(|err| {
+                log::error!("Failed to format value: {}", err);
+                String::new()
+            });
+            self.value_text.value = self.value_text.formatted(formatted);
         }
     }
 
@@ -99,9 +103,9 @@ impl Spinner {
         (value + delta).min(1.0).max(0.0)
     }
 
-    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, mut f: F) {
+    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, label_color: &Color, mut f: F) {
         if let Some(label) = &self.label {
-            f(label, &widgets::DEFAULT_TEXT_COLOR);
+            f(label, label_color);
         }
         f(&self.value_text, &self.value_text_color);
     }
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use crate::params::ParamsMeta;
+use crate::ui::alignment::{HorizontalAlign, VerticalAlign};
+use crate::ui::buffer_memory::GpuShape;
+use crate::ui::coords::Rect;
+use crate::ui::shape_util;
+use crate::ui::shapes;
+use crate::ui::shapes::{Color, ScreenMetrics};
+use crate::ui::sprites;
+
+use crate::ui::widgets::{
+    LabelPosition, ShapeIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId,
+};
+
+const TAB_BAR_OUTLINE_COLOR: [f32; 3] = [0.30039, 0.30039, 0.3019];
+const TAB_BAR_OUTLINE_WIDTH: f32 = 0.0015;
+const TAB_BAR_SELECTED_COLOR: [f32; 3] = [0.95, 0.6, 0.1];
+
+/// A row of tabs, one per page of the layout; clicking a tab shows its page.
+#[derive(Debug)]
+pub struct TabBar {
+    names: Vec<String>,
+    selected: usize,
+    text_scale: f32,
+    selected_index: ShapeIndex,
+    outline_index: ShapeIndex,
+    texts: Vec<Text>,
+}
+
+impl TabBar {
+    pub fn new(names: Vec<String>, text_scale: f32) -> Self {
+        TabBar {
+            names,
+            selected: 0,
+            text_scale,
+            selected_index: ShapeIndex(0),
+            outline_index: ShapeIndex(0),
+            texts: vec![],
+        }
+    }
+
+    pub fn new_widget(
+        meta: Arc<ParamsMeta>,
+        id: WidgetId,
+        rect: Rect,
+        names: Vec<String>,
+        text_scale: f32,
+    ) -> Widget {
+        let tab_bar = Self::new(names, text_scale);
+        Widget::new(meta, id, rect, 0.0, WidgetClass::TabBar(tab_bar))
+    }
+
+    // Tabs split the bar evenly.
+    fn tab_rects(&self, rect: &Rect) -> Vec<Rect> {
+        let width = rect.width() / self.names.len() as f32;
+        (0..self.names.len())
+            .map(|index| {
+                let x1 = rect.x1() + index as f32 * width;
+                Rect::new(x1, rect.y1(), x1 + width, rect.y2())
+            })
+            .collect()
+    }
+
+    /// The page of the tab at `x`, `y`, if any.
+    pub fn tab_at(&self, rect: &Rect, x: f32, y: f32) -> Option<usize> {
+        self.tab_rects(rect)
+            .iter()
+            .position(|tab_rect| tab_rect.in_bounds(x, y))
+    }
+
+    pub fn set_selected(&mut self, page: usize) {
+        self.selected = page;
+    }
+
+    pub fn initialize(
+        &mut self,
+        rect: &Rect,
+        screen_metrics: &ScreenMetrics,
+        _spritesheet_builder: &mut sprites::SpriteSheetBuilder,
+        shapes_builder: &mut shapes::ShapesBuilder,
+    ) {
+        let mut add = |buffers: shapes::Buffers| {
+            let max_v_count = buffers.vertices.len();
+            let max_i_count = buffers.indices.len();
+            ShapeIndex(shapes_builder.add(GpuShape::from_lyon(buffers, max_v_count, max_i_count)))
+        };
+        // Filled under the outlines, so they stay visible.
+        self.selected_index = add(shape_util::rectangles_filled(
+            std::slice::from_ref(rect),
+            screen_metrics,
+            &TAB_BAR_SELECTED_COLOR,
+        ));
+        self.outline_index = add(shape_util::rectangles_outline(
+            &self.tab_rects(rect),
+            screen_metrics,
+            TAB_BAR_OUTLINE_WIDTH,
+            &TAB_BAR_OUTLINE_COLOR,
+        ));
+    }
+
+    pub fn update(&mut self, ctx: &mut UpdateContext) {
+        let rect = ctx.rect;
+        let tab_rects = self.tab_rects(rect);
+        self.texts = self
+            .names
+            .iter()
+            .zip(tab_rects.iter())
+            .map(|(name, tab_rect)| Text {
+                value: name.clone(),
+                pos: LabelPosition::Relative {
+                    x: tab_rect.mid_x() - rect.x1(),
+                    y: tab_rect.mid_y() - rect.y1(),
+                    h_align: HorizontalAlign::Center,
+                    v_align: VerticalAlign::Center,
+                },
+                scale: self.text_scale,
+                color: None,
+                format: None,
+            })
+            .collect();
+
+        match tab_rects.get(self.selected) {
+            Some(selected_rect) => {
+                let selected = shape_util::rectangles_filled(
+                    std::slice::from_ref(selected_rect),
+                    ctx.screen_metrics,
+                    &TAB_BAR_SELECTED_COLOR,
+                );
+                ctx.shapes
+                    .update(self.selected_index.0, &selected.vertices, &selected.indices);
+            }
+            None => ctx.shapes.update(self.selected_index.0, &[], &[]),
+        }
+    }
+
+    pub fn on_resize(&mut self, ctx: &mut UpdateContext) {
+        let outline = shape_util::rectangles_outline(
+            &self.tab_rects(ctx.rect),
+            ctx.screen_metrics,
+            TAB_BAR_OUTLINE_WIDTH,
+            &TAB_BAR_OUTLINE_COLOR,
+        );
+        ctx.shapes
+            .update(self.outline_index.0, &outline.vertices, &outline.indices);
+        self.update(ctx);
+    }
+
+    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, label_color: &Color, mut f: F) {
+        for text in self.texts.iter() {
+            f(text, label_color);
+        }
+    }
+}
@@ -11,7 +11,7 @@ use crate::ui::shapes::{Color, ScreenMetrics};
 use crate::ui::sprites;
 
 use crate::ui::widgets::{
-    self, ShapeIndex, SpriteIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId,
+    ShapeIndex, SpriteIndex, Text, UpdateContext, Widget, WidgetClass, WidgetId,
 };
 
 #[allow(dead_code)]
@@ -28,8 +28,16 @@ pub struct Toggle {
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ToggleSprite {
-    on: Rect,
-    off: Rect,
+    on: sprites::SpriteRect,
+    off: sprites::SpriteRect,
+}
+
+impl ToggleSprite {
+    /// Swap the sprites' names for their rects; see `SpriteRect::resolve`.
+    pub fn resolve(&mut self, rects: &sprites::SpriteRects) -> anyhow::Result<()> {
+        self.on.resolve(rects)?;
+        self.off.resolve(rects)
+    }
 }
 
 impl Toggle {
@@ -74,7 +82,7 @@ impl Toggle {
                     }),
                     size: UserVec2::Rel(Vec2 { pos: rect.size() }),
                     src_px: sprites::SpriteSource {
-                        src_rect: sprite_info.on.pos,
+                        src_rect: sprite_info.on.pos(),
                     },
                 },
             )));
@@ -119,9 +127,9 @@ impl Toggle {
                 &sprites::SpriteUpdate {
                     src_px: Some(sprites::SpriteSource {
                         src_rect: if value {
-                            sprite_info.on.pos
+                            sprite_info.on.pos()
                         } else {
-                            sprite_info.off.pos
+                            sprite_info.off.pos()
                         },
                     }),
                     ..Default::default()
@@ -146,9 +154,9 @@ impl Toggle {
         }
     }
 
-    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, mut f: F) {
+    pub fn apply_to_texts<F: FnMut(&Text, &Color)>(&self, label_color: &Color, mut f: F) {
         if let Some(label) = &self.label {
-            f(label, &widgets::DEFAULT_TEXT_COLOR);
+            f(label, label_color);
         }
     }
 }
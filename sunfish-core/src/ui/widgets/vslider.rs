@@ -31,7 +31,14 @@ pub struct VSlider {
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct VSliderSprite {
-    active: Rect,
+    active: sprites::SpriteRect,
+}
+
+impl VSliderSprite {
+    /// Swap the sprite's name for its rect; see `SpriteRect::resolve`.
+    pub fn resolve(&mut self, rects: &sprites::SpriteRects) -> anyhow::Result<()> {
+        self.active.resolve(rects)
+    }
 }
 
 impl VSlider {
@@ -97,7 +104,7 @@ impl VSlider {
                         pos: thumb_rect.size(),
                     }),
                     src_px: sprites::SpriteSource {
-                        src_rect: sprite_info.active.pos,
+                        src_rect: sprite_info.active.pos(),
                     },
                 },
             )));
@@ -157,7 +164,11 @@ impl VSlider {
 
         // Update value label.
         if let WidgetId::Bound { eparam } = ctx.id {
-            self.value_text.value = ctx.params.formatted_value(*eparam);
+            let formatted = ctx.params.formatted_value(*eparam).unwrap_or_else(|err| {
+                log::error!("Failed to format value: {}", err);
+                String::new()
+            });
+            self.value_text.value = self.value_text.formatted(formatted);
         }
     }
 
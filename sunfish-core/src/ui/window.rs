@@ -1,24 +1,37 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fmt::Write;
 use std::iter;
+use std::path::Path;
 use std::sync;
 use std::sync::atomic::AtomicU32;
 use std::time::{Duration, Instant};
 
+use keyboard_types::{Key, KeyState};
 use twox_hash::RandomXxHashBuilder64;
-use wgpu_glyph::{ab_glyph, GlyphBrush, GlyphBrushBuilder, Section, Text};
-use wgpu_glyph::{HorizontalAlign, Layout, VerticalAlign};
-
-use crate::params::sync::{Subscriber, Synchronizer};
-use crate::params::{Params, ParamsMeta};
-use crate::ui::buffer_memory;
+use wgpu_glyph::{ab_glyph, Extra, GlyphBrush, GlyphBrushBuilder, GlyphCruncher, Section, Text};
+use wgpu_glyph::{HorizontalAlign, Layout, SectionGlyph, VerticalAlign};
+
+use crate::midi::{self, MidiSender};
+use crate::params::compare::CompareSlot;
+use crate::params::randomize;
+use crate::params::sync::{ParamDeltaRouter, Synchronizer};
+use crate::params::{EParam, Params, ParamsMeta};
+use crate::ui::buffer_memory::{self, GpuShape};
+use crate::ui::context_menu::{self, ContextMenu, MenuItem};
 use crate::ui::controls::Controls;
-use crate::ui::coords::{Coord2, UserVec2, Vec2};
+use crate::ui::coords::{Coord2, Rect, UserVec2, Vec2};
+use crate::ui::preferences::Preferences;
+use crate::ui::shape_util;
 use crate::ui::shapes::{self, ScreenMetrics};
 use crate::ui::sprites;
-use crate::ui::styling;
-use crate::ui::widgets::{LabelPosition, Widget, WidgetId};
+use crate::ui::styling::{self, StylingWatcher};
+use crate::ui::text_entry::{self, EntryKey, TextEntry};
+use crate::ui::tooltip::{self, Tooltip};
+use crate::ui::widgets::preset_browser::{BrowserHit, PresetBrowser};
+use crate::ui::widgets::{self, Action, LabelPosition, Widget, WidgetId};
 use crate::util::borrow_return::{Borrower, Owner};
+use crate::util::rng::Rng;
 
 use baseview::{EventStatus, Window, WindowHandler, WindowScalePolicy};
 use iced_baseview::Size;
@@ -30,9 +43,35 @@ use iced_wgpu::{wgpu, Backend, Renderer, Settings, Viewport};
 
 const DRAG_FACTOR_NORMAL: f32 = 4.0;
 const DRAG_FACTOR_SLOW: f32 = 0.7;
+/// How far (window-relative) one notch of the mouse wheel drags a widget.
+const SCROLL_DISTANCE_PER_LINE: f32 = 0.005;
+/// How far (window-relative) an arrow key drags the focused widget.
+const KEY_STEP_DISTANCE: f32 = 0.005;
+/// Widgets whose middles are this close vertically count as one row when moving the focus.
+const FOCUS_ROW_HEIGHT: f32 = 0.02;
+const FOCUS_OUTLINE_COLOR: [f32; 3] = [0.95, 0.6, 0.1];
+const FOCUS_OUTLINE_WIDTH: f32 = 0.002;
+
+/// Two clicks on a widget this close together make a double-click.
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+/// How hard keys on the on-screen keyboard are struck.
+const KEYBOARD_VELOCITY: u8 = 100;
 
 /// How often to query the host for parameter updates (and thus update the GUI).
 const PARAM_SYNC_PER_SEC: f32 = 60.0;
+/// With nothing changing, how often to draw the editor anyway.
+const IDLE_FRAME_INTERVAL: Duration = Duration::from_millis(250);
+/// How often to check whether the styling (or its sprite image) was edited.
+const STYLING_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// After the GPU fails to draw a frame, how long to wait before trying again; it doubles with
+/// each failure in a row, up to `MAX_RENDER_RETRY_INTERVAL`.
+const RENDER_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_RENDER_RETRY_INTERVAL: Duration = Duration::from_secs(8);
+
+/// Errors shown over the editor, in pixels between lines, and how many at most.
+const ERROR_LINE_HEIGHT: f32 = 18.0;
+const ERROR_TEXT_COLOR: [f32; 4] = [1.0, 0.45, 0.3, 1.0];
+const MAX_ERRORS_SHOWN: usize = 5;
 
 type WidgetMap = HashMap<WidgetId, Widget>;
 
@@ -44,6 +83,11 @@ pub enum InteractiveState {
         id: WidgetId,
         mouse: ActiveMouseState,
     },
+    // Holding the mouse down on a keyboard; `note` is the key under it, if any.
+    Playing {
+        id: WidgetId,
+        note: Option<u8>,
+    },
 }
 
 struct Poller {
@@ -91,33 +135,252 @@ impl std::default::Default for ActiveMouseState {
 
 struct State {
     widgets: WidgetMap,
-    render_state: RenderState,
+    // Shared with the render thread, which draws from it.
+    render_state: SharedRenderState,
     interactive_state: InteractiveState,
     mouse_pos_norm: Coord2,
     // TODO: Change to distinguish Ctrl, Shift, Cmd, etc.
     modifier_active_ctrl: bool,
+    modifier_active_shift: bool,
+    // The widget last clicked (or tabbed to), which takes arrow keys and typed values.
+    focused: Option<WidgetId>,
+    // The widget last clicked and when, to spot double-clicks.
+    last_click: Option<(WidgetId, Instant)>,
+    // A value being typed in, if any.
+    text_entry: Option<TextEntry>,
+    // The open context menu, if any.
+    context_menu: Option<ContextMenu>,
+    // The widget under the mouse and since when, for its tooltip.
+    hovered: Option<(WidgetId, Instant)>,
 }
 
 impl State {
     pub async fn new<'a>(
-        window: &'a Window<'a>,
+        window: Option<&'a Window<'a>>,
         size: baseview::Size,
         scaling: f64,
+        preferences: &Preferences,
         meta: sync::Arc<ParamsMeta>,
         styling: &styling::Styling,
-    ) -> Self {
-        let widgets = styling::create_widgets(styling, meta);
+        theme: &styling::Theme,
+    ) -> anyhow::Result<(Self, Option<Presenter>)> {
+        let presets_dir = preferences.presets_dir();
+        let widgets = styling::create_widgets(styling, theme, presets_dir.as_deref(), meta);
 
-        let (render_state, widgets) =
-            RenderState::new(widgets, window, size, scaling, styling).await;
+        let (render_state, widgets, presenter) =
+            RenderState::new(widgets, window, size, scaling, preferences, styling).await?;
 
-        Self {
+        let state = Self {
             widgets,
             interactive_state: InteractiveState::Idle,
-            render_state,
+            render_state: SharedRenderState::new(render_state),
             mouse_pos_norm: Coord2::new(-1.0, -1.0),
             modifier_active_ctrl: false,
+            modifier_active_shift: false,
+            focused: None,
+            last_click: None,
+            text_entry: None,
+            context_menu: None,
+            hovered: None,
+        };
+        Ok((state, presenter))
+    }
+
+    /// What the render thread needs of the widgets to draw them as they stand, along with
+    /// whatever's open over them.
+    fn frame(&self, tooltip: Option<Tooltip>) -> Frame {
+        let mut texts = vec![];
+        for widget in self.widgets.values().filter(|widget| widget.is_visible()) {
+            widget.apply_to_texts(|text, color| {
+                texts.push((widget.id, widget.rect.clone(), text.clone(), color.clone()))
+            });
+        }
+        let text_entry = self.text_entry.as_ref().and_then(|entry| {
+            let widget = self.widgets.get(&entry.id)?;
+            Some((entry.clone(), widget.rect.clone()))
+        });
+        Frame {
+            texts,
+            text_entry,
+            context_menu: self.context_menu.clone(),
+            tooltip,
+        }
+    }
+}
+
+/// There's no GPU to draw the editor with, e.g. on a build machine.
+#[derive(Debug, thiserror::Error)]
+#[error("No GPU adapter to draw the editor with")]
+pub struct NoAdapter;
+
+/// A snapshot of what's drawn over the shapes and sprites, taken by the GUI thread for the
+/// render thread: the visible widgets' texts, and the text entry, context menu and tooltip.
+#[derive(Default)]
+struct Frame {
+    // Each text with its widget's rect.
+    texts: Vec<(WidgetId, Rect, widgets::Text, shapes::Color)>,
+    // The value being typed in, and the rect of the widget it's for.
+    text_entry: Option<(TextEntry, Rect)>,
+    context_menu: Option<ContextMenu>,
+    tooltip: Option<Tooltip>,
+}
+
+/// The widgets' texts laid out into glyphs, kept from frame to frame until a text changes.
+#[derive(Default)]
+struct TextCache {
+    // The texts laid out, with their widget and color, to tell when one changes.
+    texts: Vec<(WidgetId, widgets::Text, shapes::Color)>,
+    glyphs: Vec<SectionGlyph>,
+    // Each text's color, by its glyphs' section index.
+    extra: Vec<Extra>,
+    // Set when the texts move without changing, e.g. on resize.
+    stale: bool,
+}
+
+impl TextCache {
+    // Whether the frame's texts are still the ones laid out.
+    fn is_current(&self, frame: &Frame) -> bool {
+        !self.stale
+            && self.texts.len() == frame.texts.len()
+            && self.texts.iter().zip(frame.texts.iter()).all(
+                |((id, text, color), (frame_id, _, frame_text, frame_color))| {
+                    id == frame_id && text == frame_text && color == frame_color
+                },
+            )
+    }
+}
+
+/// What's drawn behind a page's widgets.
+#[derive(Clone, Debug)]
+struct PageBackground {
+    color: [f64; 3],
+    sprite_index: Option<usize>,
+}
+
+impl PageBackground {
+    fn new(
+        background: &styling::Background,
+        spritesheet_builder: &mut sprites::SpriteSheetBuilder,
+    ) -> Self {
+        match background {
+            styling::Background::Solid { color } => PageBackground {
+                color: [color.r as f64, color.g as f64, color.b as f64],
+                sprite_index: None,
+            },
+            styling::Background::Sprite {
+                dest_rect,
+                src_rect,
+            } => PageBackground {
+                color: [1.0, 1.0, 1.0], // TODO set back to black
+                sprite_index: Some(spritesheet_builder.add(sprites::SpriteBuilder {
+                    pos: UserVec2::Rel(Vec2 {
+                        pos: [dest_rect.x1(), dest_rect.y1()],
+                    }),
+                    size: UserVec2::Rel(Vec2 {
+                        pos: [dest_rect.width(), dest_rect.height()],
+                    }),
+                    src_px: sprites::SpriteSource {
+                        src_rect: src_rect.pos(),
+                    },
+                })),
+            },
+        }
+    }
+}
+
+/// A styling's widgets, set up for drawing, and what's drawn behind them.
+struct Scene {
+    widgets: WidgetMap,
+    // One per page.
+    backgrounds: Vec<PageBackground>,
+    spritesheet: sprites::SpriteSheet,
+    shapes: shapes::Shapes,
+    // What went wrong without stopping it being drawn, e.g. a sprite image that can't be read.
+    errors: Vec<String>,
+}
+
+impl Scene {
+    fn build(
+        mut widgets: Vec<Widget>,
+        styling: &styling::Styling,
+        device: &wgpu::Device,
+        swapchain_format: &wgpu::TextureFormat,
+        queue: &wgpu::Queue,
+        screen_metrics: &ScreenMetrics,
+    ) -> anyhow::Result<Self> {
+        /////////////////////////////////////////////////////////////////
+        // Sprites
+        /////////////////////////////////////////////////////////////////
+        let filename = styling.stylesheet_path();
+        log::info!("Sprite base filename: {:?}", filename);
+
+        let mut spritesheet_builder = sprites::SpriteSheetBuilder::new(
+            device,
+            swapchain_format,
+            styling.sample_count(),
+            queue,
+            filename
+                .as_deref()
+                .and_then(|filename| filename.to_str())
+                .unwrap_or_default(),
+        );
+
+        // Add the backgrounds; pages without their own share the default one. A styling
+        // without pages has just the one.
+        let page_count = styling.pages.len().max(1);
+        let mut default_background: Option<PageBackground> = None;
+        let mut backgrounds = Vec::with_capacity(page_count);
+        for page in 0..page_count {
+            let background = match styling
+                .pages
+                .get(page)
+                .and_then(|page| page.background.as_ref())
+            {
+                Some(background) => PageBackground::new(background, &mut spritesheet_builder),
+                None => default_background
+                    .get_or_insert_with(|| {
+                        PageBackground::new(&styling.background, &mut spritesheet_builder)
+                    })
+                    .clone(),
+            };
+            backgrounds.push(background);
+        }
+
+        let mut widget_map = HashMap::new();
+        let mut shapes_builder = shapes::ShapesBuilder::with_capacity(
+            128,
+            device,
+            swapchain_format,
+            styling.sample_count(),
+        );
+        for mut widget in widgets.drain(..) {
+            widget.initialize(
+                screen_metrics,
+                &mut spritesheet_builder,
+                &mut shapes_builder,
+            );
+            widget_map.insert(widget.id, widget);
         }
+        /////////////////////////////////////////////////////////////////
+        // Shapes
+        /////////////////////////////////////////////////////////////////
+
+        let mut errors = vec![];
+        let texture = match &filename {
+            Some(filename) => spritesheet_builder
+                .load_texture()
+                .map_err(|err| format!("Can't load the sprite image {:?}: {:#}", filename, err)),
+            None => Err("The styling has no sprite image".to_string()),
+        };
+        let texture = texture.map_err(|err| errors.push(err)).ok();
+
+        Ok(Scene {
+            widgets: widget_map,
+            backgrounds,
+            spritesheet: spritesheet_builder.build(texture, screen_metrics)?,
+            shapes: shapes_builder.build(),
+            errors,
+        })
     }
 }
 
@@ -128,12 +391,14 @@ struct RenderState {
 
     viewport: Viewport,
     device: wgpu::Device,
-    swap_chain: wgpu::SwapChain,
-    surface: wgpu::Surface,
     format: wgpu::TextureFormat,
     staging_belt: wgpu::util::StagingBelt,
     queue: wgpu::Queue,
+    // The swap chain's settings; the render thread's `Presenter` follows them.
     sc_desc: wgpu::SwapChainDescriptor,
+    // Sprites and shapes are drawn here and resolved into the frame, when multisampled.
+    msaa_view: Option<wgpu::TextureView>,
+    sample_count: u32,
 
     cursor_position: iced_baseview::Point,
     resized: bool,
@@ -142,35 +407,81 @@ struct RenderState {
     window_info: baseview::WindowInfo,
     // TODO: Consider removing logical_size, use window_info directly.
     // TODO: Replace ScreenMetrics with window info.
+    // The area drawn to, at the layout's proportions, in the window's top-left corner.
     screen_metrics: ScreenMetrics,
+    // The layout's width / height.
+    layout_ratio: f32,
 
     renderer: Renderer,
 
-    background: [f64; 3],
-    background_sprite_index: Option<usize>,
+    // One per page.
+    backgrounds: Vec<PageBackground>,
+    // The page shown.
+    page: usize,
     spritesheet: sprites::SpriteSheet,
     shapes: shapes::Shapes,
     glyph_brush: GlyphBrush<(), ab_glyph::FontArc, RandomXxHashBuilder64>,
+    text_cache: TextCache,
+    // Drawn over the widgets and their texts.
+    overlay_shapes: shapes::Shapes,
+    // Background of the field values are typed into.
+    text_entry_shape_index: usize,
+    // Context menu background, outline and hovered item outline.
+    context_menu_shape_indices: [usize; 3],
+    // Tooltip background and outline.
+    tooltip_shape_indices: [usize; 2],
+    // Outline around the widget with the focus.
+    focus_shape_index: usize,
+    tooltip_shown: bool,
 
     default_padding: Coord2,
+    rules: Vec<styling::Rule>,
 
     // Helpful for printing debug information.
     #[allow(dead_code)]
     debug_poller: Poller,
 
-    #[allow(dead_code)]
+    // Frames drawn since the FPS was last counted, by the render thread.
     iters: AtomicU32,
     fps: u32,
+    // The FPS and such, updated along with them.
+    debug_text: String,
+    // Voices playing, as of the last parameter sync.
+    active_voices: usize,
+    // The UI scale the window opened at, and the one chosen since, shown for the next opening.
+    ui_scale: f64,
+    next_ui_scale: f64,
+    // From the preferences: the drag sensitivity, and whether to show the debug text at all.
+    drag_sensitivity: f64,
+    show_stats: bool,
+
+    // Whether the audio thread has stopped after a panic.
+    faulted: bool,
+    // Problems shown over the editor, oldest first, until dismissed with Escape.
+    errors: Vec<String>,
+    // While the GPU is failing to draw: when to try again, and how many tries have failed.
+    render_retry: Option<(Instant, u32)>,
+
+    // The least time between frames, if the frame rate's capped.
+    min_frame_interval: Option<Duration>,
+    // Whether anything drawn changed since the last frame, which was drawn at `last_frame`.
+    dirty: bool,
+    last_frame: Instant,
+    // The tooltip drawn in the last frame.
+    shown_tooltip: Option<Tooltip>,
 }
 
 impl RenderState {
+    // Without a window, there's nothing to present frames to; the editor can only be captured.
     async fn new<'a>(
-        mut widgets: Vec<Widget>,
-        window: &'a Window<'a>,
+        widgets: Vec<Widget>,
+        window: Option<&'a Window<'a>>,
         size: baseview::Size,
         scaling: f64,
+        preferences: &Preferences,
         styling: &styling::Styling,
-    ) -> (Self, WidgetMap) {
+    ) -> anyhow::Result<(Self, WidgetMap, Option<Presenter>)> {
+        let ui_scale = preferences.ui_scale;
         let window_info = baseview::WindowInfo::from_logical_size(size, scaling);
 
         let viewport = Viewport::with_physical_size(
@@ -181,103 +492,98 @@ impl RenderState {
             window_info.scale(),
         );
 
+        // The window's size already includes the UI scale.
         let screen_metrics = ScreenMetrics::new(
             window_info.physical_size().width,
             window_info.physical_size().height,
-            window_info.scale(),
+            window_info.scale() * ui_scale,
         );
 
-        // Initialize wgpu
-        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
-        let surface = unsafe { instance.create_surface(window) };
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect("Request adapter");
-        let (device, queue) = {
-            adapter
-                .request_device(
-                    &wgpu::DeviceDescriptor {
-                        label: None,
-                        features: wgpu::Features::empty(),
-                        limits: wgpu::Limits::default(),
-                    },
-                    None, // Trace path
-                )
-                .await
-                .expect("Request device")
-        };
-        let swapchain_format = adapter.get_swap_chain_preferred_format(&surface);
-
-        /////////////////////////////////////////////////////////////////
-        // Sprites
-        /////////////////////////////////////////////////////////////////
-        let stylesheet_base_filename = styling
-            .stylesheet_image
-            .as_ref()
-            .cloned()
-            .expect("Stylesheet image could not be loaded");
-
-        // Go up one folder
-        let assets_folder = {
-            let base = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-                .parent()
-                .unwrap();
-            base.join("assets")
+        // Initialize wgpu, falling back on the secondary backends (e.g. OpenGL or DX11) where
+        // the primary ones have no adapter, as with older GPUs.
+        let mut found = None;
+        for backends in [wgpu::BackendBit::PRIMARY, wgpu::BackendBit::SECONDARY] {
+            let instance = wgpu::Instance::new(backends);
+            let surface = window.map(|window| unsafe { instance.create_surface(window) });
+            let adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::HighPerformance,
+                    compatible_surface: surface.as_ref(),
+                })
+                .await;
+            match adapter {
+                Some(adapter) => {
+                    found = Some((surface, adapter));
+                    break;
+                }
+                None => log::warn!("No GPU adapter for backends {:?}", backends),
+            }
+        }
+        let (surface, adapter) = found.ok_or(NoAdapter)?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features: wgpu::Features::empty(),
+                    limits: wgpu::Limits::default(),
+                },
+                None, // Trace path
+            )
+            .await?;
+        let swapchain_format = match &surface {
+            Some(surface) => adapter.get_swap_chain_preferred_format(surface),
+            // Captures are drawn in the order PNGs keep their pixels in.
+            None => wgpu::TextureFormat::Rgba8UnormSrgb,
         };
 
-        let filename = assets_folder.join(stylesheet_base_filename);
-        log::info!("Sprite base filename: {:?}", filename);
-
-        let mut spritesheet_builder = sprites::SpriteSheetBuilder::new(
+        let scene = Scene::build(
+            widgets,
+            styling,
             &device,
             &swapchain_format,
             &queue,
-            filename.to_str().unwrap(),
-        );
-
-        // Add a background, if one is given.
-        let background_sprite_index = if let styling::Background::Sprite {
-            dest_rect,
-            src_rect,
-        } = &styling.background
-        {
-            Some(spritesheet_builder.add(sprites::SpriteBuilder {
-                pos: UserVec2::Rel(Vec2 {
-                    pos: [dest_rect.x1(), dest_rect.y1()],
-                }),
-                size: UserVec2::Rel(Vec2 {
-                    pos: [dest_rect.width(), dest_rect.height()],
-                }),
-                src_px: sprites::SpriteSource {
-                    src_rect: src_rect.pos,
-                },
-            }))
-        } else {
-            None
+            &screen_metrics,
+        )?;
+
+        // Overlays start out hidden; these only size their shapes. Rectangles take the same
+        // number of vertices whatever their size. They're drawn straight to the frame, after
+        // the texts, so aren't multisampled.
+        let mut overlay_builder =
+            shapes::ShapesBuilder::with_capacity(4, &device, &swapchain_format, 1);
+        let mut add_overlay = |buffers: shapes::Buffers| {
+            let max_v_count = buffers.vertices.len();
+            let max_i_count = buffers.indices.len();
+            overlay_builder.add(GpuShape::from_lyon(buffers, max_v_count, max_i_count))
         };
-
-        let mut widget_map = HashMap::new();
-        let mut shapes_builder =
-            shapes::ShapesBuilder::with_capacity(128, &device, &swapchain_format);
-        for mut widget in widgets.drain(..) {
-            widget.initialize(
+        let sizing_rect = TextEntry::field_rect(&Rect::default());
+        let outline = || {
+            shape_util::rectangle_outline(
+                &sizing_rect,
                 &screen_metrics,
-                &mut spritesheet_builder,
-                &mut shapes_builder,
-            );
-            widget_map.insert(widget.id, widget);
+                context_menu::CONTEXT_MENU_OUTLINE_WIDTH,
+                &context_menu::CONTEXT_MENU_OUTLINE_COLOR,
+            )
+        };
+        let text_entry_shape_index =
+            add_overlay(shape_util::rectangle_solid(&sizing_rect, &screen_metrics));
+        let context_menu_shape_indices = [
+            add_overlay(shape_util::rectangle_solid(&sizing_rect, &screen_metrics)),
+            add_overlay(outline()),
+            add_overlay(outline()),
+        ];
+        let tooltip_shape_indices = [
+            add_overlay(shape_util::rectangle_solid(&sizing_rect, &screen_metrics)),
+            add_overlay(outline()),
+        ];
+        let focus_shape_index = add_overlay(outline());
+        let mut overlay_shapes = overlay_builder.build();
+        for index in iter::once(text_entry_shape_index)
+            .chain(context_menu_shape_indices)
+            .chain(tooltip_shape_indices)
+            .chain(iter::once(focus_shape_index))
+        {
+            overlay_shapes.update(index, &[], &[]);
         }
-        /////////////////////////////////////////////////////////////////
-        // Shapes
-        /////////////////////////////////////////////////////////////////
-
-        let spritesheet = spritesheet_builder.build(&screen_metrics);
-        let shapes = shapes_builder.build();
 
         ///////////////////////////
 
@@ -288,27 +594,20 @@ impl RenderState {
                 format: swapchain_format,
                 width: size.width,
                 height: size.height,
-                present_mode: wgpu::PresentMode::Fifo,
+                present_mode: preferences.present_mode.to_wgpu(),
             }
         };
 
         /////////////////////////////////////////////////////////////////
         // Text
         /////////////////////////////////////////////////////////////////
-        let font_bytes = include_bytes!("../../../assets/fonts/Oswald-Medium.ttf");
-        let active_font = ab_glyph::FontArc::try_from_slice(font_bytes).unwrap();
         // Create staging belt and a local pool
         let staging_belt = wgpu::util::StagingBelt::new(1024);
         let glyph_brush =
-            GlyphBrushBuilder::using_font(active_font).build(&device, swapchain_format);
+            GlyphBrushBuilder::using_font(load_font(styling)).build(&device, swapchain_format);
 
-        let swap_chain = device.create_swap_chain(&surface, &sc_desc);
-        let background_color = match &styling.background {
-            styling::Background::Solid { color } => {
-                [color.r as f64, color.g as f64, color.b as f64]
-            }
-            styling::Background::Sprite { .. } => [1.0, 1.0, 1.0], // TODO set back to black
-        };
+        let presenter = surface.map(|surface| Presenter::new(&device, surface, &sc_desc));
+        let msaa_view = create_msaa_view(&device, &sc_desc, styling.sample_count());
 
         let mut debug = Debug::new();
         let mut renderer = Renderer::new(Backend::new(&device, Settings::default()));
@@ -328,14 +627,15 @@ impl RenderState {
             events: Vec::with_capacity(128),
             debug,
             screen_metrics,
+            layout_ratio: styling.size.0 as f32 / styling.size.1 as f32,
 
             viewport,
             device,
-            swap_chain,
-            surface,
             format: swapchain_format,
             queue,
+            sample_count: styling.sample_count(),
             sc_desc,
+            msaa_view,
 
             cursor_position: iced_baseview::Point::new(-1.0, -1.0),
             resized: false,
@@ -348,38 +648,106 @@ impl RenderState {
 
             renderer,
 
-            spritesheet,
-            shapes,
-            background: background_color,
-            background_sprite_index,
+            spritesheet: scene.spritesheet,
+            shapes: scene.shapes,
+            backgrounds: scene.backgrounds,
+            page: 0,
 
             default_padding: Coord2::new(styling.padding.0, styling.padding.1),
+            rules: styling.rules.clone(),
 
             glyph_brush,
+            text_cache: TextCache::default(),
+            overlay_shapes,
+            text_entry_shape_index,
+            context_menu_shape_indices,
+            tooltip_shape_indices,
+            focus_shape_index,
+            tooltip_shown: false,
             staging_belt,
 
             debug_poller: Poller::new(Duration::from_millis(1000)),
             iters: AtomicU32::new(0),
             fps: 0,
+            debug_text: String::new(),
+            active_voices: 0,
+            ui_scale,
+            next_ui_scale: ui_scale,
+            drag_sensitivity: preferences.drag_sensitivity,
+            show_stats: preferences.show_stats,
+            faulted: false,
+            errors: scene.errors,
+            render_retry: None,
+            min_frame_interval: preferences.frame_rate.min_interval(),
+            dirty: true,
+            last_frame: Instant::now(),
+            shown_tooltip: None,
         };
-        (inst, widget_map)
+        Ok((inst, scene.widgets, presenter))
+    }
+
+    /// Swap the widgets for those of a changed styling. Returns them, laid out and ready to
+    /// draw; on error (e.g. a sprite image that can't be read) nothing changes.
+    fn reload(
+        &mut self,
+        widgets: Vec<Widget>,
+        styling: &styling::Styling,
+    ) -> anyhow::Result<WidgetMap> {
+        let scene = Scene::build(
+            widgets,
+            styling,
+            &self.device,
+            &self.format,
+            &self.queue,
+            &self.screen_metrics,
+        )?;
+        self.spritesheet = scene.spritesheet;
+        self.shapes = scene.shapes;
+        self.backgrounds = scene.backgrounds;
+        // The window keeps its size until it's next resized.
+        self.layout_ratio = styling.size.0 as f32 / styling.size.1 as f32;
+        self.default_padding = Coord2::new(styling.padding.0, styling.padding.1);
+        self.errors.extend(scene.errors);
+        self.rules = styling.rules.clone();
+        self.sample_count = styling.sample_count();
+        self.msaa_view = create_msaa_view(&self.device, &self.sc_desc, self.sample_count);
+        self.glyph_brush =
+            GlyphBrushBuilder::using_font(load_font(styling)).build(&self.device, self.format);
+        self.text_cache.stale = true;
+        self.dirty = true;
+        Ok(scene.widgets)
     }
 
+    /// Lay the widgets out again for the window's new size. Returns the (logical) size they're
+    /// drawn at, which keeps the layout's proportions.
     fn resize(
         &mut self,
         new_size: &baseview::PhySize,
         widgets: &mut WidgetMap,
         params: &Synchronizer,
-    ) {
-        // Recreate the swap chain with the new size
+    ) -> (u32, u32) {
+        // Constrain going by which of the window's sides changed, then shrink to fit the window.
+        let window_metrics = ScreenMetrics::new(
+            self.sc_desc.width,
+            self.sc_desc.height,
+            self.screen_metrics.scale_factor,
+        );
+        let (width, height) =
+            window_metrics.constrain_resize(new_size.width, new_size.height, self.layout_ratio);
+        let fit = (new_size.width as f32 / width as f32)
+            .min(new_size.height as f32 / height as f32)
+            .min(1.0);
+
+        // The render thread recreates the swap chain to match.
         self.sc_desc.width = new_size.width;
         self.sc_desc.height = new_size.height;
-        self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
+        self.msaa_view = create_msaa_view(&self.device, &self.sc_desc, self.sample_count);
         self.screen_metrics = ScreenMetrics::new(
-            new_size.width,
-            new_size.height,
+            (width as f32 * fit) as u32,
+            (height as f32 * fit) as u32,
             self.screen_metrics.scale_factor,
         );
+        self.text_cache.stale = true;
         self.update_all_widgets(widgets, params);
         for (_widget_id, widget) in widgets.iter_mut() {
             widget.on_resize(
@@ -389,24 +757,55 @@ impl RenderState {
                 params,
             );
         }
+        let scale = self.window_info.scale();
+        (
+            (self.screen_metrics.width_f32 as f64 / scale).round() as u32,
+            (self.screen_metrics.height_f32 as f64 / scale).round() as u32,
+        )
+    }
+
+    /// Draw only to the area the widgets are laid out in.
+    fn set_viewport(&self, rpass: &mut wgpu::RenderPass) {
+        rpass.set_viewport(
+            0.0,
+            0.0,
+            self.screen_metrics.width_f32,
+            self.screen_metrics.height_f32,
+            0.0,
+            1.0,
+        );
     }
 
     fn update_all_widgets(&mut self, widgets: &mut WidgetMap, params: &Synchronizer) {
+        self.dirty = true;
         for (_widget_id, widget) in widgets.iter_mut() {
-            widget.update(
-                &self.screen_metrics,
-                &mut self.spritesheet,
-                &mut self.shapes,
-                params,
-            );
+            self.redraw(widget, params);
         }
-        if let Some(sprite_index) = self.background_sprite_index {
-            self.spritesheet.update_sprite(
-                sprite_index,
-                &sprites::SpriteUpdate::default(),
-                &self.screen_metrics,
-            );
+        for background in &self.backgrounds {
+            if let Some(sprite_index) = background.sprite_index {
+                self.spritesheet.update_sprite(
+                    sprite_index,
+                    &sprites::SpriteUpdate::default(),
+                    &self.screen_metrics,
+                );
+            }
+        }
+    }
+
+    /// Show `page`'s background and widgets, hiding those of the other pages.
+    fn show_page(&mut self, widgets: &mut WidgetMap, params: &Synchronizer, page: usize) {
+        self.page = page.min(self.backgrounds.len() - 1);
+        let shown_sprite = self.backgrounds[self.page].sprite_index;
+        for background in &self.backgrounds {
+            if let Some(sprite_index) = background.sprite_index {
+                self.spritesheet
+                    .set_hidden(sprite_index, Some(sprite_index) != shown_sprite);
+            }
+        }
+        for widget in widgets.values_mut() {
+            widget.show_page(self.page, &mut self.spritesheet, &mut self.shapes);
         }
+        self.update_all_widgets(widgets, params);
     }
 
     fn update_widgets(
@@ -417,106 +816,192 @@ impl RenderState {
     ) {
         for (widget_id, widget) in widgets.iter_mut() {
             if updates.contains(widget_id) {
-                widget.update(
-                    &self.screen_metrics,
-                    &mut self.spritesheet,
-                    &mut self.shapes,
-                    params,
-                );
+                self.redraw(widget, params);
+                // Widgets on other pages can change without being seen.
+                self.dirty |= widget.is_visible();
             }
         }
     }
 
+    /// Show `message` over the editor until it's dismissed, besides logging it. Only the
+    /// latest few are kept.
+    fn report_error(&mut self, message: String) {
+        log::error!("{}", message);
+        if self.errors.len() >= MAX_ERRORS_SHOWN {
+            self.errors.remove(0);
+        }
+        self.errors.push(message);
+        self.dirty = true;
+    }
+
+    /// Note that the GPU couldn't draw a frame: report it the first time, and back off before
+    /// trying again.
+    fn render_failed(&mut self, err: wgpu::SwapChainError) {
+        let failures = self.render_retry.map_or(0, |(_, failures)| failures) + 1;
+        if failures == 1 {
+            self.report_error(format!("Couldn't draw the editor ({:?}); retrying", err));
+        }
+        let delay = RENDER_RETRY_INTERVAL
+            .saturating_mul(1 << (failures - 1).min(16))
+            .min(MAX_RENDER_RETRY_INTERVAL);
+        self.render_retry = Some((Instant::now() + delay, failures));
+        self.dirty = true;
+    }
+
+    /// Stop showing the errors. Returns whether there were any.
+    fn dismiss_errors(&mut self) -> bool {
+        if self.errors.is_empty() {
+            return false;
+        }
+        self.errors.clear();
+        self.dirty = true;
+        true
+    }
+
+    /// Update `widget`, with the effect of the styling's rules as the parameters stand.
+    fn redraw(&mut self, widget: &mut Widget, params: &Synchronizer) {
+        let effect = self
+            .rules
+            .iter()
+            .filter(|rule| rule.widgets.contains(&widget.id) && rule.applies(params))
+            .map(|rule| rule.effect)
+            .max();
+        widget.set_effect(effect, &mut self.spritesheet, &mut self.shapes);
+        widget.update(
+            &self.screen_metrics,
+            &mut self.spritesheet,
+            &mut self.shapes,
+            params,
+        );
+    }
+
     fn update_widget(&mut self, widgets: &mut WidgetMap, params: &Synchronizer, id: &WidgetId) {
+        self.dirty = true;
         if let Some(widget) = widgets.get_mut(id) {
-            widget.update(
-                &self.screen_metrics,
-                &mut self.spritesheet,
-                &mut self.shapes,
-                params,
-            );
+            self.redraw(widget, params);
         } else {
             log::warn!("update_widget: widget not found! id={:?}", id);
         }
     }
 
-    async fn render(&mut self, widgets: &mut WidgetMap) {
-        if self.resized {
-            let size = self.window_info.physical_size();
-
-            self.swap_chain = self.device.create_swap_chain(
-                &self.surface,
-                &wgpu::SwapChainDescriptor {
-                    usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
-                    format: self.format,
-                    width: size.width,
-                    height: size.height,
-                    present_mode: wgpu::PresentMode::Mailbox,
-                },
-            );
-
-            self.resized = false;
+    /// Show the text entry field over the widget at `widget_rect`, or hide it.
+    fn update_text_entry(&mut self, widget_rect: Option<&Rect>) {
+        self.dirty = true;
+        match widget_rect {
+            Some(widget_rect) => {
+                let field = shape_util::rectangle_solid(
+                    &TextEntry::field_rect(widget_rect),
+                    &self.screen_metrics,
+                );
+                self.overlay_shapes.update(
+                    self.text_entry_shape_index,
+                    &field.vertices,
+                    &field.indices,
+                );
+            }
+            None => self
+                .overlay_shapes
+                .update(self.text_entry_shape_index, &[], &[]),
         }
-        if self.debug_poller.tick() {
-            self.fps = self.iters.swap(0, std::sync::atomic::Ordering::Relaxed);
-        };
-        let debug_text = format!("FPS: {}", self.fps);
+    }
 
-        let frame = self
-            .swap_chain
-            .get_current_frame()
-            .expect("Failed to acquire next swap chain texture")
-            .output;
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+    /// Outline the focused widget, or hide the outline.
+    fn update_focus(&mut self, widget_rect: Option<&Rect>) {
+        self.dirty = true;
+        match widget_rect {
+            Some(widget_rect) => {
+                let outline = shape_util::rectangle_outline(
+                    widget_rect,
+                    &self.screen_metrics,
+                    FOCUS_OUTLINE_WIDTH,
+                    &FOCUS_OUTLINE_COLOR,
+                );
+                self.overlay_shapes.update(
+                    self.focus_shape_index,
+                    &outline.vertices,
+                    &outline.indices,
+                );
+            }
+            None => self.overlay_shapes.update(self.focus_shape_index, &[], &[]),
+        }
+    }
 
-        // Note: read wgpu docs before reordering any of these operations.
-        buffer_memory::update(
-            &self.device,
-            &mut self.shapes.shapes,
-            &mut self.shapes.bufmem,
-            &mut self.staging_belt,
-            &mut encoder,
-        );
-        buffer_memory::update(
-            &self.device,
-            &mut self.spritesheet.shapes,
-            &mut self.spritesheet.bufmem,
-            &mut self.staging_belt,
-            &mut encoder,
-        );
+    /// Show the context menu, or hide it.
+    fn update_context_menu(&mut self, menu: Option<&ContextMenu>) {
+        self.dirty = true;
+        let [background_index, outline_index, hovered_index] = self.context_menu_shape_indices;
+        let outline = |rect: &Rect| {
+            shape_util::rectangle_outline(
+                rect,
+                &self.screen_metrics,
+                context_menu::CONTEXT_MENU_OUTLINE_WIDTH,
+                &context_menu::CONTEXT_MENU_OUTLINE_COLOR,
+            )
+        };
+        let (background, menu_outline, hovered) = match menu {
+            Some(menu) => (
+                Some(shape_util::rectangle_solid(
+                    &menu.rect(),
+                    &self.screen_metrics,
+                )),
+                Some(outline(&menu.rect())),
+                menu.hovered().map(|index| outline(&menu.item_rect(index))),
+            ),
+            None => (None, None, None),
+        };
+        for (index, buffers) in [
+            (background_index, background),
+            (outline_index, menu_outline),
+            (hovered_index, hovered),
+        ] {
+            match buffers {
+                Some(buffers) => {
+                    self.overlay_shapes
+                        .update(index, &buffers.vertices, &buffers.indices)
+                }
+                None => self.overlay_shapes.update(index, &[], &[]),
+            }
+        }
+    }
 
-        {
-            let rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                    attachment: &frame.view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: self.background[0],
-                            g: self.background[1],
-                            b: self.background[2],
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                }],
-                depth_stencil_attachment: None,
-            });
-            // Render sprites first, then shapes.
-            let rpass = self.spritesheet.render(rpass);
-            self.shapes.render(rpass);
+    /// Show the tooltip background at `rect`, or hide it.
+    fn update_tooltip(&mut self, rect: Option<Rect>) {
+        if rect.is_none() && !self.tooltip_shown {
+            return;
         }
+        self.tooltip_shown = rect.is_some();
+        let [background_index, outline_index] = self.tooltip_shape_indices;
+        let (background, outline) = match rect {
+            Some(rect) => (
+                shape_util::rectangle_solid(&rect, &self.screen_metrics),
+                shape_util::rectangle_outline(
+                    &rect,
+                    &self.screen_metrics,
+                    tooltip::TOOLTIP_OUTLINE_WIDTH,
+                    &tooltip::TOOLTIP_OUTLINE_COLOR,
+                ),
+            ),
+            None => (shapes::Buffers::new(), shapes::Buffers::new()),
+        };
+        self.overlay_shapes
+            .update(background_index, &background.vertices, &background.indices);
+        self.overlay_shapes
+            .update(outline_index, &outline.vertices, &outline.indices);
+    }
 
-        for widget in widgets.values_mut() {
-            let x1 = widget.rect.x1();
-            let y1 = widget.rect.y1();
-            let x2 = widget.rect.x2();
-            let y2 = widget.rect.y2();
+    /// Lay out the frame's texts, for `draw` to draw until they change.
+    fn layout_widget_texts(&mut self, frame: &Frame) {
+        let cache = &mut self.text_cache;
+        cache.texts.clear();
+        cache.glyphs.clear();
+        cache.extra.clear();
+        let glyph_brush = &mut self.glyph_brush;
+        let screen_metrics = &self.screen_metrics;
+        for (id, rect, text, color) in frame.texts.iter() {
+            let x1 = rect.x1();
+            let y1 = rect.y1();
+            let x2 = rect.x2();
+            let y2 = rect.y2();
 
             let x_delta = x2 - x1;
             let y_delta = y2 - y1;
@@ -565,53 +1050,310 @@ impl RenderState {
                 } => (x1 + x, y1 + y, h_align.to_wgpu(), v_align.to_wgpu()),
             };
 
-            widget.apply_to_texts(|text, color| {
-                let (x, y, h_align, v_align) = get_label_pos(&text.pos);
-                let screen_x = self.screen_metrics.norm_x_to_screen(x);
-                let screen_y = self.screen_metrics.norm_y_to_screen(y);
-
-                let layout = Layout::default_single_line()
+            let (x, y, h_align, v_align) = get_label_pos(&text.pos);
+            let section = Section {
+                screen_position: (
+                    screen_metrics.norm_x_to_screen(x),
+                    screen_metrics.norm_y_to_screen(y),
+                ),
+                text: vec![Text::new(&text.value).with_scale(text.scale * screen_metrics.width_f32)],
+                layout: Layout::default_single_line()
                     .h_align(h_align)
-                    .v_align(v_align);
-                self.glyph_brush.queue(Section {
-                    screen_position: (screen_x, screen_y),
-                    // TODO: can add bounds: (x_bound, y_bound),
-                    // TODO: avoid vec allocation
-                    text: vec![Text::new(&text.value)
-                        .with_color(color.to_array4())
-                        .with_scale(text.scale * self.screen_metrics.width_f32)],
-                    layout,
-                    ..Default::default()
-                });
+                    .v_align(v_align),
+                ..Default::default()
+            };
+            // One text per section, so its glyphs all take the same color.
+            let section_index = cache.extra.len();
+            cache
+                .glyphs
+                .extend(glyph_brush.glyphs(section).map(|glyph| SectionGlyph {
+                    section_index,
+                    ..glyph.clone()
+                }));
+            cache.extra.push(Extra {
+                color: color.to_array4(),
+                z: 0.0,
             });
+            cache.texts.push((*id, text.clone(), color.clone()));
         }
-        self.glyph_brush.queue(Section {
-            screen_position: (5.0, 5.0),
-            // TODO: can add bounds: (x_bound, y_bound),
-            text: vec![Text::new(&debug_text)
-                .with_color([1.0, 1.0, 1.0, 1.0])
-                .with_scale(12.0)],
-            ..Default::default()
-        });
-
-        // Draw queued text.
-        self.glyph_brush
-            .draw_queued(
-                &self.device,
-                &mut self.staging_belt,
-                &mut encoder,
-                &frame.view,
-                self.window_info.physical_size().width,
-                self.window_info.physical_size().height,
-            )
-            .expect("Draw queued");
+        cache.stale = false;
+    }
 
-        // Now draw iced over the scene.
-        let _mouse_interaction = self.renderer.backend_mut().draw(
-            &self.device,
+    /// Whether there's a new frame to draw with `tooltip`, or it's time to draw one anyway.
+    fn needs_render(&self, tooltip: Option<&Tooltip>) -> bool {
+        if self
+            .render_retry
+            .is_some_and(|(retry_at, _)| Instant::now() < retry_at)
+        {
+            return false;
+        }
+        let since_last = self.last_frame.elapsed();
+        if self
+            .min_frame_interval
+            .is_some_and(|interval| since_last < interval)
+        {
+            return false;
+        }
+        self.dirty || tooltip != self.shown_tooltip.as_ref() || since_last >= IDLE_FRAME_INTERVAL
+    }
+
+    /// Note that a frame is on its way to the render thread, which draws it with `tooltip`,
+    /// and bring what it draws along with it (the swap chain's size, the FPS and such) up to
+    /// date.
+    fn begin_frame(&mut self, tooltip: Option<&Tooltip>) {
+        self.dirty = false;
+        self.last_frame = Instant::now();
+        self.shown_tooltip = tooltip.cloned();
+        if self.resized {
+            let size = self.window_info.physical_size();
+            self.sc_desc.width = size.width;
+            self.sc_desc.height = size.height;
+            self.msaa_view = create_msaa_view(&self.device, &self.sc_desc, self.sample_count);
+
+            self.resized = false;
+        }
+        if self.debug_poller.tick() || self.debug_text.is_empty() {
+            self.fps = self.iters.swap(0, std::sync::atomic::Ordering::Relaxed);
+            self.debug_text.clear();
+            let _ = write!(
+                self.debug_text,
+                "FPS: {}  Voices: {}  UI: {:.0}%",
+                self.fps,
+                self.active_voices,
+                self.next_ui_scale * 100.0
+            );
+            if self.next_ui_scale != self.ui_scale {
+                self.debug_text.push_str(" (when reopened)");
+            }
+            let _ = write!(
+                self.debug_text,
+                "  Drag: {:.0}%",
+                self.drag_sensitivity * 100.0
+            );
+        };
+    }
+
+    // Draw everything to `view`, with the texts and whatever's open over the widgets as of
+    // `frame`.
+    fn draw(&mut self, frame: &Frame, view: &wgpu::TextureView) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+
+        // Fit the tooltip's background around its text.
+        let tooltip_section = frame.tooltip.as_ref().map(|tooltip| Section {
+            screen_position: (
+                self.screen_metrics.norm_x_to_screen(tooltip.x),
+                self.screen_metrics.norm_y_to_screen(tooltip.y),
+            ),
+            text: vec![Text::new(&tooltip.text)
+                .with_color(tooltip::TOOLTIP_COLOR.to_array4())
+                .with_scale(tooltip::TOOLTIP_SCALE * self.screen_metrics.width_f32)],
+            layout: Layout::default_single_line()
+                .h_align(HorizontalAlign::Center)
+                .v_align(VerticalAlign::Bottom),
+            ..Default::default()
+        });
+        let tooltip_rect = tooltip_section
+            .as_ref()
+            .and_then(|section| self.glyph_brush.glyph_bounds(section))
+            .map(|bounds| {
+                let padding = tooltip::TOOLTIP_PADDING;
+                Rect::new(
+                    self.screen_metrics.screen_x_to_norm(bounds.min.x) - padding,
+                    self.screen_metrics.screen_y_to_norm(bounds.min.y) - padding,
+                    self.screen_metrics.screen_x_to_norm(bounds.max.x) + padding,
+                    self.screen_metrics.screen_y_to_norm(bounds.max.y) + padding,
+                )
+            });
+        self.update_tooltip(tooltip_rect);
+
+        // Note: read wgpu docs before reordering any of these operations.
+        buffer_memory::update(
+            &self.device,
+            &mut self.shapes.shapes,
+            &mut self.shapes.bufmem,
+            &mut self.staging_belt,
+            &mut encoder,
+        );
+        buffer_memory::update(
+            &self.device,
+            &mut self.spritesheet.shapes,
+            &mut self.spritesheet.bufmem,
+            &mut self.staging_belt,
+            &mut encoder,
+        );
+        buffer_memory::update(
+            &self.device,
+            &mut self.overlay_shapes.shapes,
+            &mut self.overlay_shapes.bufmem,
             &mut self.staging_belt,
             &mut encoder,
-            &frame.view,
+        );
+
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: self.msaa_view.as_ref().unwrap_or(view),
+                    resolve_target: self.msaa_view.as_ref().map(|_| view),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: self.backgrounds[self.page].color[0],
+                            g: self.backgrounds[self.page].color[1],
+                            b: self.backgrounds[self.page].color[2],
+                            a: 1.0,
+                        }),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            self.set_viewport(&mut rpass);
+            // Render sprites first, then shapes.
+            let rpass = self.spritesheet.render(rpass);
+            self.shapes.render(rpass);
+        }
+
+        if !self.text_cache.is_current(frame) {
+            self.layout_widget_texts(frame);
+        }
+        let size = self.window_info.physical_size();
+        self.glyph_brush.queue_pre_positioned(
+            self.text_cache.glyphs.clone(),
+            self.text_cache.extra.clone(),
+            ab_glyph::Rect {
+                min: ab_glyph::point(0.0, 0.0),
+                max: ab_glyph::point(size.width as f32, size.height as f32),
+            },
+        );
+        if self.show_stats {
+            self.glyph_brush.queue(Section {
+                screen_position: (5.0, 5.0),
+                // TODO: can add bounds: (x_bound, y_bound),
+                text: vec![Text::new(&self.debug_text)
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(12.0)],
+                ..Default::default()
+            });
+        }
+        if self.faulted {
+            self.glyph_brush.queue(Section {
+                screen_position: (5.0, 20.0),
+                text: vec![
+                    Text::new("Sound stopped after an internal error; see the log.")
+                        .with_color([1.0, 0.3, 0.3, 1.0])
+                        .with_scale(16.0),
+                ],
+                ..Default::default()
+            });
+        }
+        if !self.errors.is_empty() {
+            let top = if self.faulted { 40.0 } else { 20.0 };
+            let lines = self
+                .errors
+                .iter()
+                .map(String::as_str)
+                .chain(iter::once("(Escape to dismiss)"));
+            for (line, text) in lines.enumerate() {
+                self.glyph_brush.queue(Section {
+                    screen_position: (5.0, top + line as f32 * ERROR_LINE_HEIGHT),
+                    text: vec![Text::new(text)
+                        .with_color(ERROR_TEXT_COLOR)
+                        .with_scale(14.0)],
+                    ..Default::default()
+                });
+            }
+        }
+
+        // Draw queued text.
+        if let Err(err) = self.glyph_brush.draw_queued(
+            &self.device,
+            &mut self.staging_belt,
+            &mut encoder,
+            view,
+            self.window_info.physical_size().width,
+            self.window_info.physical_size().height,
+        ) {
+            log::error!("Failed to draw text: {}", err);
+        }
+
+        // Overlays go over everything drawn so far, texts included.
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Overlay Render Pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            self.set_viewport(&mut rpass);
+            self.overlay_shapes.render(rpass);
+        }
+        if let Some((entry, rect)) = &frame.text_entry {
+            let field = TextEntry::field_rect(rect);
+            self.glyph_brush.queue(Section {
+                screen_position: (
+                    self.screen_metrics.norm_x_to_screen(field.mid_x()),
+                    self.screen_metrics.norm_y_to_screen(field.mid_y()),
+                ),
+                text: vec![Text::new(&entry.display_text())
+                    .with_color(entry.color().to_array4())
+                    .with_scale(text_entry::TEXT_ENTRY_SCALE * self.screen_metrics.width_f32)],
+                layout: Layout::default_single_line()
+                    .h_align(HorizontalAlign::Center)
+                    .v_align(VerticalAlign::Center),
+                ..Default::default()
+            });
+        }
+        if let Some(section) = &tooltip_section {
+            self.glyph_brush.queue(section);
+        }
+        if let Some(menu) = &frame.context_menu {
+            for (index, (_, label)) in menu.items().enumerate() {
+                let item = menu.item_rect(index);
+                self.glyph_brush.queue(Section {
+                    screen_position: (
+                        self.screen_metrics
+                            .norm_x_to_screen(item.x1() + context_menu::CONTEXT_MENU_TEXT_PADDING),
+                        self.screen_metrics.norm_y_to_screen(item.mid_y()),
+                    ),
+                    text: vec![Text::new(label)
+                        .with_color(widgets::DEFAULT_TEXT_COLOR.to_array4())
+                        .with_scale(
+                            context_menu::CONTEXT_MENU_SCALE * self.screen_metrics.width_f32,
+                        )],
+                    layout: Layout::default_single_line()
+                        .h_align(HorizontalAlign::Left)
+                        .v_align(VerticalAlign::Center),
+                    ..Default::default()
+                });
+            }
+        }
+        if let Err(err) = self.glyph_brush.draw_queued(
+            &self.device,
+            &mut self.staging_belt,
+            &mut encoder,
+            view,
+            self.window_info.physical_size().width,
+            self.window_info.physical_size().height,
+        ) {
+            log::error!("Failed to draw text: {}", err);
+        }
+
+        // Now draw iced over the scene.
+        let _mouse_interaction = self.renderer.backend_mut().draw(
+            &self.device,
+            &mut self.staging_belt,
+            &mut encoder,
+            view,
             &self.viewport,
             self.program_state.primitive(),
             &self.debug.overlay(),
@@ -623,6 +1365,262 @@ impl RenderState {
         let f = self.staging_belt.recall();
         async_std::task::spawn(f);
     }
+
+    /// Draw the widgets as they stand, with `frame`'s texts, to a texture the window's size
+    /// rather than to the window, and save it as a PNG at `path`; e.g. to compare layouts
+    /// before and after a change.
+    fn capture(&mut self, frame: &Frame, path: &Path) -> anyhow::Result<()> {
+        let (width, height) = (self.sc_desc.width, self.sc_desc.height);
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("capture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // The FPS and such would differ from one capture to the next.
+        let debug_text = std::mem::take(&mut self.debug_text);
+        self.draw(frame, &view);
+        self.debug_text = debug_text;
+        self.dirty = true;
+
+        // Rows are copied out padded to wgpu's alignment.
+        let row_bytes = width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row_bytes = (row_bytes + align - 1) / align * align;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("capture_buffer"),
+            size: (padded_row_bytes * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Capture Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::BufferCopyView {
+                buffer: &buffer,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: padded_row_bytes,
+                    rows_per_image: height,
+                },
+            },
+            size,
+        );
+        self.queue.submit(iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapped = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        async_std::task::block_on(mapped)
+            .map_err(|_| anyhow::anyhow!("Couldn't read the capture back from the GPU"))?;
+        let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_row_bytes as usize) {
+            pixels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        // Swap chains are usually BGRA, where PNGs are RGBA.
+        if matches!(
+            self.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        let image = image::RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| anyhow::anyhow!("The capture is the wrong size"))?;
+        image.save(path)?;
+        Ok(())
+    }
+}
+
+/// The render state, shared by the GUI thread, which updates it, and the render thread, which
+/// draws from it. Each holds the lock only briefly.
+#[derive(Clone)]
+struct SharedRenderState(sync::Arc<sync::Mutex<RenderState>>);
+
+impl SharedRenderState {
+    fn new(render_state: RenderState) -> Self {
+        SharedRenderState(sync::Arc::new(sync::Mutex::new(render_state)))
+    }
+
+    // A thread that panicked while drawing leaves the state as usable as it was.
+    fn lock(&self) -> sync::MutexGuard<'_, RenderState> {
+        self.0.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+}
+
+/// The window's surface and swap chain, owned by the render thread; getting a frame from the
+/// swap chain can wait on the display, so it's done without holding the render state.
+struct Presenter {
+    surface: wgpu::Surface,
+    swap_chain: wgpu::SwapChain,
+    // The size the swap chain was made for.
+    width: u32,
+    height: u32,
+}
+
+impl Presenter {
+    fn new(
+        device: &wgpu::Device,
+        surface: wgpu::Surface,
+        sc_desc: &wgpu::SwapChainDescriptor,
+    ) -> Self {
+        let swap_chain = device.create_swap_chain(&surface, sc_desc);
+        Presenter {
+            surface,
+            swap_chain,
+            width: sc_desc.width,
+            height: sc_desc.height,
+        }
+    }
+
+    fn recreate(&mut self, device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) {
+        self.swap_chain = device.create_swap_chain(&self.surface, sc_desc);
+        self.width = sc_desc.width;
+        self.height = sc_desc.height;
+    }
+
+    /// Draw `frame` to the window, following the render state's swap chain settings.
+    fn present(&mut self, render_state: &SharedRenderState, frame: &Frame) {
+        {
+            let state = render_state.lock();
+            if (self.width, self.height) != (state.sc_desc.width, state.sc_desc.height) {
+                self.recreate(&state.device, &state.sc_desc);
+            }
+        }
+        let output = match self.swap_chain.get_current_frame() {
+            Ok(output) => output.output,
+            // E.g. while the window's minimized; try again next frame.
+            Err(wgpu::SwapChainError::Timeout) => {
+                render_state.lock().dirty = true;
+                return;
+            }
+            Err(wgpu::SwapChainError::Outdated) | Err(wgpu::SwapChainError::Lost) => {
+                let mut state = render_state.lock();
+                self.recreate(&state.device, &state.sc_desc);
+                state.dirty = true;
+                return;
+            }
+            // Out of memory; maybe not for long, so start over with a new swap chain later.
+            Err(err) => {
+                let mut state = render_state.lock();
+                state.render_failed(err);
+                self.recreate(&state.device, &state.sc_desc);
+                return;
+            }
+        };
+        let mut state = render_state.lock();
+        // The window may have been resized while waiting; draw it at its new size instead.
+        if (self.width, self.height) != (state.sc_desc.width, state.sc_desc.height) {
+            state.dirty = true;
+            return;
+        }
+        state.draw(frame, &output.view);
+        state.render_retry = None;
+        state
+            .iters
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        // Dropping `output` presents it, which needn't hold up the GUI thread.
+        drop(state);
+    }
+}
+
+// The next frame to draw, replaced by any newer one before it's drawn.
+#[derive(Default)]
+struct FrameMailbox {
+    frame: Option<Frame>,
+    stopping: bool,
+}
+
+/// Draws the frames the GUI thread sends, so a slow frame (or a display that's slow to take
+/// one) can't hold up input or the host.
+struct RenderThread {
+    mailbox: sync::Arc<(sync::Mutex<FrameMailbox>, sync::Condvar)>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl RenderThread {
+    fn spawn(render_state: SharedRenderState, mut presenter: Presenter) -> anyhow::Result<Self> {
+        let mailbox = sync::Arc::new((
+            sync::Mutex::new(FrameMailbox::default()),
+            sync::Condvar::new(),
+        ));
+        let thread_mailbox = sync::Arc::clone(&mailbox);
+        let handle = std::thread::Builder::new()
+            .name("sunfish-render".to_string())
+            .spawn(move || {
+                while let Some(frame) = RenderThread::next_frame(&thread_mailbox) {
+                    presenter.present(&render_state, &frame);
+                }
+            })?;
+        Ok(RenderThread {
+            mailbox,
+            handle: Some(handle),
+        })
+    }
+
+    // Wait for a frame to draw; None once stopped.
+    fn next_frame(mailbox: &(sync::Mutex<FrameMailbox>, sync::Condvar)) -> Option<Frame> {
+        let (lock, ready) = mailbox;
+        let mut mailbox = lock.lock().unwrap_or_else(sync::PoisonError::into_inner);
+        loop {
+            if mailbox.stopping {
+                return None;
+            }
+            if let Some(frame) = mailbox.frame.take() {
+                return Some(frame);
+            }
+            mailbox = ready
+                .wait(mailbox)
+                .unwrap_or_else(sync::PoisonError::into_inner);
+        }
+    }
+
+    /// Draw `frame` next, in place of any frame not yet drawn.
+    fn request(&self, frame: Frame) {
+        let (lock, ready) = &*self.mailbox;
+        lock.lock()
+            .unwrap_or_else(sync::PoisonError::into_inner)
+            .frame = Some(frame);
+        ready.notify_one();
+    }
+
+    /// Stop drawing, once the frame being drawn (if any) is done, before the window closes.
+    fn stop(&mut self) {
+        let (lock, ready) = &*self.mailbox;
+        lock.lock()
+            .unwrap_or_else(sync::PoisonError::into_inner)
+            .stopping = true;
+        ready.notify_one();
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::error!("The render thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
 }
 
 pub fn main() {
@@ -631,9 +1629,13 @@ pub fn main() {
             .unwrap();
 
     let styling = styling::load_default();
+    let preferences = Preferences::load();
 
     // Logical size.
-    let size = baseview::Size::new(styling.size.0 as f64, styling.size.1 as f64);
+    let size = baseview::Size::new(
+        styling.size.0 as f64 * preferences.ui_scale,
+        styling.size.1 as f64 * preferences.ui_scale,
+    );
 
     let options = baseview::WindowOpenOptions {
         title: "Sunfish Synthesizer".into(),
@@ -653,34 +1655,111 @@ pub fn main() {
         let meta = ParamsMeta::new();
 
         let mut synchronizer = Synchronizer::new(meta, params);
-        let subscriber = synchronizer.subscriber();
+        let deltas = synchronizer.delta_router();
+        // There's no audio engine here yet, so notes played on the keyboard go unheard.
+        let (midi_sender, _) = midi::gui_channel();
         let mut params_owner = Owner::new(synchronizer);
-        let mut subscriber_owner = Owner::new(subscriber);
+        let mut deltas_owner = Owner::new(deltas);
+        let mut midi_owner = Owner::new(midi_sender);
 
-        SynthGui::create(
+        EditorWindow::new(SynthGui::create(
             window,
             &styling,
             params_owner.borrow(),
-            subscriber_owner.borrow(),
+            deltas_owner.borrow(),
+            midi_owner.borrow(),
+            preferences,
             size,
             scaling,
-        )
-        .expect("SynthGui: failed to create.")
+        ))
     });
 }
 
+/// The styling's font, or the bundled one if it has none (or it can't be read).
+fn load_font(styling: &styling::Styling) -> ab_glyph::FontArc {
+    if let Some(path) = styling.font_path() {
+        match std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| {
+                ab_glyph::FontArc::try_from_vec(bytes).map_err(|_| anyhow::anyhow!("Not a font"))
+            }) {
+            Ok(font) => return font,
+            Err(err) => log::warn!("Using the bundled font instead of {:?}: {}", path, err),
+        }
+    }
+    let font_bytes = include_bytes!("../../../assets/fonts/Oswald-Medium.ttf");
+    ab_glyph::FontArc::try_from_slice(font_bytes).unwrap()
+}
+
+/// A target to draw frames of `sc_desc` into with `sample_count` samples per pixel, if that's
+/// more than one.
+fn create_msaa_view(
+    device: &wgpu::Device,
+    sc_desc: &wgpu::SwapChainDescriptor,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count <= 1 {
+        return None;
+    }
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("msaa_framebuffer"),
+        size: wgpu::Extent3d {
+            width: sc_desc.width,
+            height: sc_desc.height,
+            depth: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: sc_desc.format,
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+/// The widgets to redraw every frame.
+fn animated_widgets(widgets: &WidgetMap) -> HashSet<WidgetId> {
+    widgets
+        .iter()
+        .filter(|(_, widget)| widget.is_animated())
+        .map(|(widget_id, _)| *widget_id)
+        .collect()
+}
+
 pub struct SynthGui {
     // GUI and rendering state.
     state: State,
+    // None without a window to draw to.
+    render_thread: Option<RenderThread>,
 
     parameters: Borrower<Synchronizer>,
-    subscriber: Borrower<Subscriber>,
+    deltas: Borrower<ParamDeltaRouter>,
+    // Notes played on the keyboard, for the audio thread.
+    midi: Borrower<MidiSender>,
+    // The UI scale is changed here; the window keeps the scale it opened at.
+    preferences: Preferences,
+    ui_scale: f64,
 
-    #[allow(dead_code)]
     meta: sync::Arc<ParamsMeta>,
     param_sync_poller: Poller,
+    // The widgets are rebuilt when their styling changes, so it can be tweaked without
+    // reopening the window, and when the theme does.
+    styling: styling::Styling,
+    styling_poller: Poller,
+    styling_watcher: StylingWatcher,
     widgets_to_update: HashSet<WidgetId>,
+    // Widgets redrawn every frame.
+    animated_widgets: HashSet<WidgetId>,
+    // Widgets redrawn this frame: the animated ones, and those following modulation.
+    frame_widgets: HashSet<WidgetId>,
     _ignore_next_resized_event: bool,
+    // For the randomize button.
+    rng: Rng,
+    // The parameter most recently changed from the GUI, for "MIDI learn".
+    last_touched: Option<EParam>,
+    // The value last copied from a context menu. It's kept as text, so it can be pasted into
+    // any parameter shown in the same units.
+    copied_value: Option<String>,
 }
 
 impl SynthGui {
@@ -688,72 +1767,303 @@ impl SynthGui {
         window: &Window<'_>,
         styling: &styling::Styling,
         parameters: Borrower<Synchronizer>,
-        subscriber: Borrower<Subscriber>,
+        deltas: Borrower<ParamDeltaRouter>,
+        midi: Borrower<MidiSender>,
+        preferences: Preferences,
+        size: baseview::Size,
+        scaling: f64,
+    ) -> anyhow::Result<SynthGui> {
+        Self::open(
+            Some(window),
+            styling,
+            parameters,
+            deltas,
+            midi,
+            preferences,
+            size,
+            scaling,
+        )
+    }
+
+    /// An editor drawn without a window, e.g. in tests; it's only ever captured (see
+    /// `capture`). Fails with `NoAdapter` where there's no GPU to draw it with.
+    pub fn create_headless(
+        styling: &styling::Styling,
+        parameters: Borrower<Synchronizer>,
+        deltas: Borrower<ParamDeltaRouter>,
+        midi: Borrower<MidiSender>,
+        preferences: Preferences,
+        size: baseview::Size,
+    ) -> anyhow::Result<SynthGui> {
+        Self::open(
+            None,
+            styling,
+            parameters,
+            deltas,
+            midi,
+            preferences,
+            size,
+            1.0,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn open(
+        window: Option<&Window<'_>>,
+        styling: &styling::Styling,
+        parameters: Borrower<Synchronizer>,
+        deltas: Borrower<ParamDeltaRouter>,
+        midi: Borrower<MidiSender>,
+        preferences: Preferences,
         size: baseview::Size,
         scaling: f64,
-    ) -> Result<SynthGui, std::io::Error> {
+    ) -> anyhow::Result<SynthGui> {
         let meta = (parameters.grabbed.as_ref().unwrap()).meta.clone();
         let meta = sync::Arc::new(meta);
         let param_count = meta.count();
 
-        let state = async_std::task::block_on(State::new(
+        let ui_scale = preferences.ui_scale;
+        let theme = styling.theme(preferences.theme.as_deref());
+        let (state, presenter) = async_std::task::block_on(State::new(
             window,
             size,
             scaling,
+            &preferences,
             sync::Arc::clone(&meta),
             styling,
-        ));
+            &theme,
+        ))?;
         let param_sync_duration = Duration::from_secs_f32(1.0 / PARAM_SYNC_PER_SEC);
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        let animated_widgets = animated_widgets(&state.widgets);
+        let render_thread = presenter
+            .map(|presenter| RenderThread::spawn(state.render_state.clone(), presenter))
+            .transpose()?;
         let mut synth_gui = SynthGui {
             state,
+            render_thread,
 
             parameters,
-            subscriber,
+            deltas,
+            midi,
+            preferences,
+            ui_scale,
             meta,
             param_sync_poller: Poller::new(param_sync_duration),
+            styling: styling.clone(),
+            styling_poller: Poller::new(STYLING_CHECK_INTERVAL),
+            styling_watcher: StylingWatcher::new(styling::default_path(), styling),
             widgets_to_update: HashSet::with_capacity(param_count),
+            animated_widgets,
+            frame_widgets: HashSet::with_capacity(param_count),
             _ignore_next_resized_event: false,
+            rng: Rng::new(seed),
+            last_touched: None,
+            copied_value: None,
         };
         synth_gui.synchronize_all_params();
+        synth_gui.show_page(0);
         Ok(synth_gui)
     }
 
+    // Send the render thread a snapshot of the widgets to draw, if there's a new frame.
     fn render_sync(&mut self) {
-        self.state
+        let Some(render_thread) = &self.render_thread else {
+            return;
+        };
+        let tooltip = self.tooltip();
+        {
+            let mut render_state = self.state.render_state.lock();
+            if !render_state.needs_render(tooltip.as_ref()) {
+                return;
+            }
+            render_state.begin_frame(tooltip.as_ref());
+        }
+        render_thread.request(self.state.frame(tooltip));
+    }
+
+    /// The tooltip for the widget under the mouse, once it's rested there a moment. While
+    /// dragging, the value follows the cursor instead. None shows while something else is going
+    /// on, e.g. an open menu.
+    fn tooltip(&self) -> Option<Tooltip> {
+        let state = &self.state;
+        if let InteractiveState::Dragging { id, .. } = state.interactive_state {
+            let eparam = state.widgets.get(&id)?.value_param()?;
+            let value = self.parameters.formatted_value(eparam).ok()?;
+            return Some(Tooltip::above_cursor(
+                state.mouse_pos_norm.x,
+                state.mouse_pos_norm.y,
+                value,
+            ));
+        }
+        let busy = state.interactive_state != InteractiveState::Idle
+            || state.text_entry.is_some()
+            || state.context_menu.is_some()
+            || state.widgets.values().any(|widget| widget.is_open());
+        if busy {
+            return None;
+        }
+        let (id, since) = state.hovered?;
+        if since.elapsed() < tooltip::TOOLTIP_DELAY {
+            return None;
+        }
+        let eparam = match id {
+            WidgetId::Bound { eparam } => eparam,
+            _ => return None,
+        };
+        let widget = state.widgets.get(&id)?;
+        let value = self.parameters.formatted_value(eparam).ok()?;
+        Some(Tooltip::above(
+            &widget.rect,
+            format!("{}: {}", eparam.as_string(false), value),
+        ))
+    }
+
+    /// Reload the styling file, which (or whose sprite image) changed, and rebuild the widgets.
+    /// If it can't be loaded, e.g. while half-edited, the widgets stay as they are.
+    fn reload_styling(&mut self) {
+        let styling_path = styling::default_path();
+        let styling = match styling::try_load_from_file(&styling_path) {
+            Ok(styling) => styling,
+            Err(err) => {
+                self.state
+                    .render_state
+                    .lock()
+                    .report_error(format!("Failed to reload the styling: {:#}", err));
+                return;
+            }
+        };
+        // The sprite image may have been swapped for another.
+        self.styling_watcher = StylingWatcher::new(styling_path, &styling);
+        self.styling = styling;
+        self.rebuild_widgets();
+    }
+
+    /// Switch to the styling's next theme, and keep it for next time.
+    fn next_theme(&mut self) {
+        let name = match self.styling.next_theme(self.preferences.theme.as_deref()) {
+            Some(theme) => theme.name.clone(),
+            None => return,
+        };
+        self.preferences.theme = Some(name);
+        self.save_preferences();
+        self.rebuild_widgets();
+    }
+
+    /// Build the widgets again, from the styling and in the theme picked.
+    fn rebuild_widgets(&mut self) {
+        let theme = self.styling.theme(self.preferences.theme.as_deref());
+        let presets_dir = self.preferences.presets_dir();
+        let widgets = styling::create_widgets(
+            &self.styling,
+            &theme,
+            presets_dir.as_deref(),
+            sync::Arc::clone(&self.meta),
+        );
+        let reloaded = self
+            .state
             .render_state
-            .iters
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        async_std::task::block_on(self.state.render_state.render(&mut self.state.widgets));
+            .lock()
+            .reload(widgets, &self.styling);
+        let widgets = match reloaded {
+            Ok(widgets) => widgets,
+            Err(err) => {
+                self.state
+                    .render_state
+                    .lock()
+                    .report_error(format!("Failed to rebuild the widgets: {:#}", err));
+                return;
+            }
+        };
+        // Nothing open carries over to the new widgets.
+        self.close_text_entry(false);
+        self.close_context_menu();
+        self.state.last_click = None;
+        self.state.widgets = widgets;
+        self.animated_widgets = animated_widgets(&self.state.widgets);
+        self.synchronize_all_params();
+        let page = self.state.render_state.lock().page;
+        self.show_page(page);
+    }
+
+    /// Save the editor, as it's drawn, as a PNG at `path`.
+    pub fn capture(&mut self, path: &Path) -> anyhow::Result<()> {
+        // Nothing open (menus and such) is drawn.
+        let frame = Frame {
+            texts: self.state.frame(None).texts,
+            ..Frame::default()
+        };
+        self.state.render_state.lock().capture(&frame, path)
+    }
+
+    // Save a capture in the temp folder, named for when it was taken.
+    fn capture_to_temp(&mut self) {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let path = std::env::temp_dir().join(format!("sunfish-{}.png", seconds));
+        match self.capture(&path) {
+            Ok(()) => log::info!("Saved a capture to {:?}", path),
+            Err(err) => self
+                .state
+                .render_state
+                .lock()
+                .report_error(format!("Failed to save a capture: {:#}", err)),
+        }
     }
 
     /// Load all baseline parameters.
     fn synchronize_all_params(&mut self) {
-        self.synchronize_params();
+        // Changes made before the window opened are already in the parameters.
+        self.deltas.flush(|_, _| ());
+        self.parameters.refresh();
+        for widget in self.state.widgets.values_mut() {
+            for eparam in widget.params() {
+                widget.set_param_value(eparam, self.parameters.read_parameter(eparam));
+            }
+        }
+        let stats = WidgetId::Action {
+            action: Action::ToggleStats,
+        };
+        if let Some(widget) = self.state.widgets.get_mut(&stats) {
+            widget.value = if self.preferences.show_stats {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        self.state
+            .render_state
+            .lock()
+            .update_all_widgets(&mut self.state.widgets, &self.parameters);
     }
 
     /// Returns true if any parameters need changing.
     fn synchronize_params(&mut self) -> bool {
-        let mut any_changed = false;
-        if let Ok(guard) = self.subscriber.changes.try_lock() {
-            let changes = &(*guard);
-            self.widgets_to_update.clear();
-            for (updated_eparam, updated_value) in changes {
-                any_changed = true;
-                let widget_id = WidgetId::Bound {
-                    eparam: *updated_eparam,
-                };
-                if let Some(widget) = self.state.widgets.get_mut(&widget_id) {
-                    widget.value = *updated_value;
+        let widgets = &mut self.state.widgets;
+        let widgets_to_update = &mut self.widgets_to_update;
+        let rules = &self.styling.rules;
+        widgets_to_update.clear();
+        self.deltas.flush(|updated_eparam, updated_value| {
+            for widget_id in WidgetId::showing(updated_eparam) {
+                if let Some(widget) = widgets.get_mut(&widget_id) {
+                    widget.set_param_value(updated_eparam, updated_value);
                 }
-                self.widgets_to_update.insert(widget_id);
+                widgets_to_update.insert(widget_id);
             }
-            if any_changed {
-                self.state.render_state.update_widgets(
-                    &mut self.state.widgets,
-                    &self.parameters,
-                    &self.widgets_to_update,
-                );
+            for rule in rules.iter().filter(|rule| rule.param == updated_eparam) {
+                widgets_to_update.extend(rule.widgets.iter().copied());
             }
+        });
+        let any_changed = !self.widgets_to_update.is_empty();
+        if any_changed {
+            self.state.render_state.lock().update_widgets(
+                &mut self.state.widgets,
+                &self.parameters,
+                &self.widgets_to_update,
+            );
         }
         any_changed
     }
@@ -761,16 +2071,612 @@ impl SynthGui {
     fn update_param(&mut self, id: &WidgetId, val: f64) {
         let eparam = match id {
             WidgetId::Unspecified { .. } => return,
+            WidgetId::Action { action } => {
+                self.run_action(*action);
+                // Buttons spring back once pressed, except the switches: A/B shows the active
+                // slot, and the stats button whether they're shown.
+                let value = match action {
+                    Action::CompareAB if self.parameters.compare_slot() == CompareSlot::B => 1.0,
+                    Action::ToggleStats if self.preferences.show_stats => 1.0,
+                    _ => 0.0,
+                };
+                if let Some(widget) = self.state.widgets.get_mut(id) {
+                    widget.value = value;
+                }
+                return;
+            }
             WidgetId::Bound { eparam } => *eparam,
+            WidgetId::Envelope { .. } => {
+                // An envelope's breakpoints each move one or two parameters.
+                if let Some(widget) = self.state.widgets.get(id) {
+                    for (eparam, value) in widget.edited_values() {
+                        self.last_touched = Some(eparam);
+                        self.parameters.write_parameter(eparam, value);
+                    }
+                }
+                return;
+            }
         };
+        self.last_touched = Some(eparam);
         self.parameters.write_parameter(eparam, val);
+        // Widgets the styling's rules tie to the parameter follow it.
+        for rule in self
+            .styling
+            .rules
+            .iter()
+            .filter(|rule| rule.param == eparam)
+        {
+            for widget_id in &rule.widgets {
+                self.state.render_state.lock().update_widget(
+                    &mut self.state.widgets,
+                    &self.parameters,
+                    widget_id,
+                );
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Randomize => {
+                let values = randomize::random_values(&mut self.rng, &randomize::default_locks());
+                self.parameters.write_parameters(values);
+            }
+            Action::InitPatch => self.parameters.reset_to_defaults(),
+            Action::CompareAB => self.parameters.toggle_compare(),
+            Action::CopyAToB => self.parameters.copy_compare_a_to_b(),
+            Action::MidiLearn => {
+                if let Some(eparam) = self.last_touched {
+                    self.parameters.learn_cc(eparam);
+                }
+            }
+            Action::ShrinkUi => self.step_ui_scale(-1),
+            Action::GrowUi => self.step_ui_scale(1),
+            Action::NextTheme => self.next_theme(),
+            Action::PreviousPreset => self.step_preset(-1),
+            Action::NextPreset => self.step_preset(1),
+            Action::SlowerDrag => self.step_drag_sensitivity(-1),
+            Action::FasterDrag => self.step_drag_sensitivity(1),
+            Action::ToggleStats => {
+                self.preferences.show_stats = !self.preferences.show_stats;
+                self.save_preferences();
+                let mut render_state = self.state.render_state.lock();
+                render_state.show_stats = self.preferences.show_stats;
+                render_state.dirty = true;
+            }
+        }
+    }
+
+    // The preset browser to act on: the first one `pred` picks.
+    fn find_preset_browser<P: Fn(&Widget) -> bool>(&self, pred: P) -> Option<WidgetId> {
+        self.state
+            .widgets
+            .iter()
+            .find(|(_, widget)| widget.preset_browser().is_some() && pred(widget))
+            .map(|(widget_id, _)| *widget_id)
+    }
+
+    /// Change the preset browser `id` with `f`, then redraw it.
+    fn with_preset_browser<F: FnOnce(&mut PresetBrowser)>(&mut self, id: &WidgetId, f: F) {
+        if let Some(browser) = self
+            .state
+            .widgets
+            .get_mut(id)
+            .and_then(Widget::preset_browser_mut)
+        {
+            f(browser);
+        }
+        self.state
+            .render_state
+            .lock()
+            .update_widget(&mut self.state.widgets, &self.parameters, id);
+    }
+
+    /// Load the preset at `index` in the library of the preset browser `id`, writing all its
+    /// parameters in one go.
+    fn load_preset(&mut self, id: &WidgetId, index: usize) {
+        let mut loaded = None;
+        self.with_preset_browser(id, |browser| loaded = Some(browser.load_preset(index)));
+        match loaded {
+            Some(Ok(preset)) => self.parameters.write_params(&preset.params),
+            Some(Err(err)) => self
+                .state
+                .render_state
+                .lock()
+                .report_error(format!("Failed to load preset: {:#}", err)),
+            None => (),
+        }
+    }
+
+    /// Load the preset `steps` away from the one loaded in the preset browser's list.
+    fn step_preset(&mut self, steps: i32) {
+        let id = match self.find_preset_browser(|_| true) {
+            Some(id) => id,
+            None => return,
+        };
+        let mut stepped = None;
+        self.with_preset_browser(&id, |browser| stepped = browser.step(steps));
+        if let Some(index) = stepped {
+            self.load_preset(&id, index);
+        }
+    }
+
+    /// Act on a click on the preset browser `id`.
+    fn on_browser_click(&mut self, id: &WidgetId, hit: BrowserHit) {
+        match hit {
+            BrowserHit::Folder(folder) => {
+                self.with_preset_browser(id, |browser| browser.set_folder(folder))
+            }
+            BrowserHit::Category(category) => {
+                self.with_preset_browser(id, |browser| browser.set_category(category))
+            }
+            BrowserHit::Search => {
+                self.with_preset_browser(id, |browser| browser.set_searching(true))
+            }
+            BrowserHit::Preset(index) => self.load_preset(id, index),
+        }
+    }
+
+    // Stop typing into the preset browser's search box, if it was.
+    fn stop_preset_search(&mut self) {
+        if let Some(id) = self.find_preset_browser(|widget| {
+            widget
+                .preset_browser()
+                .is_some_and(PresetBrowser::is_searching)
+        }) {
+            self.with_preset_browser(&id, |browser| browser.set_searching(false));
+        }
+    }
+
+    /// Change the UI scale in the preferences. It applies when the editor next opens, since the
+    /// window can't resize itself.
+    fn step_ui_scale(&mut self, steps: i32) {
+        self.preferences.step_ui_scale(steps);
+        self.save_preferences();
+        self.state.render_state.lock().next_ui_scale = self.preferences.ui_scale;
+    }
+
+    /// Change how far parameters move for the distance dragged; it applies right away.
+    fn step_drag_sensitivity(&mut self, steps: i32) {
+        self.preferences.step_drag_sensitivity(steps);
+        self.save_preferences();
+        let mut render_state = self.state.render_state.lock();
+        render_state.drag_sensitivity = self.preferences.drag_sensitivity;
+        // Show the new sensitivity now, rather than at the next FPS update.
+        render_state.debug_text.clear();
+        render_state.dirty = true;
+    }
+
+    // Keep the preferences for next time, telling the user if they can't be.
+    fn save_preferences(&mut self) {
+        if let Err(err) = self.preferences.save() {
+            self.state
+                .render_state
+                .lock()
+                .report_error(format!("Failed to save preferences: {:#}", err));
+        }
+    }
+
+    // How far parameters move for the distance dragged, finely if `fine`.
+    fn drag_factor(&self, fine: bool) -> f32 {
+        let factor = if fine {
+            DRAG_FACTOR_SLOW
+        } else {
+            DRAG_FACTOR_NORMAL
+        };
+        factor * self.preferences.drag_sensitivity as f32
+    }
+
+    /// Put a widget's parameter back to its default.
+    fn reset_param(&mut self, id: &WidgetId, eparam: EParam) {
+        let value = match self.meta.default_value(eparam) {
+            Ok(value) => value,
+            Err(err) => {
+                log::error!("Failed to reset parameter: {}", err);
+                return;
+            }
+        };
+        self.set_param(id, eparam, value);
+    }
+
+    /// Set a widget's parameter in one go, as a single edit, rather than by dragging.
+    fn set_param(&mut self, id: &WidgetId, eparam: EParam, value: f64) {
+        self.parameters.begin_edit(eparam);
+        self.update_param(id, value);
+        self.parameters.end_edit(eparam);
+        if let Some(widget) = self.state.widgets.get_mut(id) {
+            widget.set_param_value(eparam, value);
+        }
+        self.state
+            .render_state
+            .lock()
+            .update_widget(&mut self.state.widgets, &self.parameters, id);
+    }
+
+    fn set_focus(&mut self, id: Option<WidgetId>) {
+        self.state.focused = id;
+        self.show_focus();
+    }
+
+    fn show_focus(&mut self) {
+        let widget_rect = self
+            .state
+            .focused
+            .and_then(|id| self.state.widgets.get(&id))
+            .map(|widget| &widget.rect);
+        self.state.render_state.lock().update_focus(widget_rect);
+    }
+
+    /// Move the focus to the next widget with a value on the page (or the previous one, if
+    /// `backwards`), going across each row, then down.
+    fn move_focus(&mut self, backwards: bool) {
+        let mut focusable: Vec<&Widget> = self
+            .state
+            .widgets
+            .values()
+            .filter(|widget| {
+                widget.interactive && widget.is_visible() && widget.value_param().is_some()
+            })
+            .collect();
+        if focusable.is_empty() {
+            return;
+        }
+        let row = |widget: &Widget| (widget.rect.mid_y() / FOCUS_ROW_HEIGHT).round() as i32;
+        focusable.sort_by(|a, b| {
+            row(a)
+                .cmp(&row(b))
+                .then(a.rect.x1().total_cmp(&b.rect.x1()))
+        });
+        let count = focusable.len();
+        let next = match self
+            .state
+            .focused
+            .and_then(|id| focusable.iter().position(|widget| widget.id == id))
+        {
+            Some(position) if backwards => (position + count - 1) % count,
+            Some(position) => (position + 1) % count,
+            None if backwards => count - 1,
+            None => 0,
+        };
+        let id = focusable[next].id;
+        self.set_focus(Some(id));
+    }
+
+    /// Nudge the focused widget up (or down, if `steps` is negative), finely with Shift held.
+    fn step_focused(&mut self, id: WidgetId, eparam: EParam, steps: f32) {
+        let drag_factor = self.drag_factor(self.state.modifier_active_shift);
+        let value = match self
+            .state
+            .widgets
+            .get_mut(&id)
+            .and_then(|widget| widget.on_scroll(steps * KEY_STEP_DISTANCE, &drag_factor))
+        {
+            Some(value) => value,
+            None => return,
+        };
+        self.set_param(&id, eparam, value);
+    }
+
+    fn open_text_entry(&mut self, entry: TextEntry) {
+        self.state.text_entry = Some(entry);
+        self.show_text_entry();
+    }
+
+    fn show_text_entry(&mut self) {
+        let widget_rect = self
+            .state
+            .text_entry
+            .as_ref()
+            .and_then(|entry| self.state.widgets.get(&entry.id))
+            .map(|widget| &widget.rect);
+        self.state
+            .render_state
+            .lock()
+            .update_text_entry(widget_rect);
+    }
+
+    /// Close the text entry, setting its parameter to the value typed if `commit`.
+    fn close_text_entry(&mut self, commit: bool) {
+        let entry = match self.state.text_entry.take() {
+            Some(entry) => entry,
+            None => return,
+        };
+        self.show_text_entry();
+        if !commit {
+            return;
+        }
+        match self.parameters.parse_value(entry.eparam, &entry.text) {
+            Ok(value) => self.set_param(&entry.id, entry.eparam, value),
+            Err(err) => log::warn!("Ignoring typed value: {}", err),
+        }
+    }
+
+    fn on_key(&mut self, key: &Key) {
+        if *key == Key::F12 {
+            self.capture_to_temp();
+            return;
+        }
+        if let Some(entry) = &mut self.state.text_entry {
+            match entry.on_key(key) {
+                Some(EntryKey::Commit) => self.close_text_entry(true),
+                Some(EntryKey::Cancel) => self.close_text_entry(false),
+                Some(EntryKey::Edited) | None => (),
+            }
+            return;
+        }
+        // Typing goes to the preset browser's search box while it's active.
+        if let Some(id) = self.find_preset_browser(|widget| {
+            widget
+                .preset_browser()
+                .is_some_and(PresetBrowser::is_searching)
+        }) {
+            self.with_preset_browser(&id, |browser| browser.on_key(key));
+            return;
+        }
+        if *key == Key::Tab {
+            self.move_focus(self.state.modifier_active_shift);
+            return;
+        }
+        if *key == Key::Escape && self.state.render_state.lock().dismiss_errors() {
+            return;
+        }
+        // Typing over the focused widget starts an entry; arrow keys nudge it.
+        let (id, eparam) = match self.state.focused.and_then(|id| {
+            let widget = self.state.widgets.get(&id)?;
+            Some((id, widget.value_param()?))
+        }) {
+            Some(focused) => focused,
+            None => return,
+        };
+        match key {
+            Key::Character(typed) => self.open_text_entry(TextEntry::with_typed(id, eparam, typed)),
+            Key::Enter => {
+                let value = self.parameters.formatted_value(eparam).unwrap_or_default();
+                self.open_text_entry(TextEntry::with_value(id, eparam, value));
+            }
+            Key::ArrowUp | Key::ArrowRight => self.step_focused(id, eparam, 1.0),
+            Key::ArrowDown | Key::ArrowLeft => self.step_focused(id, eparam, -1.0),
+            Key::Escape => self.set_focus(None),
+            _ => (),
+        }
+    }
+
+    /// Nudge the widget under the mouse, as a drag of `distance` would.
+    fn on_scroll(&mut self, distance: f32) {
+        let drag_factor = self.drag_factor(self.state.modifier_active_ctrl);
+        let (x, y) = (self.state.mouse_pos_norm.x, self.state.mouse_pos_norm.y);
+        // The preset browser scrolls its list instead, a row at a time.
+        if let Some(id) = self.find_preset_browser(|widget| widget.in_bounds_rel(x, y)) {
+            let rows = if distance > 0.0 { -1 } else { 1 };
+            self.with_preset_browser(&id, |browser| browser.scroll_by(rows));
+            return;
+        }
+        let scrolled = self
+            .state
+            .widgets
+            .iter_mut()
+            .find_map(|(widget_id, widget)| {
+                if !widget.in_bounds_rel(x, y) {
+                    return None;
+                }
+                let eparam = widget.value_param()?;
+                Some((
+                    *widget_id,
+                    eparam,
+                    widget.on_scroll(distance, &drag_factor)?,
+                ))
+            });
+        if let Some((widget_id, eparam, value)) = scrolled {
+            self.set_param(&widget_id, eparam, value);
+        }
+    }
+
+    /// Switch to `page`. The widgets left behind can't keep focus.
+    fn show_page(&mut self, page: usize) {
+        self.set_focus(None);
+        self.state.hovered = None;
+        self.state
+            .render_state
+            .lock()
+            .show_page(&mut self.state.widgets, &self.parameters, page);
+    }
+
+    /// Start dragging the widget clicked, or reset it if double-clicked.
+    fn on_left_press(&mut self) {
+        let (x, y) = (self.state.mouse_pos_norm.x, self.state.mouse_pos_norm.y);
+        // Clicking away drops a value being typed in.
+        self.close_text_entry(false);
+        // With a context menu open, a click only picks from it (or closes it).
+        if let Some(menu) = self.close_context_menu() {
+            if let Some(item) = menu.item_at(x, y) {
+                self.run_menu_item(&menu, item);
+            }
+            return;
+        }
+        // An open dropdown takes the next click, wherever it lands.
+        let open_id = self
+            .state
+            .widgets
+            .iter()
+            .find(|(_, widget)| widget.is_open())
+            .map(|(widget_id, _)| *widget_id);
+        // Clicking a tab switches pages.
+        let tab = self
+            .state
+            .widgets
+            .values()
+            .find_map(|widget| widget.tab_at(x, y));
+        if let (None, Some(page)) = (open_id, tab) {
+            self.show_page(page);
+            return;
+        }
+        // Only clicking the preset browser's search box keeps typing going to it.
+        let browser_hit = self
+            .state
+            .widgets
+            .iter()
+            .find_map(|(widget_id, widget)| Some((*widget_id, widget.browser_at(x, y)?)));
+        self.stop_preset_search();
+        if let (None, Some((widget_id, hit))) = (open_id, browser_hit) {
+            self.on_browser_click(&widget_id, hit);
+            return;
+        }
+        let hit_id = open_id.or_else(|| {
+            self.state
+                .widgets
+                .iter()
+                .find(|(_, widget)| widget.interactive && widget.in_bounds_rel(x, y))
+                .map(|(widget_id, _)| *widget_id)
+        });
+        let now = Instant::now();
+        let double_click = open_id.is_none()
+            && matches!(self.state.last_click, Some((id, at))
+                if Some(id) == hit_id && now - at <= DOUBLE_CLICK_TIME);
+        self.state.last_click = hit_id.map(|id| (id, now));
+        self.set_focus(hit_id);
+        // Wait a moment after the click before showing a tooltip again.
+        self.state.hovered = hit_id.map(|id| (id, now));
+        let value_param = hit_id
+            .and_then(|id| self.state.widgets.get(&id))
+            .and_then(|widget| widget.value_param());
+        let note = hit_id
+            .and_then(|id| self.state.widgets.get(&id))
+            .and_then(|widget| widget.note_at(x, y));
+        if let (Some(widget_id), Some(note)) = (hit_id, note) {
+            self.state.interactive_state = InteractiveState::Playing {
+                id: widget_id,
+                note: None,
+            };
+            self.play_note(widget_id, Some(note));
+            return;
+        }
+        match (hit_id, value_param) {
+            // Double-clicking puts the parameter back to its default.
+            (Some(widget_id), Some(eparam)) if double_click => {
+                self.state.last_click = None;
+                self.reset_param(&widget_id, eparam);
+            }
+            (Some(widget_id), _) => {
+                let drag_factor = self.drag_factor(false);
+                if let Some(widget) = self.state.widgets.get_mut(&widget_id) {
+                    let mouse = ActiveMouseState {
+                        pos: Coord2::new(x, y),
+                        start: Coord2::new(x, y),
+                    };
+                    widget.on_drag_start(&mouse, &drag_factor);
+                    for (eparam, _) in widget.edited_values() {
+                        self.parameters.begin_edit(eparam);
+                    }
+                    self.state.interactive_state = InteractiveState::Dragging {
+                        id: widget_id,
+                        mouse,
+                    };
+                }
+            }
+            (None, _) => (),
+        }
+    }
+
+    /// Move the key held on the keyboard `id` to `note`, or let go of it with `None`, and send
+    /// the notes on to the audio thread.
+    fn play_note(&mut self, id: WidgetId, note: Option<u8>) {
+        let held = match &mut self.state.interactive_state {
+            InteractiveState::Playing { note: held, .. } if *held != note => held,
+            _ => return,
+        };
+        let released = std::mem::replace(held, note);
+        if let Some(released) = released {
+            self.send_midi(midi::note_off(released));
+        }
+        if let Some(note) = note {
+            self.send_midi(midi::note_on(note, KEYBOARD_VELOCITY));
+        }
+        if let Some(widget) = self.state.widgets.get_mut(&id) {
+            widget.set_held_note(note);
+        }
+        self.state.render_state.lock().update_widget(
+            &mut self.state.widgets,
+            &self.parameters,
+            &id,
+        );
+    }
+
+    fn send_midi(&mut self, data: [u8; 3]) {
+        if self.midi.push(data).is_err() {
+            log::warn!("Dropped a MIDI message from the GUI; the audio thread isn't taking them");
+        }
+    }
+
+    /// Open a context menu for the widget under the mouse, if it has a parameter.
+    fn open_context_menu(&mut self) {
+        self.close_text_entry(false);
+        self.close_context_menu();
+        let (x, y) = (self.state.mouse_pos_norm.x, self.state.mouse_pos_norm.y);
+        let (id, value_param) =
+            match self.state.widgets.values().find(|widget| {
+                widget.interactive && widget.in_bounds_rel(x, y) && !widget.is_open()
+            }) {
+                Some(widget) => (widget.id, widget.value_param()),
+                None => return,
+            };
+        let eparam = match id {
+            WidgetId::Bound { eparam } => eparam,
+            _ => return,
+        };
+        let mut items = vec![(MenuItem::ResetToDefault, "Reset to default".to_string())];
+        if value_param.is_some() {
+            items.push((MenuItem::EnterValue, "Enter value...".to_string()));
+        }
+        items.push((MenuItem::MidiLearn, "MIDI learn".to_string()));
+        items.push((MenuItem::CopyValue, "Copy value".to_string()));
+        if let Some(copied) = &self.copied_value {
+            items.push((MenuItem::PasteValue, format!("Paste {}", copied)));
+        }
+        let menu = ContextMenu::new(id, eparam, items, x, y);
+        self.state
+            .render_state
+            .lock()
+            .update_context_menu(Some(&menu));
+        self.state.context_menu = Some(menu);
+    }
+
+    fn close_context_menu(&mut self) -> Option<ContextMenu> {
+        let menu = self.state.context_menu.take()?;
+        self.state.render_state.lock().update_context_menu(None);
+        Some(menu)
+    }
+
+    fn run_menu_item(&mut self, menu: &ContextMenu, item: MenuItem) {
+        let (id, eparam) = (menu.id, menu.eparam);
+        match item {
+            MenuItem::ResetToDefault => self.reset_param(&id, eparam),
+            MenuItem::EnterValue => {
+                let value = self.parameters.formatted_value(eparam).unwrap_or_default();
+                self.open_text_entry(TextEntry::with_value(id, eparam, value));
+            }
+            MenuItem::MidiLearn => {
+                self.last_touched = Some(eparam);
+                self.parameters.learn_cc(eparam);
+            }
+            MenuItem::CopyValue => self.copied_value = self.parameters.formatted_value(eparam).ok(),
+            MenuItem::PasteValue => {
+                let copied = match &self.copied_value {
+                    Some(copied) => copied,
+                    None => return,
+                };
+                match self.parameters.parse_value(eparam, copied) {
+                    Ok(value) => self.set_param(&id, eparam, value),
+                    Err(err) => log::warn!("Ignoring pasted value: {}", err),
+                }
+            }
+        }
     }
 
     fn refresh_widget(&mut self, id: &WidgetId) {
         if let Some(widget) = self.state.widgets.get_mut(id) {
             if let Some(new_value) = widget.on_drag_done() {
                 self.update_param(id, new_value);
-                self.state.render_state.update_widget(
+                self.state.render_state.lock().update_widget(
                     &mut self.state.widgets,
                     &self.parameters,
                     id,
@@ -780,152 +2686,292 @@ impl SynthGui {
     }
 }
 
+/// What runs in the editor's window: the GUI, or nothing if it couldn't start (e.g. without a
+/// usable GPU), so the host carries on without it.
+pub enum EditorWindow {
+    Gui(Box<SynthGui>),
+    Unavailable,
+}
+
+impl EditorWindow {
+    pub fn new(gui: anyhow::Result<SynthGui>) -> Self {
+        match gui {
+            Ok(gui) => EditorWindow::Gui(Box::new(gui)),
+            Err(err) => {
+                log::error!("Failed to start the editor: {:#}", err);
+                EditorWindow::Unavailable
+            }
+        }
+    }
+}
+
+impl WindowHandler for EditorWindow {
+    fn on_frame(&mut self, window: &mut baseview::Window) {
+        if let EditorWindow::Gui(gui) = self {
+            gui.on_frame(window);
+        }
+    }
+
+    fn on_event(&mut self, window: &mut baseview::Window, event: baseview::Event) -> EventStatus {
+        match self {
+            EditorWindow::Gui(gui) => gui.on_event(window, event),
+            EditorWindow::Unavailable => EventStatus::Ignored,
+        }
+    }
+}
+
 impl WindowHandler for SynthGui {
     fn on_frame(&mut self, _window: &mut baseview::Window) {
         if self.param_sync_poller.tick() {
+            self.parameters.finish_learn();
             self.parameters.refresh_maybe();
             self.synchronize_params();
+            let faulted = self.parameters.is_faulted();
+            let mut render_state = self.state.render_state.lock();
+            if faulted != render_state.faulted {
+                render_state.faulted = faulted;
+                render_state.dirty = true;
+            }
+            render_state.active_voices = self.parameters.active_voices();
         };
+        // Not in the middle of a drag, or while a key's held.
+        if self.state.interactive_state == InteractiveState::Idle
+            && self.styling_poller.tick()
+            && self.styling_watcher.changed()
+        {
+            self.reload_styling();
+        }
+        self.frame_widgets.clear();
+        self.frame_widgets
+            .extend(self.animated_widgets.iter().copied());
+        self.frame_widgets.extend(
+            self.state
+                .widgets
+                .iter()
+                .filter(|(_, widget)| widget.follows_modulation(&self.parameters))
+                .map(|(widget_id, _)| *widget_id),
+        );
+        if !self.frame_widgets.is_empty() {
+            self.state.render_state.lock().update_widgets(
+                &mut self.state.widgets,
+                &self.parameters,
+                &self.frame_widgets,
+            );
+        }
         self.render_sync();
     }
 
     fn on_event(&mut self, _window: &mut baseview::Window, event: baseview::Event) -> EventStatus {
+        // Whatever happens may change what's drawn, even if only in iced.
+        self.state.render_state.lock().dirty = true;
         match &event {
             baseview::Event::Mouse(e) => {
                 match e {
                     baseview::MouseEvent::ButtonPressed(baseview::MouseButton::Left) => {
                         match self.state.interactive_state {
-                            InteractiveState::Idle => {
-                                let (x, y) =
-                                    (self.state.mouse_pos_norm.x, self.state.mouse_pos_norm.y);
-                                for (widget_id, widget) in self.state.widgets.iter_mut() {
-                                    if widget.interactive && widget.in_bounds_rel(x, y) {
-                                        let mouse = ActiveMouseState {
-                                            pos: Coord2::new(x, y),
-                                            start: Coord2::new(x, y),
-                                        };
-                                        let drag_factor = DRAG_FACTOR_NORMAL;
-                                        widget.on_drag_start(&mouse, &drag_factor);
-                                        self.state.interactive_state = InteractiveState::Dragging {
-                                            id: *widget_id,
-                                            mouse,
-                                        };
-                                        break;
-                                    }
-                                }
-                            }
+                            InteractiveState::Idle => self.on_left_press(),
                             InteractiveState::Dragging { id, .. } => {
                                 self.refresh_widget(&id);
                             }
+                            InteractiveState::Playing { .. } => (),
+                        }
+                    }
+                    baseview::MouseEvent::ButtonPressed(baseview::MouseButton::Right) => {
+                        if self.state.interactive_state == InteractiveState::Idle {
+                            self.open_context_menu();
                         }
                     }
                     baseview::MouseEvent::ButtonReleased(baseview::MouseButton::Left) => {
+                        if let InteractiveState::Playing { id, .. } = self.state.interactive_state {
+                            self.play_note(id, None);
+                        }
                         if let InteractiveState::Dragging { id, .. } = self.state.interactive_state
                         {
                             self.refresh_widget(&id);
+                            if let Some(widget) = self.state.widgets.get(&id) {
+                                for (eparam, _) in widget.edited_values() {
+                                    self.parameters.end_edit(eparam);
+                                }
+                            }
                         }
                         self.state.interactive_state = InteractiveState::Idle;
                     }
-                    baseview::MouseEvent::WheelScrolled(_scroll_delta) => {}
+                    baseview::MouseEvent::WheelScrolled(scroll_delta) => {
+                        if self.state.interactive_state == InteractiveState::Idle {
+                            let distance = match scroll_delta {
+                                baseview::ScrollDelta::Lines { y, .. } => {
+                                    y * SCROLL_DISTANCE_PER_LINE
+                                }
+                                baseview::ScrollDelta::Pixels { y, .. } => {
+                                    let render_state = self.state.render_state.lock();
+                                    let scaling = render_state.window_info.scale();
+                                    render_state
+                                        .screen_metrics
+                                        .screen_y_to_norm(y * scaling as f32)
+                                }
+                            };
+                            self.on_scroll(distance);
+                        }
+                    }
 
                     baseview::MouseEvent::CursorMoved { position } => {
                         // Grab relative position.
-                        let scaling = self.state.render_state.window_info.scale();
-                        let (x, y) = (
-                            self.state
-                                .render_state
-                                .screen_metrics
-                                .screen_x_to_norm((position.x * scaling) as f32),
-                            self.state
-                                .render_state
-                                .screen_metrics
-                                .screen_y_to_norm((position.y * scaling) as f32),
-                        );
+                        let (scaling, x, y) = {
+                            let render_state = self.state.render_state.lock();
+                            let scaling = render_state.window_info.scale();
+                            let metrics = &render_state.screen_metrics;
+                            (
+                                scaling,
+                                metrics.screen_x_to_norm((position.x * scaling) as f32),
+                                metrics.screen_y_to_norm((position.y * scaling) as f32),
+                            )
+                        };
                         self.state.mouse_pos_norm.x = x;
                         self.state.mouse_pos_norm.y = y;
+                        let hovered_id = self
+                            .state
+                            .widgets
+                            .iter()
+                            .find(|(_, widget)| widget.interactive && widget.in_bounds_rel(x, y))
+                            .map(|(widget_id, _)| *widget_id);
+                        if self.state.hovered.map(|(id, _)| id) != hovered_id {
+                            self.state.hovered = hovered_id.map(|id| (id, Instant::now()));
+                        }
+                        if let Some(menu) = &mut self.state.context_menu {
+                            if menu.hover(x, y) {
+                                self.state
+                                    .render_state
+                                    .lock()
+                                    .update_context_menu(Some(&*menu));
+                            }
+                        }
+                        // Sliding across the keyboard plays each key in turn.
+                        if let InteractiveState::Playing { id, .. } = self.state.interactive_state {
+                            let note = self
+                                .state
+                                .widgets
+                                .get(&id)
+                                .and_then(|widget| widget.note_at(x, y));
+                            self.play_note(id, note);
+                        }
                         if let InteractiveState::Dragging { id, mouse } =
                             &mut self.state.interactive_state
                         {
                             let id = *id;
-                            let (cursor_x, cursor_y) = (
-                                self.state.render_state.screen_metrics.screen_x_to_norm(
-                                    self.state.render_state.cursor_position.x * (scaling as f32),
-                                ),
-                                self.state.render_state.screen_metrics.screen_y_to_norm(
-                                    self.state.render_state.cursor_position.y * (scaling as f32),
-                                ),
-                            );
+                            let (cursor_x, cursor_y) = {
+                                let render_state = self.state.render_state.lock();
+                                let metrics = &render_state.screen_metrics;
+                                let cursor = render_state.cursor_position;
+                                (
+                                    metrics.screen_x_to_norm(cursor.x * (scaling as f32)),
+                                    metrics.screen_y_to_norm(cursor.y * (scaling as f32)),
+                                )
+                            };
                             mouse.pos.x = cursor_x;
                             mouse.pos.y = cursor_y;
-                            let df = if self.state.modifier_active_ctrl {
-                                DRAG_FACTOR_SLOW
-                            } else {
-                                DRAG_FACTOR_NORMAL
-                            };
+                            let df = self.drag_factor(self.state.modifier_active_ctrl);
                             if let Some(widget) = self.state.widgets.get_mut(&id) {
                                 let tentative_value = widget.on_dragging(mouse, &df);
                                 self.update_param(&id, tentative_value);
                             }
-                            self.state.render_state.update_widget(
+                            self.state.render_state.lock().update_widget(
                                 &mut self.state.widgets,
                                 &self.parameters,
                                 &id,
                             );
                         }
-                        self.state.render_state.cursor_position =
+                        self.state.render_state.lock().cursor_position =
                             conversion::baseview_point_to_iced_baseview_point(position);
                     }
                     // TODO: CursorEntered, CursorLeft
                     _ => {}
                 }
             }
-            baseview::Event::Keyboard(_) => {}
+            baseview::Event::Keyboard(e) => {
+                // The Ctrl key's own press or release may not show in its modifiers yet.
+                self.state.modifier_active_ctrl = match e.key {
+                    Key::Control => e.state == KeyState::Down,
+                    _ => e.modifiers.contains(keyboard_types::Modifiers::CONTROL),
+                };
+                self.state.modifier_active_shift = match e.key {
+                    Key::Shift => e.state == KeyState::Down,
+                    _ => e.modifiers.contains(keyboard_types::Modifiers::SHIFT),
+                };
+                if e.state == KeyState::Down {
+                    self.on_key(&e.key);
+                }
+            }
             baseview::Event::Window(e) => {
                 match e {
                     baseview::WindowEvent::Resized(window_info) => {
-                        self.state.render_state.logical_size =
-                            conversion::baseview_size_to_iced_baseview_size(
-                                &window_info.logical_size(),
+                        let editor_size = {
+                            let mut render_state = self.state.render_state.lock();
+                            render_state.logical_size =
+                                conversion::baseview_size_to_iced_baseview_size(
+                                    &window_info.logical_size(),
+                                );
+                            render_state.viewport = Viewport::with_physical_size(
+                                Size::new(
+                                    window_info.physical_size().width,
+                                    window_info.physical_size().height,
+                                ),
+                                window_info.scale(),
                             );
-                        self.state.render_state.viewport = Viewport::with_physical_size(
-                            Size::new(
-                                window_info.physical_size().width,
-                                window_info.physical_size().height,
-                            ),
-                            window_info.scale(),
-                        );
-                        self.state.render_state.window_info = *window_info;
-                        self.state.render_state.resized = true;
-                        self.state.render_state.resize(
-                            &window_info.physical_size(),
-                            &mut self.state.widgets,
-                            &self.parameters,
-                        );
+                            render_state.window_info = *window_info;
+                            render_state.resized = true;
+                            render_state.resize(
+                                &window_info.physical_size(),
+                                &mut self.state.widgets,
+                                &self.parameters,
+                            )
+                        };
+                        // Remembered with the plugin state (without the UI scale, which can
+                        // change in between), for the next time the editor opens.
+                        let (width, height) = editor_size;
+                        self.parameters.set_editor_size((
+                            (width as f64 / self.ui_scale).round() as u32,
+                            (height as f64 / self.ui_scale).round() as u32,
+                        ));
+                        self.show_text_entry();
+                        self.show_focus();
+                        self.state
+                            .render_state
+                            .lock()
+                            .update_context_menu(self.state.context_menu.as_ref());
                     }
                     baseview::WindowEvent::WillClose => {
-                        // TODO: Handle window close events.
+                        // Don't leave a note from the keyboard hanging.
+                        if let InteractiveState::Playing { id, .. } = self.state.interactive_state {
+                            self.play_note(id, None);
+                        }
+                        // The surface goes with the window.
+                        if let Some(render_thread) = &mut self.render_thread {
+                            render_thread.stop();
+                        }
                     }
                     _ => {}
                 }
             }
         }
 
+        let mut guard = self.state.render_state.lock();
+        let render_state = &mut *guard;
         iced_baseview::conversion::baseview_to_iced_events(
             event,
-            &mut self.state.render_state.events,
-            &mut self.state.render_state.modifiers,
+            &mut render_state.events,
+            &mut render_state.modifiers,
         );
-        for event in self.state.render_state.events.drain(..) {
-            self.state.render_state.program_state.queue_event(event);
+        for event in render_state.events.drain(..) {
+            render_state.program_state.queue_event(event);
         }
-        if !self.state.render_state.program_state.is_queue_empty() {
+        if !render_state.program_state.is_queue_empty() {
             // We update iced
-            let _ = self.state.render_state.program_state.update(
-                self.state.render_state.viewport.logical_size(),
-                self.state.render_state.cursor_position,
-                &mut self.state.render_state.renderer,
+            let _ = render_state.program_state.update(
+                render_state.viewport.logical_size(),
+                render_state.cursor_position,
+                &mut render_state.renderer,
                 &mut clipboard::Null,
-                &mut self.state.render_state.debug,
+                &mut render_state.debug,
             );
         }
         EventStatus::Captured
@@ -942,3 +2988,43 @@ mod conversion {
         iced_baseview::Point::new(point.x as f32, point.y as f32)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn captures_the_editor_without_a_window() {
+        let styling = styling::load_default();
+        let preferences = Preferences::default();
+        let size = baseview::Size::new(styling.size.0 as f64, styling.size.1 as f64);
+        let mut synchronizer = Synchronizer::new(ParamsMeta::new(), Params::new(44100.0));
+        let deltas = synchronizer.delta_router();
+        let (midi_sender, _) = midi::gui_channel();
+        let mut params_owner = Owner::new(synchronizer);
+        let mut deltas_owner = Owner::new(deltas);
+        let mut midi_owner = Owner::new(midi_sender);
+        let gui = SynthGui::create_headless(
+            &styling,
+            params_owner.borrow(),
+            deltas_owner.borrow(),
+            midi_owner.borrow(),
+            preferences,
+            size,
+        );
+        let mut gui = match gui {
+            Ok(gui) => gui,
+            Err(err) if err.is::<NoAdapter>() => {
+                eprintln!("Skipping the capture: {}", err);
+                return;
+            }
+            Err(err) => panic!("Failed to draw the editor: {:#}", err),
+        };
+
+        let path = std::env::temp_dir().join(format!("sunfish-capture-{}.png", std::process::id()));
+        gui.capture(&path).unwrap();
+        let dimensions = image::image_dimensions(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(dimensions, (styling.size.0 as u32, styling.size.1 as u32));
+    }
+}
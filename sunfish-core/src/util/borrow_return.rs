@@ -22,8 +22,13 @@ impl<T> Owner<T> {
     }
 
     pub fn borrow(&mut self) -> Borrower<T> {
-        let taken = self.val.lock().unwrap().take().unwrap();
-        Borrower::new(taken, self.clone_ref())
+        self.try_borrow().unwrap()
+    }
+
+    /// Borrow the object, unless it's already borrowed.
+    pub fn try_borrow(&mut self) -> Option<Borrower<T>> {
+        let taken = self.val.lock().unwrap().take()?;
+        Some(Borrower::new(taken, self.clone_ref()))
     }
 }
 
@@ -1,4 +1,5 @@
 use log::error;
+use std::backtrace::Backtrace;
 use std::ops::Deref;
 use std::panic;
 
@@ -25,6 +26,12 @@ pub fn setup_panic_handling() {
                 .unwrap_or("<cause unknown>")
         });
 
-        error!("A panic occurred at {}:{}: {}", filename, line, cause);
+        error!(
+            "A panic occurred at {}:{}: {}\n{}",
+            filename,
+            line,
+            cause,
+            Backtrace::force_capture()
+        );
     }));
 }
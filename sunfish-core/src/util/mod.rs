@@ -3,7 +3,10 @@ pub mod enumerable;
 pub mod errors;
 pub mod mailbox;
 pub mod note_freq;
+pub mod rng;
+pub mod spsc;
 pub mod test_utils;
+pub mod triple_buffer;
 
 // From freeverb.c
 // #define undenormalize(n) { if (xabs(n) < 1e-37) { (n) = 0; } }
@@ -56,3 +59,7 @@ pub fn frequency_to_semitones(freq_hz: f64, min_hz: f64) -> f64 {
 pub fn gain_to_db(gain: f64) -> f64 {
     20.0 * gain.log10()
 }
+
+pub fn db_to_gain(db: f64) -> f64 {
+    10.0f64.powf(db / 20.0)
+}
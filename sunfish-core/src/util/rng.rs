@@ -0,0 +1,49 @@
+/// Small, fast pseudo-random number generator (xorshift64*). The sequence depends only on the
+/// seed, which keeps renders reproducible.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck at zero; scramble the seed so small seeds don't start out similar.
+        let state = (seed ^ 0x9E37_79B9_7F4A_7C15).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        Rng {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniformly distributed value from -1.0 to 1.0.
+    pub fn next_bipolar(&mut self) -> f64 {
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        unit * 2.0 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+        let mut c = Rng::new(8);
+        let a_values: Vec<f64> = (0..16).map(|_| a.next_bipolar()).collect();
+        let b_values: Vec<f64> = (0..16).map(|_| b.next_bipolar()).collect();
+        let c_values: Vec<f64> = (0..16).map(|_| c.next_bipolar()).collect();
+        assert_eq!(a_values, b_values);
+        assert_ne!(a_values, c_values);
+        assert!(a_values.iter().all(|v| (-1.0..=1.0).contains(v)));
+    }
+}
@@ -0,0 +1,148 @@
+//! A bounded, lock-free queue with a single producer and a single consumer.
+//!
+//! Neither side ever blocks or allocates once the queue is created, so either may live on the
+//! audio thread.
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub fn channel<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    assert!(capacity > 0, "Queue capacity must be positive");
+    let slots = (0..capacity)
+        .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+        .collect();
+    let ring = Arc::new(Ring {
+        slots,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            ring: Arc::clone(&ring),
+        },
+        Consumer { ring },
+    )
+}
+
+struct Ring<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    // Count of values popped; only the consumer moves it.
+    head: AtomicUsize,
+    // Count of values pushed; only the producer moves it.
+    tail: AtomicUsize,
+}
+
+// Each slot is only touched by one side at a time: the producer before publishing it through
+// `tail`, and the consumer after seeing it there and before releasing it through `head`.
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    fn slot(&self, count: usize) -> *mut MaybeUninit<T> {
+        self.slots[count % self.slots.len()].get()
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let (head, tail) = (*self.head.get_mut(), *self.tail.get_mut());
+        for count in head..tail {
+            unsafe { (*self.slot(count)).assume_init_drop() };
+        }
+    }
+}
+
+pub struct Producer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+impl<T> Producer<T> {
+    /// Add `value` to the back of the queue, or hand it back if the queue is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        let head = self.ring.head.load(Ordering::Acquire);
+        if tail - head == self.ring.slots.len() {
+            return Err(value);
+        }
+        unsafe { (*self.ring.slot(tail)).write(value) };
+        self.ring.tail.store(tail + 1, Ordering::Release);
+        Ok(())
+    }
+}
+
+pub struct Consumer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+impl<T> Consumer<T> {
+    /// Take the value at the front of the queue, if there is one.
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        let tail = self.ring.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let value = unsafe { (*self.ring.slot(head)).assume_init_read() };
+        self.ring.head.store(head + 1, Ordering::Release);
+        Some(value)
+    }
+
+    /// Whether the producer is gone, so nothing more will arrive once the queue is empty.
+    pub fn is_closed(&self) -> bool {
+        Arc::strong_count(&self.ring) == 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn values_arrive_in_order_until_full() {
+        let (mut producer, mut consumer) = channel(3);
+        for round in 0..4 {
+            assert_eq!(producer.push(round), Ok(()));
+            assert_eq!(producer.push(round + 10), Ok(()));
+            assert_eq!(producer.push(round + 20), Ok(()));
+            assert_eq!(producer.push(round + 30), Err(round + 30));
+            assert_eq!(consumer.pop(), Some(round));
+            assert_eq!(consumer.pop(), Some(round + 10));
+            assert_eq!(consumer.pop(), Some(round + 20));
+            assert_eq!(consumer.pop(), None);
+        }
+    }
+
+    #[test]
+    fn values_cross_threads() {
+        let (mut producer, mut consumer) = channel(16);
+        let thread = std::thread::spawn(move || {
+            for value in 0..10_000 {
+                let mut value = value;
+                while let Err(rejected) = producer.push(value) {
+                    value = rejected;
+                    std::thread::yield_now();
+                }
+            }
+        });
+        let mut expected = 0;
+        while expected < 10_000 {
+            if let Some(value) = consumer.pop() {
+                assert_eq!(value, expected);
+                expected += 1;
+            }
+        }
+        thread.join().unwrap();
+        assert!(consumer.is_closed());
+    }
+
+    #[test]
+    fn queued_values_are_dropped_with_the_queue() {
+        let value = Arc::new(());
+        let (mut producer, consumer) = channel(4);
+        producer.push(Arc::clone(&value)).unwrap();
+        producer.push(Arc::clone(&value)).unwrap();
+        drop((producer, consumer));
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,114 @@
+//! A triple buffer: a writer keeps publishing new values of `T`, and a reader picks up the latest
+//! whenever it likes. Neither side blocks the other; the third buffer holds the most recently
+//! published value between them.
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// The middle buffer's index, and whether it holds a value the reader hasn't seen.
+const INDEX_MASK: usize = 0b11;
+const FRESH: usize = 0b100;
+
+pub fn triple_buffer<T: Clone>(initial: &T) -> (Writer<T>, Reader<T>) {
+    let shared = Arc::new(Shared {
+        buffers: [
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+        ],
+        middle: AtomicUsize::new(1),
+    });
+    (
+        Writer {
+            shared: Arc::clone(&shared),
+            back: 0,
+        },
+        Reader { shared, front: 2 },
+    )
+}
+
+struct Shared<T> {
+    buffers: [UnsafeCell<T>; 3],
+    middle: AtomicUsize,
+}
+
+// The writer only touches its back buffer and the reader its front buffer; they trade buffers
+// through `middle`.
+unsafe impl<T: Send> Send for Shared<T> {}
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+pub struct Writer<T> {
+    shared: Arc<Shared<T>>,
+    back: usize,
+}
+
+impl<T> Writer<T> {
+    /// Publish a new value, filled in by `fill`. It's handed an older value to overwrite, which
+    /// saves allocating a new one.
+    pub fn write<F: FnOnce(&mut T)>(&mut self, fill: F) {
+        fill(unsafe { &mut *self.shared.buffers[self.back].get() });
+        let middle = self.shared.middle.swap(self.back | FRESH, Ordering::AcqRel);
+        self.back = middle & INDEX_MASK;
+    }
+}
+
+pub struct Reader<T> {
+    shared: Arc<Shared<T>>,
+    front: usize,
+}
+
+impl<T> Reader<T> {
+    /// The latest published value, if there's been one since the last read.
+    pub fn read(&mut self) -> Option<&T> {
+        if self.shared.middle.load(Ordering::Relaxed) & FRESH == 0 {
+            return None;
+        }
+        let middle = self.shared.middle.swap(self.front, Ordering::AcqRel);
+        self.front = middle & INDEX_MASK;
+        Some(self.latest())
+    }
+
+    /// The value returned by the last read (or the initial value).
+    pub fn latest(&self) -> &T {
+        unsafe { &*self.shared.buffers[self.front].get() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reads_see_only_the_latest_value() {
+        let (mut writer, mut reader) = triple_buffer(&0);
+        assert_eq!(reader.read(), None);
+        writer.write(|value| *value = 1);
+        writer.write(|value| *value = 2);
+        assert_eq!(reader.read(), Some(&2));
+        assert_eq!(reader.read(), None);
+        assert_eq!(*reader.latest(), 2);
+        for next in 3..10 {
+            writer.write(|value| *value = next);
+            assert_eq!(reader.read(), Some(&next));
+        }
+    }
+
+    #[test]
+    fn values_cross_threads_whole() {
+        let (mut writer, mut reader) = triple_buffer(&[0u32; 64]);
+        let thread = std::thread::spawn(move || {
+            for next in 1..=10_000 {
+                writer.write(|value| value.iter_mut().for_each(|x| *x = next));
+            }
+        });
+        let mut last = 0;
+        while last < 10_000 {
+            if let Some(value) = reader.read() {
+                assert!(value.iter().all(|x| *x == value[0]));
+                assert!(value[0] >= last);
+                last = value[0];
+            }
+        }
+        thread.join().unwrap();
+    }
+}
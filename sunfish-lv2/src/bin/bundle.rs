@@ -0,0 +1,41 @@
+//! Writes the Turtle files of an LV2 bundle. The compiled library goes alongside them:
+//!
+//! ```text
+//! cargo run -p sunfish-lv2 --bin sunfish-lv2-bundle -- sunfish.lv2
+//! cp target/debug/libsunfish_lv2.so sunfish.lv2/
+//! ```
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use sunfish::params::ParamsMeta;
+use sunfish_lv2::ttl;
+
+fn write_bundle(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let binary = format!(
+        "{}sunfish_lv2{}",
+        env::consts::DLL_PREFIX,
+        env::consts::DLL_SUFFIX
+    );
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("manifest.ttl"), ttl::manifest(&binary))?;
+    fs::write(dir.join("sunfish.ttl"), ttl::plugin(&ParamsMeta::new())?)?;
+    Ok(())
+}
+
+fn main() {
+    let dir = match env::args_os().nth(1) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            eprintln!("usage: sunfish-lv2-bundle <bundle directory>");
+            process::exit(1);
+        }
+    };
+    if let Err(err) = write_bundle(&dir) {
+        eprintln!("Failed to write {}: {}", dir.display(), err);
+        process::exit(1);
+    }
+}
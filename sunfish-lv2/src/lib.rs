@@ -0,0 +1,224 @@
+//! LV2 bindings for Sunfish.
+//!
+//! Hosts find the plugin through the Turtle files in its bundle, which `sunfish-lv2-bundle`
+//! generates from the parameter metadata (see `ttl`). There's no UI yet; hosts show their generic
+//! controls for the ports.
+
+pub mod ttl;
+
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::slice;
+
+use lv2_sys::{
+    LV2_Atom_Event, LV2_Atom_Sequence, LV2_Atom_Sequence_Body, LV2_Descriptor, LV2_Feature,
+    LV2_Handle, LV2_URID_Map, LV2_URID,
+};
+
+use sunfish::core::CHANNEL_COUNT;
+use sunfish::plugin::SunfishPlugin;
+use sunfish::shell::{PluginShell, Transport};
+use sunfish::util::errors;
+
+use crate::ttl::{FIRST_PARAM_PORT, MIDI_IN_PORT, OUTPUT_PORTS};
+
+// `ttl::PLUGIN_URI`, null-terminated.
+const PLUGIN_URI: &[u8] = b"https://github.com/kunalarya/sunfish-rs\0";
+const URID_MAP_URI: &[u8] = b"http://lv2plug.in/ns/ext/urid#map\0";
+const MIDI_EVENT_URI: &[u8] = b"http://lv2plug.in/ns/ext/midi#MidiEvent\0";
+
+struct Descriptor(LV2_Descriptor);
+
+// The descriptor is never written to; it only holds pointers to statics.
+unsafe impl Sync for Descriptor {}
+
+static DESCRIPTOR: Descriptor = Descriptor(LV2_Descriptor {
+    URI: PLUGIN_URI.as_ptr() as *const c_char,
+    instantiate: Some(instantiate),
+    connect_port: Some(connect_port),
+    activate: Some(activate),
+    run: Some(run),
+    deactivate: Some(deactivate),
+    cleanup: Some(cleanup),
+    extension_data: Some(extension_data),
+});
+
+struct SunfishLv2 {
+    plugin: SunfishPlugin,
+    // The host's ID for MIDI event atoms.
+    midi_event: LV2_URID,
+    // Port buffers, as connected by the host.
+    midi_in: *const LV2_Atom_Sequence,
+    outputs: [*mut f32; CHANNEL_COUNT],
+    controls: Vec<*const f32>,
+    // Last value read from each control port, so only changes reach the plugin.
+    control_values: Vec<f32>,
+}
+
+impl SunfishLv2 {
+    unsafe fn read_controls(&mut self) {
+        let ports = self.controls.iter().zip(self.control_values.iter_mut());
+        for (index, (&port, last_value)) in ports.enumerate() {
+            if port.is_null() || *port == *last_value {
+                continue;
+            }
+            *last_value = *port;
+            if let Err(err) = self.plugin.set_param_value(index, *port as f64) {
+                log::error!("read_controls: {}", err);
+            }
+        }
+    }
+
+    unsafe fn read_midi(&mut self) {
+        if self.midi_in.is_null() {
+            return;
+        }
+        let sequence = &*self.midi_in;
+        let body = &sequence.body as *const LV2_Atom_Sequence_Body as *const u8;
+        let end = body.add(sequence.atom.size as usize);
+        let mut event = body.add(mem::size_of::<LV2_Atom_Sequence_Body>());
+        while event < end {
+            let header = &*(event as *const LV2_Atom_Event);
+            let size = header.body.size as usize;
+            if header.body.type_ == self.midi_event {
+                let message =
+                    slice::from_raw_parts(event.add(mem::size_of::<LV2_Atom_Event>()), size);
+                let mut data = [0; 3];
+                let len = size.min(data.len());
+                data[..len].copy_from_slice(&message[..len]);
+                // Hosts stamp events in frames unless a plugin asks for beats.
+                let sample_offset = header.time.frames.max(0) as usize;
+                self.plugin.midi_event(data, sample_offset);
+            }
+            // Each event is padded out to 64 bits.
+            event = event.add((mem::size_of::<LV2_Atom_Event>() + size + 7) & !7);
+        }
+    }
+
+    unsafe fn render(&mut self, sample_count: usize) {
+        // A fixed array of slices, to avoid any heap allocations.
+        let mut outputs: [&mut [f32]; CHANNEL_COUNT] = Default::default();
+        for (output, &port) in outputs.iter_mut().zip(self.outputs.iter()) {
+            if port.is_null() {
+                return;
+            }
+            *output = slice::from_raw_parts_mut(port, sample_count);
+        }
+        // The host's transport isn't available without a time:Position port, so tempo-synced
+        // LFOs keep their last tempo. There are no audio input ports yet, so the external input
+        // source is silent.
+        self.plugin
+            .process(&[], &mut outputs, &Transport::default());
+    }
+}
+
+unsafe extern "C" fn instantiate(
+    _descriptor: *const LV2_Descriptor,
+    sample_rate: f64,
+    _bundle_path: *const c_char,
+    features: *const *const LV2_Feature,
+) -> LV2_Handle {
+    errors::setup_panic_handling();
+
+    let map = match find_feature(features, URID_MAP_URI) {
+        Some(map) => &*(map as *const LV2_URID_Map),
+        None => {
+            log::error!("instantiate: the host doesn't support urid:map");
+            return ptr::null_mut();
+        }
+    };
+    let midi_event = match map.map {
+        Some(map_uri) => map_uri(map.handle, MIDI_EVENT_URI.as_ptr() as *const c_char),
+        None => return ptr::null_mut(),
+    };
+
+    let mut plugin = SunfishPlugin::new();
+    plugin.set_sample_rate(sample_rate);
+    let param_count = plugin.param_count();
+    let sunfish = Box::new(SunfishLv2 {
+        plugin,
+        midi_event,
+        midi_in: ptr::null(),
+        outputs: [ptr::null_mut(); CHANNEL_COUNT],
+        controls: vec![ptr::null(); param_count],
+        // NaN never compares equal, so the first run applies every port's value.
+        control_values: vec![f32::NAN; param_count],
+    });
+    Box::into_raw(sunfish) as LV2_Handle
+}
+
+unsafe extern "C" fn connect_port(instance: LV2_Handle, port: u32, data: *mut c_void) {
+    let sunfish = &mut *(instance as *mut SunfishLv2);
+    if port == MIDI_IN_PORT {
+        sunfish.midi_in = data as *const LV2_Atom_Sequence;
+    } else if let Some(channel) = OUTPUT_PORTS.iter().position(|&output| output == port) {
+        sunfish.outputs[channel] = data as *mut f32;
+    } else if let Some(control) = port
+        .checked_sub(FIRST_PARAM_PORT)
+        .and_then(|index| sunfish.controls.get_mut(index as usize))
+    {
+        *control = data as *const f32;
+    }
+}
+
+unsafe extern "C" fn activate(instance: LV2_Handle) {
+    let sunfish = &mut *(instance as *mut SunfishLv2);
+    sunfish.plugin.resume();
+}
+
+unsafe extern "C" fn run(instance: LV2_Handle, sample_count: u32) {
+    let sunfish = &mut *(instance as *mut SunfishLv2);
+    sunfish.read_controls();
+    sunfish.read_midi();
+    sunfish.render(sample_count as usize);
+}
+
+unsafe extern "C" fn deactivate(instance: LV2_Handle) {
+    let sunfish = &mut *(instance as *mut SunfishLv2);
+    sunfish.plugin.suspend();
+}
+
+unsafe extern "C" fn cleanup(instance: LV2_Handle) {
+    drop(Box::from_raw(instance as *mut SunfishLv2));
+}
+
+unsafe extern "C" fn extension_data(_uri: *const c_char) -> *const c_void {
+    ptr::null()
+}
+
+/// The data of the host feature with this (null-terminated) URI, if it's provided.
+unsafe fn find_feature(features: *const *const LV2_Feature, uri: &[u8]) -> Option<*mut c_void> {
+    if features.is_null() {
+        return None;
+    }
+    let mut feature = features;
+    while !(*feature).is_null() {
+        if CStr::from_ptr((**feature).URI).to_bytes_with_nul() == uri {
+            return Some((**feature).data);
+        }
+        feature = feature.add(1);
+    }
+    None
+}
+
+#[no_mangle]
+pub extern "C" fn lv2_descriptor(index: u32) -> *const LV2_Descriptor {
+    if index == 0 {
+        &DESCRIPTOR.0
+    } else {
+        ptr::null()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plugin_uri_matches_ttl() {
+        let uri = &PLUGIN_URI[..PLUGIN_URI.len() - 1];
+        assert_eq!(uri, ttl::PLUGIN_URI.as_bytes());
+    }
+}
@@ -0,0 +1,137 @@
+//! Turtle descriptions of the plugin, which hosts read before loading the binary.
+
+use std::fmt::Write;
+
+use sunfish::core::CHANNEL_COUNT;
+use sunfish::params::error::ParamError;
+use sunfish::params::ParamsMeta;
+
+pub const PLUGIN_URI: &str = "https://github.com/kunalarya/sunfish-rs";
+
+// Port layout. Each parameter has a control port after the outputs, in host order.
+pub const MIDI_IN_PORT: u32 = 0;
+pub const OUTPUT_PORTS: [u32; CHANNEL_COUNT] = [1, 2];
+pub const FIRST_PARAM_PORT: u32 = 3;
+
+const PREFIXES: &str = "@prefix atom: <http://lv2plug.in/ns/ext/atom#> .
+@prefix doap: <http://usefulinc.com/ns/doap#> .
+@prefix lv2: <http://lv2plug.in/ns/lv2core#> .
+@prefix midi: <http://lv2plug.in/ns/ext/midi#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+@prefix urid: <http://lv2plug.in/ns/ext/urid#> .
+";
+
+/// The bundle's `manifest.ttl`, pointing hosts at `binary` and `sunfish.ttl`.
+pub fn manifest(binary: &str) -> String {
+    format!(
+        "{prefixes}
+<{uri}>
+    a lv2:Plugin ;
+    lv2:binary <{binary}> ;
+    rdfs:seeAlso <sunfish.ttl> .
+",
+        prefixes = PREFIXES,
+        uri = PLUGIN_URI,
+        binary = binary,
+    )
+}
+
+/// The bundle's `sunfish.ttl`, describing every port.
+pub fn plugin(meta: &ParamsMeta) -> Result<String, ParamError> {
+    let mut ports = vec![format!(
+        "a lv2:InputPort, atom:AtomPort ;
+        atom:bufferType atom:Sequence ;
+        atom:supports midi:MidiEvent ;
+        lv2:designation lv2:control ;
+        lv2:index {} ;
+        lv2:symbol \"midi_in\" ;
+        lv2:name \"MIDI In\"",
+        MIDI_IN_PORT
+    )];
+    for (port, (symbol, name)) in OUTPUT_PORTS
+        .iter()
+        .zip([("out_left", "Left Out"), ("out_right", "Right Out")])
+    {
+        ports.push(format!(
+            "a lv2:OutputPort, lv2:AudioPort ;
+        lv2:index {} ;
+        lv2:symbol \"{}\" ;
+        lv2:name \"{}\"",
+            port, symbol, name
+        ));
+    }
+    // Control ports take the same normalized values as the other plugin formats.
+    for index in 0..meta.count() {
+        let eparam = meta.parameter_index(index)?;
+        let name = eparam.host_name();
+        ports.push(format!(
+            "a lv2:InputPort, lv2:ControlPort ;
+        lv2:index {} ;
+        lv2:symbol \"{}\" ;
+        lv2:name \"{}\" ;
+        lv2:default {:.6} ;
+        lv2:minimum 0.0 ;
+        lv2:maximum 1.0",
+            FIRST_PARAM_PORT as usize + index,
+            symbol(&name),
+            name.replace('"', "\\\""),
+            meta.default_value(eparam)?,
+        ));
+    }
+
+    let mut ttl = String::from(PREFIXES);
+    // Writing to a String can't fail.
+    let _ = write!(
+        ttl,
+        "
+<{}>
+    a lv2:Plugin, lv2:InstrumentPlugin ;
+    doap:name \"Sunfish\" ;
+    lv2:requiredFeature urid:map ;
+    lv2:port [
+        {}
+    ] .
+",
+        PLUGIN_URI,
+        ports.join("\n    ] , [\n        ")
+    );
+    Ok(ttl)
+}
+
+/// A port symbol for the parameter name: lowercase, with each run of other characters replaced
+/// by an underscore ("Mod Env 2:Attack" becomes "mod_env_2_attack").
+pub fn symbol(name: &str) -> String {
+    let mut symbol = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            symbol.push(c.to_ascii_lowercase());
+        } else if !symbol.is_empty() && !symbol.ends_with('_') {
+            symbol.push('_');
+        }
+    }
+    symbol.trim_end_matches('_').to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn symbols_are_unique_identifiers() {
+        let meta = ParamsMeta::new();
+        let mut symbols = HashSet::new();
+        for index in 0..meta.count() {
+            let name = meta.parameter_index(index).unwrap().host_name();
+            let symbol = symbol(&name);
+            assert!(
+                symbol.starts_with(|c: char| c.is_ascii_lowercase()),
+                "{}",
+                symbol
+            );
+            assert!(symbols.insert(symbol), "duplicate symbol for {}", name);
+        }
+        assert_eq!(symbol("Mod Env 2:Attack"), "mod_env_2_attack");
+    }
+}
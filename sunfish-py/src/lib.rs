@@ -1,64 +1,298 @@
 use ndarray;
 use ndarray::{ArrayD, ArrayViewD, ArrayViewMutD};
+use num_traits::Float;
 use numpy;
 use numpy::PyArray1;
 use numpy::{IntoPyArray, PyArrayDyn};
 use pyo3::exceptions;
 use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
 
 use sunfish::core;
 use sunfish::dsp::osc;
 use sunfish::lfo;
+use sunfish::midi;
 use sunfish::modulation::target::ModulationTarget;
+use sunfish::params::error::ParamError;
+use sunfish::params::randomize;
 use sunfish::params::NormalizedParams;
 use sunfish::params::MAX_CUTOFF_SEMI;
 use sunfish::params::{ELfoParams, EOscParams, EParam};
 use sunfish::plugin;
+use sunfish::presets::{factory, Preset, PresetMeta};
+use sunfish::util::enumerable::Enumerable;
+use sunfish::util::rng::Rng;
 
 const DEFAULT_TEMPO_BPS: f64 = 120.0;
+// Parameters with at most this many steps list them as options.
+const MAX_OPTIONS: usize = 64;
+// Largest MIDI data byte, for scaling controllers and pressure.
+const MAX_MIDI_VALUE: f64 = 127.0;
+
+fn param_error(err: ParamError) -> PyErr {
+    exceptions::PyValueError::new_err(err.to_string())
+}
 
 #[pymodule]
 fn pysunfish(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<CoreWrapper>()?;
+    m.add_class::<ParamInfo>()?;
+    m.add_function(wrap_pyfunction!(list_factory_presets, m)?)?;
     Ok(())
 }
 
+/// The factory presets, as (index, name, category) for `CoreWrapper.load_factory_preset`.
+#[pyfunction]
+fn list_factory_presets() -> Vec<(usize, String, Option<String>)> {
+    (0..factory::count())
+        .filter_map(|index| {
+            let meta = factory::load(index)?.meta;
+            Some((
+                index,
+                meta.name,
+                meta.category.map(|category| category.as_string()),
+            ))
+        })
+        .collect()
+}
+
+// For failures to read or write files, with their causes.
+fn io_error<E: std::fmt::Display>(err: E) -> PyErr {
+    exceptions::PyIOError::new_err(format!("{:#}", err))
+}
+
+/// A parameter's description and current value, as `CoreWrapper.params` lists them.
+#[pyclass]
+#[derive(Clone)]
+pub struct ParamInfo {
+    /// Position in the host's parameter list, as `set_param_normalized` takes.
+    #[pyo3(get)]
+    index: usize,
+    /// The name `update_param` takes, e.g. `{"Osc1":"Gain"}`.
+    #[pyo3(get)]
+    key: String,
+    /// The name shown to the host.
+    #[pyo3(get)]
+    name: String,
+    /// Normalized, from 0.0 to 1.0.
+    #[pyo3(get)]
+    value: f64,
+    #[pyo3(get)]
+    text: String,
+    /// The formatted values at either end of the range.
+    #[pyo3(get)]
+    min: String,
+    #[pyo3(get)]
+    max: String,
+    /// The normalized values a stepped parameter (e.g. an enum) can take, each with its text;
+    /// empty for continuous parameters.
+    #[pyo3(get)]
+    options: Vec<(f64, String)>,
+}
+
+// Something to happen partway through a render.
+#[derive(Clone, Copy, Debug)]
+enum Event {
+    Midi([u8; 3]),
+    // A parameter's new normalized value.
+    Param(EParam, f64),
+}
+
+// A parameter's breakpoints, as (seconds, normalized value) in time order; it holds the first
+// value before them, moves linearly between them, and holds the last one after.
+struct Automation {
+    eparam: EParam,
+    points: Vec<(f64, f64)>,
+}
+
+impl Automation {
+    fn value_at(&self, time: f64) -> f64 {
+        let next = self
+            .points
+            .partition_point(|(point_time, _)| *point_time <= time);
+        match (next.checked_sub(1), self.points.get(next)) {
+            (Some(last), Some(&(time1, value1))) => {
+                let (time0, value0) = self.points[last];
+                value0 + (value1 - value0) * (time - time0) / (time1 - time0)
+            }
+            (Some(last), None) => self.points[last].1,
+            (None, Some(&(_, value))) => value,
+            (None, None) => 0.0,
+        }
+    }
+}
+
 #[pyclass]
 pub struct CoreWrapper {
     inst: core::Sunfish,
     tempo_bps: f64,
+    // Events for the next render, by sample offset from its start.
+    scheduled: Vec<(usize, Event)>,
 }
 
 #[pymethods]
 impl CoreWrapper {
     #[new]
     pub fn new(sample_rate: f64) -> Self {
-        let mut plugin = plugin::SunfishPlugin::new();
-        plugin.core.update_sample_rate(sample_rate);
-        CoreWrapper {
+        let plugin = plugin::SunfishPlugin::new();
+        let mut wrapper = CoreWrapper {
             inst: plugin.core,
             tempo_bps: DEFAULT_TEMPO_BPS,
-        }
+            scheduled: vec![],
+        };
+        wrapper.set_sample_rate(sample_rate);
+        wrapper
     }
 
     pub fn update_param(&mut self, param_name: &str, param_value: f64) -> PyResult<()> {
-        let eparam: EParam = serde_json::from_str(param_name)
-            .map_err(|err| exceptions::PyValueError::new_err(err.to_string()))?;
-        core::Sunfish::on_param_update(
-            &self.inst.meta,
-            &mut self.inst.params,
-            &mut self.inst.params_modulated,
-            &self.inst.tempo,
-            &mut self.inst.voices,
-            &mut self.inst.modulation,
-            eparam,
-            param_value,
-        );
+        let eparam: EParam = param_name.parse().map_err(param_error)?;
+        let param_value = eparam.check_value(param_value).map_err(param_error)?;
+        self.apply_param(eparam, param_value);
         Ok(())
     }
 
-    fn note_on(&mut self, note: u8) -> PyResult<()> {
-        self.inst.note_on(note, 100);
+    /// Set the parameter at `index` (see `params`) to a normalized value.
+    fn set_param_normalized(&mut self, index: usize, value: f64) -> PyResult<()> {
+        let eparam = self.inst.meta.parameter_index(index).map_err(param_error)?;
+        let value = eparam.check_value(value).map_err(param_error)?;
+        self.apply_param(eparam, value);
+        Ok(())
+    }
+
+    /// Load a preset file's parameters, returning its name.
+    fn load_preset(&mut self, path: &str) -> PyResult<String> {
+        let preset = Preset::load(Path::new(path)).map_err(io_error)?;
+        self.apply_preset(&preset);
+        Ok(preset.meta.name)
+    }
+
+    /// Load the factory preset at `index` (see `list_factory_presets`), returning its name.
+    fn load_factory_preset(&mut self, index: usize) -> PyResult<String> {
+        let preset = factory::load(index).ok_or_else(|| {
+            exceptions::PyIndexError::new_err(format!("no factory preset at {}", index))
+        })?;
+        self.apply_preset(&preset);
+        Ok(preset.meta.name)
+    }
+
+    /// Save the parameters as a preset file, named after the file unless `name` is given.
+    #[args(name = "None")]
+    fn save_preset(&self, path: &str, name: Option<String>) -> PyResult<()> {
+        let path = Path::new(path);
+        let name = name.unwrap_or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+        let meta = PresetMeta {
+            name,
+            ..PresetMeta::default()
+        };
+        Preset::new(meta, self.inst.params.clone())
+            .save(path)
+            .map_err(io_error)
+    }
+
+    /// The prerendered waveform (one cycle) the oscillators read for `shape` (e.g. "HardSaw")
+    /// at `freq` Hz, and the mipmap frequency it was rendered at.
+    fn reference_waveform(
+        &self,
+        py: Python,
+        shape: &str,
+        freq: f64,
+    ) -> PyResult<(f64, Py<PyArray1<f64>>)> {
+        let wave_shape = osc::WaveShape::enumerate()
+            .into_iter()
+            .find(|wave_shape| wave_shape.as_string() == shape)
+            .ok_or_else(|| {
+                exceptions::PyValueError::new_err(format!("unknown shape: {}", shape))
+            })?;
+        if freq <= 0.0 {
+            return Err(exceptions::PyValueError::new_err("freq must be above 0"));
+        }
+        let (ref_freq, waveform) = self
+            .inst
+            .interpolator
+            .reference(wave_shape, freq)
+            .ok_or_else(|| {
+                exceptions::PyValueError::new_err(format!("no waveform for {} Hz", freq))
+            })?;
+        Ok((ref_freq, PyArray1::from_slice(py, waveform).to_owned()))
+    }
+
+    /// Every parameter, in the host's order, with its current value.
+    fn params(&self) -> PyResult<Vec<ParamInfo>> {
+        let meta = &self.inst.meta;
+        let params = &self.inst.params;
+        let mut probe = params.clone();
+        (0..meta.count())
+            .map(|index| {
+                let eparam = meta.parameter_index(index).map_err(param_error)?;
+                let mut text_at = |value: f64| {
+                    probe.write_parameter(meta, eparam, value);
+                    probe.formatted_value(meta, eparam).map_err(param_error)
+                };
+                let (min, max) = (text_at(0.0)?, text_at(1.0)?);
+                let mut options = params.options(meta, eparam);
+                if options.len() > MAX_OPTIONS {
+                    options.clear();
+                }
+                Ok(ParamInfo {
+                    index,
+                    key: serde_json::to_string(&eparam)
+                        .map_err(|err| exceptions::PyValueError::new_err(err.to_string()))?,
+                    name: eparam.host_name(),
+                    value: params.read_parameter(meta, eparam),
+                    text: params.formatted_value(meta, eparam).map_err(param_error)?,
+                    min,
+                    max,
+                    options,
+                })
+            })
+            .collect()
+    }
+
+    /// Set every parameter to a random value, except those in `locked` (parameter names as
+    /// for `update_param`; by default, the output gain).
+    #[args(locked = "None")]
+    fn randomize(&mut self, seed: u64, locked: Option<Vec<String>>) -> PyResult<()> {
+        let locks = match locked {
+            Some(names) => names
+                .iter()
+                .map(|name| name.parse())
+                .collect::<Result<_, _>>()
+                .map_err(param_error)?,
+            None => randomize::default_locks(),
+        };
+        let values = randomize::random_values(&mut Rng::new(seed), &locks);
+        for (eparam, value) in values {
+            self.apply_param(eparam, value);
+        }
+        Ok(())
+    }
+
+    #[args(velocity = "100")]
+    fn note_on(&mut self, note: u8, velocity: i8) -> PyResult<()> {
+        self.inst.note_on(note, velocity);
+        Ok(())
+    }
+
+    /// Time (in seconds) for gains, cutoff and tuning to follow parameter changes.
+    fn set_param_smoothing(&mut self, time_sec: f64) -> PyResult<()> {
+        self.inst.set_param_smoothing(time_sec);
+        Ok(())
+    }
+
+    /// Mod wheel position, from 0.0 to 1.0.
+    fn set_mod_wheel(&mut self, value: f64) -> PyResult<()> {
+        self.inst.modulation.set_mod_wheel(value.clamp(0.0, 1.0));
+        Ok(())
+    }
+
+    /// Channel pressure, from 0.0 to 1.0.
+    fn set_aftertouch(&mut self, value: f64) -> PyResult<()> {
+        self.inst.modulation.set_aftertouch(value.clamp(0.0, 1.0));
         Ok(())
     }
 
@@ -67,27 +301,339 @@ impl CoreWrapper {
         Ok(())
     }
 
+    /// Render `buf_len` samples, `chunk_size` at a time. `automation` maps parameter names (as
+    /// `update_param` takes) to (seconds, normalized value) breakpoints, from the start of this
+    /// render; parameters follow them at the start of every chunk, as often as the modulation
+    /// updates.
+    #[args(automation = "None")]
     fn render(
         &mut self,
         py: Python,
         chunk_size: usize,
         buf_len: usize,
         shape: String,
+        automation: Option<HashMap<String, Vec<(f64, f64)>>>,
     ) -> PyResult<(Py<PyArray1<f32>>, Py<PyArray1<f32>>)> {
+        let automation = automation_curves(automation.unwrap_or_default())?;
         let mut l_signal = vec![0.0; buf_len];
         let mut r_signal = vec![0.0; buf_len];
-        for start_idx in (0..buf_len).step_by(chunk_size) {
-            let end_idx = (start_idx + chunk_size).min(buf_len);
-            let mut l_chunk = &mut l_signal[start_idx..end_idx];
-            let mut r_chunk = &mut r_signal[start_idx..end_idx];
-            self.inst.render(&mut [&mut l_chunk, &mut r_chunk]);
+        self.render_scheduled(&mut l_signal, &mut r_signal, chunk_size, &automation)?;
+        let l_array = l_signal.into_pyarray(py);
+        let r_array = r_signal.into_pyarray(py);
+        Ok((l_array.to_owned(), r_array.to_owned()))
+    }
+
+    /// Render into `l` and `r`, contiguous float64 arrays of the same length, replacing what's
+    /// in them. Nothing is converted to f32, so this matches what a host asking for f64 gets.
+    /// `automation` is as for `render`.
+    #[args(chunk_size = "64", automation = "None")]
+    fn render_into(
+        &mut self,
+        l: &PyArray1<f64>,
+        r: &PyArray1<f64>,
+        chunk_size: usize,
+        automation: Option<HashMap<String, Vec<(f64, f64)>>>,
+    ) -> PyResult<()> {
+        let automation = automation_curves(automation.unwrap_or_default())?;
+        if l.is(r) {
+            return Err(exceptions::PyValueError::new_err(
+                "l and r must be different arrays",
+            ));
+        }
+        // Safe, as the arrays are distinct and nothing else touches them while the GIL's held.
+        let (l_signal, r_signal) = unsafe { (l.as_slice_mut()?, r.as_slice_mut()?) };
+        if l_signal.len() != r_signal.len() {
+            return Err(exceptions::PyValueError::new_err(format!(
+                "l and r differ in length ({} and {})",
+                l_signal.len(),
+                r_signal.len()
+            )));
+        }
+        // The voices add themselves to the output.
+        l_signal.fill(0.0);
+        r_signal.fill(0.0);
+        self.render_scheduled(l_signal, r_signal, chunk_size, &automation)
+    }
+
+    /// Queue events for the next renders, as (sample_offset, kind, data) tuples. Offsets count
+    /// from the start of the next `render` or `render_into`; events past its end wait for the
+    /// ones after. The kinds, and their data:
+    ///
+    /// - "note_on": (note, velocity)
+    /// - "note_off": (note,)
+    /// - "cc": (controller, value)
+    /// - "aftertouch": (value,)
+    /// - "midi": the message's 2 or 3 bytes
+    /// - "param": (index, normalized value), with the index as `params` lists it
+    ///
+    /// Notes play from their exact sample; the rest apply from the start of the chunk they
+    /// fall in, as they would in a host.
+    fn schedule(&mut self, events: Vec<(usize, String, Vec<f64>)>) -> PyResult<()> {
+        let events = events
+            .into_iter()
+            .map(|(offset, kind, data)| Ok((offset, self.event(&kind, &data)?)))
+            .collect::<PyResult<Vec<_>>>()?;
+        self.scheduled.extend(events);
+        // Stable, so events at the same offset keep the order they were scheduled in.
+        self.scheduled.sort_by_key(|(offset, _)| *offset);
+        Ok(())
+    }
+
+    /// Drop any events scheduled but not yet rendered.
+    fn clear_schedule(&mut self) {
+        self.scheduled.clear();
+    }
+
+    /// Render a MIDI file (given its path), or a list of (seconds, message bytes) events, then
+    /// `tail_sec` more for the notes to ring out. Notes play from the sample they fall on, as a
+    /// host would send them; if `sample_rate` is given, the core switches to it first.
+    #[args(sample_rate = "None", tail_sec = "2.0", chunk_size = "64")]
+    fn render_midi(
+        &mut self,
+        py: Python,
+        source: &PyAny,
+        sample_rate: Option<f64>,
+        tail_sec: f64,
+        chunk_size: usize,
+    ) -> PyResult<(Py<PyArray1<f32>>, Py<PyArray1<f32>>)> {
+        let messages: Vec<(f64, [u8; 3])> = match source.extract::<String>() {
+            Ok(path) => midi::file::load(Path::new(&path))
+                .map_err(io_error)?
+                .into_iter()
+                .map(|message| (message.time, message.data))
+                .collect(),
+            Err(_) => source
+                .extract::<Vec<(f64, Vec<u8>)>>()?
+                .into_iter()
+                .map(|(time, bytes)| Ok((time, midi_message(&bytes)?)))
+                .collect::<PyResult<_>>()?,
+        };
+        if let Some(sample_rate) = sample_rate {
+            self.set_sample_rate(sample_rate);
         }
+        let sample_rate = self.inst.params.sample_rate;
+        let mut events: Vec<(usize, Event)> = messages
+            .into_iter()
+            .map(|(time, data)| {
+                let offset = (time.max(0.0) * sample_rate).round() as usize;
+                (offset, Event::Midi(data))
+            })
+            .collect();
+        events.sort_by_key(|(offset, _)| *offset);
+        let buf_len = events.last().map_or(0, |(offset, _)| offset + 1)
+            + (tail_sec.max(0.0) * sample_rate).ceil() as usize;
+
+        let mut l_signal = vec![0.0; buf_len];
+        let mut r_signal = vec![0.0; buf_len];
+        self.render_chunks(&mut l_signal, &mut r_signal, chunk_size, &events, &[])?;
         let l_array = l_signal.into_pyarray(py);
         let r_array = r_signal.into_pyarray(py);
         Ok((l_array.to_owned(), r_array.to_owned()))
     }
 }
 
+impl CoreWrapper {
+    // Render with the scheduled events that fall within the buffer, keeping the rest (moved
+    // earlier by the buffer's length) for the next render.
+    fn render_scheduled<F: Float>(
+        &mut self,
+        l_signal: &mut [F],
+        r_signal: &mut [F],
+        chunk_size: usize,
+        automation: &[Automation],
+    ) -> PyResult<()> {
+        let buf_len = l_signal.len();
+        let events = std::mem::take(&mut self.scheduled);
+        let split = events.partition_point(|(offset, _)| *offset < buf_len);
+        let result =
+            self.render_chunks(l_signal, r_signal, chunk_size, &events[..split], automation);
+        self.scheduled = match result {
+            Ok(()) => events[split..]
+                .iter()
+                .map(|(offset, event)| (offset - buf_len, *event))
+                .collect(),
+            Err(_) => events,
+        };
+        result
+    }
+
+    // Render `chunk_size` samples at a time, as a host would call for them, handing on the
+    // `events` (sorted by the sample they happen at) that fall in each chunk. Automated
+    // parameters move at the start of each chunk.
+    fn render_chunks<F: Float>(
+        &mut self,
+        l_signal: &mut [F],
+        r_signal: &mut [F],
+        chunk_size: usize,
+        events: &[(usize, Event)],
+        automation: &[Automation],
+    ) -> PyResult<()> {
+        if chunk_size == 0 {
+            return Err(exceptions::PyValueError::new_err(
+                "chunk_size must be at least 1",
+            ));
+        }
+        let sample_rate = self.inst.params.sample_rate;
+        // The value each automated parameter was last set to, so it's only set when it moves.
+        let mut automated: Vec<Option<f64>> = vec![None; automation.len()];
+        let mut events = events.iter().peekable();
+        for (index, (l_chunk, r_chunk)) in l_signal
+            .chunks_mut(chunk_size)
+            .zip(r_signal.chunks_mut(chunk_size))
+            .enumerate()
+        {
+            let start = index * chunk_size;
+            for (curve, last_value) in automation.iter().zip(automated.iter_mut()) {
+                let value = curve.value_at(start as f64 / sample_rate);
+                if *last_value != Some(value) {
+                    self.apply_param(curve.eparam, value);
+                    *last_value = Some(value);
+                }
+            }
+            while let Some((offset, event)) =
+                events.next_if(|(offset, _)| *offset < start + chunk_size)
+            {
+                match *event {
+                    Event::Midi(data) => self.inst.handle_midi(data, offset.saturating_sub(start)),
+                    Event::Param(eparam, value) => self.apply_param(eparam, value),
+                }
+            }
+            self.inst.render(&mut [l_chunk, r_chunk]);
+        }
+        Ok(())
+    }
+
+    // An event of the kind `schedule` takes, from its data.
+    fn event(&self, kind: &str, data: &[f64]) -> PyResult<Event> {
+        let event = match (kind, data) {
+            ("note_on", &[note, velocity]) => {
+                Event::Midi([0x90, midi_data(note)?, midi_data(velocity)?])
+            }
+            ("note_off", &[note]) => Event::Midi([0x80, midi_data(note)?, 0]),
+            ("cc", &[cc, value]) => Event::Midi([0xB0, midi_data(cc)?, midi_data(value)?]),
+            ("aftertouch", &[value]) => Event::Midi([0xD0, midi_data(value)?, 0]),
+            ("midi", bytes) => {
+                let bytes = bytes
+                    .iter()
+                    .map(|byte| match *byte {
+                        byte if (0.0..=255.0).contains(&byte) && byte.fract() == 0.0 => {
+                            Ok(byte as u8)
+                        }
+                        byte => Err(exceptions::PyValueError::new_err(format!(
+                            "{} isn't a byte",
+                            byte
+                        ))),
+                    })
+                    .collect::<PyResult<Vec<u8>>>()?;
+                Event::Midi(midi_message(&bytes)?)
+            }
+            ("param", &[index, value]) if index >= 0.0 && index.fract() == 0.0 => {
+                let eparam = self
+                    .inst
+                    .meta
+                    .parameter_index(index as usize)
+                    .map_err(param_error)?;
+                Event::Param(eparam, eparam.check_value(value).map_err(param_error)?)
+            }
+            _ => {
+                return Err(exceptions::PyValueError::new_err(format!(
+                    "can't schedule {:?} with {:?}",
+                    kind, data
+                )))
+            }
+        };
+        Ok(event)
+    }
+
+    // Switch the core to `sample_rate`, as the plugin does: the voices take theirs from the
+    // parameters.
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.inst.update_sample_rate(sample_rate);
+        self.inst.params.update_sample_rate(sample_rate);
+    }
+
+    // Set every parameter to the preset's value.
+    fn apply_preset(&mut self, preset: &Preset) {
+        for eparam in EParam::all() {
+            let value = preset.params.read_parameter(&self.inst.meta, eparam);
+            self.apply_param(eparam, value);
+        }
+    }
+
+    // Set a parameter, passing the change on to the voices and modulation.
+    fn apply_param(&mut self, eparam: EParam, value: f64) {
+        // Modulation works from the unmodulated value, and `params` and `save_preset` read it.
+        self.inst
+            .params
+            .write_parameter(&self.inst.meta, eparam, value);
+        core::Sunfish::on_param_update(
+            &self.inst.meta,
+            &mut self.inst.params,
+            &mut self.inst.params_modulated,
+            &self.inst.tempo,
+            &mut self.inst.voices,
+            &mut self.inst.modulation,
+            eparam,
+            value,
+        );
+    }
+}
+
+// A MIDI message from Python's bytes, padded to three bytes.
+fn midi_message(bytes: &[u8]) -> PyResult<[u8; 3]> {
+    match *bytes {
+        [status, data1] => Ok([status, data1, 0]),
+        [status, data1, data2] => Ok([status, data1, data2]),
+        _ => Err(exceptions::PyValueError::new_err(format!(
+            "expected a MIDI message of 2 or 3 bytes, got {:?}",
+            bytes
+        ))),
+    }
+}
+
+// Breakpoints given by parameter name, checked and sorted by time (and the parameters in the
+// host's order, so they're set in the same order every time).
+fn automation_curves(automation: HashMap<String, Vec<(f64, f64)>>) -> PyResult<Vec<Automation>> {
+    let mut curves = automation
+        .into_iter()
+        .map(|(name, mut points)| {
+            let eparam: EParam = name.parse().map_err(param_error)?;
+            if points.is_empty() {
+                return Err(exceptions::PyValueError::new_err(format!(
+                    "no breakpoints for {}",
+                    name
+                )));
+            }
+            for (time, value) in points.iter() {
+                if !time.is_finite() {
+                    return Err(exceptions::PyValueError::new_err(format!(
+                        "{} isn't a time",
+                        time
+                    )));
+                }
+                eparam.check_value(*value).map_err(param_error)?;
+            }
+            // Stable, so of breakpoints at the same time, the last one given holds after it.
+            points.sort_by(|a, b| a.0.total_cmp(&b.0));
+            Ok(Automation { eparam, points })
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    curves.sort_by_key(|curve| curve.eparam.index());
+    Ok(curves)
+}
+
+// A MIDI data byte (a note, velocity or controller value) from Python's number.
+fn midi_data(value: f64) -> PyResult<u8> {
+    if (0.0..=MAX_MIDI_VALUE).contains(&value) && value.fract() == 0.0 {
+        Ok(value as u8)
+    } else {
+        Err(exceptions::PyValueError::new_err(format!(
+            "{} isn't a MIDI value (0 to 127)",
+            value
+        )))
+    }
+}
+
 /// Render the waveforms.
 ///
 /// chunk_size: How big a buffer to handle render.
@@ -0,0 +1,1066 @@
+//! VST3 bindings for Sunfish.
+//!
+//! The synth is a single component: the same object is both the audio processor and the edit
+//! controller. The processor owns the `SunfishPlugin`, while the controller answers the host's
+//! parameter queries through a handle of its own, so they never hold up `process`. There's no
+//! editor view yet; hosts show their generic parameter UI.
+//!
+//! VST3 has no MIDI controller events: hosts turn controllers into changes of whichever
+//! parameters the plugin maps them to. Each controller gets a hidden parameter, after the synth's
+//! own, whose changes go back to the synth as controller messages.
+
+use std::mem::MaybeUninit;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, TryLockError};
+
+use num_traits::Float;
+use vst3_sys::base::{
+    kInvalidArgument, kNoInterface, kNotImplemented, kResultFalse, kResultOk, kResultTrue, tresult,
+    ClassCardinality, FIDString, FactoryFlags, IBStream, IPluginBase, IPluginFactory,
+    IPluginFactory2, IUnknown, PClassInfo, PClassInfo2, PFactoryInfo, TBool,
+};
+use vst3_sys::utils::SharedVstPtr;
+use vst3_sys::vst::{
+    BusDirection, BusDirections, BusFlags, BusInfo, BusTypes, Event, EventTypes, IAudioProcessor,
+    IComponent, IComponentHandler, IEditController, IMidiMapping, IoMode, MediaType, MediaTypes,
+    ParameterFlags, ParameterInfo, ProcessData, ProcessSetup, RoutingInfo, SpeakerArrangement,
+    SymbolicSampleSizes, TChar,
+};
+use vst3_sys::{IID, VST3};
+
+use sunfish::core::{CHANNEL_COUNT, MAX_CHANNEL_COUNT};
+use sunfish::params::error::ParamError;
+use sunfish::params::sync::Synchronizer;
+use sunfish::params::{EParam, NormalizedParams, ParamsMeta};
+use sunfish::plugin::SunfishPlugin;
+use sunfish::shell::{PluginShell, Transport};
+use sunfish::util::errors;
+
+const NAME: &str = "Sunfish";
+const VENDOR: &str = "Kunal Arya";
+const URL: &str = "https://github.com/kunalarya/sunfish-rs";
+const SUBCATEGORIES: &str = "Instrument|Synth";
+const SDK_VERSION: &str = "VST 3.6.14";
+// Category of every processor class.
+const AUDIO_MODULE_CLASS: &str = "Audio Module Class";
+
+/// Identifies the plugin to hosts: "SunfishVST3", then the VST2 unique ID.
+const SUNFISH_CID: IID = IID {
+    data: [
+        0x53, 0x75, 0x6E, 0x66, 0x69, 0x73, 0x68, 0x56, 0x53, 0x54, 0x33, 0x00, 0x78, 0xB5, 0x2B,
+        0xBC,
+    ],
+};
+
+// Left and right speakers.
+const STEREO: SpeakerArrangement = 0b11;
+
+// Stereo output buses: the main output, and an optional second one that oscillator 2's chain
+// moves to when the host activates it.
+const OUTPUT_BUSES: [&str; 2] = ["Output", "Osc 2 Output"];
+// A stereo input bus, for the external input sound source. It's a side input, as instruments
+// don't take a main one.
+const INPUT_BUS: &str = "Input";
+
+// Length of the host's (UTF-16) string buffers, including the terminator.
+const STRING128_LEN: usize = 128;
+
+// `ProcessContext` state flags.
+const PLAYING: u32 = 1 << 1;
+const PROJECT_TIME_MUSIC_VALID: u32 = 1 << 9;
+const TEMPO_VALID: u32 = 1 << 10;
+
+const NOTE_ON_STATUS: u8 = 144;
+const NOTE_OFF_STATUS: u8 = 128;
+const POLY_PRESSURE_STATUS: u8 = 160;
+const CONTROL_CHANGE_STATUS: u8 = 176;
+const CHANNEL_PRESSURE_STATUS: u8 = 208;
+
+// VST3's controller number for channel pressure, after the 128 MIDI CCs.
+const CHANNEL_PRESSURE: usize = 128;
+// Hidden parameters for MIDI controllers: one per CC, then channel pressure.
+const MIDI_PARAM_COUNT: usize = CHANNEL_PRESSURE + 1;
+
+// MIDI messages held for the processor while it's busy; any more are dropped.
+const MAX_DEFERRED_MIDI: usize = 256;
+// Parameter change points and events per buffer that are put in order without allocating; any
+// more apply as they come.
+const MAX_PROCESS_EVENTS: usize = 1024;
+
+#[VST3(implements(IComponent, IAudioProcessor, IEditController, IMidiMapping))]
+pub struct SunfishVst3 {
+    // The processor. `process` never waits for it: if the host's UI thread has it (e.g. to save
+    // the state), that buffer is left silent.
+    plugin: Mutex<SunfishPlugin>,
+    // What only `process` uses, so locking it never waits.
+    audio: Mutex<AudioState>,
+    // The controller's own handle on the parameters.
+    controller: Mutex<Synchronizer>,
+    // How long the synth keeps sounding after its last note, as of the last buffer.
+    tail_samples: AtomicUsize,
+    // Whether the host has activated the second output bus.
+    osc2_output: AtomicBool,
+    // Whether the host has activated the input bus.
+    input_active: AtomicBool,
+}
+
+// The audio thread's state outside the processor.
+struct AudioState {
+    // Writes the host's parameter changes, whether or not the processor is free.
+    params: Synchronizer,
+    // Note and controller messages that arrived while the processor was busy, for the start of
+    // the next buffer it renders.
+    deferred_midi: Vec<[u8; 3]>,
+    // The buffer's parameter changes and events, with their sample offsets and the order they
+    // came in.
+    events: Vec<(i32, usize, ProcessEvent)>,
+    // Copies of the input bus's channels, in either sample size.
+    inputs32: [Vec<f32>; CHANNEL_COUNT],
+    inputs64: [Vec<f64>; CHANNEL_COUNT],
+}
+
+#[derive(Clone, Copy)]
+enum ProcessEvent {
+    // A synth parameter, by host index, and its new normalized value.
+    Param(usize, f64),
+    Midi([u8; 3]),
+}
+
+impl AudioState {
+    // Queue `event` to apply in order with the rest of the buffer's, or apply it right away if
+    // there's no room left.
+    fn push_event(
+        &mut self,
+        plugin: Option<&mut SunfishPlugin>,
+        sample_offset: i32,
+        event: ProcessEvent,
+    ) {
+        if self.events.len() < self.events.capacity() {
+            let order = self.events.len();
+            self.events.push((sample_offset, order, event));
+        } else {
+            self.apply(plugin, sample_offset.max(0) as usize, event);
+        }
+    }
+
+    // Write a parameter, or pass a MIDI message to the processor; if it's busy, the message
+    // waits for the next buffer.
+    fn apply(
+        &mut self,
+        plugin: Option<&mut SunfishPlugin>,
+        sample_offset: usize,
+        event: ProcessEvent,
+    ) {
+        match (event, plugin) {
+            (ProcessEvent::Param(index, value), _) => {
+                if let Err(err) = write_param(&mut self.params, index, value) {
+                    log::error!("process: {}", err);
+                }
+            }
+            (ProcessEvent::Midi(midi), Some(plugin)) => plugin.midi_event(midi, sample_offset),
+            (ProcessEvent::Midi(midi), None) => {
+                if self.deferred_midi.len() < MAX_DEFERRED_MIDI {
+                    self.deferred_midi.push(midi);
+                }
+            }
+        }
+    }
+}
+
+impl SunfishVst3 {
+    fn new() -> Box<Self> {
+        let plugin = SunfishPlugin::new();
+        let audio = AudioState {
+            params: plugin.core.params_sync.clone(),
+            deferred_midi: Vec::with_capacity(MAX_DEFERRED_MIDI),
+            events: Vec::with_capacity(MAX_PROCESS_EVENTS),
+            inputs32: Default::default(),
+            inputs64: Default::default(),
+        };
+        let controller = plugin.core.params_sync.clone();
+        let tail_samples = AtomicUsize::new(plugin.tail_samples());
+        Self::allocate(
+            Mutex::new(plugin),
+            Mutex::new(audio),
+            Mutex::new(controller),
+            tail_samples,
+            AtomicBool::new(false),
+            AtomicBool::new(false),
+        )
+    }
+
+    fn plugin(&self) -> MutexGuard<SunfishPlugin> {
+        // A panic elsewhere doesn't leave the plugin in a state we can't keep using.
+        self.plugin.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn controller(&self) -> MutexGuard<Synchronizer> {
+        self.controller
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl IPluginBase for SunfishVst3 {
+    unsafe fn initialize(&self, _context: *mut c_void) -> tresult {
+        errors::setup_panic_handling();
+        kResultOk
+    }
+
+    unsafe fn terminate(&self) -> tresult {
+        kResultOk
+    }
+}
+
+impl IComponent for SunfishVst3 {
+    unsafe fn get_controller_class_id(&self, _tuid: *mut IID) -> tresult {
+        // The component is its own controller.
+        kNotImplemented
+    }
+
+    unsafe fn set_io_mode(&self, _mode: IoMode) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn get_bus_count(&self, type_: MediaType, dir: BusDirection) -> i32 {
+        bus_count(type_, dir)
+    }
+
+    unsafe fn get_bus_info(
+        &self,
+        type_: MediaType,
+        dir: BusDirection,
+        index: i32,
+        info: *mut BusInfo,
+    ) -> tresult {
+        if index < 0 || index >= bus_count(type_, dir) || info.is_null() {
+            return kInvalidArgument;
+        }
+        let info = &mut *info;
+        info.media_type = type_;
+        info.direction = dir;
+        let audio_input = type_ == MediaTypes::kAudio as i32 && dir == BusDirections::kInput as i32;
+        if audio_input {
+            info.channel_count = CHANNEL_COUNT as i32;
+            copy_wstring(INPUT_BUS, &mut info.name);
+        } else if type_ == MediaTypes::kAudio as i32 {
+            info.channel_count = CHANNEL_COUNT as i32;
+            copy_wstring(OUTPUT_BUSES[index as usize], &mut info.name);
+        } else {
+            info.channel_count = 1;
+            copy_wstring("MIDI In", &mut info.name);
+        }
+        if index == 0 && !audio_input {
+            info.bus_type = BusTypes::kMain as i32;
+            info.flags = BusFlags::kDefaultActive as u32;
+        } else {
+            info.bus_type = BusTypes::kAux as i32;
+            info.flags = 0;
+        }
+        kResultOk
+    }
+
+    unsafe fn get_routing_info(
+        &self,
+        _in_info: *mut RoutingInfo,
+        _out_info: *mut RoutingInfo,
+    ) -> tresult {
+        kNotImplemented
+    }
+
+    unsafe fn activate_bus(
+        &self,
+        type_: MediaType,
+        dir: BusDirection,
+        index: i32,
+        state: TBool,
+    ) -> tresult {
+        if type_ != MediaTypes::kAudio as i32 {
+            return kResultOk;
+        }
+        if dir == BusDirections::kOutput as i32 && index == 1 {
+            self.osc2_output.store(state != 0, Ordering::Relaxed);
+        } else if dir == BusDirections::kInput as i32 && index == 0 {
+            self.input_active.store(state != 0, Ordering::Relaxed);
+        }
+        kResultOk
+    }
+
+    unsafe fn set_active(&self, state: TBool) -> tresult {
+        if state == 0 {
+            self.plugin().suspend();
+        } else {
+            self.plugin().resume();
+        }
+        kResultOk
+    }
+
+    unsafe fn set_state(&self, state: SharedVstPtr<dyn IBStream>) -> tresult {
+        match read_stream(state) {
+            Some(data) => {
+                self.plugin().load_state(&data);
+                kResultOk
+            }
+            None => kInvalidArgument,
+        }
+    }
+
+    unsafe fn get_state(&self, state: SharedVstPtr<dyn IBStream>) -> tresult {
+        let data = self.plugin().save_state();
+        write_stream(state, &data)
+    }
+}
+
+impl IAudioProcessor for SunfishVst3 {
+    unsafe fn set_bus_arrangements(
+        &self,
+        inputs: *mut SpeakerArrangement,
+        num_ins: i32,
+        outputs: *mut SpeakerArrangement,
+        num_outs: i32,
+    ) -> tresult {
+        // Stereo buses, and nothing else.
+        let all_stereo = |arrangements: *mut SpeakerArrangement, count: i32| {
+            count == 0
+                || (!arrangements.is_null()
+                    && slice::from_raw_parts(arrangements, count as usize)
+                        .iter()
+                        .all(|arrangement| *arrangement == STEREO))
+        };
+        let inputs_ok = (0..=1).contains(&num_ins) && all_stereo(inputs, num_ins);
+        let outputs_ok =
+            (1..=OUTPUT_BUSES.len() as i32).contains(&num_outs) && all_stereo(outputs, num_outs);
+        if inputs_ok && outputs_ok {
+            kResultTrue
+        } else {
+            kResultFalse
+        }
+    }
+
+    unsafe fn get_bus_arrangement(
+        &self,
+        dir: BusDirection,
+        index: i32,
+        arr: *mut SpeakerArrangement,
+    ) -> tresult {
+        let bus_count = if dir == BusDirections::kOutput as i32 {
+            OUTPUT_BUSES.len() as i32
+        } else {
+            1
+        };
+        if !(0..bus_count).contains(&index) || arr.is_null() {
+            return kInvalidArgument;
+        }
+        *arr = STEREO;
+        kResultOk
+    }
+
+    unsafe fn can_process_sample_size(&self, symbolic_sample_size: i32) -> tresult {
+        if symbolic_sample_size == SymbolicSampleSizes::kSample32 as i32
+            || symbolic_sample_size == SymbolicSampleSizes::kSample64 as i32
+        {
+            kResultTrue
+        } else {
+            kResultFalse
+        }
+    }
+
+    unsafe fn get_latency_samples(&self) -> u32 {
+        0
+    }
+
+    unsafe fn setup_processing(&self, setup: *const ProcessSetup) -> tresult {
+        if setup.is_null() {
+            return kInvalidArgument;
+        }
+        let setup = &*setup;
+        self.plugin().set_sample_rate(setup.sample_rate);
+        // Room for the largest buffer, so copying the input doesn't allocate while processing.
+        let max_samples = setup.max_samples_per_block.max(0) as usize;
+        let mut audio = self.audio.lock().unwrap_or_else(|err| err.into_inner());
+        for input in audio.inputs32.iter_mut() {
+            input.clear();
+            input.reserve(max_samples);
+        }
+        for input in audio.inputs64.iter_mut() {
+            input.clear();
+            input.reserve(max_samples);
+        }
+        kResultOk
+    }
+
+    unsafe fn set_processing(&self, _state: TBool) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn process(&self, data: *mut ProcessData) -> tresult {
+        if data.is_null() {
+            return kInvalidArgument;
+        }
+        let data = &mut *data;
+        // Only `process` takes this, so it's always free.
+        let mut audio = self.audio.lock().unwrap_or_else(|err| err.into_inner());
+        let audio = &mut *audio;
+        let mut plugin = match self.plugin.try_lock() {
+            Ok(plugin) => Some(plugin),
+            Err(TryLockError::Poisoned(err)) => Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        };
+        if let Some(plugin) = plugin.as_mut() {
+            for midi in audio.deferred_midi.drain(..) {
+                plugin.midi_event(midi, 0);
+            }
+        }
+
+        // Gather every parameter change point and event, to apply in order. Parameters mapped
+        // to MIDI controllers come back as controller messages.
+        let param_count = audio.params.meta.count();
+        audio.events.clear();
+        if let Some(changes) = data.input_param_changes.upgrade() {
+            for change_index in 0..changes.get_parameter_count() {
+                let queue = match changes.get_parameter_data(change_index).upgrade() {
+                    Some(queue) => queue,
+                    None => continue,
+                };
+                let id = queue.get_parameter_id() as usize;
+                for point in 0..queue.get_point_count() {
+                    let (mut sample_offset, mut value) = (0, 0.0);
+                    if queue.get_point(point, &mut sample_offset, &mut value) != kResultOk {
+                        continue;
+                    }
+                    let event = match id.checked_sub(param_count) {
+                        None => ProcessEvent::Param(id, value),
+                        Some(controller) => match controller_midi(controller, value) {
+                            Some(midi) => ProcessEvent::Midi(midi),
+                            None => continue,
+                        },
+                    };
+                    audio.push_event(plugin.as_deref_mut(), sample_offset, event);
+                }
+            }
+        }
+        if let Some(events) = data.input_events.upgrade() {
+            for event_index in 0..events.get_event_count() {
+                let mut event = MaybeUninit::<Event>::uninit();
+                if events.get_event(event_index, event.as_mut_ptr()) != kResultOk {
+                    continue;
+                }
+                let event = event.assume_init();
+                let midi = if event.type_ == EventTypes::kNoteOnEvent as u16 {
+                    let note_on = event.event.note_on;
+                    let velocity = midi_value(note_on.velocity as f64);
+                    [NOTE_ON_STATUS, note_on.pitch as u8, velocity]
+                } else if event.type_ == EventTypes::kNoteOffEvent as u16 {
+                    let note_off = event.event.note_off;
+                    let velocity = midi_value(note_off.velocity as f64);
+                    [NOTE_OFF_STATUS, note_off.pitch as u8, velocity]
+                } else if event.type_ == EventTypes::kPolyPressureEvent as u16 {
+                    let poly_pressure = event.event.poly_pressure;
+                    let pressure = midi_value(poly_pressure.pressure as f64);
+                    [POLY_PRESSURE_STATUS, poly_pressure.pitch as u8, pressure]
+                } else {
+                    continue;
+                };
+                let sample_offset = event.sample_offset;
+                audio.push_event(
+                    plugin.as_deref_mut(),
+                    sample_offset,
+                    ProcessEvent::Midi(midi),
+                );
+            }
+        }
+        // Same-offset changes keep the order they came in: parameters, then events.
+        audio
+            .events
+            .sort_unstable_by_key(|(sample_offset, order, _)| (*sample_offset, *order));
+        for index in 0..audio.events.len() {
+            let (sample_offset, _, event) = audio.events[index];
+            audio.apply(plugin.as_deref_mut(), sample_offset.max(0) as usize, event);
+        }
+
+        let mut plugin = match plugin {
+            Some(plugin) => plugin,
+            None => {
+                silence_outputs(data);
+                return kResultOk;
+            }
+        };
+
+        let mut transport = Transport::default();
+        if let Some(context) = data.context.as_ref() {
+            transport.playing = context.state & PLAYING != 0;
+            if context.state & TEMPO_VALID != 0 {
+                transport.tempo_bpm = Some(context.tempo);
+            }
+            if context.state & PROJECT_TIME_MUSIC_VALID != 0 {
+                transport.ppq_pos = Some(context.project_time_music);
+            }
+        }
+
+        // Hosts may call with no outputs just to deliver parameter changes.
+        if data.num_outputs < 1 || data.outputs.is_null() || data.num_samples <= 0 {
+            return kResultOk;
+        }
+        let num_samples = data.num_samples as usize;
+        let sample64 = data.symbolic_sample_size == SymbolicSampleSizes::kSample64 as i32;
+
+        // Copy the input bus's channels, if the host activated and connected it, before touching
+        // any output: hosts may process in place, handing over the same memory for both.
+        let mut input_count = 0;
+        if self.input_active.load(Ordering::Relaxed)
+            && data.num_inputs > 0
+            && !data.inputs.is_null()
+        {
+            let bus = &*data.inputs;
+            if !bus.buffers.is_null() && bus.num_channels as usize == CHANNEL_COUNT {
+                let channels = slice::from_raw_parts(bus.buffers, CHANNEL_COUNT);
+                if sample64 {
+                    copy_channels(&mut audio.inputs64, channels, num_samples);
+                } else {
+                    copy_channels(&mut audio.inputs32, channels, num_samples);
+                }
+                input_count = CHANNEL_COUNT;
+            }
+        }
+
+        // Render into the active buses' channels; the core splits the oscillators' chains when
+        // given both pairs. Silence any bus the host hasn't activated.
+        let active_buses = if self.osc2_output.load(Ordering::Relaxed) {
+            OUTPUT_BUSES.len()
+        } else {
+            1
+        };
+        let mut buffers = [ptr::null_mut(); MAX_CHANNEL_COUNT];
+        let mut channel_count = 0;
+        let buses = slice::from_raw_parts(data.outputs, data.num_outputs as usize);
+        for (index, bus) in buses.iter().enumerate() {
+            if bus.buffers.is_null() {
+                continue;
+            }
+            let channels = slice::from_raw_parts(bus.buffers, bus.num_channels.max(0) as usize);
+            if index < active_buses && channels.len() == CHANNEL_COUNT {
+                buffers[channel_count..channel_count + CHANNEL_COUNT].copy_from_slice(channels);
+                channel_count += CHANNEL_COUNT;
+            } else {
+                silence_channels(channels, num_samples, sample64);
+            }
+        }
+
+        if sample64 {
+            render::<f64>(
+                &mut plugin,
+                &audio.inputs64[..input_count],
+                &buffers[..channel_count],
+                num_samples,
+                &transport,
+            );
+        } else {
+            render::<f32>(
+                &mut plugin,
+                &audio.inputs32[..input_count],
+                &buffers[..channel_count],
+                num_samples,
+                &transport,
+            );
+        }
+        self.tail_samples
+            .store(plugin.tail_samples(), Ordering::Relaxed);
+        kResultOk
+    }
+
+    unsafe fn get_tail_samples(&self) -> u32 {
+        self.tail_samples.load(Ordering::Relaxed) as u32
+    }
+}
+
+impl IEditController for SunfishVst3 {
+    unsafe fn set_component_state(&self, _state: SharedVstPtr<dyn IBStream>) -> tresult {
+        // The controller shares the component's state.
+        kResultOk
+    }
+
+    unsafe fn set_state(&self, _state: SharedVstPtr<dyn IBStream>) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn get_state(&self, _state: SharedVstPtr<dyn IBStream>) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn get_parameter_count(&self) -> i32 {
+        (self.controller().meta.count() + MIDI_PARAM_COUNT) as i32
+    }
+
+    unsafe fn get_parameter_info(&self, param_index: i32, info: *mut ParameterInfo) -> tresult {
+        if param_index < 0 || info.is_null() {
+            return kInvalidArgument;
+        }
+        let controller = self.controller();
+        let meta = &controller.meta;
+        let index = param_index as usize;
+        let param = match midi_controller(meta, index) {
+            Some(cc) => Ok((controller_name(cc), 0.0, ParameterFlags::kIsHidden as i32)),
+            None => meta.parameter_index(index).and_then(|eparam| {
+                let default = meta.default_value(eparam)?;
+                Ok((
+                    eparam.host_name(),
+                    default,
+                    ParameterFlags::kCanAutomate as i32,
+                ))
+            }),
+        };
+        let (name, default, flags) = match param {
+            Ok(param) => param,
+            Err(err) => {
+                log::error!("get_parameter_info: {}", err);
+                return kInvalidArgument;
+            }
+        };
+        let info = &mut *info;
+        // Host indices never change, so they double as parameter IDs.
+        info.id = index as u32;
+        copy_wstring(&name, &mut info.title);
+        copy_wstring(&name, &mut info.short_title);
+        copy_wstring("", &mut info.units);
+        info.step_count = 0;
+        info.default_normalized_value = default;
+        info.unit_id = 0;
+        info.flags = flags;
+        kResultOk
+    }
+
+    unsafe fn get_param_string_by_value(
+        &self,
+        id: u32,
+        value_normalized: f64,
+        string: *mut TChar,
+    ) -> tresult {
+        if string.is_null() {
+            return kInvalidArgument;
+        }
+        let controller = self.controller();
+        let text = match midi_controller(&controller.meta, id as usize) {
+            Some(_) => Ok(midi_value(value_normalized).to_string()),
+            None => param_eparam(&controller, id as usize, value_normalized).and_then(
+                |(eparam, value)| {
+                    controller
+                        .params()
+                        .formatted_normalized(&controller.meta, eparam, value)
+                },
+            ),
+        };
+        match text {
+            Ok(text) => {
+                copy_wstring(&text, slice::from_raw_parts_mut(string, STRING128_LEN));
+                kResultOk
+            }
+            Err(err) => {
+                log::error!("get_param_string_by_value: {}", err);
+                kInvalidArgument
+            }
+        }
+    }
+
+    unsafe fn get_param_value_by_string(
+        &self,
+        _id: u32,
+        _string: *const TChar,
+        _value_normalized: *mut f64,
+    ) -> tresult {
+        kNotImplemented
+    }
+
+    // Parameters are only exposed in their normalized form.
+    unsafe fn normalized_param_to_plain(&self, _id: u32, value_normalized: f64) -> f64 {
+        value_normalized
+    }
+
+    unsafe fn plain_param_to_normalized(&self, _id: u32, plain_value: f64) -> f64 {
+        plain_value
+    }
+
+    unsafe fn get_param_normalized(&self, id: u32) -> f64 {
+        let mut controller = self.controller();
+        // Controllers only pass through; they don't keep a value.
+        if midi_controller(&controller.meta, id as usize).is_some() {
+            return 0.0;
+        }
+        // Pick up edits from the GUI and the processor, unless they're being passed on now.
+        controller.refresh_maybe();
+        match controller.meta.parameter_index(id as usize) {
+            Ok(eparam) => controller.read_parameter(eparam),
+            Err(err) => {
+                log::error!("get_param_normalized: {}", err);
+                0.0
+            }
+        }
+    }
+
+    unsafe fn set_param_normalized(&self, id: u32, value: f64) -> tresult {
+        let mut controller = self.controller();
+        if midi_controller(&controller.meta, id as usize).is_some() {
+            return kResultOk;
+        }
+        match write_param(&mut controller, id as usize, value) {
+            Ok(()) => kResultOk,
+            Err(err) => {
+                log::error!("set_param_normalized: {}", err);
+                kInvalidArgument
+            }
+        }
+    }
+
+    unsafe fn set_component_handler(
+        &self,
+        _handler: SharedVstPtr<dyn IComponentHandler>,
+    ) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn create_view(&self, _name: FIDString) -> *mut c_void {
+        ptr::null_mut()
+    }
+}
+
+impl IMidiMapping for SunfishVst3 {
+    unsafe fn get_midi_controller_assignment(
+        &self,
+        bus_index: i32,
+        _channel: i16,
+        midi_cc_number: i16,
+        param_id: *mut u32,
+    ) -> tresult {
+        // Controllers on any channel, like notes.
+        let cc = midi_cc_number as usize;
+        if bus_index != 0 || midi_cc_number < 0 || cc >= MIDI_PARAM_COUNT || param_id.is_null() {
+            return kResultFalse;
+        }
+        *param_id = (self.controller().meta.count() + cc) as u32;
+        kResultTrue
+    }
+}
+
+#[VST3(implements(IPluginFactory, IPluginFactory2))]
+pub struct Factory {}
+
+impl Factory {
+    fn new() -> Box<Self> {
+        Self::allocate()
+    }
+}
+
+impl IPluginFactory for Factory {
+    unsafe fn get_factory_info(&self, info: *mut PFactoryInfo) -> tresult {
+        if info.is_null() {
+            return kInvalidArgument;
+        }
+        let info = &mut *info;
+        copy_cstring(VENDOR, &mut info.vendor);
+        copy_cstring(URL, &mut info.url);
+        copy_cstring("", &mut info.email);
+        info.flags = FactoryFlags::kUnicode as i32;
+        kResultOk
+    }
+
+    unsafe fn count_classes(&self) -> i32 {
+        1
+    }
+
+    unsafe fn get_class_info(&self, index: i32, info: *mut PClassInfo) -> tresult {
+        if index != 0 || info.is_null() {
+            return kInvalidArgument;
+        }
+        let info = &mut *info;
+        info.cid = SUNFISH_CID;
+        info.cardinality = ClassCardinality::kManyInstances as i32;
+        copy_cstring(AUDIO_MODULE_CLASS, &mut info.category);
+        copy_cstring(NAME, &mut info.name);
+        kResultOk
+    }
+
+    unsafe fn create_instance(
+        &self,
+        cid: *const IID,
+        iid: *const IID,
+        obj: *mut *mut c_void,
+    ) -> tresult {
+        if cid.is_null() || iid.is_null() || obj.is_null() {
+            return kInvalidArgument;
+        }
+        if (*cid).data != SUNFISH_CID.data {
+            return kNoInterface;
+        }
+        // Hand over the interface asked for, holding the only reference; if the synth doesn't
+        // implement it, releasing our own reference frees the instance again.
+        let instance = Box::into_raw(SunfishVst3::new());
+        (*instance).add_ref();
+        let result = (*instance).query_interface(iid, obj);
+        (*instance).release();
+        result
+    }
+}
+
+impl IPluginFactory2 for Factory {
+    unsafe fn get_class_info2(&self, index: i32, info: *mut PClassInfo2) -> tresult {
+        if index != 0 || info.is_null() {
+            return kInvalidArgument;
+        }
+        let info = &mut *info;
+        info.cid = SUNFISH_CID;
+        info.cardinality = ClassCardinality::kManyInstances as i32;
+        copy_cstring(AUDIO_MODULE_CLASS, &mut info.category);
+        copy_cstring(NAME, &mut info.name);
+        info.class_flags = 0;
+        copy_cstring(SUBCATEGORIES, &mut info.subcategories);
+        copy_cstring(VENDOR, &mut info.vendor);
+        copy_cstring(env!("CARGO_PKG_VERSION"), &mut info.version);
+        copy_cstring(SDK_VERSION, &mut info.sdk_version);
+        kResultOk
+    }
+}
+
+/// Number of buses of this type and direction: stereo outputs, a stereo input and a MIDI input.
+fn bus_count(type_: MediaType, dir: BusDirection) -> i32 {
+    if type_ == MediaTypes::kAudio as i32 && dir == BusDirections::kOutput as i32 {
+        OUTPUT_BUSES.len() as i32
+    } else if type_ == MediaTypes::kAudio as i32 && dir == BusDirections::kInput as i32 {
+        1
+    } else if type_ == MediaTypes::kEvent as i32 && dir == BusDirections::kInput as i32 {
+        1
+    } else {
+        0
+    }
+}
+
+/// The controller whose hidden parameter is at host index `index`, if it's one of them.
+fn midi_controller(meta: &ParamsMeta, index: usize) -> Option<usize> {
+    index
+        .checked_sub(meta.count())
+        .filter(|cc| *cc < MIDI_PARAM_COUNT)
+}
+
+fn controller_name(cc: usize) -> String {
+    if cc == CHANNEL_PRESSURE {
+        "Channel Pressure".to_string()
+    } else {
+        format!("MIDI CC {}", cc)
+    }
+}
+
+/// The parameter at host index `index`, and `value` if it's a valid normalized value for it.
+fn param_eparam(
+    params: &Synchronizer,
+    index: usize,
+    value: f64,
+) -> Result<(EParam, f64), ParamError> {
+    let eparam = params.meta.parameter_index(index)?;
+    Ok((eparam, eparam.check_value(value)?))
+}
+
+/// Set the parameter at host index `index` to the normalized `value`.
+fn write_param(params: &mut Synchronizer, index: usize, value: f64) -> Result<(), ParamError> {
+    let (eparam, value) = param_eparam(params, index, value)?;
+    params.write_parameter(eparam, value);
+    Ok(())
+}
+
+/// Silence every output channel, e.g. when the processor is busy.
+unsafe fn silence_outputs(data: &ProcessData) {
+    if data.num_outputs < 1 || data.outputs.is_null() || data.num_samples <= 0 {
+        return;
+    }
+    let sample64 = data.symbolic_sample_size == SymbolicSampleSizes::kSample64 as i32;
+    for bus in slice::from_raw_parts(data.outputs, data.num_outputs as usize) {
+        if !bus.buffers.is_null() {
+            let channels = slice::from_raw_parts(bus.buffers, bus.num_channels.max(0) as usize);
+            silence_channels(channels, data.num_samples as usize, sample64);
+        }
+    }
+}
+
+unsafe fn silence_channels(channels: &[*mut c_void], num_samples: usize, sample64: bool) {
+    let sample_size = if sample64 { 8 } else { 4 };
+    for channel in channels {
+        ptr::write_bytes(*channel as *mut u8, 0, num_samples * sample_size);
+    }
+}
+
+/// VST3 velocities and controller values run from 0.0 to 1.0.
+fn midi_value(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 127.0).round() as u8
+}
+
+/// The MIDI message for the hidden parameter of `controller` (counted from the first one) at
+/// the normalized `value`.
+fn controller_midi(controller: usize, value: f64) -> Option<[u8; 3]> {
+    let value = midi_value(value);
+    match controller {
+        0..=127 => Some([CONTROL_CHANGE_STATUS, controller as u8, value]),
+        CHANNEL_PRESSURE => Some([CHANNEL_PRESSURE_STATUS, value, 0]),
+        _ => None,
+    }
+}
+
+/// Copy the host's channel buffers, which hold samples of type `F`, into `copies`. This only
+/// allocates if the host sends more samples than it said it would in `setup_processing`.
+unsafe fn copy_channels<F: Float>(
+    copies: &mut [Vec<F>; CHANNEL_COUNT],
+    buffers: &[*mut c_void],
+    num_samples: usize,
+) {
+    for (copy, buffer) in copies.iter_mut().zip(buffers) {
+        copy.clear();
+        copy.extend_from_slice(slice::from_raw_parts(*buffer as *const F, num_samples));
+    }
+}
+
+/// Render from `inputs` (copies of the host's input channels, so they can't overlap the
+/// outputs) into the host's channel buffers, which hold samples of type `F`.
+unsafe fn render<F: Float>(
+    plugin: &mut SunfishPlugin,
+    inputs: &[Vec<F>],
+    buffers: &[*mut c_void],
+    num_samples: usize,
+    transport: &Transport,
+) {
+    // Fixed arrays of slices, to avoid any heap allocations.
+    let mut input_slices: [&[F]; CHANNEL_COUNT] = Default::default();
+    for (input_slice, input) in input_slices.iter_mut().zip(inputs) {
+        *input_slice = input.as_slice();
+    }
+    let mut outputs: [&mut [F]; MAX_CHANNEL_COUNT] = Default::default();
+    for (output, buffer) in outputs.iter_mut().zip(buffers) {
+        *output = slice::from_raw_parts_mut(*buffer as *mut F, num_samples);
+    }
+    plugin.process(
+        &input_slices[..inputs.len()],
+        &mut outputs[..buffers.len()],
+        transport,
+    );
+}
+
+/// Read the rest of the host's stream.
+unsafe fn read_stream(stream: SharedVstPtr<dyn IBStream>) -> Option<Vec<u8>> {
+    let stream = stream.upgrade()?;
+    let mut data = vec![];
+    let mut chunk = [0u8; 4096];
+    loop {
+        let mut bytes_read = 0;
+        let result = stream.read(
+            chunk.as_mut_ptr() as *mut c_void,
+            chunk.len() as i32,
+            &mut bytes_read,
+        );
+        if result != kResultOk || bytes_read <= 0 {
+            break;
+        }
+        data.extend_from_slice(&chunk[..bytes_read as usize]);
+    }
+    Some(data)
+}
+
+unsafe fn write_stream(stream: SharedVstPtr<dyn IBStream>, data: &[u8]) -> tresult {
+    let stream = match stream.upgrade() {
+        Some(stream) => stream,
+        None => return kInvalidArgument,
+    };
+    let mut bytes_written = 0;
+    let result = stream.write(
+        data.as_ptr() as *const c_void,
+        data.len() as i32,
+        &mut bytes_written,
+    );
+    if result == kResultOk && bytes_written as usize == data.len() {
+        kResultOk
+    } else {
+        kResultFalse
+    }
+}
+
+/// Copy `src` into a null-terminated UTF-16 buffer, truncating it if needed.
+fn copy_wstring(src: &str, dst: &mut [TChar]) {
+    let max_len = dst.len() - 1;
+    let mut len = 0;
+    for (unit, dst_unit) in src.encode_utf16().zip(dst[..max_len].iter_mut()) {
+        *dst_unit = unit as TChar;
+        len += 1;
+    }
+    dst[len] = 0;
+}
+
+/// Copy `src` into a null-terminated C string buffer, truncating it if needed.
+fn copy_cstring(src: &str, dst: &mut [c_char]) {
+    let max_len = dst.len() - 1;
+    let mut len = 0;
+    for (byte, dst_byte) in src.bytes().zip(dst[..max_len].iter_mut()) {
+        *dst_byte = byte as c_char;
+        len += 1;
+    }
+    dst[len] = 0;
+}
+
+// The factory, made on the first call to `GetPluginFactory`, as a pointer. It keeps a reference
+// of its own, so it lives as long as the module.
+static FACTORY: Mutex<usize> = Mutex::new(0);
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn GetPluginFactory() -> *mut c_void {
+    let mut factory = FACTORY.lock().unwrap_or_else(|err| err.into_inner());
+    unsafe {
+        if *factory == 0 {
+            let new_factory = Box::into_raw(Factory::new());
+            (*new_factory).add_ref();
+            *factory = new_factory as usize;
+        }
+        // Every caller gets a reference to release.
+        (*(*factory as *mut Factory)).add_ref();
+    }
+    *factory as *mut c_void
+}
+
+// Hosts call these when loading and unloading the module; there's nothing to set up.
+#[cfg(target_os = "linux")]
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "C" fn ModuleEntry(_library_handle: *mut c_void) -> bool {
+    true
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "C" fn ModuleExit() -> bool {
+    true
+}
+
+#[cfg(target_os = "macos")]
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "C" fn bundleEntry(_bundle_ref: *mut c_void) -> bool {
+    true
+}
+
+#[cfg(target_os = "macos")]
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "C" fn bundleExit() -> bool {
+    true
+}
+
+#[cfg(target_os = "windows")]
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn InitDll() -> bool {
+    true
+}
+
+#[cfg(target_os = "windows")]
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "system" fn ExitDll() -> bool {
+    true
+}